@@ -0,0 +1,442 @@
+//! JP2 file writing.
+//!
+//! This module assembles the minimal set of boxes required for a conforming
+//! JP2 file (ITU T.800 | ISO/IEC 15444-1 Annex I) around a codestream that
+//! has been produced elsewhere.
+
+use std::error;
+use std::io;
+
+use super::{
+    CaptureResolutionBox, DefaultDisplayResolutionBox, EnumeratedColourSpaces, JP2Error,
+    ResolutionSuperBox, BOX_TYPE_CAPTURE_RESOLUTION, BOX_TYPE_COLOUR_SPECIFICATION,
+    BOX_TYPE_CONTIGUOUS_CODESTREAM, BOX_TYPE_DEFAULT_DISPLAY_RESOLUTION, BOX_TYPE_FILE_TYPE,
+    BOX_TYPE_HEADER, BOX_TYPE_IMAGE_HEADER, BOX_TYPE_RESOLUTION, BOX_TYPE_SIGNATURE, BRAND_JP2,
+    COMPRESSION_TYPE_WAVELET, ENUMERATED_COLOUR_SPACE_GREYSCALE, ENUMERATED_COLOUR_SPACE_SRGB,
+    ENUMERATED_COLOUR_SPACE_SYCC, METHOD_ENUMERATED_COLOUR_SPACE, METRES_PER_INCH, SIGNATURE_MAGIC,
+};
+
+/// Builder for a minimal JP2 file.
+///
+/// The written file contains, in order, the Signature box, the File Type box,
+/// a JP2 Header box (holding an Image Header box, a single enumerated
+/// Colour Specification box and an optional Resolution box) and one Contiguous Codestream box wrapping the
+/// supplied codestream bytes.
+///
+/// The defaults are three unsigned 8 bit components in the sRGB colourspace,
+/// with a brand and compatibility list of `'jp2 '`.
+///
+/// The codestream is not inspected; it is up to the caller to ensure the
+/// image header values agree with the SIZ marker segment of the codestream.
+#[derive(Debug)]
+pub struct Jp2Builder {
+    width: u32,
+    height: u32,
+    components_num: u16,
+    bit_depth: u8,
+    values_are_signed: bool,
+    colour_space: EnumeratedColourSpaces,
+    resolution: Option<ResolutionSuperBox>,
+    codestream: Vec<u8>,
+}
+
+impl Jp2Builder {
+    /// Create a builder for an image of the given size wrapping `codestream`.
+    pub fn new(width: u32, height: u32, codestream: Vec<u8>) -> Jp2Builder {
+        Jp2Builder {
+            width,
+            height,
+            components_num: 3,
+            bit_depth: 8,
+            values_are_signed: false,
+            colour_space: EnumeratedColourSpaces::sRGB,
+            resolution: None,
+            codestream,
+        }
+    }
+
+    /// Set the number of components (NC).
+    pub fn components_num(mut self, components_num: u16) -> Jp2Builder {
+        self.components_num = components_num;
+        self
+    }
+
+    /// Set the bit depth of all components, from 1 to 38 bits inclusive.
+    pub fn bit_depth(mut self, bit_depth: u8, values_are_signed: bool) -> Jp2Builder {
+        self.bit_depth = bit_depth;
+        self.values_are_signed = values_are_signed;
+        self
+    }
+
+    /// Set the enumerated colourspace written to the Colour Specification box.
+    pub fn colour_space(mut self, colour_space: EnumeratedColourSpaces) -> Jp2Builder {
+        self.colour_space = colour_space;
+        self
+    }
+
+    /// Set the Resolution box written to the JP2 Header box.
+    pub fn resolution(mut self, resolution: ResolutionSuperBox) -> Jp2Builder {
+        self.resolution = Some(resolution);
+        self
+    }
+
+    /// Serialize the file to `writer`.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), Box<dyn error::Error>> {
+        if self.width == 0 || self.height == 0 {
+            return Err(JP2Error::InvalidParameter { name: "size" }.into());
+        }
+        if self.components_num == 0 {
+            return Err(JP2Error::InvalidParameter {
+                name: "components_num",
+            }
+            .into());
+        }
+        if !(1..=38).contains(&self.bit_depth) {
+            return Err(JP2Error::InvalidParameter { name: "bit_depth" }.into());
+        }
+        let enumerated_colour_space = match self.colour_space {
+            EnumeratedColourSpaces::sRGB => ENUMERATED_COLOUR_SPACE_SRGB,
+            EnumeratedColourSpaces::Greyscale => ENUMERATED_COLOUR_SPACE_GREYSCALE,
+            EnumeratedColourSpaces::sYCC => ENUMERATED_COLOUR_SPACE_SYCC,
+            EnumeratedColourSpaces::Reserved => {
+                return Err(JP2Error::InvalidParameter {
+                    name: "colour_space",
+                }
+                .into());
+            }
+        };
+
+        write_box(writer, BOX_TYPE_SIGNATURE, &SIGNATURE_MAGIC)?;
+
+        // Brand, minor version and a single entry compatibility list
+        let mut file_type = Vec::with_capacity(12);
+        file_type.extend_from_slice(&BRAND_JP2);
+        file_type.extend_from_slice(&0u32.to_be_bytes());
+        file_type.extend_from_slice(&BRAND_JP2);
+        write_box(writer, BOX_TYPE_FILE_TYPE, &file_type)?;
+
+        let mut components_bits = self.bit_depth - 1;
+        if self.values_are_signed {
+            components_bits |= 0x80;
+        }
+        let mut image_header = Vec::with_capacity(14);
+        image_header.extend_from_slice(&self.height.to_be_bytes());
+        image_header.extend_from_slice(&self.width.to_be_bytes());
+        image_header.extend_from_slice(&self.components_num.to_be_bytes());
+        image_header.push(components_bits);
+        image_header.push(COMPRESSION_TYPE_WAVELET);
+        // Colourspace is known, and there is no intellectual property box
+        image_header.push(0);
+        image_header.push(0);
+
+        let mut colour_specification = Vec::with_capacity(7);
+        colour_specification.extend_from_slice(&METHOD_ENUMERATED_COLOUR_SPACE);
+        // Precedence and approximation shall be zero
+        colour_specification.push(0);
+        colour_specification.push(0);
+        colour_specification.extend_from_slice(&enumerated_colour_space);
+
+        let mut header = Vec::with_capacity(37);
+        write_box(&mut header, BOX_TYPE_IMAGE_HEADER, &image_header)?;
+        write_box(
+            &mut header,
+            BOX_TYPE_COLOUR_SPECIFICATION,
+            &colour_specification,
+        )?;
+        if let Some(resolution) = &self.resolution {
+            resolution.write_to(&mut header)?;
+        }
+        write_box(writer, BOX_TYPE_HEADER, &header)?;
+
+        write_box(writer, BOX_TYPE_CONTIGUOUS_CODESTREAM, &self.codestream)?;
+
+        Ok(())
+    }
+}
+
+impl ResolutionSuperBox {
+    /// Create a Resolution box from a capture resolution, a default display
+    /// resolution, or both.
+    pub fn new(
+        capture_resolution_box: Option<CaptureResolutionBox>,
+        default_display_resolution_box: Option<DefaultDisplayResolutionBox>,
+    ) -> Result<ResolutionSuperBox, JP2Error> {
+        if capture_resolution_box.is_none() && default_display_resolution_box.is_none() {
+            return Err(JP2Error::InvalidParameter { name: "resolution" });
+        }
+        let mut length = 0;
+        if capture_resolution_box.is_some() {
+            length += 8 + RESOLUTION_BOX_LENGTH;
+        }
+        if default_display_resolution_box.is_some() {
+            length += 8 + RESOLUTION_BOX_LENGTH;
+        }
+        Ok(ResolutionSuperBox {
+            length,
+            offset: 0,
+            capture_resolution_box,
+            default_display_resolution_box,
+        })
+    }
+
+    /// Serialize the box, including the contained boxes, to `writer`.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut contents = Vec::with_capacity(self.length as usize);
+        if let Some(capture) = &self.capture_resolution_box {
+            write_box(
+                &mut contents,
+                BOX_TYPE_CAPTURE_RESOLUTION,
+                &resolution_parameters(
+                    capture.vertical_capture_grid_resolution_numerator,
+                    capture.vertical_capture_grid_resolution_denominator,
+                    capture.horizontal_capture_grid_resolution_numerator,
+                    capture.horizontal_capture_grid_resolution_denominator,
+                    capture.vertical_capture_grid_resolution_exponent,
+                    capture.horizontal_capture_grid_resolution_exponent,
+                ),
+            )?;
+        }
+        if let Some(display) = &self.default_display_resolution_box {
+            write_box(
+                &mut contents,
+                BOX_TYPE_DEFAULT_DISPLAY_RESOLUTION,
+                &resolution_parameters(
+                    display.vertical_display_grid_resolution_numerator,
+                    display.vertical_display_grid_resolution_denominator,
+                    display.horizontal_display_grid_resolution_numerator,
+                    display.horizontal_display_grid_resolution_denominator,
+                    display.vertical_display_grid_resolution_exponent,
+                    display.horizontal_display_grid_resolution_exponent,
+                ),
+            )?;
+        }
+        write_box(writer, BOX_TYPE_RESOLUTION, &contents)
+    }
+}
+
+impl CaptureResolutionBox {
+    /// Create a Capture Resolution box from the numerator, denominator and
+    /// exponent of each direction, in grid points per metre.
+    ///
+    /// The parameters are in the order they are stored, VRcN, VRcD, HRcN,
+    /// HRcD, VRcE and HRcE. The denominators shall not be zero.
+    pub fn new(
+        vertical_numerator: u16,
+        vertical_denominator: u16,
+        horizontal_numerator: u16,
+        horizontal_denominator: u16,
+        vertical_exponent: i8,
+        horizontal_exponent: i8,
+    ) -> Result<CaptureResolutionBox, JP2Error> {
+        if vertical_denominator == 0 || horizontal_denominator == 0 {
+            return Err(JP2Error::InvalidParameter {
+                name: "capture_resolution",
+            });
+        }
+        Ok(CaptureResolutionBox {
+            length: RESOLUTION_BOX_LENGTH,
+            offset: 0,
+            vertical_capture_grid_resolution_numerator: vertical_numerator.to_be_bytes(),
+            vertical_capture_grid_resolution_denominator: vertical_denominator.to_be_bytes(),
+            horizontal_capture_grid_resolution_numerator: horizontal_numerator.to_be_bytes(),
+            horizontal_capture_grid_resolution_denominator: horizontal_denominator.to_be_bytes(),
+            vertical_capture_grid_resolution_exponent: vertical_exponent.to_be_bytes(),
+            horizontal_capture_grid_resolution_exponent: horizontal_exponent.to_be_bytes(),
+        })
+    }
+
+    /// Create a Capture Resolution box from horizontal and vertical
+    /// resolutions in dots per inch.
+    ///
+    /// The closest encoding is chosen, which is exact for whole numbers of
+    /// dots per inch up to 1310.
+    pub fn from_dpi(
+        horizontal_dpi: f64,
+        vertical_dpi: f64,
+    ) -> Result<CaptureResolutionBox, JP2Error> {
+        let invalid = || JP2Error::InvalidParameter {
+            name: "capture_resolution",
+        };
+        let (horizontal_numerator, horizontal_denominator, horizontal_exponent) =
+            encode_resolution(horizontal_dpi / METRES_PER_INCH).ok_or_else(invalid)?;
+        let (vertical_numerator, vertical_denominator, vertical_exponent) =
+            encode_resolution(vertical_dpi / METRES_PER_INCH).ok_or_else(invalid)?;
+        CaptureResolutionBox::new(
+            vertical_numerator,
+            vertical_denominator,
+            horizontal_numerator,
+            horizontal_denominator,
+            vertical_exponent,
+            horizontal_exponent,
+        )
+    }
+}
+
+impl DefaultDisplayResolutionBox {
+    /// Create a Default Display Resolution box from the numerator,
+    /// denominator and exponent of each direction, in grid points per metre.
+    ///
+    /// The parameters are in the order they are stored, VRdN, VRdD, HRdN,
+    /// HRdD, VRdE and HRdE. The denominators shall not be zero.
+    pub fn new(
+        vertical_numerator: u16,
+        vertical_denominator: u16,
+        horizontal_numerator: u16,
+        horizontal_denominator: u16,
+        vertical_exponent: i8,
+        horizontal_exponent: i8,
+    ) -> Result<DefaultDisplayResolutionBox, JP2Error> {
+        if vertical_denominator == 0 || horizontal_denominator == 0 {
+            return Err(JP2Error::InvalidParameter {
+                name: "display_resolution",
+            });
+        }
+        Ok(DefaultDisplayResolutionBox {
+            length: RESOLUTION_BOX_LENGTH,
+            offset: 0,
+            vertical_display_grid_resolution_numerator: vertical_numerator.to_be_bytes(),
+            vertical_display_grid_resolution_denominator: vertical_denominator.to_be_bytes(),
+            horizontal_display_grid_resolution_numerator: horizontal_numerator.to_be_bytes(),
+            horizontal_display_grid_resolution_denominator: horizontal_denominator.to_be_bytes(),
+            vertical_display_grid_resolution_exponent: vertical_exponent.to_be_bytes(),
+            horizontal_display_grid_resolution_exponent: horizontal_exponent.to_be_bytes(),
+        })
+    }
+
+    /// Create a Default Display Resolution box from horizontal and vertical
+    /// resolutions in dots per inch.
+    ///
+    /// The closest encoding is chosen, which is exact for whole numbers of
+    /// dots per inch up to 1310.
+    pub fn from_dpi(
+        horizontal_dpi: f64,
+        vertical_dpi: f64,
+    ) -> Result<DefaultDisplayResolutionBox, JP2Error> {
+        let invalid = || JP2Error::InvalidParameter {
+            name: "display_resolution",
+        };
+        let (horizontal_numerator, horizontal_denominator, horizontal_exponent) =
+            encode_resolution(horizontal_dpi / METRES_PER_INCH).ok_or_else(invalid)?;
+        let (vertical_numerator, vertical_denominator, vertical_exponent) =
+            encode_resolution(vertical_dpi / METRES_PER_INCH).ok_or_else(invalid)?;
+        DefaultDisplayResolutionBox::new(
+            vertical_numerator,
+            vertical_denominator,
+            horizontal_numerator,
+            horizontal_denominator,
+            vertical_exponent,
+            horizontal_exponent,
+        )
+    }
+}
+
+// Contents of the Capture and Default Display Resolution boxes
+const RESOLUTION_BOX_LENGTH: u64 = 10;
+
+fn resolution_parameters(
+    vertical_numerator: [u8; 2],
+    vertical_denominator: [u8; 2],
+    horizontal_numerator: [u8; 2],
+    horizontal_denominator: [u8; 2],
+    vertical_exponent: [u8; 1],
+    horizontal_exponent: [u8; 1],
+) -> Vec<u8> {
+    let mut parameters = Vec::with_capacity(10);
+    parameters.extend_from_slice(&vertical_numerator);
+    parameters.extend_from_slice(&vertical_denominator);
+    parameters.extend_from_slice(&horizontal_numerator);
+    parameters.extend_from_slice(&horizontal_denominator);
+    parameters.extend_from_slice(&vertical_exponent);
+    parameters.extend_from_slice(&horizontal_exponent);
+    parameters
+}
+
+/// Find the numerator, denominator and exponent closest to a resolution in
+/// grid points per metre, as R = N / D * 10^E.
+///
+/// Exponents are tried in order of increasing magnitude, stopping at the
+/// first exact encoding.
+fn encode_resolution(value: f64) -> Option<(u16, u16, i8)> {
+    if !value.is_finite() || value <= 0.0 {
+        return None;
+    }
+    let mut best: Option<(u16, u16, i8)> = None;
+    let mut best_error = f64::INFINITY;
+    for magnitude in 0..=i8::MAX {
+        for exponent in [magnitude, -magnitude].iter() {
+            let scale = 10_f64.powi(*exponent as i32);
+            let (numerator, denominator) = match closest_fraction(value / scale) {
+                Some(fraction) => fraction,
+                None => continue,
+            };
+            let error = (numerator as f64 / denominator as f64 * scale - value).abs() / value;
+            if error < best_error {
+                best = Some((numerator, denominator, *exponent));
+                best_error = error;
+            }
+        }
+        // Exact, other than floating point rounding
+        if best_error < 1e-12 {
+            break;
+        }
+    }
+    best
+}
+
+/// Closest fraction with a 16 bit numerator and denominator, using the
+/// convergents of the continued fraction.
+fn closest_fraction(value: f64) -> Option<(u16, u16)> {
+    let max = u16::MAX as f64;
+    if value > max || value < 1.0 / max {
+        return None;
+    }
+    // Previous two convergents, h / k
+    let (mut h0, mut h1) = (0_f64, 1_f64);
+    let (mut k0, mut k1) = (1_f64, 0_f64);
+    let mut x = value;
+    for _ in 0..64 {
+        let a = x.floor();
+        let h2 = a * h1 + h0;
+        let k2 = a * k1 + k0;
+        if h2 > max || k2 > max {
+            break;
+        }
+        h0 = h1;
+        h1 = h2;
+        k0 = k1;
+        k1 = k2;
+        let fraction = x - a;
+        if fraction < 1e-12 {
+            break;
+        }
+        x = 1.0 / fraction;
+    }
+    if h1 < 1.0 || k1 < 1.0 {
+        return None;
+    }
+    Some((h1 as u16, k1 as u16))
+}
+
+/// Write a box header (LBox, TBox and if required XLBox) followed by the contents.
+fn write_box<W: io::Write>(writer: &mut W, box_type: [u8; 4], contents: &[u8]) -> io::Result<()> {
+    write_box_header(writer, box_type, contents.len() as u64)?;
+    writer.write_all(contents)
+}
+
+// LBox and TBox, with an XLBox field if the box is too long for LBox
+pub(crate) fn write_box_header<W: io::Write>(
+    writer: &mut W,
+    box_type: [u8; 4],
+    contents_length: u64,
+) -> io::Result<()> {
+    let length = contents_length + 8;
+    if length <= u32::MAX as u64 {
+        writer.write_all(&(length as u32).to_be_bytes())?;
+        writer.write_all(&box_type)?;
+    } else {
+        writer.write_all(&1u32.to_be_bytes())?;
+        writer.write_all(&box_type)?;
+        writer.write_all(&(length + 8).to_be_bytes())?;
+    }
+    Ok(())
+}
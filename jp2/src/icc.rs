@@ -0,0 +1,335 @@
+//! Restricted ICC profile parsing.
+//!
+//! A Colour Specification box using the restricted ICC method holds a
+//! Monochrome or Three-Component Matrix-Based profile (ITU T.800 | ISO/IEC
+//! 15444-1 Section I.5.3.3). Those profile classes transform the decoded
+//! samples to the PCS using a tone reproduction curve per channel and, for
+//! three components, a matrix. This module reads the tags needed for that,
+//! as defined in ISO 15076-1.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::convert::TryFrom;
+use core::error;
+use core::fmt;
+
+type Signature = [u8; 4];
+
+// Header, followed by the tag count
+const HEADER_LENGTH: usize = 128;
+const TAG_ENTRY_LENGTH: usize = 12;
+
+const COLOUR_SPACE_GREY: Signature = *b"GRAY";
+const COLOUR_SPACE_RGB: Signature = *b"RGB ";
+
+const TAG_MEDIA_WHITE_POINT: Signature = *b"wtpt";
+const TAG_GREY_TRC: Signature = *b"kTRC";
+const TAG_RED_TRC: Signature = *b"rTRC";
+const TAG_GREEN_TRC: Signature = *b"gTRC";
+const TAG_BLUE_TRC: Signature = *b"bTRC";
+const TAG_RED_COLORANT: Signature = *b"rXYZ";
+const TAG_GREEN_COLORANT: Signature = *b"gXYZ";
+const TAG_BLUE_COLORANT: Signature = *b"bXYZ";
+
+const TYPE_XYZ: Signature = *b"XYZ ";
+const TYPE_CURVE: Signature = *b"curv";
+const TYPE_PARAMETRIC_CURVE: Signature = *b"para";
+
+/// Error values that may be returned when reading a restricted ICC profile.
+#[derive(Debug, PartialEq)]
+pub enum IccProfileError {
+    /// Profile too short.
+    ///
+    /// The profile ended before the header, tag table or a tag was complete.
+    TooShort { length: usize },
+
+    /// Unsupported colour space.
+    ///
+    /// Restricted profiles are limited to greyscale and RGB data colour
+    /// spaces.
+    UnsupportedColourSpace { colour_space: [u8; 4] },
+
+    /// Required tag missing.
+    ///
+    /// The tag is required by the Monochrome or Three-Component
+    /// Matrix-Based profile class.
+    TagMissing { signature: [u8; 4] },
+
+    /// Malformed tag.
+    ///
+    /// The tag data lies outside the profile, or does not have the expected
+    /// type and size.
+    TagMalformed { signature: [u8; 4] },
+}
+
+impl error::Error for IccProfileError {}
+impl fmt::Display for IccProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooShort { length } => {
+                write!(f, "ICC profile too short at {} bytes", length)
+            }
+            Self::UnsupportedColourSpace { colour_space } => {
+                write!(f, "unsupported ICC profile colour space {:?}", colour_space)
+            }
+            Self::TagMissing { signature } => {
+                write!(f, "missing ICC profile tag {:?}", signature)
+            }
+            Self::TagMalformed { signature } => {
+                write!(f, "malformed ICC profile tag {:?}", signature)
+            }
+        }
+    }
+}
+
+/// Tone reproduction curve of a channel.
+///
+/// This maps a channel value, normalised to the range 0 to 1, to a linear
+/// value in the same range.
+///
+/// See ISO 15076-1 Sections 10.6 and 10.18.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToneReproductionCurve {
+    /// Identity response, from a curve with no entries.
+    Identity,
+
+    /// Power function with the given gamma, from a curve with one entry.
+    Gamma(f32),
+
+    /// Sampled curve, with equally spaced entries from 0 to 65535.
+    Table(Vec<u16>),
+
+    /// Parametric curve.
+    ///
+    /// The function type, from 0 to 4, determines the number of parameters
+    /// and how they are applied.
+    Parametric {
+        function_type: u16,
+        parameters: Vec<f32>,
+    },
+}
+
+/// Restricted ICC profile.
+///
+/// The profile held by a Colour Specification box using the restricted ICC
+/// method. Only the tags required to transform decoded samples to the PCS
+/// are read.
+///
+/// See ITU T.800 | ISO/IEC 15444-1 Section I.5.3.3 and ISO 15076-1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestrictedIccProfile {
+    profile_class: Signature,
+    colour_space: Signature,
+    white_point: Option<[f32; 3]>,
+    colorants: Option<[[f32; 3]; 3]>,
+    curves: Vec<ToneReproductionCurve>,
+}
+
+impl RestrictedIccProfile {
+    /// Read a restricted ICC profile.
+    ///
+    /// The tone reproduction curve for each channel is required, as are the
+    /// red, green and blue colorant tags for an RGB profile.
+    pub fn decode(profile: &[u8]) -> Result<RestrictedIccProfile, IccProfileError> {
+        if profile.len() < HEADER_LENGTH + 4 {
+            return Err(IccProfileError::TooShort {
+                length: profile.len(),
+            });
+        }
+        let tag_count = read_u32(profile, HEADER_LENGTH) as usize;
+        let tag_table = &profile[HEADER_LENGTH + 4..];
+        if tag_table.len() / TAG_ENTRY_LENGTH < tag_count {
+            return Err(IccProfileError::TooShort {
+                length: profile.len(),
+            });
+        }
+        let tags = Tags {
+            profile,
+            table: &tag_table[..tag_count * TAG_ENTRY_LENGTH],
+        };
+
+        let colour_space = read_signature(profile, 16);
+        let (curve_tags, colorants) = match colour_space {
+            COLOUR_SPACE_GREY => (vec![TAG_GREY_TRC], None),
+            COLOUR_SPACE_RGB => {
+                let colorants = [
+                    tags.xyz(TAG_RED_COLORANT)?,
+                    tags.xyz(TAG_GREEN_COLORANT)?,
+                    tags.xyz(TAG_BLUE_COLORANT)?,
+                ];
+                (
+                    vec![TAG_RED_TRC, TAG_GREEN_TRC, TAG_BLUE_TRC],
+                    Some(colorants),
+                )
+            }
+            _ => return Err(IccProfileError::UnsupportedColourSpace { colour_space }),
+        };
+        let curves = curve_tags
+            .into_iter()
+            .map(|signature| tags.curve(signature))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The media white point is required by ISO 15076-1, but not by the
+        // restricted profile transform, so older profiles may omit it.
+        let white_point = match tags.find(TAG_MEDIA_WHITE_POINT)? {
+            Some(_) => Some(tags.xyz(TAG_MEDIA_WHITE_POINT)?),
+            None => None,
+        };
+
+        Ok(RestrictedIccProfile {
+            profile_class: read_signature(profile, 12),
+            colour_space,
+            white_point,
+            colorants,
+            curves,
+        })
+    }
+
+    /// Profile class signature, such as `scnr` for an input device or `mntr`
+    /// for a display device.
+    pub fn profile_class(&self) -> [u8; 4] {
+        self.profile_class
+    }
+
+    /// Data colour space signature, either `GRAY` or `RGB `.
+    pub fn colour_space(&self) -> [u8; 4] {
+        self.colour_space
+    }
+
+    /// Number of channels the profile applies to, 1 or 3.
+    pub fn num_channels(&self) -> usize {
+        self.curves.len()
+    }
+
+    /// Media white point, as PCS XYZ values.
+    ///
+    /// This is `None` if the profile does not have a `wtpt` tag.
+    pub fn white_point(&self) -> Option<[f32; 3]> {
+        self.white_point
+    }
+
+    /// Red, green and blue colorants, as PCS XYZ values.
+    ///
+    /// These are the columns of the matrix for an RGB profile, and `None`
+    /// for a greyscale profile.
+    pub fn colorants(&self) -> Option<[[f32; 3]; 3]> {
+        self.colorants
+    }
+
+    /// Tone reproduction curve for the channel.
+    ///
+    /// Channel 0 is grey for a greyscale profile, and channels 0 to 2 are
+    /// red, green and blue for an RGB profile.
+    pub fn tone_reproduction_curve(&self, channel: usize) -> Option<&ToneReproductionCurve> {
+        self.curves.get(channel)
+    }
+}
+
+struct Tags<'a> {
+    profile: &'a [u8],
+    table: &'a [u8],
+}
+
+impl<'a> Tags<'a> {
+    // Tag data, checked to lie within the profile
+    fn find(&self, signature: Signature) -> Result<Option<&'a [u8]>, IccProfileError> {
+        let entry = match self
+            .table
+            .chunks_exact(TAG_ENTRY_LENGTH)
+            .find(|entry| entry[0..4] == signature)
+        {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let offset = read_u32(entry, 4) as usize;
+        let size = read_u32(entry, 8) as usize;
+        match offset.checked_add(size) {
+            Some(end) if end <= self.profile.len() => Ok(Some(&self.profile[offset..end])),
+            _ => Err(IccProfileError::TagMalformed { signature }),
+        }
+    }
+
+    // Tag data, which must be present and of the given type
+    fn get(
+        &self,
+        signature: Signature,
+        types: &[Signature],
+        min_size: usize,
+    ) -> Result<&'a [u8], IccProfileError> {
+        let data = self
+            .find(signature)?
+            .ok_or(IccProfileError::TagMissing { signature })?;
+        if data.len() < min_size || !types.contains(&read_signature(data, 0)) {
+            return Err(IccProfileError::TagMalformed { signature });
+        }
+        Ok(data)
+    }
+
+    // XYZType, with a single XYZ value
+    fn xyz(&self, signature: Signature) -> Result<[f32; 3], IccProfileError> {
+        let data = self.get(signature, &[TYPE_XYZ], 20)?;
+        Ok([
+            read_s15_fixed16(data, 8),
+            read_s15_fixed16(data, 12),
+            read_s15_fixed16(data, 16),
+        ])
+    }
+
+    // curveType or parametricCurveType
+    fn curve(&self, signature: Signature) -> Result<ToneReproductionCurve, IccProfileError> {
+        let data = self.get(signature, &[TYPE_CURVE, TYPE_PARAMETRIC_CURVE], 12)?;
+        if read_signature(data, 0) == TYPE_PARAMETRIC_CURVE {
+            let function_type = u16::from_be_bytes([data[8], data[9]]);
+            let parameter_count = match function_type {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(IccProfileError::TagMalformed { signature }),
+            };
+            if data.len() < 12 + parameter_count * 4 {
+                return Err(IccProfileError::TagMalformed { signature });
+            }
+            let parameters = (0..parameter_count)
+                .map(|index| read_s15_fixed16(data, 12 + index * 4))
+                .collect();
+            return Ok(ToneReproductionCurve::Parametric {
+                function_type,
+                parameters,
+            });
+        }
+
+        let entry_count = read_u32(data, 8) as usize;
+        let entries = &data[12..];
+        if entries.len() / 2 < entry_count {
+            return Err(IccProfileError::TagMalformed { signature });
+        }
+        Ok(match entry_count {
+            0 => ToneReproductionCurve::Identity,
+            // u8Fixed8Number
+            1 => ToneReproductionCurve::Gamma(
+                f32::from(u16::from_be_bytes([entries[0], entries[1]])) / 256.0,
+            ),
+            _ => ToneReproductionCurve::Table(
+                entries[..entry_count * 2]
+                    .chunks_exact(2)
+                    .map(|entry| u16::from_be_bytes([entry[0], entry[1]]))
+                    .collect(),
+            ),
+        })
+    }
+}
+
+fn read_signature(data: &[u8], offset: usize) -> Signature {
+    <Signature>::try_from(&data[offset..offset + 4]).unwrap()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(read_signature(data, offset))
+}
+
+// s15Fixed16Number
+fn read_s15_fixed16(data: &[u8], offset: usize) -> f32 {
+    read_u32(data, offset) as i32 as f32 / 65536.0
+}
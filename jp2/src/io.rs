@@ -0,0 +1,190 @@
+//! Minimal byte input for builds without the `std` feature.
+//!
+//! This mirrors the subset of `std::io` used by the box parsers, so that
+//! [decode_jp2](crate::decode_jp2) can read from any source implementing
+//! [Read] and [Seek]. [Cursor] provides both over an in-memory buffer.
+
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Result of an I/O operation.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Category of an I/O error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input ended before the requested bytes could be read.
+    UnexpectedEof,
+    /// A seek to a position before the start of the input.
+    InvalidInput,
+}
+
+/// Error returned from [Read] and [Seek].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+}
+
+impl Error {
+    /// Create an error of the given kind.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error { kind }
+    }
+
+    /// Category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl core::error::Error for Error {}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::UnexpectedEof => write!(f, "failed to fill whole buffer"),
+            ErrorKind::InvalidInput => {
+                write!(f, "invalid seek to a negative or overflowing position")
+            }
+        }
+    }
+}
+
+/// Position to seek to, relative to the start, end or current position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+/// Source of bytes.
+pub trait Read {
+    /// Read exactly enough bytes to fill `buf`.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+}
+
+/// Source of bytes with a movable position.
+pub trait Seek {
+    /// Move to `pos`, returning the new position from the start.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    /// Current position from the start.
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        (**self).read_exact(buf)
+    }
+}
+
+impl<S: Seek + ?Sized> Seek for &mut S {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        (**self).seek(pos)
+    }
+}
+
+/// In-memory buffer with a position, implementing [Read] and [Seek].
+#[derive(Debug, Clone, Default)]
+pub struct Cursor<T> {
+    inner: T,
+    position: u64,
+}
+
+impl<T> Cursor<T> {
+    /// Create a cursor at the start of `inner`.
+    pub fn new(inner: T) -> Cursor<T> {
+        Cursor { inner, position: 0 }
+    }
+
+    /// Current position from the start.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Unwrap the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let data = self.inner.as_ref();
+        let start = usize::try_from(self.position)
+            .unwrap_or(usize::MAX)
+            .min(data.len());
+        let remaining = &data[start..];
+        if remaining.len() < buf.len() {
+            self.position = data.len() as u64;
+            return Err(Error::new(ErrorKind::UnexpectedEof));
+        }
+        buf.copy_from_slice(&remaining[..buf.len()]);
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.inner.as_ref().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(Error::new(ErrorKind::InvalidInput)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_read_exact() {
+        let mut cursor = Cursor::new([1u8, 2, 3, 4, 5]);
+        let mut buf = [0u8; 2];
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(cursor.stream_position(), Ok(2));
+
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            cursor.read_exact(&mut buf).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_cursor_seek() {
+        let mut cursor = Cursor::new([1u8, 2, 3, 4, 5]);
+        assert_eq!(cursor.seek(SeekFrom::End(-1)), Ok(4));
+        let mut buf = [0u8; 1];
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [5]);
+        assert_eq!(cursor.seek(SeekFrom::Current(-3)), Ok(2));
+        assert_eq!(
+            cursor.seek(SeekFrom::Current(-3)).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(cursor.seek(SeekFrom::Start(0)), Ok(0));
+    }
+
+    #[test]
+    fn test_decode_without_std() {
+        let data = include_bytes!("../tests/geojp2.jp2");
+        let file = crate::decode_jp2_slice(data).unwrap();
+        assert_eq!(file.contiguous_codestreams_boxes().len(), 1);
+    }
+}
@@ -0,0 +1,110 @@
+//! Intellectual property rights XML.
+//!
+//! The Intellectual Property box holds XML in the `jp:IPR` schema of ITU-T
+//! T.801 | ISO/IEC 15444-2 Annex N. This module reads the exploitation
+//! elements that describe how the image may be used: the use restriction,
+//! the rights management system and the countries the rights apply in.
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use roxmltree::{Document, Node};
+
+// Namespace of the JPX metadata and IPR schemas
+const NAMESPACE_JPX: &str = "http://www.jpeg.org/jpx/1.0/xml";
+
+/// Intellectual property rights from the IPR XML.
+///
+/// Elements that are absent from the XML are `None`, or empty for the
+/// protection countries.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section N.5.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntellectualPropertyRights {
+    use_restriction: Option<String>,
+    management_type: Option<String>,
+    protection_countries: Vec<String>,
+}
+
+impl IntellectualPropertyRights {
+    // Rights from XML with a jp:IPR root element
+    pub(crate) fn parse(xml: &str) -> Option<IntellectualPropertyRights> {
+        let document = Document::parse(xml).ok()?;
+        let root = document.root_element();
+        if !is_element(root, "IPR") {
+            return None;
+        }
+        let exploitation = match child(root, "IPR_EXPLOITATION") {
+            Some(exploitation) => exploitation,
+            None => return Some(IntellectualPropertyRights::default()),
+        };
+
+        let management_type = child(exploitation, "IPR_MGMT_SYS")
+            .and_then(|management| child(management, "IPR_MGMT_TYPE"))
+            .and_then(text);
+        // A semicolon separated list of ISO 3166 country codes
+        let protection_countries = child(exploitation, "IPR_PROTECTION")
+            .and_then(text)
+            .map(|countries| {
+                countries
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|country| !country.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(IntellectualPropertyRights {
+            use_restriction: child(exploitation, "IPR_USE_RESTRICTION").and_then(text),
+            management_type,
+            protection_countries,
+        })
+    }
+
+    /// Use restriction, such as a security classification.
+    ///
+    /// This is the `IPR_USE_RESTRICTION` element.
+    pub fn use_restriction(&self) -> Option<&str> {
+        self.use_restriction.as_deref()
+    }
+
+    /// Type of the rights management system.
+    ///
+    /// This is the `IPR_MGMT_TYPE` element of `IPR_MGMT_SYS`.
+    pub fn management_type(&self) -> Option<&str> {
+        self.management_type.as_deref()
+    }
+
+    /// Countries in which the rights are protected.
+    ///
+    /// These are the entries of the `IPR_PROTECTION` element, such as ISO
+    /// 3166 country codes.
+    pub fn protection_countries(&self) -> &[String] {
+        &self.protection_countries
+    }
+}
+
+fn is_element(node: Node, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().name() == name
+        && node.tag_name().namespace() == Some(NAMESPACE_JPX)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is_element(*child, name))
+}
+
+// Text of an element, trimmed, or None if it is empty
+fn text(node: Node) -> Option<String> {
+    let text = node.text()?.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! JP2 file format.
 //!
@@ -16,12 +17,52 @@
 //!
 //! The main entry point for this module is the `decode_jp2` function. That reads from the provided input, and returns a `JP2File` on success,
 //! or an error on failure.
-
+//!
+//! A minimal JP2 file can be written around an existing codestream with `Jp2Builder`.
+//!
+//! Box parsing only needs `core` and `alloc`. Without the default `std` feature the crate is `no_std`, reading
+//! through the minimal `io` module in place of `std::io`, and `Jp2Builder` is not available.
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use core::error;
+use core::fmt;
+use core::str;
 use log::{debug, info, warn};
-use std::error;
-use std::fmt;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
 use std::io;
-use std::str;
+
+#[cfg(feature = "std")]
+mod builder;
+mod icc;
+#[cfg(not(feature = "std"))]
+pub mod io;
+#[cfg(feature = "xml")]
+mod ipr;
+mod metadata;
+#[cfg(feature = "mmap")]
+mod mmap;
+
+#[cfg(feature = "std")]
+pub use builder::Jp2Builder;
+pub use icc::{IccProfileError, RestrictedIccProfile, ToneReproductionCurve};
+#[cfg(feature = "xml")]
+pub use ipr::IntellectualPropertyRights;
+pub use metadata::{MetadataCategory, MetadataItem, MetadataSource, MetadataView};
+#[cfg(feature = "mmap")]
+pub use mmap::{decode_jp2_mmap, MappedJP2File};
 
 /// Error values that may be returned from JP2 functions.
 #[derive(Debug)]
@@ -76,6 +117,36 @@ pub enum JP2Error {
     /// Some boxes are required to be present. If a required
     /// box is not present, this error will be returned.
     BoxMissing { box_type: BoxType },
+
+    /// Invalid parameter.
+    ///
+    /// A value supplied when writing a file is outside the range permitted
+    /// by the file format.
+    InvalidParameter { name: &'static str },
+
+    /// Channel count mismatch.
+    ///
+    /// The number of colour channels does not match the number required by
+    /// the enumerated colourspace, and the image cannot be interpreted in
+    /// that colourspace.
+    ChannelCountMismatch {
+        colour_space: EnumeratedColourSpaces,
+        expected: u16,
+        found: u16,
+    },
+
+    /// Unsupported palette bit depth.
+    ///
+    /// Palette entries are held as 32 bit values, so palette columns of 33
+    /// to 38 bits cannot be read.
+    PaletteBitDepthUnsupported { bit_depth: u8, offset: u64 },
+
+    /// Non-conformant colourspace approximation.
+    ///
+    /// A Colour Specification box in the JP2 Header box of a file with the
+    /// `jp2 ` brand sets the approximation to other than zero. This is only
+    /// an error with [DecodeOptions::strict].
+    ApproximationNonConformant { approximation: u8, offset: u64 },
 }
 
 impl error::Error for JP2Error {}
@@ -127,6 +198,100 @@ impl fmt::Display for JP2Error {
                     "only JPEG 2000 part-1 (ISO 15444-1 / T.800) is supported",
                 )
             }
+            Self::InvalidParameter { name } => {
+                write!(f, "invalid value for {}", name)
+            }
+            Self::ChannelCountMismatch {
+                colour_space,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "{} requires {} colour channels but {} found",
+                    colour_space, expected, found
+                )
+            }
+            Self::PaletteBitDepthUnsupported { bit_depth, offset } => {
+                write!(
+                    f,
+                    "unsupported palette bit depth {} at offset {}",
+                    bit_depth, offset
+                )
+            }
+            Self::ApproximationNonConformant {
+                approximation,
+                offset,
+            } => {
+                write!(
+                    f,
+                    "colourspace approximation {} at offset {} shall be 0 in a JP2 file",
+                    approximation, offset
+                )
+            }
+        }
+    }
+}
+
+/// Warnings that may be returned from [`JP2File::validate`].
+///
+/// These indicate a file that does not conform, but can still be
+/// interpreted.
+#[derive(Debug, PartialEq)]
+pub enum JP2Warning {
+    /// Unassociated channels.
+    ///
+    /// There is no Channel Definition box, and there are more channels than
+    /// the enumerated colourspace requires. The first channels are used as
+    /// the colour channels, and the type of the remaining channels is not
+    /// known.
+    UnassociatedChannels {
+        colour_space: EnumeratedColourSpaces,
+        expected: u16,
+        found: u16,
+    },
+
+    /// Missing Component Mapping box.
+    ///
+    /// A Palette box is present without the Component Mapping box that
+    /// shall accompany it. Each palette column is assumed to create one
+    /// channel.
+    ComponentMappingMissing,
+
+    /// Unknown colourspace with an enumerated colourspace.
+    ///
+    /// The Image Header box marks the colourspace as unknown, while the
+    /// Colour Specification box gives an enumerated colourspace. The samples
+    /// may not be in that colourspace.
+    ColourspaceUnknown {
+        colour_space: EnumeratedColourSpaces,
+    },
+}
+
+impl fmt::Display for JP2Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnassociatedChannels {
+                colour_space,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "{} requires {} colour channels, {} channels found without a channel definition",
+                    colour_space, expected, found
+                )
+            }
+            Self::ComponentMappingMissing => {
+                write!(f, "palette box without a component mapping box")
+            }
+            Self::ColourspaceUnknown { colour_space } => {
+                write!(
+                    f,
+                    "colourspace marked as unknown, but specified as {}",
+                    colour_space
+                )
+            }
         }
     }
 }
@@ -151,13 +316,47 @@ const BOX_TYPE_UUID: BoxType = [117, 117, 105, 100];
 const BOX_TYPE_UUID_INFO: BoxType = [117, 105, 110, 102];
 const BOX_TYPE_UUID_LIST: BoxType = [117, 108, 115, 116];
 const BOX_TYPE_DATA_ENTRY_URL: BoxType = [117, 114, 108, 32];
+const BOX_TYPE_CODESTREAM_HEADER: BoxType = [106, 112, 99, 104];
+const BOX_TYPE_COMPOSITING_LAYER_HEADER: BoxType = [106, 112, 108, 104];
+const BOX_TYPE_COLOUR_GROUP: BoxType = [99, 103, 114, 112];
+const BOX_TYPE_ASSOCIATION: BoxType = [97, 115, 111, 99];
+const BOX_TYPE_LABEL: BoxType = [108, 98, 108, 32];
+
+/// Free box type, ‘free’ (0x6672 6565).
+///
+/// This box, from ISO/IEC 14496-12 and ITU T.801 | ISO/IEC 15444-2, holds
+/// padding only. Readers skip it, and it is listed in
+/// [JP2File::unknown_boxes].
+pub const BOX_TYPE_FREE: [u8; 4] = [102, 114, 101, 101];
+
+/// Media Data box type, ‘mdat’ (0x6D64 6174).
+///
+/// This box, from ISO/IEC 14496-12 and ITU T.801 | ISO/IEC 15444-2, holds
+/// data referenced from elsewhere in the file. Readers skip it, and it is
+/// listed in [JP2File::unknown_boxes].
+pub const BOX_TYPE_MEDIA_DATA: [u8; 4] = [109, 100, 97, 116];
 
 // jp2\040
 const BRAND_JP2: [u8; 4] = [106, 112, 50, 32];
 
-// jp2\040
+// jpx\040
 const BRAND_JPX: [u8; 4] = [106, 112, 120, 32];
 
+// jpxb
+const BRAND_JPXB: [u8; 4] = [106, 112, 120, 98];
+
+// jpm\040
+const BRAND_JPM: [u8; 4] = [106, 112, 109, 32];
+
+// mjp2
+const BRAND_MJ2: [u8; 4] = [109, 106, 112, 50];
+
+// jph\040
+const BRAND_JPH: [u8; 4] = [106, 112, 104, 32];
+
+// \0\0\0\x01
+const BRAND_J2K_CODESTREAM: [u8; 4] = [0, 0, 0, 1];
+
 // <CR><LF><0x87><LF> (0x0D0A 870A).
 const SIGNATURE_MAGIC: [u8; 4] = [13, 10, 135, 10];
 
@@ -182,6 +381,13 @@ enum BoxTypes {
     UUIDInfo,
     UUIDList,
     DataEntryURL,
+    CodestreamHeader,
+    CompositingLayerHeader,
+    ColourGroup,
+    Association,
+    Label,
+    Free,
+    MediaData,
     Unknown,
 }
 
@@ -216,6 +422,16 @@ impl BoxTypes {
             BOX_TYPE_UUID_INFO => BoxTypes::UUIDInfo,
             BOX_TYPE_UUID_LIST => BoxTypes::UUIDList,
             BOX_TYPE_DATA_ENTRY_URL => BoxTypes::DataEntryURL,
+
+            BOX_TYPE_CODESTREAM_HEADER => BoxTypes::CodestreamHeader,
+            BOX_TYPE_COMPOSITING_LAYER_HEADER => BoxTypes::CompositingLayerHeader,
+            BOX_TYPE_COLOUR_GROUP => BoxTypes::ColourGroup,
+
+            BOX_TYPE_ASSOCIATION => BoxTypes::Association,
+            BOX_TYPE_LABEL => BoxTypes::Label,
+
+            BOX_TYPE_FREE => BoxTypes::Free,
+            BOX_TYPE_MEDIA_DATA => BoxTypes::MediaData,
             _ => BoxTypes::Unknown,
         }
     }
@@ -244,6 +460,91 @@ pub trait JBox {
         &mut self,
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>>;
+
+    /// Contents of the box, borrowed from the file data it was decoded from.
+    ///
+    /// This is the box after the LBox, TBox and XLBox fields, and is `None` if
+    /// the box does not lie within `data`.
+    fn payload<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let start = usize::try_from(self.offset()).ok()?;
+        let end = start.checked_add(usize::try_from(self.length()).ok()?)?;
+        data.get(start..end)
+    }
+
+    /// Write the box back out as it was read.
+    ///
+    /// The contents are copied from `reader`, which must be the one the box
+    /// was decoded from, so fields and padding that are not modelled are
+    /// kept without the box holding on to its contents. The header is written
+    /// with an XLBox field only if the length needs one, see [JP2File::write]
+    /// for the headers exactly as read.
+    #[cfg(feature = "std")]
+    fn write_back<R: io::Read + io::Seek, W: io::Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        builder::write_box_header(writer, self.identifier(), self.length())?;
+        copy_contents(reader, writer, self.offset(), self.length())
+    }
+}
+
+// Copy `length` bytes from `offset` of the reader
+#[cfg(feature = "std")]
+fn copy_contents<R: io::Read + io::Seek, W: io::Write>(
+    reader: &mut R,
+    writer: &mut W,
+    offset: u64,
+    length: u64,
+) -> io::Result<()> {
+    reader.seek(io::SeekFrom::Start(offset))?;
+    let copied = io::copy(&mut io::Read::take(&mut *reader, length), writer)?;
+    if copied != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+// Read `length` bytes of the contents of a box, growing the buffer as they
+// are read so that a corrupt length fails at the end of the input rather
+// than allocating it up front
+fn read_contents<R: io::Read>(
+    reader: &mut R,
+    length: u64,
+) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    let mut contents = vec![];
+    let mut buffer = [0u8; 8192];
+    let mut remaining = length;
+    while remaining > 0 {
+        let size = remaining.min(buffer.len() as u64) as usize;
+        reader.read_exact(&mut buffer[..size])?;
+        contents.extend_from_slice(&buffer[..size]);
+        remaining -= size as u64;
+    }
+    Ok(contents)
+}
+
+// Contents of a box of `length` bytes at the position of the reader,
+// borrowed from `data` if the whole file is held in memory, and otherwise
+// read
+fn decode_contents<'a, R: io::Read + io::Seek>(
+    reader: &mut R,
+    data: Option<&'a [u8]>,
+    box_type: BoxType,
+    length: u64,
+) -> Result<Cow<'a, [u8]>, Box<dyn error::Error>> {
+    let data = match data {
+        Some(data) => data,
+        None => return Ok(Cow::Owned(read_contents(reader, length)?)),
+    };
+    let offset = reader.stream_position()?;
+    let contents = usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(length).ok())
+        .and_then(|(start, length)| data.get(start..start.checked_add(length)?))
+        .ok_or(JP2Error::BoxMalformed { box_type, offset })?;
+    reader.seek(io::SeekFrom::Start(offset + length))?;
+    Ok(Cow::Borrowed(contents))
 }
 
 /// JPEG 2000 Signature box.
@@ -274,11 +575,19 @@ pub trait JBox {
 pub struct SignatureBox {
     length: u64,
     offset: u64,
+    signature: [u8; 4],
 }
 
 impl SignatureBox {
+    /// Contents of the box, as read.
     pub fn signature(&self) -> [u8; 4] {
-        SIGNATURE_MAGIC
+        self.signature
+    }
+
+    /// Whether the box type is `jP\040\040` and the contents are
+    /// `<CR><LF><0x87><LF>`.
+    pub fn is_valid(&self) -> bool {
+        self.identifier() == BOX_TYPE_SIGNATURE && self.signature == SIGNATURE_MAGIC
     }
 }
 
@@ -303,13 +612,11 @@ impl JBox for SignatureBox {
     ) -> Result<(), Box<dyn error::Error>> {
         self.length = 12;
 
-        let mut buffer = [0u8; 4];
-
-        reader.read_exact(&mut buffer)?;
+        reader.read_exact(&mut self.signature)?;
 
-        if buffer != SIGNATURE_MAGIC {
+        if self.signature != SIGNATURE_MAGIC {
             return Err(JP2Error::InvalidSignature {
-                signature: buffer,
+                signature: self.signature,
                 offset: reader.stream_position()?,
             }
             .into());
@@ -321,6 +628,59 @@ impl JBox for SignatureBox {
 
 type CompatibilityList = Vec<[u8; 4]>;
 
+/// Brand of a File Type box, or an entry of its compatibility list.
+///
+/// Values not listed here are [Brand::Other].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Brand {
+    /// ‘jp2\040’, JP2 of T.800 | ISO/IEC 15444-1 Annex I
+    Jp2,
+    /// ‘jpx\040’, JPX of T.801 | ISO/IEC 15444-2 Annex M
+    Jpx,
+    /// ‘jpxb’, baseline JPX of T.801 | ISO/IEC 15444-2 Annex M
+    Jpxb,
+    /// ‘jpm\040’, JPM of T.805 | ISO/IEC 15444-6
+    Jpm,
+    /// ‘mjp2’, Motion JPEG 2000 of T.802 | ISO/IEC 15444-3
+    Mj2,
+    /// ‘jph\040’, HTJ2K of T.814 | ISO/IEC 15444-15
+    Jph,
+    /// 0x0000 0001, a codestream compatibility entry
+    J2kCodestream,
+    Other([u8; 4]),
+}
+
+impl Brand {
+    /// Four bytes of the brand, as in the file.
+    pub fn bytes(&self) -> [u8; 4] {
+        match self {
+            Self::Jp2 => BRAND_JP2,
+            Self::Jpx => BRAND_JPX,
+            Self::Jpxb => BRAND_JPXB,
+            Self::Jpm => BRAND_JPM,
+            Self::Mj2 => BRAND_MJ2,
+            Self::Jph => BRAND_JPH,
+            Self::J2kCodestream => BRAND_J2K_CODESTREAM,
+            Self::Other(bytes) => *bytes,
+        }
+    }
+}
+
+impl From<[u8; 4]> for Brand {
+    fn from(bytes: [u8; 4]) -> Self {
+        match bytes {
+            BRAND_JP2 => Self::Jp2,
+            BRAND_JPX => Self::Jpx,
+            BRAND_JPXB => Self::Jpxb,
+            BRAND_JPM => Self::Jpm,
+            BRAND_MJ2 => Self::Mj2,
+            BRAND_JPH => Self::Jph,
+            BRAND_J2K_CODESTREAM => Self::J2kCodestream,
+            _ => Self::Other(bytes),
+        }
+    }
+}
+
 /// File Type box.
 ///
 /// The File Type box completely defines all of the contents of this file, as
@@ -366,6 +726,11 @@ impl FileTypeBox {
         str::from_utf8(&self.brand).unwrap()
     }
 
+    /// Brand, as a [Brand].
+    pub fn major_brand(&self) -> Brand {
+        Brand::from(self.brand)
+    }
+
     /// Minor version.
     ///
     /// This parameter defines the minor version number of this JP2 specification
@@ -390,7 +755,15 @@ impl FileTypeBox {
     pub fn compatibility_list(&self) -> Vec<String> {
         self.compatibility_list
             .iter()
-            .map(|c| str::from_utf8(c).unwrap().to_owned())
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect()
+    }
+
+    /// Compatibility list, as [Brand] values.
+    pub fn brands(&self) -> Vec<Brand> {
+        self.compatibility_list
+            .iter()
+            .map(|&brand| Brand::from(brand))
             .collect()
     }
 }
@@ -414,9 +787,9 @@ impl JBox for FileTypeBox {
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>> {
         reader.read_exact(&mut self.brand)?;
-        if self.brand == BRAND_JPX {
-            return Err(JP2Error::Unsupported {}.into());
-        } else if self.brand != BRAND_JP2 {
+        // A JPX file with 'jp2 ' in the compatibility list can be read as a
+        // JP2 file, ignoring the ITU T.801 | ISO/IEC 15444-2 extensions
+        if self.brand != BRAND_JP2 && self.brand != BRAND_JPX {
             return Err(JP2Error::InvalidBrand {
                 brand: self.brand,
                 offset: reader.stream_position()?,
@@ -429,7 +802,10 @@ impl JBox for FileTypeBox {
         let mut buffer = [0u8; 4];
 
         // The number of CL fields is determined by the length of this box
-        let mut size = (self.length() - 8) / 4;
+        let mut size = self.length().checked_sub(8).ok_or(JP2Error::BoxMalformed {
+            box_type: BOX_TYPE_FILE_TYPE,
+            offset: self.offset,
+        })? / 4;
         while size > 0 {
             reader.read_exact(&mut buffer)?;
             self.compatibility_list.extend_from_slice(&[buffer]);
@@ -692,46 +1068,405 @@ impl JBox for HeaderSuperBox {
                         offset: reader.stream_position()?,
                         ..Default::default()
                     };
-                    info!("ResolutionBox start at {:?}", resolution_box.offset);
+                    info!("ResolutionBox start at {:?}", resolution_box.offset);
+                    resolution_box.decode(reader)?;
+                    info!("ResolutionBox finish at {:?}", reader.stream_position()?);
+                    self.resolution_box = Some(resolution_box);
+                }
+
+                BoxTypes::Unknown => {
+                    warn!(
+                        "Unknown box type 2 {:?} {:?}",
+                        reader.stream_position(),
+                        box_type
+                    );
+                    break;
+                }
+
+                // End of header but recognised new box type
+                _ => {
+                    reader.seek(io::SeekFrom::Current(-(header_length as i64)))?;
+                    break;
+                }
+            }
+        }
+
+        // There shall be at least one Colour Specification box
+        // within the JP2 Header box.
+        if self.colour_specification_boxes.is_empty() {
+            return Err(JP2Error::BoxMalformed {
+                box_type: BOX_TYPE_IMAGE_HEADER,
+                offset: reader.stream_position()?,
+            }
+            .into());
+        }
+
+        // If the components vary in bit depth, the JP2 Header box shall
+        // also contain a Bits Per Component box.
+        if self.image_header_box.components_bits_varies() && self.bits_per_component_box.is_none() {
+            return Err(JP2Error::BoxMissing {
+                box_type: BOX_TYPE_BITS_PER_COMPONENT,
+            }
+            .into());
+        }
+
+        // TODO
+        // Check that all u16/i16 are correct / big endian is correct
+
+        Ok(())
+    }
+}
+
+impl HeaderSuperBox {
+    /// Colour Specification box to use for the image.
+    ///
+    /// This is the box with the highest precedence. Where several boxes share
+    /// that precedence, the one with the most accurate colourspace
+    /// approximation is chosen, with an unspecified approximation ranked
+    /// last, and any remaining tie goes to the first box in the file.
+    ///
+    /// See ITU T.801 | ISO/IEC 15444-2 Section M.11.7.
+    pub fn preferred_colour_specification(&self) -> Option<&ColourSpecificationBox> {
+        preferred_colour_specification(&self.colour_specification_boxes)
+    }
+
+    /// Enumerated colourspace that the image samples are known to be in.
+    ///
+    /// This is the colourspace of the preferred Colour Specification box, or
+    /// `None` if that is not an enumerated colourspace, or if the Image Header
+    /// box marks the colourspace as unknown. Output should then not assume
+    /// the semantics of that colourspace, such as sRGB.
+    pub fn known_colour_space(&self) -> Option<EnumeratedColourSpaces> {
+        if self.image_header_box.is_colourspace_unknown() {
+            return None;
+        }
+        self.preferred_colour_specification()?
+            .enumerated_colour_space()
+    }
+
+    /// Bit depth of each component.
+    ///
+    /// This is taken from the Bits Per Component box if the components vary
+    /// in bit depth, and otherwise from the Image Header box.
+    pub fn component_bit_depths(&self) -> Vec<BitDepth> {
+        match &self.bits_per_component_box {
+            Some(bits_per_component_box) if self.image_header_box.components_bits_varies() => {
+                bits_per_component_box.bits_per_component()
+            }
+            _ => vec![
+                BitDepth::new(self.image_header_box.components_bits[0]);
+                self.image_header_box.components_num() as usize
+            ],
+        }
+    }
+}
+
+/// Codestream Header box.
+///
+/// This superbox, defined in ITU-T T.801 | ISO/IEC 15444-2, holds the header
+/// information for a single codestream in a JPX file. The codestream header
+/// boxes are associated with the codestreams in the order they appear in the
+/// file.
+///
+/// It may contain an Image Header box, a Bits Per Component box, a Palette box
+/// and a Component Mapping box. Where a box is absent, the value from the JP2
+/// Header box applies. Other boxes, such as the ROI Description box, are
+/// recorded as [UnknownBox] instances.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section M.11.5.
+#[derive(Debug, Default)]
+pub struct CodestreamHeaderSuperBox {
+    length: u64,
+    offset: u64,
+    pub image_header_box: Option<ImageHeaderBox>,
+    pub bits_per_component_box: Option<BitsPerComponentBox>,
+    pub palette_box: Option<PaletteBox>,
+    pub component_mapping_box: Option<ComponentMappingBox>,
+    pub unknown_boxes: Vec<UnknownBox>,
+}
+
+impl JBox for CodestreamHeaderSuperBox {
+    // The type of the Codestream Header box shall be ‘jpch’ (0x6A70 6368)
+    fn identifier(&self) -> BoxType {
+        BOX_TYPE_CODESTREAM_HEADER
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn decode<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let end = self.offset + self.length;
+        while reader.stream_position()? < end {
+            let BoxHeader {
+                box_length,
+                box_type,
+                header_length: _,
+            } = decode_box_header(reader)?;
+            let offset = reader.stream_position()?;
+
+            match BoxTypes::new(box_type) {
+                BoxTypes::ImageHeader => {
+                    if self.image_header_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_IMAGE_HEADER,
+                            offset,
+                        }
+                        .into());
+                    }
+                    let mut image_header_box = ImageHeaderBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    image_header_box.decode(reader)?;
+                    self.image_header_box = Some(image_header_box);
+                }
+                BoxTypes::BitsPerComponent => {
+                    if self.bits_per_component_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_BITS_PER_COMPONENT,
+                            offset,
+                        }
+                        .into());
+                    }
+                    // The Image Header box for this codestream may be in the
+                    // JP2 Header box, so take the number of components from
+                    // the box length.
+                    let components_num =
+                        u16::try_from(box_length).map_err(|_| JP2Error::BoxMalformed {
+                            box_type: BOX_TYPE_BITS_PER_COMPONENT,
+                            offset,
+                        })?;
+                    let mut bits_per_component_box = BitsPerComponentBox {
+                        components_num,
+                        bits_per_component: vec![0; components_num as usize],
+                        length: box_length,
+                        offset,
+                    };
+                    bits_per_component_box.decode(reader)?;
+                    self.bits_per_component_box = Some(bits_per_component_box);
+                }
+                BoxTypes::Palette => {
+                    if self.palette_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_PALETTE,
+                            offset,
+                        }
+                        .into());
+                    }
+                    let mut palette_box = PaletteBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    palette_box.decode(reader)?;
+                    self.palette_box = Some(palette_box);
+                }
+                BoxTypes::ComponentMapping => {
+                    if self.component_mapping_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_COMPONENT_MAPPING,
+                            offset,
+                        }
+                        .into());
+                    }
+                    let mut component_mapping_box = ComponentMappingBox {
+                        length: box_length,
+                        offset,
+                        mapping: vec![],
+                    };
+                    component_mapping_box.decode(reader)?;
+                    self.component_mapping_box = Some(component_mapping_box);
+                }
+                _ => {
+                    let mut unknown_box = UnknownBox {
+                        box_type,
+                        length: box_length,
+                        offset,
+                    };
+                    unknown_box.decode(reader)?;
+                    self.unknown_boxes.push(unknown_box);
+                }
+            }
+            reader.seek(io::SeekFrom::Start(offset + box_length))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compositing Layer Header box.
+///
+/// This superbox, defined in ITU-T T.801 | ISO/IEC 15444-2, holds the header
+/// information for a single compositing layer in a JPX file.
+///
+/// The Colour Specification boxes of the layer are held within a Colour Group
+/// box, and are listed here in the order they appear. The layer may also
+/// contain a Channel Definition box and a Resolution box. Other boxes, such as
+/// the Opacity box and Codestream Registration box, are recorded as
+/// [UnknownBox] instances.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section M.11.9.
+#[derive(Debug, Default)]
+pub struct CompositingLayerHeaderSuperBox {
+    length: u64,
+    offset: u64,
+    pub colour_specification_boxes: Vec<ColourSpecificationBox>,
+    pub channel_definition_box: Option<ChannelDefinitionBox>,
+    pub resolution_box: Option<ResolutionSuperBox>,
+    pub unknown_boxes: Vec<UnknownBox>,
+}
+
+impl JBox for CompositingLayerHeaderSuperBox {
+    // The type of the Compositing Layer Header box shall be ‘jplh’ (0x6A70 6C68)
+    fn identifier(&self) -> BoxType {
+        BOX_TYPE_COMPOSITING_LAYER_HEADER
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn decode<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let end = self.offset + self.length;
+        while reader.stream_position()? < end {
+            let BoxHeader {
+                box_length,
+                box_type,
+                header_length: _,
+            } = decode_box_header(reader)?;
+            let offset = reader.stream_position()?;
+
+            match BoxTypes::new(box_type) {
+                BoxTypes::ColourGroup => {
+                    self.decode_colour_group(reader, offset + box_length)?;
+                }
+                BoxTypes::ChannelDefinition => {
+                    if self.channel_definition_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_CHANNEL_DEFINITION,
+                            offset,
+                        }
+                        .into());
+                    }
+                    let mut channel_definition_box = ChannelDefinitionBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    channel_definition_box.decode(reader)?;
+                    self.channel_definition_box = Some(channel_definition_box);
+                }
+                BoxTypes::Resolution => {
+                    if self.resolution_box.is_some() {
+                        return Err(JP2Error::BoxDuplicate {
+                            box_type: BOX_TYPE_RESOLUTION,
+                            offset,
+                        }
+                        .into());
+                    }
+                    let mut resolution_box = ResolutionSuperBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
                     resolution_box.decode(reader)?;
-                    info!("ResolutionBox finish at {:?}", reader.stream_position()?);
                     self.resolution_box = Some(resolution_box);
                 }
-
-                BoxTypes::Unknown => {
-                    warn!(
-                        "Unknown box type 2 {:?} {:?}",
-                        reader.stream_position(),
-                        box_type
-                    );
-                    break;
-                }
-
-                // End of header but recognised new box type
                 _ => {
-                    reader.seek(io::SeekFrom::Current(-(header_length as i64)))?;
-                    break;
+                    let mut unknown_box = UnknownBox {
+                        box_type,
+                        length: box_length,
+                        offset,
+                    };
+                    unknown_box.decode(reader)?;
+                    self.unknown_boxes.push(unknown_box);
                 }
             }
+            reader.seek(io::SeekFrom::Start(offset + box_length))?;
         }
 
-        // There shall be at least one Colour Specification box
-        // within the JP2 Header box.
-        if self.colour_specification_boxes.is_empty() {
-            return Err(JP2Error::BoxMalformed {
-                box_type: BOX_TYPE_IMAGE_HEADER,
-                offset: reader.stream_position()?,
+        Ok(())
+    }
+}
+
+impl CompositingLayerHeaderSuperBox {
+    /// Colour Specification box to use for the compositing layer.
+    ///
+    /// The selection is the same as for
+    /// [HeaderSuperBox::preferred_colour_specification].
+    pub fn preferred_colour_specification(&self) -> Option<&ColourSpecificationBox> {
+        preferred_colour_specification(&self.colour_specification_boxes)
+    }
+
+    // The Colour Group box shall contain only Colour Specification boxes
+    fn decode_colour_group<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        end: u64,
+    ) -> Result<(), Box<dyn error::Error>> {
+        while reader.stream_position()? < end {
+            let BoxHeader {
+                box_length,
+                box_type,
+                header_length: _,
+            } = decode_box_header(reader)?;
+            let offset = reader.stream_position()?;
+            if box_type != BOX_TYPE_COLOUR_SPECIFICATION {
+                return Err(JP2Error::BoxUnexpected { box_type, offset }.into());
             }
-            .into());
+            let mut colour_specification_box = ColourSpecificationBox {
+                length: box_length,
+                offset,
+                method: [0; 1],
+                precedence: [0; 1],
+                colourspace_approximation: [0; 1],
+                enumerated_colour_space: ENUMERATED_COLOUR_SPACE_UNKNOWN,
+                restricted_icc_profile: vec![],
+            };
+            colour_specification_box.decode(reader)?;
+            self.colour_specification_boxes
+                .push(colour_specification_box);
+            reader.seek(io::SeekFrom::Start(offset + box_length))?;
         }
 
-        // TODO
-        // Check that all u16/i16 are correct / big endian is correct
-
         Ok(())
     }
 }
 
+// Higher precedence first, then APPROX of 1 (accurate) through 4 (poor), then
+// 0 (not specified) and reserved values. max_by_key returns the last of equal
+// elements, so search in reverse to prefer the first box in the file.
+fn preferred_colour_specification(
+    colour_specification_boxes: &[ColourSpecificationBox],
+) -> Option<&ColourSpecificationBox> {
+    colour_specification_boxes
+        .iter()
+        .rev()
+        .max_by_key(|colour_specification_box| {
+            let approximation = match colour_specification_box.colourspace_approximation() {
+                approximation @ 1..=4 => 5 - approximation,
+                _ => 0,
+            };
+            (colour_specification_box.precedence(), approximation)
+        })
+}
+
 const COMPRESSION_TYPE_WAVELET: u8 = 7;
 
 /// Image Header box.
@@ -834,6 +1569,9 @@ impl ImageHeaderBox {
     /// The high-bit indicates whether the components are signed or unsigned.
     /// If the high-bit is 1, then the components contain signed values.
     /// If the high-bit is 0, then the components contain unsigned values.
+    ///
+    /// If the components vary in bit depth, 255 is returned. See
+    /// [components_bits_varies](fn@ImageHeaderBox::components_bits_varies).
     pub fn components_bits(&self) -> u8 {
         // 1111 1111 (255) Components vary in bit depth
         // 1xxx xxxx (128 - 254) Components are signed values
@@ -854,6 +1592,16 @@ impl ImageHeaderBox {
         }
     }
 
+    /// Whether the components vary in bit depth.
+    ///
+    /// If true, [components_bits](fn@ImageHeaderBox::components_bits) is 255
+    /// and the bit depths are given by the Bits Per Component box. See
+    /// [HeaderSuperBox::component_bit_depths] for the bit depth of each
+    /// component in either case.
+    pub fn components_bits_varies(&self) -> bool {
+        self.components_bits[0] == 255
+    }
+
     /// Signedness of the values.
     ///
     /// See [components_bits](fn@ImageHeaderBox::components_bits) for the BPC encoding.
@@ -907,6 +1655,15 @@ impl ImageHeaderBox {
         self.colourspace_unknown[0]
     }
 
+    /// Whether the colourspace of the image data is not known, from an UnkC
+    /// value of 1.
+    ///
+    /// The colourspace given by the Colour Specification boxes may then not
+    /// be the actual colourspace of the samples.
+    pub fn is_colourspace_unknown(&self) -> bool {
+        self.colourspace_unknown[0] == 1
+    }
+
     /// Intellectual Property.
     ///
     /// This parameter indicates whether this JP2 file contains intellectual
@@ -1200,15 +1957,17 @@ impl JBox for ChannelDefinitionBox {
     }
 }
 
-const COMPONENT_MAP_TYPE_DIRECT: [u8; 1] = [1];
-const COMPONENT_MAP_TYPE_PALETTE: [u8; 1] = [2];
+const COMPONENT_MAP_TYPE_DIRECT: [u8; 1] = [0];
+const COMPONENT_MAP_TYPE_PALETTE: [u8; 1] = [1];
 
 /// Type of component mapping.
 ///
 /// The Component Mapping box supports both direct mapping and indirect
 /// (palette) mapping. This enumeration represents which kind of
 /// mapping is used.
-#[derive(Debug)]
+///
+/// For more information, see ISO/IEC 15444-1 / ITU T-800 Table I.15.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ComponentMapType {
     /// Direct use.
     ///
@@ -1286,7 +2045,14 @@ impl ComponentMap {
     /// This specifies how this channel is generated from the actual
     /// components in the file. This field is encoded as a 1-byte unsigned
     /// integer, and represented here as an enumerated value.
-    pub fn mapping_type(&self) -> u8 {
+    pub fn mapping_type(&self) -> ComponentMapType {
+        self.mapping_type
+    }
+
+    /// Mapping type (MTYP<sup>i</sup>) as unsigned value.
+    ///
+    /// This is 0 for direct use of the component, and 1 for palette mapping.
+    pub fn mapping_type_u8(&self) -> u8 {
         match self.mapping_type {
             ComponentMapType::Direct => COMPONENT_MAP_TYPE_DIRECT[0],
             ComponentMapType::Palette => COMPONENT_MAP_TYPE_PALETTE[0],
@@ -1387,7 +2153,7 @@ impl JBox for ComponentMappingBox {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// Bit depth variations.
 pub enum BitDepth {
     /// Signed values.
@@ -1510,12 +2276,31 @@ impl PaletteBox {
     ///
     /// The entry_index specifies the row, and the column_index specifies the
     /// column.
+    ///
+    /// Values of a column with a signed bit depth are sign extended to 32
+    /// bits, so can be cast to `i32`.
     pub fn entry(&self, entry_index: u16, column_index: u8) -> Option<&u32> {
         match &self.entries.get(entry_index as usize) {
             Some(entries) => entries.get(column_index as usize),
             None => None,
         }
     }
+
+    /// The rows of the palette, in entry order.
+    ///
+    /// Each row has one value for each component column.
+    pub fn rows(&self) -> impl Iterator<Item = &[u32]> {
+        self.entries.iter().map(|row| row.as_slice())
+    }
+
+    /// The values of a single component column, in entry order.
+    ///
+    /// This is empty if the column_index is not less than num_components().
+    pub fn entries_for_component(&self, column_index: u8) -> impl Iterator<Item = u32> + '_ {
+        self.entries
+            .iter()
+            .filter_map(move |row| row.get(column_index as usize).copied())
+    }
 }
 
 impl JBox for PaletteBox {
@@ -1548,25 +2333,39 @@ impl JBox for PaletteBox {
             reader.read_exact(&mut bit_depth_bytes)?;
             self.bit_depths.push(BitDepth::new(bit_depth_bytes[0]));
         }
+        for bit_depth in &self.bit_depths {
+            // Bit depths are 1 to 38 bits
+            if matches!(bit_depth, BitDepth::Reserved { .. }) || bit_depth.value() > 38 {
+                return Err(JP2Error::BoxMalformed {
+                    box_type: BOX_TYPE_PALETTE,
+                    offset: self.offset,
+                }
+                .into());
+            }
+            if bit_depth.value() > 32 {
+                return Err(JP2Error::PaletteBitDepthUnsupported {
+                    bit_depth: bit_depth.value(),
+                    offset: self.offset,
+                }
+                .into());
+            }
+        }
         for _ in 0..num_entries {
             let mut entry_components = Vec::<u32>::with_capacity(num_palette_columns as usize);
-            for i in 0..num_palette_columns as usize {
-                let num_bytes = self.bit_depths[i].num_bytes() as usize;
-                let value = match num_bytes {
-                    1 => {
-                        let mut value_bytes = [0u8; 1];
-                        reader.read_exact(&mut value_bytes)?;
-                        u8::from_be_bytes(value_bytes) as u32
+            for bit_depth in &self.bit_depths {
+                // Each value is stored in the low bits of the fewest whole
+                // bytes that hold the bit depth
+                let num_bytes = bit_depth.num_bytes() as usize;
+                let mut value_bytes = [0u8; 4];
+                reader.read_exact(&mut value_bytes[4 - num_bytes..])?;
+                let bits = u32::from(bit_depth.value());
+                let mask = u32::MAX >> (32 - bits);
+                let mut value = u32::from_be_bytes(value_bytes) & mask;
+                if let BitDepth::Signed { .. } = bit_depth {
+                    if value >> (bits - 1) != 0 {
+                        value |= !mask;
                     }
-                    2 => {
-                        let mut value_bytes = [0u8; 2];
-                        reader.read_exact(&mut value_bytes)?;
-                        u16::from_be_bytes(value_bytes) as u32
-                    }
-                    _ => unimplemented!(
-                        "more than 16 bit data is not yet supported for palette entries"
-                    ),
-                };
+                }
                 entry_components.push(value);
             }
             self.entries.push(entry_components);
@@ -1696,7 +2495,7 @@ const ENUMERATED_COLOUR_SPACE_SRGB: EnumeratedColourSpace = [0, 0, 0, 16];
 const ENUMERATED_COLOUR_SPACE_GREYSCALE: EnumeratedColourSpace = [0, 0, 0, 17];
 const ENUMERATED_COLOUR_SPACE_SYCC: EnumeratedColourSpace = [0, 0, 0, 18];
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// Enumerated colour space values (EnumCS)
 ///
 /// See ISO/IEC 15444-1:2024 Table I.10.
@@ -1707,9 +2506,15 @@ pub enum EnumeratedColourSpaces {
     /// sRGB as defined by IEC 61966-2-1 with Lmin<sub>i</sub>=0 and Lmax<sub>i</sub>=255.
     /// This colourspace shall be used with channels carrying unsigned values only.
     sRGB,
+
+    /// Greyscale
+    ///
+    /// A greyscale space where image luminance is related to code values using the sRGB non-linearity
+    /// given in IEC 61966-2-1 with Lmin<sub>i</sub>=0 and Lmax<sub>i</sub>=255.
+    /// This colourspace shall be used with channels carrying unsigned values only.
     Greyscale,
-    #[allow(non_camel_case_types)]
 
+    #[allow(non_camel_case_types)]
     /// sYCC
     ///
     /// sYCC as defined by IEC 61966-2-1 / Amd.1 with Lmin<sub>i</sub>=0 and Lmax<sub>i</sub>=255.
@@ -1734,6 +2539,40 @@ impl EnumeratedColourSpaces {
             _ => EnumeratedColourSpaces::Reserved,
         }
     }
+
+    /// Number of colour channels the colourspace is defined over.
+    ///
+    /// This is 3 for sRGB and sYCC, 1 for greyscale, and `None` for reserved
+    /// values. Opacity channels are not included.
+    pub fn channels_expected(&self) -> Option<u16> {
+        match self {
+            EnumeratedColourSpaces::sRGB => Some(3),
+            EnumeratedColourSpaces::Greyscale => Some(1),
+            EnumeratedColourSpaces::sYCC => Some(3),
+            EnumeratedColourSpaces::Reserved => None,
+        }
+    }
+
+    /// Bit depth the colourspace is defined for.
+    ///
+    /// sRGB, greyscale and sYCC are each defined with Lmax<sub>i</sub>=255, so
+    /// 8 bits. Samples of other bit depths, such as 16-bit sYCC, are allowed
+    /// but atypical, and are usually scaled to this range for output. `None`
+    /// for reserved values.
+    pub fn nominal_bit_depth(&self) -> Option<u8> {
+        match self {
+            EnumeratedColourSpaces::sRGB => Some(8),
+            EnumeratedColourSpaces::Greyscale => Some(8),
+            EnumeratedColourSpaces::sYCC => Some(8),
+            EnumeratedColourSpaces::Reserved => None,
+        }
+    }
+
+    /// Whether the value is reserved, or the colourspace was not signalled
+    /// with the enumerated method.
+    pub fn is_reserved(&self) -> bool {
+        *self == EnumeratedColourSpaces::Reserved
+    }
 }
 
 impl fmt::Display for EnumeratedColourSpaces {
@@ -1801,6 +2640,9 @@ impl ColourSpecificationBox {
     /// This field is reserved for ISO use and the value shall be set to zero;
     /// however, conforming readers shall ignore the value of this field.
     ///
+    /// In ITU T.801 | ISO/IEC 15444-2, larger values indicate higher
+    /// precedence. See [HeaderSuperBox::preferred_colour_specification].
+    ///
     /// This field is specified as a signed 1 byte integer
     pub fn precedence(&self) -> i8 {
         self.precedence[0] as i8
@@ -1820,6 +2662,19 @@ impl ColourSpecificationBox {
         self.colourspace_approximation[0]
     }
 
+    /// Whether the colourspace approximation conforms to T.800 | ISO/IEC
+    /// 15444-1.
+    ///
+    /// JP2 files, with the `jp2 ` brand, shall set the approximation to zero.
+    /// This returns false for any other value.
+    ///
+    /// Files with the `jpx ` brand may also use the values 1 (accurate) to 4
+    /// (poor) defined by T.801 | ISO/IEC 15444-2 Section M.11.7.2, and those
+    /// are not an error in a JPX file, even though this returns false.
+    pub fn is_approximation_conformant(&self) -> bool {
+        self.colourspace_approximation() == 0
+    }
+
     /// Enumerated colourspace.
     ///
     /// This field specifies the colourspace of the image using integer codes.
@@ -1852,6 +2707,9 @@ impl ColourSpecificationBox {
     /// the Monochrome Input Profile class, the Three-Component Matrix-Based Input
     /// Profile class, the Monochrome Display profile type, or the Three-Component
     /// Matrix-Based Display profile type as defined in ISO 15076-1.
+    ///
+    /// The tags needed for colour management can be read from the profile with
+    /// [RestrictedIccProfile::decode].
     pub fn restricted_icc_profile(&self) -> Option<&Vec<u8>> {
         if self.method() == ColourSpecificationMethods::RestrictedICCProfile {
             Some(&self.restricted_icc_profile)
@@ -1936,9 +2794,11 @@ impl JBox for ColourSpecificationBox {
             //
             // If the value of METH is 2, then the PROFILE field shall immediately follow the APPROX field and the PROFILE field shall be the last field in the box.
             ColourSpecificationMethods::RestrictedICCProfile => {
-                self.restricted_icc_profile = vec![0; self.length as usize - 3];
-
-                reader.read_exact(&mut self.restricted_icc_profile)?;
+                let length = self.length.checked_sub(3).ok_or(JP2Error::BoxMalformed {
+                    box_type: BOX_TYPE_COLOUR_SPECIFICATION,
+                    offset: self.offset,
+                })?;
+                self.restricted_icc_profile = read_contents(reader, length)?;
                 debug!("Restricted ICC Profile");
             }
 
@@ -2111,6 +2971,22 @@ impl IntellectualPropertyBox {
     pub fn format(&self) -> String {
         str::from_utf8(&self.data).unwrap().to_string()
     }
+
+    /// Rights described by the IPR XML of the box.
+    ///
+    /// The XML is expected to have a `jp:IPR` root element, as defined in
+    /// ITU-T T.801 | ISO/IEC 15444-2 Annex N. Malformed XML, or XML with a
+    /// different root element, is an error.
+    #[cfg(feature = "xml")]
+    pub fn rights(&self) -> Result<IntellectualPropertyRights, JP2Error> {
+        str::from_utf8(&self.data)
+            .ok()
+            .and_then(IntellectualPropertyRights::parse)
+            .ok_or(JP2Error::BoxMalformed {
+                box_type: BOX_TYPE_INTELLECTUAL_PROPERTY,
+                offset: self.offset,
+            })
+    }
 }
 
 impl JBox for IntellectualPropertyBox {
@@ -2131,8 +3007,7 @@ impl JBox for IntellectualPropertyBox {
         &mut self,
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>> {
-        self.data = vec![0; self.length as usize];
-        reader.read_exact(&mut self.data)?;
+        self.data = read_contents(reader, self.length)?;
         Ok(())
     }
 }
@@ -2149,20 +3024,45 @@ impl JBox for IntellectualPropertyBox {
 ///
 /// See ISO/IEC 15444-1:2024 Section I.7.1 for more details on this box.
 #[derive(Debug, Default)]
-pub struct XMLBox {
+pub struct XMLBox<'a> {
     length: u64,
     offset: u64,
-    xml: Vec<u8>,
+    xml: Cow<'a, [u8]>,
 }
 
-impl XMLBox {
+impl<'a> XMLBox<'a> {
     /// Get the XML body as a UTF-8 string.
     pub fn format(&self) -> String {
         str::from_utf8(&self.xml).unwrap().to_string()
     }
+
+    /// Get the XML body as bytes.
+    ///
+    /// For a file decoded with [decode_jp2_slice], this is borrowed from the
+    /// input.
+    pub fn xml(&self) -> &[u8] {
+        &self.xml
+    }
+
+    fn decode_from<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        data: Option<&'a [u8]>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.xml = decode_contents(reader, data, BOX_TYPE_XML, self.length)?;
+        Ok(())
+    }
+
+    fn into_owned(self) -> XMLBox<'static> {
+        XMLBox {
+            length: self.length,
+            offset: self.offset,
+            xml: Cow::Owned(self.xml.into_owned()),
+        }
+    }
 }
 
-impl JBox for XMLBox {
+impl JBox for XMLBox<'_> {
     // The type of an XML box is ‘xml\040’ (0x786D 6C20).
     fn identifier(&self) -> BoxType {
         BOX_TYPE_XML
@@ -2180,9 +3080,19 @@ impl JBox for XMLBox {
         &mut self,
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>> {
-        self.xml = vec![0; self.length as usize];
-        reader.read_exact(&mut self.xml)?;
-        Ok(())
+        self.decode_from(reader, None)
+    }
+
+    // The box holds its contents, and may have been added after the file
+    // was read
+    #[cfg(feature = "std")]
+    fn write_back<R: io::Read + io::Seek, W: io::Write>(
+        &self,
+        _reader: &mut R,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        builder::write_box_header(writer, self.identifier(), self.length)?;
+        writer.write_all(&self.xml)
     }
 }
 
@@ -2196,14 +3106,14 @@ impl JBox for XMLBox {
 ///
 /// See ISO/IEC 15444-1:2024 Section I.7.2 for more details on this box.
 #[derive(Debug, Default)]
-pub struct UUIDBox {
+pub struct UUIDBox<'a> {
     length: u64,
     offset: u64,
     uuid: [u8; 16],
-    data: Vec<u8>,
+    data: Cow<'a, [u8]>,
 }
 
-impl UUIDBox {
+impl<'a> UUIDBox<'a> {
     /// Get the UUID for the box.
     ///
     /// This field contains a 16-byte UUID as specified by ISO/IEC 11578. The
@@ -2218,12 +3128,41 @@ impl UUIDBox {
     /// This field contains vendor-specific information. The format of this information
     /// is defined outside of the scope of ISO/IEC 15444-1, but is indicated by the
     /// value of the UUID field.
-    pub fn data(&self) -> &Vec<u8> {
+    ///
+    /// For a file decoded with [decode_jp2_slice], this is borrowed from the
+    /// input.
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
+
+    fn decode_from<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        data: Option<&'a [u8]>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let length =
+            self.length
+                .checked_sub(self.uuid.len() as u64)
+                .ok_or(JP2Error::BoxMalformed {
+                    box_type: BOX_TYPE_UUID,
+                    offset: self.offset,
+                })?;
+        reader.read_exact(&mut self.uuid)?;
+        self.data = decode_contents(reader, data, BOX_TYPE_UUID, length)?;
+        Ok(())
+    }
+
+    fn into_owned(self) -> UUIDBox<'static> {
+        UUIDBox {
+            length: self.length,
+            offset: self.offset,
+            uuid: self.uuid,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
 }
 
-impl JBox for UUIDBox {
+impl JBox for UUIDBox<'_> {
     // The type of a UUID box shall be ‘uuid’ (0x7575 6964).
     fn identifier(&self) -> BoxType {
         BOX_TYPE_UUID
@@ -2241,11 +3180,20 @@ impl JBox for UUIDBox {
         &mut self,
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>> {
-        reader.read_exact(&mut self.uuid)?;
-        self.data = vec![0; self.length as usize - self.uuid.len()];
-        reader.read_exact(&mut self.data)?;
+        self.decode_from(reader, None)
+    }
 
-        Ok(())
+    // The box holds its contents, and may have replaced one after the file
+    // was read
+    #[cfg(feature = "std")]
+    fn write_back<R: io::Read + io::Seek, W: io::Write>(
+        &self,
+        _reader: &mut R,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        builder::write_box_header(writer, self.identifier(), self.length)?;
+        writer.write_all(&self.uuid)?;
+        writer.write_all(&self.data)
     }
 }
 
@@ -2450,25 +3398,221 @@ impl DataEntryURLBox {
         &self.flags
     }
 
-    /// Location (LOC).
-    ///
-    /// This field specifies the URL of the additional information associated
-    /// with the UUIDs contained in the UUID List box within the same UUID Info
-    /// superbox.
-    ///
-    /// The URL is encoded as a null terminated string of ISO/IEC 646 (effectively ASCII)
-    /// characters, but this accessor function converts it a standard Rust string without
-    /// the null terminator.
-    pub fn location(&self) -> Result<&str, str::Utf8Error> {
-        let ascii = str::from_utf8(&self.location)?;
-        Ok(ascii.trim_matches(char::from(0)))
+    /// Location (LOC).
+    ///
+    /// This field specifies the URL of the additional information associated
+    /// with the UUIDs contained in the UUID List box within the same UUID Info
+    /// superbox.
+    ///
+    /// The URL is encoded as a null terminated string of ISO/IEC 646 (effectively ASCII)
+    /// characters, but this accessor function converts it a standard Rust string without
+    /// the null terminator.
+    pub fn location(&self) -> Result<&str, str::Utf8Error> {
+        let ascii = str::from_utf8(&self.location)?;
+        Ok(ascii.trim_matches(char::from(0)))
+    }
+}
+
+impl JBox for DataEntryURLBox {
+    // The type of a Data Entry URL box shall be 'url\040' (0x7572 6C20).
+    fn identifier(&self) -> BoxType {
+        BOX_TYPE_DATA_ENTRY_URL
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn decode<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), Box<dyn error::Error>> {
+        reader.read_exact(&mut self.version)?;
+        reader.read_exact(&mut self.flags)?;
+
+        // location
+        let mut size = self.length().checked_sub(4).ok_or(JP2Error::BoxMalformed {
+            box_type: BOX_TYPE_DATA_ENTRY_URL,
+            offset: self.offset,
+        })?;
+
+        let mut buffer = [0u8; 1];
+        while size > 0 {
+            reader.read_exact(&mut buffer)?;
+            self.location.extend_from_slice(&buffer);
+            size -= 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Association box (superbox).
+///
+/// This superbox, defined in ITU-T T.801 | ISO/IEC 15444-2, associates the
+/// boxes it holds with each other. Typically the first box is a Label box,
+/// naming the metadata in the XML, UUID or nested Association boxes that
+/// follow, as used by GMLJP2 to hold its GML.
+///
+/// Other boxes, such as the Number List box, are recorded as [UnknownBox]
+/// instances.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section M.11.3.
+#[derive(Debug, Default)]
+pub struct AssociationSuperBox<'a> {
+    length: u64,
+    offset: u64,
+    pub label_box: Option<LabelBox>,
+    pub xml_boxes: Vec<XMLBox<'a>>,
+    pub uuid_boxes: Vec<UUIDBox<'a>>,
+    pub association_boxes: Vec<AssociationSuperBox<'a>>,
+    pub unknown_boxes: Vec<UnknownBox>,
+}
+
+impl<'a> AssociationSuperBox<'a> {
+    /// Text of the Label box, if the association has one.
+    pub fn label(&self) -> Option<&str> {
+        self.label_box.as_ref().map(LabelBox::label)
+    }
+
+    fn decode_from<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        data: Option<&'a [u8]>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let end = self.offset + self.length;
+        while reader.stream_position()? < end {
+            let BoxHeader {
+                box_length,
+                box_type,
+                header_length: _,
+            } = decode_box_header(reader)?;
+            let offset = reader.stream_position()?;
+
+            match BoxTypes::new(box_type) {
+                BoxTypes::Label if self.label_box.is_none() => {
+                    let mut label_box = LabelBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    label_box.decode(reader)?;
+                    self.label_box = Some(label_box);
+                }
+                BoxTypes::Xml => {
+                    let mut xml_box = XMLBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    xml_box.decode_from(reader, data)?;
+                    self.xml_boxes.push(xml_box);
+                }
+                BoxTypes::Uuid => {
+                    let mut uuid_box = UUIDBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    uuid_box.decode_from(reader, data)?;
+                    self.uuid_boxes.push(uuid_box);
+                }
+                BoxTypes::Association => {
+                    let mut association_box = AssociationSuperBox {
+                        length: box_length,
+                        offset,
+                        ..Default::default()
+                    };
+                    association_box.decode_from(reader, data)?;
+                    self.association_boxes.push(association_box);
+                }
+                _ => {
+                    let mut unknown_box = UnknownBox {
+                        box_type,
+                        length: box_length,
+                        offset,
+                    };
+                    unknown_box.decode(reader)?;
+                    self.unknown_boxes.push(unknown_box);
+                }
+            }
+            reader.seek(io::SeekFrom::Start(offset + box_length))?;
+        }
+
+        Ok(())
+    }
+
+    fn into_owned(self) -> AssociationSuperBox<'static> {
+        AssociationSuperBox {
+            length: self.length,
+            offset: self.offset,
+            label_box: self.label_box,
+            xml_boxes: self.xml_boxes.into_iter().map(XMLBox::into_owned).collect(),
+            uuid_boxes: self
+                .uuid_boxes
+                .into_iter()
+                .map(UUIDBox::into_owned)
+                .collect(),
+            association_boxes: self
+                .association_boxes
+                .into_iter()
+                .map(AssociationSuperBox::into_owned)
+                .collect(),
+            unknown_boxes: self.unknown_boxes,
+        }
+    }
+}
+
+impl JBox for AssociationSuperBox<'_> {
+    // The type of the Association box shall be ‘asoc’ (0x6173 6F63)
+    fn identifier(&self) -> BoxType {
+        BOX_TYPE_ASSOCIATION
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn decode<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.decode_from(reader, None)
+    }
+}
+
+/// Label box.
+///
+/// This box, defined in ITU-T T.801 | ISO/IEC 15444-2, holds a textual label,
+/// usually naming the other boxes of its Association box.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section M.11.13.
+#[derive(Debug, Default)]
+pub struct LabelBox {
+    length: u64,
+    offset: u64,
+    label: String,
+}
+
+impl LabelBox {
+    /// The label, as UTF-8 text.
+    pub fn label(&self) -> &str {
+        &self.label
     }
 }
 
-impl JBox for DataEntryURLBox {
-    // The type of a Data Entry URL box shall be 'url\040' (0x7572 6C20).
+impl JBox for LabelBox {
+    // The type of the Label box shall be ‘lbl\040’ (0x6C62 6C20)
     fn identifier(&self) -> BoxType {
-        BOX_TYPE_DATA_ENTRY_URL
+        BOX_TYPE_LABEL
     }
 
     fn length(&self) -> u64 {
@@ -2483,19 +3627,11 @@ impl JBox for DataEntryURLBox {
         &mut self,
         reader: &mut R,
     ) -> Result<(), Box<dyn error::Error>> {
-        reader.read_exact(&mut self.version)?;
-        reader.read_exact(&mut self.flags)?;
-
-        // location
-        let mut size = self.length() - 4;
-
-        let mut buffer = [0u8; 1];
-        while size > 0 {
-            reader.read_exact(&mut buffer)?;
-            self.location.extend_from_slice(&buffer);
-            size -= 1;
-        }
-
+        let label = read_contents(reader, self.length)?;
+        self.label = String::from_utf8(label).map_err(|_| JP2Error::BoxMalformed {
+            box_type: BOX_TYPE_LABEL,
+            offset: self.offset,
+        })?;
         Ok(())
     }
 }
@@ -2522,6 +3658,40 @@ impl JBox for DataEntryURLBox {
 pub struct ContiguousCodestreamBox {
     length: u64,
     pub offset: u64,
+    truncated: bool,
+}
+
+impl ContiguousCodestreamBox {
+    /// Whether the box extends past the end of the file, such as for a
+    /// partially downloaded file.
+    ///
+    /// The length is then the number of bytes available rather than the
+    /// length of the box.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// SHA-256 hash of the codestream.
+    ///
+    /// This covers the contents of the box only, so the same codestream in
+    /// different files, or in a box with an XLBox field, has the same hash.
+    /// The reader must be the one the box was decoded from.
+    #[cfg(feature = "sha2")]
+    pub fn content_hash<R: io::Read + io::Seek>(&self, reader: &mut R) -> io::Result<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        reader.seek(io::SeekFrom::Start(self.offset))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 8192];
+        let mut remaining = self.length;
+        while remaining > 0 {
+            let size = remaining.min(buffer.len() as u64) as usize;
+            reader.read_exact(&mut buffer[..size])?;
+            hasher.update(&buffer[..size]);
+            remaining -= size as u64;
+        }
+        Ok(hasher.finalize().into())
+    }
 }
 
 impl JBox for ContiguousCodestreamBox {
@@ -2538,6 +3708,55 @@ impl JBox for ContiguousCodestreamBox {
         self.offset
     }
 
+    fn decode<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let end = reader.seek(io::SeekFrom::End(0))?;
+        let available = end.saturating_sub(self.offset);
+        if self.length == 0 {
+            self.length = available;
+        } else if self.length > available {
+            warn!(
+                "ContiguousCodestreamBox has {} of {} bytes",
+                available, self.length
+            );
+            self.length = available;
+            self.truncated = true;
+        } else {
+            reader.seek(io::SeekFrom::Start(self.offset + self.length))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Box of a type that is not recognised.
+///
+/// Conforming readers shall ignore boxes they do not understand, so these are
+/// skipped, recording only the type and location of the contents.
+///
+/// See T.800 | ISO/IEC 15444-1 Section I.4.
+#[derive(Debug, Default)]
+pub struct UnknownBox {
+    box_type: BoxType,
+    length: u64,
+    offset: u64,
+}
+
+impl JBox for UnknownBox {
+    fn identifier(&self) -> BoxType {
+        self.box_type
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
     fn decode<R: io::Read + io::Seek>(
         &mut self,
         reader: &mut R,
@@ -2553,6 +3772,35 @@ impl JBox for ContiguousCodestreamBox {
     }
 }
 
+// Resolutions are stored in grid points per metre
+const METRES_PER_INCH: f64 = 0.0254;
+
+// 10^|exponent|, without the floating point functions that need std. This
+// is exact up to 10^22, and at most 10^128 for an i8 exponent, so it cannot
+// overflow
+fn power_of_ten(exponent: i8) -> f64 {
+    let mut power = 1_f64;
+    for _ in 0..exponent.unsigned_abs() {
+        power *= 10.0;
+    }
+    power
+}
+
+// Grid resolution of Equations I-1 to I-4, numerator / denominator ·
+// 10^exponent, with a single division so that a negative exponent is as
+// accurate as a positive one. The result is at most 65535 · 10^127, well
+// within the range of f64, and at least 10^-128 / 65535 when not zero.
+// A zero denominator, which shall not be used, gives infinity, or NaN with a
+// zero numerator.
+fn grid_resolution(numerator: u16, denominator: u16, exponent: i8) -> f64 {
+    let (numerator, denominator) = (f64::from(numerator), f64::from(denominator));
+    if exponent < 0 {
+        numerator / (denominator * power_of_ten(exponent))
+    } else {
+        numerator * power_of_ten(exponent) / denominator
+    }
+}
+
 /// Default Display Resolution box.
 ///
 /// This box specifies a desired display grid resolution.
@@ -2610,16 +3858,28 @@ impl DefaultDisplayResolutionBox {
 
     // VRd = VRdN/VRdD * 10^VRdE
     pub fn vertical_display_grid_resolution(&self) -> f64 {
-        self.vertical_display_grid_resolution_numerator() as f64
-            / self.vertical_display_grid_resolution_denominator() as f64
-            * (10_f64).powi(self.vertical_display_grid_resolution_exponent() as i32)
+        grid_resolution(
+            self.vertical_display_grid_resolution_numerator(),
+            self.vertical_display_grid_resolution_denominator(),
+            self.vertical_display_grid_resolution_exponent(),
+        )
     }
 
     // HRd = HRdN/HRdD * 10^HRdE
     pub fn horizontal_display_grid_resolution(&self) -> f64 {
-        self.horizontal_display_grid_resolution_numerator() as f64
-            / self.horizontal_display_grid_resolution_denominator() as f64
-            * (10_f64).powi(self.horizontal_display_grid_resolution_exponent() as i32)
+        grid_resolution(
+            self.horizontal_display_grid_resolution_numerator(),
+            self.horizontal_display_grid_resolution_denominator(),
+            self.horizontal_display_grid_resolution_exponent(),
+        )
+    }
+
+    /// Display grid resolution in dots per inch, as (horizontal, vertical).
+    pub fn display_dpi(&self) -> (f64, f64) {
+        (
+            self.horizontal_display_grid_resolution() * METRES_PER_INCH,
+            self.vertical_display_grid_resolution() * METRES_PER_INCH,
+        )
     }
 }
 
@@ -2740,27 +4000,29 @@ impl CaptureResolutionBox {
     // VRc = (VRcN / VRcD) * 10^VRcE
     // The values VRc and HRc are always in reference grid points per meter.
     pub fn vertical_resolution_capture(&self) -> f64 {
-        let mut vertical_resolution_capture: f64 = self.vertical_capture_grid_resolution_numerator()
-            as f64
-            / self.vertical_capture_grid_resolution_denominator() as f64;
-
-        vertical_resolution_capture *=
-            10_f64.powi(self.vertical_capture_grid_resolution_exponent() as i32);
-
-        vertical_resolution_capture
+        grid_resolution(
+            self.vertical_capture_grid_resolution_numerator(),
+            self.vertical_capture_grid_resolution_denominator(),
+            self.vertical_capture_grid_resolution_exponent(),
+        )
     }
 
     // HRc = (HRcN / HRcD) * 10^HRcE
     // The values VRc and HRc are always in reference grid points per meter.
     pub fn horizontal_resolution_capture(&self) -> f64 {
-        let mut horizontal_resolution_capture: f64 =
-            self.horizontal_capture_grid_resolution_numerator() as f64
-                / self.horizontal_capture_grid_resolution_denominator() as f64;
-
-        horizontal_resolution_capture *=
-            10_f64.powi(self.horizontal_capture_grid_resolution_exponent() as i32);
+        grid_resolution(
+            self.horizontal_capture_grid_resolution_numerator(),
+            self.horizontal_capture_grid_resolution_denominator(),
+            self.horizontal_capture_grid_resolution_exponent(),
+        )
+    }
 
-        horizontal_resolution_capture
+    /// Capture grid resolution in dots per inch, as (horizontal, vertical).
+    pub fn capture_dpi(&self) -> (f64, f64) {
+        (
+            self.horizontal_resolution_capture() * METRES_PER_INCH,
+            self.vertical_resolution_capture() * METRES_PER_INCH,
+        )
     }
 }
 
@@ -2818,23 +4080,190 @@ impl JBox for CaptureResolutionBox {
 /// ISO Base Media File Format (ISO/IEC 14496-12), which is used to encapsulate video in
 /// MPEG 4 (ISO/IEC 14496-14) and HEIF (ISO/IEC 23008-12) amongst other uses.
 #[derive(Debug)]
-pub struct JP2File {
+pub struct JP2File<'a> {
     length: u64,
     signature: Option<SignatureBox>,
     file_type: Option<FileTypeBox>,
     header: Option<HeaderSuperBox>,
     contiguous_codestreams: Vec<ContiguousCodestreamBox>,
     intellectual_property: Option<IntellectualPropertyBox>,
-    xml: Vec<XMLBox>,
-    uuid: Vec<UUIDBox>,
+    xml: Vec<XMLBox<'a>>,
+    uuid: Vec<UUIDBox<'a>>,
     uuid_info: Vec<UUIDInfoSuperBox>,
+    codestream_headers: Vec<CodestreamHeaderSuperBox>,
+    compositing_layers: Vec<CompositingLayerHeaderSuperBox>,
+    associations: Vec<AssociationSuperBox<'a>>,
+    unknown: Vec<UnknownBox>,
+    // Top-level boxes in file order
+    boxes: Vec<BoxRecord>,
 }
 
-impl JP2File {
+impl<'a> JP2File<'a> {
     pub fn length(&self) -> u64 {
         self.length
     }
 
+    /// Copy any box contents borrowed from the input of [decode_jp2_slice],
+    /// so that the file can outlive it.
+    pub fn into_owned(self) -> JP2File<'static> {
+        JP2File {
+            length: self.length,
+            signature: self.signature,
+            file_type: self.file_type,
+            header: self.header,
+            contiguous_codestreams: self.contiguous_codestreams,
+            intellectual_property: self.intellectual_property,
+            xml: self.xml.into_iter().map(XMLBox::into_owned).collect(),
+            uuid: self.uuid.into_iter().map(UUIDBox::into_owned).collect(),
+            uuid_info: self.uuid_info,
+            codestream_headers: self.codestream_headers,
+            compositing_layers: self.compositing_layers,
+            associations: self
+                .associations
+                .into_iter()
+                .map(AssociationSuperBox::into_owned)
+                .collect(),
+            unknown: self.unknown,
+            boxes: self.boxes,
+        }
+    }
+
+    /// Write the file back out as it was read, with any edits.
+    ///
+    /// Each top-level box is written in file order with its header as read,
+    /// including any XLBox field or LBox of 0, and its contents copied from
+    /// `reader`, which must be the one the file was decoded from. Without
+    /// edits, this reproduces the file byte for byte.
+    ///
+    /// Boxes added or replaced by [JP2File::add_xml_box] and
+    /// [JP2File::replace_uuid_box] are written with a header for their new
+    /// length, and boxes removed by [JP2File::remove_uuid_box] are left out.
+    #[cfg(feature = "std")]
+    pub fn write<R: io::Read + io::Seek, W: io::Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        for (index, record) in self.boxes.iter().enumerate() {
+            match record {
+                BoxRecord::Source {
+                    box_type,
+                    header,
+                    header_length,
+                    offset,
+                    length,
+                } => {
+                    // An LBox of 0 extends the box to the end of the file, so
+                    // the length is needed if a box has been added after it
+                    if header[..4] == [0; 4] && index + 1 < self.boxes.len() {
+                        builder::write_box_header(writer, *box_type, *length)?;
+                    } else {
+                        writer.write_all(&header[..usize::from(*header_length)])?;
+                    }
+                    copy_contents(reader, writer, *offset, *length)?;
+                }
+                BoxRecord::Edited { box_type, contents } => {
+                    builder::write_box_header(writer, *box_type, contents.len() as u64)?;
+                    writer.write_all(contents)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the UUID box at `index` of [JP2File::uuid_boxes], such as a
+    /// GeoJP2 box, returning it.
+    ///
+    /// The box is left out when the file is written with [JP2File::write].
+    pub fn remove_uuid_box(&mut self, index: usize) -> Result<UUIDBox<'a>, JP2Error> {
+        let position = self.box_position(BOX_TYPE_UUID, index)?;
+        self.boxes.remove(position);
+        Ok(self.uuid.remove(index))
+    }
+
+    /// Replace the UUID box at `index` of [JP2File::uuid_boxes] with one of
+    /// the given UUID and data, in the same place in the file.
+    ///
+    /// The new box is not in the file that was read, so its offset is 0.
+    pub fn replace_uuid_box(
+        &mut self,
+        index: usize,
+        uuid: [u8; 16],
+        data: Vec<u8>,
+    ) -> Result<(), JP2Error> {
+        let position = self.box_position(BOX_TYPE_UUID, index)?;
+        let mut contents = uuid.to_vec();
+        contents.extend_from_slice(&data);
+        self.boxes[position] = BoxRecord::Edited {
+            box_type: BOX_TYPE_UUID,
+            contents,
+        };
+        self.uuid[index] = UUIDBox {
+            length: (uuid.len() + data.len()) as u64,
+            offset: 0,
+            uuid,
+            data: Cow::Owned(data),
+        };
+        Ok(())
+    }
+
+    /// Add an XML box with the given content.
+    ///
+    /// The box follows the last XML box, or if there is none, precedes the
+    /// first Contiguous Codestream box. The new box is not in the file that
+    /// was read, so its offset is 0.
+    pub fn add_xml_box(&mut self, content: &str) {
+        let position = match self
+            .boxes
+            .iter()
+            .rposition(|record| record.box_type() == BOX_TYPE_XML)
+        {
+            Some(position) => position + 1,
+            None => self
+                .boxes
+                .iter()
+                .position(|record| record.box_type() == BOX_TYPE_CONTIGUOUS_CODESTREAM)
+                .unwrap_or(self.boxes.len()),
+        };
+        self.boxes.insert(
+            position,
+            BoxRecord::Edited {
+                box_type: BOX_TYPE_XML,
+                contents: content.as_bytes().to_vec(),
+            },
+        );
+        self.xml.push(XMLBox {
+            length: content.len() as u64,
+            offset: 0,
+            xml: Cow::Owned(content.as_bytes().to_vec()),
+        });
+    }
+
+    // Position in the top-level boxes of the box with the type at `index`
+    // of those of that type
+    fn box_position(&self, box_type: BoxType, index: usize) -> Result<usize, JP2Error> {
+        self.boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.box_type() == box_type)
+            .nth(index)
+            .map(|(position, _)| position)
+            .ok_or(JP2Error::InvalidParameter { name: "index" })
+    }
+
+    /// Whether the file holds all of its codestreams.
+    ///
+    /// A truncated file, such as one that is partially downloaded, still
+    /// decodes to the boxes before the end of the file, with the truncated
+    /// Contiguous Codestream box holding the bytes that are available. See
+    /// [ContiguousCodestreamBox::is_truncated].
+    pub fn is_complete(&self) -> bool {
+        !self
+            .contiguous_codestreams
+            .iter()
+            .any(|codestream_box| codestream_box.truncated)
+    }
+
     /// JPEG 2000 Signature box.
     ///
     /// This box uniquely identifies the file as being part of the JPEG 2000 family of files.
@@ -2898,7 +4327,7 @@ impl JP2File {
     /// a JP2 file.
     ///
     /// This box is not required, and can be present multiple times.
-    pub fn xml_boxes(&self) -> &Vec<XMLBox> {
+    pub fn xml_boxes(&self) -> &Vec<XMLBox<'a>> {
         &self.xml
     }
 
@@ -2908,7 +4337,7 @@ impl JP2File {
     /// without risking conflict with other vendors.
     ///
     /// This box is not required, and can be present multiple times.
-    pub fn uuid_boxes(&self) -> &Vec<UUIDBox> {
+    pub fn uuid_boxes(&self) -> &Vec<UUIDBox<'a>> {
         &self.uuid
     }
 
@@ -2919,6 +4348,166 @@ impl JP2File {
     pub fn uuid_info_boxes(&self) -> &Vec<UUIDInfoSuperBox> {
         &self.uuid_info
     }
+
+    /// Boxes at the top level of the file that were not recognised, and
+    /// were skipped.
+    ///
+    /// These include boxes from ITU T.801 | ISO/IEC 15444-2, such as the
+    /// Reader Requirements box.
+    pub fn unknown_boxes(&self) -> &Vec<UnknownBox> {
+        &self.unknown
+    }
+
+    /// Codestream Header boxes.
+    ///
+    /// These are defined in ITU T.801 | ISO/IEC 15444-2, and hold the header
+    /// information for each codestream of a JPX file, in file order.
+    pub fn codestream_header_boxes(&self) -> &Vec<CodestreamHeaderSuperBox> {
+        &self.codestream_headers
+    }
+
+    /// Contiguous Codestream box of a reduced size preview of the image, if
+    /// the file has one.
+    ///
+    /// A JPX file may hold a low resolution codestream alongside the full
+    /// resolution one. The size of each codestream is taken from the Image
+    /// Header box of its Codestream Header box, or of the JP2 Header box if
+    /// it has none. The preview is the codestream with the smallest area,
+    /// provided that it is smaller than another codestream, so a file with a
+    /// single codestream has no preview.
+    pub fn preview_codestream(&self) -> Option<&ContiguousCodestreamBox> {
+        let default_image_header = self.header.as_ref().map(|header| &header.image_header_box);
+        let sized: Vec<(u64, &ContiguousCodestreamBox)> = self
+            .contiguous_codestreams
+            .iter()
+            .enumerate()
+            .filter_map(|(i, codestream_box)| {
+                let image_header = self
+                    .codestream_headers
+                    .get(i)
+                    .and_then(|header| header.image_header_box.as_ref())
+                    .or(default_image_header)?;
+                let area = u64::from(image_header.width()) * u64::from(image_header.height());
+                Some((area, codestream_box))
+            })
+            .collect();
+        let (smallest, preview) = sized.iter().min_by_key(|(area, _)| *area)?;
+        if sized.iter().any(|(area, _)| area > smallest) {
+            Some(preview)
+        } else {
+            None
+        }
+    }
+
+    /// Compositing Layer Header boxes.
+    ///
+    /// These are defined in ITU T.801 | ISO/IEC 15444-2, and hold the header
+    /// information for each compositing layer of a JPX file, in file order.
+    pub fn compositing_layer_boxes(&self) -> &Vec<CompositingLayerHeaderSuperBox> {
+        &self.compositing_layers
+    }
+
+    /// Association boxes at the top level of the file.
+    ///
+    /// These are defined in ITU T.801 | ISO/IEC 15444-2, and associate
+    /// metadata, such as a label and XML, with each other, in file order.
+    pub fn association_boxes(&self) -> &Vec<AssociationSuperBox<'a>> {
+        &self.associations
+    }
+
+    /// Metadata of the file, from the Intellectual Property, XML, UUID and
+    /// Association boxes.
+    ///
+    /// See [MetadataView].
+    pub fn metadata(&self) -> MetadataView<'_> {
+        MetadataView::new(self)
+    }
+
+    /// Check the number of channels against the colourspace.
+    ///
+    /// The channels are the codestream components, or those created by the
+    /// Palette and Component Mapping boxes. With a Channel Definition box,
+    /// the number of colour channels shall match the enumerated colourspace.
+    /// Without one, the first channels are the colour channels.
+    ///
+    /// Only the first Colour Specification box is used. Colourspaces that
+    /// are not enumerated, or are reserved, are not checked. An enumerated
+    /// colourspace where the Image Header box marks the colourspace as
+    /// unknown is returned as a warning.
+    ///
+    /// Too few colour channels is an error. Problems that still allow the
+    /// image to be interpreted are returned as warnings.
+    pub fn validate(&self) -> Result<Vec<JP2Warning>, JP2Error> {
+        let mut warnings = vec![];
+        let header = match &self.header {
+            Some(header) => header,
+            None => {
+                return Err(JP2Error::BoxMissing {
+                    box_type: BOX_TYPE_HEADER,
+                })
+            }
+        };
+
+        let found = match (&header.palette_box, &header.component_mapping_box) {
+            (_, Some(component_mapping_box)) => component_mapping_box.component_map().len() as u16,
+            (Some(palette_box), None) => {
+                warnings.push(JP2Warning::ComponentMappingMissing);
+                palette_box.num_components() as u16
+            }
+            (None, None) => header.image_header_box.components_num(),
+        };
+
+        let colour_space = match header
+            .colour_specification_boxes
+            .first()
+            .and_then(|colour_specification| colour_specification.enumerated_colour_space())
+        {
+            Some(colour_space) => colour_space,
+            None => return Ok(warnings),
+        };
+        if header.image_header_box.is_colourspace_unknown() {
+            warnings.push(JP2Warning::ColourspaceUnknown { colour_space });
+        }
+        let expected = match colour_space.channels_expected() {
+            Some(expected) => expected,
+            None => return Ok(warnings),
+        };
+
+        match &header.channel_definition_box {
+            Some(channel_definition_box) => {
+                let colour_channels = channel_definition_box
+                    .channels()
+                    .iter()
+                    .filter(|channel| channel.channel_type() == ChannelTypes::ColourImageData)
+                    .count() as u16;
+                if colour_channels != expected {
+                    return Err(JP2Error::ChannelCountMismatch {
+                        colour_space,
+                        expected,
+                        found: colour_channels,
+                    });
+                }
+            }
+            None => {
+                if found < expected {
+                    return Err(JP2Error::ChannelCountMismatch {
+                        colour_space,
+                        expected,
+                        found,
+                    });
+                }
+                if found > expected {
+                    warnings.push(JP2Warning::UnassociatedChannels {
+                        colour_space,
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
 }
 
 struct BoxHeader {
@@ -2951,6 +4540,53 @@ struct BoxHeader {
     header_length: u8,
 }
 
+// Top-level box, as read or as edited
+#[derive(Debug)]
+enum BoxRecord {
+    // Box as read, with its header and the location of its contents
+    Source {
+        box_type: BoxType,
+        header: [u8; 16],
+        header_length: u8,
+        offset: u64,
+        length: u64,
+    },
+    // Box added or replaced
+    Edited {
+        box_type: BoxType,
+        contents: Vec<u8>,
+    },
+}
+
+impl BoxRecord {
+    fn box_type(&self) -> BoxType {
+        match self {
+            Self::Source { box_type, .. } | Self::Edited { box_type, .. } => *box_type,
+        }
+    }
+}
+
+// Decode the header of a top-level box, recording it in `boxes`
+fn decode_recorded_box_header<R: io::Read + io::Seek>(
+    reader: &mut R,
+    boxes: &mut Vec<BoxRecord>,
+) -> Result<BoxHeader, Box<dyn error::Error>> {
+    let start = reader.stream_position()?;
+    let box_header = decode_box_header(reader)?;
+    let mut header = [0u8; 16];
+    reader.seek(io::SeekFrom::Start(start))?;
+    reader.read_exact(&mut header[..usize::from(box_header.header_length)])?;
+    // The length is set once the next box is found
+    boxes.push(BoxRecord::Source {
+        box_type: box_header.box_type,
+        header,
+        header_length: box_header.header_length,
+        offset: reader.stream_position()?,
+        length: 0,
+    });
+    Ok(box_header)
+}
+
 fn decode_box_header<R: io::Read + io::Seek>(
     reader: &mut R,
 ) -> Result<BoxHeader, Box<dyn error::Error>> {
@@ -2973,11 +4609,22 @@ fn decode_box_header<R: io::Read + io::Seek>(
         // This field is stored as an 8-byte big endian unsigned integer. The value includes all of the fields of the box, including the LBox, TBox and XLBox fields
         reader.read_exact(&mut xl_length)?;
 
-        box_length_value = u64::from_be_bytes(xl_length) - 16;
+        box_length_value =
+            u64::from_be_bytes(xl_length)
+                .checked_sub(16)
+                .ok_or(JP2Error::BoxMalformed {
+                    box_type,
+                    offset: reader.stream_position()?,
+                })?;
         header_length = 16;
     } else if box_length_value <= 7 {
         // The values 2–7 are reserved for ISO use.
-        panic!("unsupported reserved box length {:?}", box_length_value);
+        reader.read_exact(&mut box_type)?;
+        return Err(JP2Error::BoxMalformed {
+            box_type,
+            offset: reader.stream_position()?,
+        }
+        .into());
     } else {
         reader.read_exact(&mut box_type)?;
 
@@ -2992,15 +4639,69 @@ fn decode_box_header<R: io::Read + io::Seek>(
     })
 }
 
+/// Options for [decode_jp2_with_options].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Reject files that do not conform to T.800 | ISO/IEC 15444-1 Annex I,
+    /// rather than reading what can be read.
+    ///
+    /// This makes errors of a non-zero colourspace approximation in a file
+    /// with the `jp2 ` brand, a missing JP2 Header or Contiguous Codestream
+    /// box, a second JP2 Header box, and a box of the JP2 Header box at the
+    /// top level of the file.
+    pub strict: bool,
+}
+
+/// Whether the next 12 bytes of the reader are the Signature box of a JP2
+/// file, 0x0000 000C 6A50 2020 0D0A 870A.
+///
+/// The reader is left where it was, so that the file can then be decoded.
+/// A reader that cannot be read or repositioned is not JP2.
+pub fn is_jp2<R: io::Read + io::Seek>(reader: &mut R) -> bool {
+    let start = match reader.stream_position() {
+        Ok(start) => start,
+        Err(_) => return false,
+    };
+    let mut buffer = [0u8; 12];
+    let read = reader.read_exact(&mut buffer);
+    if reader.seek(io::SeekFrom::Start(start)).is_err() || read.is_err() {
+        return false;
+    }
+    buffer[0..4] == 12u32.to_be_bytes()
+        && buffer[4..8] == BOX_TYPE_SIGNATURE
+        && buffer[8..12] == SIGNATURE_MAGIC
+}
+
 // TODO: Consider lazy parsing where possible
 pub fn decode_jp2<R: io::Read + io::Seek>(
     reader: &mut R,
-) -> Result<JP2File, Box<dyn error::Error>> {
+) -> Result<JP2File<'static>, Box<dyn error::Error>> {
+    decode_jp2_with_options(reader, DecodeOptions::default())
+}
+
+/// Decode a JP2 file, with the given options.
+///
+/// With the default options, this is [decode_jp2].
+pub fn decode_jp2_with_options<R: io::Read + io::Seek>(
+    reader: &mut R,
+    options: DecodeOptions,
+) -> Result<JP2File<'static>, Box<dyn error::Error>> {
+    decode_boxes(reader, options, None)
+}
+
+// Decode the boxes of the file, borrowing the contents of the XML and UUID
+// boxes from `data` if the whole file is held in memory
+fn decode_boxes<'a, R: io::Read + io::Seek>(
+    reader: &mut R,
+    options: DecodeOptions,
+    data: Option<&'a [u8]>,
+) -> Result<JP2File<'a>, Box<dyn error::Error>> {
+    let mut boxes: Vec<BoxRecord> = vec![];
     let BoxHeader {
         box_length,
         box_type,
         header_length: _,
-    } = decode_box_header(reader)?;
+    } = decode_recorded_box_header(reader, &mut boxes)?;
 
     // TODO: Enforce the following
     // Check Image Headerbox (header, width) with codestream and allow user to read it otherwise
@@ -3025,7 +4726,7 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
         box_length,
         box_type,
         header_length: _,
-    } = decode_box_header(reader)?;
+    } = decode_recorded_box_header(reader, &mut boxes)?;
     // The File Type box shall immediately follow the Signature box
     let mut file_type_box = FileTypeBox {
         length: box_length,
@@ -3053,13 +4754,17 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
     let mut uuid_boxes: Vec<UUIDBox> = vec![];
     let mut uuid_info_boxes: Vec<UUIDInfoSuperBox> = vec![];
     let mut current_uuid_info_box: Option<UUIDInfoSuperBox> = None;
+    let mut codestream_header_boxes: Vec<CodestreamHeaderSuperBox> = vec![];
+    let mut compositing_layer_boxes: Vec<CompositingLayerHeaderSuperBox> = vec![];
+    let mut association_boxes: Vec<AssociationSuperBox<'a>> = vec![];
+    let mut unknown_boxes: Vec<UnknownBox> = vec![];
 
     loop {
         let BoxHeader {
             box_length,
             box_type,
             header_length: _,
-        } = match decode_box_header(reader) {
+        } = match decode_recorded_box_header(reader, &mut boxes) {
             Ok(value) => value,
             Err(derr) => {
                 // TODO: Improve check for EOF
@@ -3072,7 +4777,44 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
             }
         };
 
+        // Only the Contiguous Codestream box may be truncated, so any other
+        // box running past the end of the data is malformed
+        if let Some(data) = data {
+            let offset = reader.stream_position()?;
+            if box_type != BOX_TYPE_CONTIGUOUS_CODESTREAM
+                && box_length > (data.len() as u64).saturating_sub(offset)
+            {
+                return Err(JP2Error::BoxMalformed { box_type, offset }.into());
+            }
+        }
+
         match BoxTypes::new(box_type) {
+            // There shall be one and only one JP2 Header box
+            BoxTypes::Header if options.strict && header_box_option.is_some() => {
+                return Err(JP2Error::BoxDuplicate {
+                    box_type,
+                    offset: reader.stream_position()?,
+                }
+                .into());
+            }
+            // Boxes of the JP2 Header box shall only be found within it
+            BoxTypes::ImageHeader
+            | BoxTypes::BitsPerComponent
+            | BoxTypes::ColourSpecification
+            | BoxTypes::Palette
+            | BoxTypes::ComponentMapping
+            | BoxTypes::ChannelDefinition
+            | BoxTypes::Resolution
+            | BoxTypes::CaptureResolution
+            | BoxTypes::DefaultDisplayResolution
+                if options.strict =>
+            {
+                return Err(JP2Error::BoxUnexpected {
+                    box_type,
+                    offset: reader.stream_position()?,
+                }
+                .into());
+            }
             BoxTypes::Header => {
                 // The header box must be at the same level as the Signature
                 // and File Type boxes it shall not be inside any other
@@ -3091,7 +4833,7 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                 let mut intellectual_property_box = IntellectualPropertyBox {
                     length: box_length,
                     offset: reader.stream_position()?,
-                    data: vec![0; box_length as usize],
+                    data: vec![],
                 };
                 info!(
                     "IntellectualPropertyBox start at {:?}",
@@ -3108,10 +4850,10 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                 let mut xml_box = XMLBox {
                     length: box_length,
                     offset: reader.stream_position()?,
-                    xml: Vec::with_capacity(box_length as usize).to_owned(),
+                    ..Default::default()
                 };
                 info!("XMLBox start at {:?}", xml_box.offset);
-                xml_box.decode(reader)?;
+                xml_box.decode_from(reader, data)?;
                 xml_boxes.push(xml_box);
                 info!("XMLBox finish at {:?}", reader.stream_position()?);
             }
@@ -3122,7 +4864,7 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                     ..Default::default()
                 };
                 info!("UUIDBox start at {:?}", uuid_box.offset);
-                uuid_box.decode(reader)?;
+                uuid_box.decode_from(reader, data)?;
                 uuid_boxes.push(uuid_box);
                 info!("UUIDBox finish at {:?}", reader.stream_position()?);
             }
@@ -3168,7 +4910,7 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                     offset: reader.stream_position()?,
                     version: [0; 1],
                     flags: [0; 3],
-                    location: Vec::with_capacity(box_length as usize - 4).to_owned(),
+                    location: vec![],
                 };
 
                 data_entry_url_box.length = box_length;
@@ -3201,6 +4943,7 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                 let mut continuous_codestream_box = ContiguousCodestreamBox {
                     length: box_length,
                     offset: reader.stream_position()?,
+                    ..Default::default()
                 };
                 info!(
                     "ContiguousCodestreamBox start at {:?}",
@@ -3213,13 +4956,64 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
                 );
                 contiguous_codestream_boxes.push(continuous_codestream_box);
             }
+            BoxTypes::CodestreamHeader => {
+                let mut codestream_header_box = CodestreamHeaderSuperBox {
+                    length: box_length,
+                    offset: reader.stream_position()?,
+                    ..Default::default()
+                };
+                info!(
+                    "CodestreamHeaderBox start at {:?}",
+                    codestream_header_box.offset
+                );
+                codestream_header_box.decode(reader)?;
+                info!(
+                    "CodestreamHeaderBox finish at {:?}",
+                    reader.stream_position()?
+                );
+                codestream_header_boxes.push(codestream_header_box);
+            }
+            BoxTypes::CompositingLayerHeader => {
+                let mut compositing_layer_box = CompositingLayerHeaderSuperBox {
+                    length: box_length,
+                    offset: reader.stream_position()?,
+                    ..Default::default()
+                };
+                info!(
+                    "CompositingLayerHeaderBox start at {:?}",
+                    compositing_layer_box.offset
+                );
+                compositing_layer_box.decode(reader)?;
+                info!(
+                    "CompositingLayerHeaderBox finish at {:?}",
+                    reader.stream_position()?
+                );
+                compositing_layer_boxes.push(compositing_layer_box);
+            }
+            BoxTypes::Association => {
+                let mut association_box = AssociationSuperBox {
+                    length: box_length,
+                    offset: reader.stream_position()?,
+                    ..Default::default()
+                };
+                info!("AssociationBox start at {:?}", association_box.offset);
+                association_box.decode_from(reader, data)?;
+                info!("AssociationBox finish at {:?}", reader.stream_position()?);
+                association_boxes.push(association_box);
+            }
 
             _ => {
-                panic!(
-                    "Unexpected box type {:?} {:?}",
-                    reader.stream_position(),
-                    box_type
+                let mut unknown_box = UnknownBox {
+                    box_type,
+                    length: box_length,
+                    offset: reader.stream_position()?,
+                };
+                info!(
+                    "UnknownBox {:?} start at {:?}",
+                    box_type, unknown_box.offset
                 );
+                unknown_box.decode(reader)?;
+                unknown_boxes.push(unknown_box);
             }
         }
     }
@@ -3228,8 +5022,32 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
         uuid_info_boxes.push(uuid_box);
     }
 
+    if options.strict {
+        check_conformance(
+            &file_type_box,
+            header_box_option.as_ref(),
+            &contiguous_codestream_boxes,
+        )?;
+    }
+
+    // Each box runs up to the header of the next, or the end of the file
+    let length = reader.stream_position()?;
+    let mut end = length;
+    for record in boxes.iter_mut().rev() {
+        if let BoxRecord::Source {
+            header_length,
+            offset,
+            length,
+            ..
+        } = record
+        {
+            *length = end - *offset;
+            end = *offset - u64::from(*header_length);
+        }
+    }
+
     let result = JP2File {
-        length: reader.stream_position()?,
+        length,
         signature: Some(signature_box),
         file_type: Some(file_type_box),
         header: header_box_option,
@@ -3238,7 +5056,67 @@ pub fn decode_jp2<R: io::Read + io::Seek>(
         xml: xml_boxes,
         uuid: uuid_boxes,
         uuid_info: uuid_info_boxes,
+        codestream_headers: codestream_header_boxes,
+        compositing_layers: compositing_layer_boxes,
+        associations: association_boxes,
+        unknown: unknown_boxes,
+        boxes,
     };
 
     Ok(result)
 }
+
+// Checks of T.800 | ISO/IEC 15444-1 Annex I that are only made with
+// DecodeOptions::strict
+fn check_conformance(
+    file_type_box: &FileTypeBox,
+    header_box: Option<&HeaderSuperBox>,
+    contiguous_codestream_boxes: &[ContiguousCodestreamBox],
+) -> Result<(), JP2Error> {
+    // A JP2 Header box and at least one Contiguous Codestream box are required
+    let header_box = header_box.ok_or(JP2Error::BoxMissing {
+        box_type: BOX_TYPE_HEADER,
+    })?;
+    if contiguous_codestream_boxes.is_empty() {
+        return Err(JP2Error::BoxMissing {
+            box_type: BOX_TYPE_CONTIGUOUS_CODESTREAM,
+        });
+    }
+
+    // Section I.5.3.3, the approximation shall be 0 in a JP2 file
+    if file_type_box.major_brand() == Brand::Jp2 {
+        if let Some(colour_specification_box) =
+            header_box
+                .colour_specification_boxes
+                .iter()
+                .find(|colour_specification_box| {
+                    !colour_specification_box.is_approximation_conformant()
+                })
+        {
+            return Err(JP2Error::ApproximationNonConformant {
+                approximation: colour_specification_box.colourspace_approximation(),
+                offset: colour_specification_box.offset,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Decode a JP2 file held in memory.
+///
+/// This is for callers that already have the whole file as a slice, such as
+/// from a memory map or in WASM. The contents of the XML and UUID boxes are
+/// borrowed from `data` rather than copied, and the contents of any other
+/// box, such as the Contiguous Codestream box, can be borrowed with
+/// [JBox::payload]. Use [JP2File::into_owned] for a file that outlives
+/// `data`.
+///
+/// A box other than the Contiguous Codestream box whose length runs past
+/// the end of `data` is an error, rather than being read up to the end.
+pub fn decode_jp2_slice(data: &[u8]) -> Result<JP2File<'_>, Box<dyn error::Error>> {
+    decode_boxes(
+        &mut io::Cursor::new(data),
+        DecodeOptions::default(),
+        Some(data),
+    )
+}
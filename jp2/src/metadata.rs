@@ -0,0 +1,179 @@
+//! Unified view of the metadata of a file.
+//!
+//! Metadata in a JP2 or JPX file is spread over the Intellectual Property
+//! box, XML boxes, UUID boxes and the labelled contents of Association
+//! boxes. The view gathers these into a single list, with each item given a
+//! category and a reference to the box that holds it.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use super::{AssociationSuperBox, IntellectualPropertyBox, JP2File, UUIDBox, XMLBox};
+
+// GeoJP2 UUID, b14bf8bd-083d-4b43-a5ae-8cd7d5a6ce03
+const UUID_GEOJP2: [u8; 16] = [
+    0xB1, 0x4B, 0xF8, 0xBD, 0x08, 0x3D, 0x4B, 0x43, 0xA5, 0xAE, 0x8C, 0xD7, 0xD5, 0xA6, 0xCE, 0x03,
+];
+
+// Prefix of the Association box labels used by GMLJP2
+const LABEL_PREFIX_GML: &str = "gml.";
+
+/// Kind of metadata held by a [MetadataItem].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataCategory {
+    /// Georeferencing, from a GeoJP2 UUID box, or the XML of an
+    /// Association box labelled for GMLJP2.
+    Geospatial,
+    /// Intellectual property rights, from the Intellectual Property box.
+    Rights,
+    /// Other XML.
+    Xml,
+    /// Other vendor specific information in a UUID box.
+    VendorUuid,
+}
+
+/// Box holding a [MetadataItem], for access to the raw contents.
+#[derive(Debug, Clone, Copy)]
+pub enum MetadataSource<'a> {
+    IntellectualProperty(&'a IntellectualPropertyBox),
+    Xml(&'a XMLBox<'a>),
+    Uuid(&'a UUIDBox<'a>),
+}
+
+/// Single item of metadata.
+#[derive(Debug, Clone)]
+pub struct MetadataItem<'a> {
+    category: MetadataCategory,
+    source: MetadataSource<'a>,
+    labels: Vec<&'a str>,
+}
+
+impl<'a> MetadataItem<'a> {
+    /// Kind of metadata.
+    pub fn category(&self) -> MetadataCategory {
+        self.category
+    }
+
+    /// Box holding the metadata.
+    pub fn source(&self) -> MetadataSource<'a> {
+        self.source
+    }
+
+    /// Labels of the Association boxes the item is within, outermost first.
+    ///
+    /// This is empty for a box at the top level of the file, and skips any
+    /// Association box without a Label box.
+    pub fn labels(&self) -> &[&'a str] {
+        &self.labels
+    }
+
+    /// Label of the innermost labelled Association box the item is within.
+    pub fn label(&self) -> Option<&'a str> {
+        self.labels.last().copied()
+    }
+}
+
+/// Metadata of a file, from [JP2File::metadata].
+///
+/// The items are those of the Intellectual Property box, then the XML and
+/// UUID boxes at the top level of the file, then those within each
+/// Association box in file order, depth first.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataView<'a> {
+    items: Vec<MetadataItem<'a>>,
+}
+
+impl<'a> MetadataView<'a> {
+    pub(crate) fn new(file: &'a JP2File<'a>) -> MetadataView<'a> {
+        let mut view = MetadataView::default();
+        if let Some(intellectual_property) = file.intellectual_property_box() {
+            view.items.push(MetadataItem {
+                category: MetadataCategory::Rights,
+                source: MetadataSource::IntellectualProperty(intellectual_property),
+                labels: vec![],
+            });
+        }
+        view.push_boxes(file.xml_boxes(), file.uuid_boxes(), &[]);
+        for association in file.association_boxes() {
+            view.push_association(association, &[]);
+        }
+        view
+    }
+
+    fn push_boxes(
+        &mut self,
+        xml_boxes: &'a [XMLBox<'a>],
+        uuid_boxes: &'a [UUIDBox<'a>],
+        labels: &[&'a str],
+    ) {
+        let geospatial = labels
+            .iter()
+            .any(|label| label.starts_with(LABEL_PREFIX_GML));
+        for xml_box in xml_boxes {
+            self.items.push(MetadataItem {
+                category: if geospatial {
+                    MetadataCategory::Geospatial
+                } else {
+                    MetadataCategory::Xml
+                },
+                source: MetadataSource::Xml(xml_box),
+                labels: labels.to_vec(),
+            });
+        }
+        for uuid_box in uuid_boxes {
+            self.items.push(MetadataItem {
+                category: if *uuid_box.uuid() == UUID_GEOJP2 {
+                    MetadataCategory::Geospatial
+                } else {
+                    MetadataCategory::VendorUuid
+                },
+                source: MetadataSource::Uuid(uuid_box),
+                labels: labels.to_vec(),
+            });
+        }
+    }
+
+    fn push_association(&mut self, association: &'a AssociationSuperBox<'a>, labels: &[&'a str]) {
+        let mut labels = labels.to_vec();
+        labels.extend(association.label());
+        self.push_boxes(&association.xml_boxes, &association.uuid_boxes, &labels);
+        for nested in &association.association_boxes {
+            self.push_association(nested, &labels);
+        }
+    }
+
+    /// All items.
+    pub fn items(&self) -> &[MetadataItem<'a>] {
+        &self.items
+    }
+
+    /// Items of the given category.
+    pub fn category(
+        &self,
+        category: MetadataCategory,
+    ) -> impl Iterator<Item = &MetadataItem<'a>> + '_ {
+        self.items
+            .iter()
+            .filter(move |item| item.category == category)
+    }
+
+    /// Georeferencing items.
+    pub fn geospatial(&self) -> impl Iterator<Item = &MetadataItem<'a>> + '_ {
+        self.category(MetadataCategory::Geospatial)
+    }
+
+    /// Intellectual property rights items.
+    pub fn rights(&self) -> impl Iterator<Item = &MetadataItem<'a>> + '_ {
+        self.category(MetadataCategory::Rights)
+    }
+
+    /// Other XML items.
+    pub fn xml(&self) -> impl Iterator<Item = &MetadataItem<'a>> + '_ {
+        self.category(MetadataCategory::Xml)
+    }
+
+    /// Other vendor UUID items.
+    pub fn vendor_uuids(&self) -> impl Iterator<Item = &MetadataItem<'a>> + '_ {
+        self.category(MetadataCategory::VendorUuid)
+    }
+}
@@ -0,0 +1,75 @@
+//! Memory-mapped input.
+//!
+//! Large files, such as the HiRISE images, can be parsed without reading them
+//! into memory by mapping the file and parsing the boxes over the mapped
+//! slice with [decode_jp2_slice](crate::decode_jp2_slice). The contents of
+//! the XML and UUID boxes are borrowed from the map, and the Contiguous
+//! Codestream boxes can be decoded directly from the mapped region.
+
+use std::error;
+use std::fs::File;
+use std::path::Path;
+use std::slice;
+
+use memmap2::Mmap;
+
+use crate::{decode_jp2_slice, JBox, JP2File};
+
+/// JP2 file decoded from a memory map.
+///
+/// The map is kept open for as long as this is, so that box contents can be
+/// borrowed from it without copying.
+pub struct MappedJP2File {
+    // Borrows from the map, so is declared first to be dropped before it
+    file: JP2File<'static>,
+    map: Mmap,
+}
+
+impl MappedJP2File {
+    /// Boxes of the file, with the contents of the XML and UUID boxes
+    /// borrowed from the map.
+    pub fn file(&self) -> &JP2File<'_> {
+        &self.file
+    }
+
+    /// Contents of the whole file.
+    pub fn data(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Codestream held by the Contiguous Codestream box, borrowed from the
+    /// map.
+    ///
+    /// This is `None` if there is no box at the index.
+    pub fn codestream(&self, index: usize) -> Option<&[u8]> {
+        self.file
+            .contiguous_codestreams_boxes()
+            .get(index)?
+            .payload(&self.map)
+    }
+}
+
+/// Decode a JP2 file by memory mapping it.
+///
+/// This avoids copying the file into memory, which matters for files of
+/// hundreds of megabytes.
+///
+/// # Safety assumptions
+///
+/// Mapping a file is only sound if the file is not modified or truncated,
+/// by this or any other process, while the map is in use. This function
+/// assumes that holds for the lifetime of the returned [MappedJP2File].
+/// Otherwise, reads from the map may return inconsistent contents, or the
+/// process may be terminated with a bus error on some platforms.
+pub fn decode_jp2_mmap<P: AsRef<Path>>(path: P) -> Result<MappedJP2File, Box<dyn error::Error>> {
+    let file = File::open(path)?;
+    // SAFETY: the caller guarantees that the file is not modified while it
+    // is mapped, as documented above
+    let map = unsafe { Mmap::map(&file)? };
+    // SAFETY: the mapped region does not move when the map does, and is
+    // only unmapped when the map is dropped. The file borrowing from it is
+    // dropped first, and only lent out for as long as the map is borrowed.
+    let data: &'static [u8] = unsafe { slice::from_raw_parts(map.as_ptr(), map.len()) };
+    let file = decode_jp2_slice(data)?;
+    Ok(MappedJP2File { file, map })
+}
@@ -0,0 +1,193 @@
+use jp2::{IccProfileError, RestrictedIccProfile, ToneReproductionCurve};
+
+// Profile with the given data colour space and tags, each given as a
+// signature and tag data, placed in order after the tag table.
+fn profile(colour_space: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut header = vec![0u8; 128];
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(colour_space);
+    header[20..24].copy_from_slice(b"XYZ ");
+    header.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+
+    let mut offset = header.len() + tags.len() * 12;
+    let mut data = Vec::new();
+    for (signature, tag_data) in tags {
+        header.extend_from_slice(*signature);
+        header.extend_from_slice(&(offset as u32).to_be_bytes());
+        header.extend_from_slice(&(tag_data.len() as u32).to_be_bytes());
+        offset += tag_data.len();
+        data.extend_from_slice(tag_data);
+    }
+    header.extend(data);
+    let size = header.len() as u32;
+    header[0..4].copy_from_slice(&size.to_be_bytes());
+    header
+}
+
+fn xyz(values: [i32; 3]) -> Vec<u8> {
+    let mut data = b"XYZ \0\0\0\0".to_vec();
+    for value in values.iter() {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+    data
+}
+
+fn curve(entries: &[u16]) -> Vec<u8> {
+    let mut data = b"curv\0\0\0\0".to_vec();
+    data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for entry in entries {
+        data.extend_from_slice(&entry.to_be_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_grey_profile() {
+    let data = profile(
+        b"GRAY",
+        &[
+            (b"wtpt", xyz([0x0000_F6D6, 0x0001_0000, 0x0000_D32D])),
+            (b"kTRC", curve(&[0x0233])),
+        ],
+    );
+    let profile = RestrictedIccProfile::decode(&data).unwrap();
+    assert_eq!(profile.profile_class(), *b"mntr");
+    assert_eq!(profile.colour_space(), *b"GRAY");
+    assert_eq!(profile.num_channels(), 1);
+    assert_eq!(profile.white_point(), Some([0.964_202_9, 1.0, 0.824_905_4]));
+    assert_eq!(
+        profile.tone_reproduction_curve(0),
+        Some(&ToneReproductionCurve::Gamma(2.199_218_8))
+    );
+}
+
+#[test]
+fn test_rgb_profile_curves() {
+    let mut parametric = b"para\0\0\0\0\0\x03\0\0".to_vec();
+    for parameter in [
+        0x0002_6666i32,
+        0x0000_F2A7,
+        0x0000_0D59,
+        0x0000_13D0,
+        0x0000_0A5B,
+    ]
+    .iter()
+    {
+        parametric.extend_from_slice(&parameter.to_be_bytes());
+    }
+    let data = profile(
+        b"RGB ",
+        &[
+            (b"rXYZ", xyz([0x6FA2, 0x38F5, 0x0390])),
+            (b"gXYZ", xyz([0x6299, 0xB785, 0x18DA])),
+            (b"bXYZ", xyz([0x24A0, 0x0F84, 0xB6CF])),
+            (b"rTRC", curve(&[])),
+            (b"gTRC", curve(&[0, 0x8000, 0xFFFF])),
+            (b"bTRC", parametric),
+        ],
+    );
+    let profile = RestrictedIccProfile::decode(&data).unwrap();
+    assert_eq!(profile.num_channels(), 3);
+    assert!(profile.white_point().is_none());
+    assert_eq!(profile.colorants().unwrap()[1][1], 0.716_873_17);
+    assert_eq!(
+        profile.tone_reproduction_curve(0),
+        Some(&ToneReproductionCurve::Identity)
+    );
+    assert_eq!(
+        profile.tone_reproduction_curve(1),
+        Some(&ToneReproductionCurve::Table(vec![0, 0x8000, 0xFFFF]))
+    );
+    match profile.tone_reproduction_curve(2) {
+        Some(ToneReproductionCurve::Parametric {
+            function_type,
+            parameters,
+        }) => {
+            assert_eq!(*function_type, 3);
+            assert_eq!(parameters.len(), 5);
+            assert_eq!(parameters[0], 2.399_994);
+        }
+        curve => panic!("expected a parametric curve, got {:?}", curve),
+    }
+}
+
+#[test]
+fn test_required_tag_missing() {
+    let data = profile(b"GRAY", &[(b"wtpt", xyz([0, 0, 0]))]);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data).unwrap_err(),
+        IccProfileError::TagMissing {
+            signature: *b"kTRC"
+        }
+    );
+
+    let data = profile(
+        b"RGB ",
+        &[
+            (b"rXYZ", xyz([0, 0, 0])),
+            (b"gXYZ", xyz([0, 0, 0])),
+            (b"bXYZ", xyz([0, 0, 0])),
+            (b"rTRC", curve(&[])),
+            (b"gTRC", curve(&[])),
+        ],
+    );
+    let error = RestrictedIccProfile::decode(&data).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "missing ICC profile tag [98, 84, 82, 67]"
+    );
+}
+
+#[test]
+fn test_tag_malformed() {
+    // XYZ data in place of a curve
+    let data = profile(b"GRAY", &[(b"kTRC", xyz([0, 0, 0]))]);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data).unwrap_err(),
+        IccProfileError::TagMalformed {
+            signature: *b"kTRC"
+        }
+    );
+
+    // Curve with more entries than the tag holds
+    let mut tag = curve(&[1, 2]);
+    tag[11] = 3;
+    let data = profile(b"GRAY", &[(b"kTRC", tag)]);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data).unwrap_err(),
+        IccProfileError::TagMalformed {
+            signature: *b"kTRC"
+        }
+    );
+
+    // Tag data past the end of the profile
+    let mut data = profile(b"GRAY", &[(b"kTRC", curve(&[]))]);
+    data.truncate(data.len() - 1);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data).unwrap_err(),
+        IccProfileError::TagMalformed {
+            signature: *b"kTRC"
+        }
+    );
+}
+
+#[test]
+fn test_unsupported_profile() {
+    let data = profile(b"CMYK", &[]);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data).unwrap_err(),
+        IccProfileError::UnsupportedColourSpace {
+            colour_space: *b"CMYK"
+        }
+    );
+
+    let data = profile(b"GRAY", &[(b"kTRC", curve(&[]))]);
+    assert_eq!(
+        RestrictedIccProfile::decode(&data[..100]).unwrap_err(),
+        IccProfileError::TooShort { length: 100 }
+    );
+    assert_eq!(
+        RestrictedIccProfile::decode(&data[..140]).unwrap_err(),
+        IccProfileError::TooShort { length: 140 }
+    );
+}
@@ -0,0 +1,438 @@
+use std::io::Cursor;
+
+use jp2::{
+    decode_jp2, BitDepth, ChannelTypes, ComponentMapType, EnumeratedColourSpaces, JBox as _,
+    MetadataCategory, MetadataSource, BOX_TYPE_FREE, BOX_TYPE_MEDIA_DATA,
+};
+
+// SOC, EOC
+const CODESTREAM: [u8; 4] = [0xFF, 0x4F, 0xFF, 0xD9];
+
+fn jbox(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut buffer = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    buffer.extend_from_slice(box_type);
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+// HEIGHT, WIDTH, NC, BPC, C = 7, UnkC = 0, IPR = 0
+fn image_header(height: u32, width: u32, components_num: u16, bpc: u8) -> Vec<u8> {
+    let mut payload = height.to_be_bytes().to_vec();
+    payload.extend_from_slice(&width.to_be_bytes());
+    payload.extend_from_slice(&components_num.to_be_bytes());
+    payload.extend_from_slice(&[bpc, 7, 0, 0]);
+    jbox(b"ihdr", &payload)
+}
+
+// METH = 1, PREC, APPROX, EnumCS
+fn enumerated_colour(precedence: i8, approximation: u8, colour_space: u32) -> Vec<u8> {
+    let mut payload = vec![1, precedence as u8, approximation];
+    payload.extend_from_slice(&colour_space.to_be_bytes());
+    jbox(b"colr", &payload)
+}
+
+// A JPX file with the given boxes between the JP2 Header box and the
+// Contiguous Codestream box.
+fn jpx_file(boxes: &[Vec<u8>]) -> Vec<u8> {
+    jpx_file_with_colours(&[enumerated_colour(0, 0, 16)], boxes)
+}
+
+// As jpx_file, with the given Colour Specification boxes in the JP2 Header box.
+fn jpx_file_with_colours(colours: &[Vec<u8>], boxes: &[Vec<u8>]) -> Vec<u8> {
+    let mut buffer = jbox(b"jP  ", &[0x0D, 0x0A, 0x87, 0x0A]);
+    buffer.extend(jbox(b"ftyp", b"jpx \0\0\0\0jp2 jpx "));
+    let mut header = image_header(32, 16, 3, 7);
+    for colour in colours {
+        header.extend_from_slice(colour);
+    }
+    buffer.extend(jbox(b"jp2h", &header));
+    for b in boxes {
+        buffer.extend_from_slice(b);
+    }
+    buffer.extend(jbox(b"jp2c", &CODESTREAM));
+    buffer
+}
+
+#[test]
+fn test_codestream_header_box() {
+    let mut contents = image_header(64, 128, 2, 0xFF);
+    contents.extend(jbox(b"bpcc", &[7, 0x8B]));
+    // ROI Description box, with no regions
+    contents.extend(jbox(b"roid", &[0]));
+    let data = jpx_file(&[jbox(b"jpch", &contents)]);
+
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(jpx.codestream_header_boxes().len(), 1);
+    assert!(jpx.compositing_layer_boxes().is_empty());
+    assert!(jpx.unknown_boxes().is_empty());
+    assert_eq!(jpx.contiguous_codestreams_boxes().len(), 1);
+
+    let codestream_header = &jpx.codestream_header_boxes()[0];
+    assert_eq!(codestream_header.identifier(), *b"jpch");
+    assert_eq!(codestream_header.offset(), 89);
+    assert_eq!(codestream_header.length(), contents.len() as u64);
+
+    let image_header_box = codestream_header.image_header_box.as_ref().unwrap();
+    assert_eq!(image_header_box.height(), 64);
+    assert_eq!(image_header_box.width(), 128);
+    assert_eq!(image_header_box.components_num(), 2);
+
+    let bits_per_component_box = codestream_header.bits_per_component_box.as_ref().unwrap();
+    assert_eq!(
+        bits_per_component_box.bits_per_component(),
+        vec![
+            BitDepth::Unsigned { value: 8 },
+            BitDepth::Signed { value: 12 }
+        ]
+    );
+
+    assert!(codestream_header.palette_box.is_none());
+    assert!(codestream_header.component_mapping_box.is_none());
+    assert_eq!(codestream_header.unknown_boxes.len(), 1);
+    assert_eq!(codestream_header.unknown_boxes[0].identifier(), *b"roid");
+    assert_eq!(codestream_header.unknown_boxes[0].length(), 1);
+}
+
+#[test]
+fn test_component_mapping_box() {
+    let mut contents = image_header(64, 128, 2, 7);
+    // Palette box, NE = 2, NPC = 2, 8 bit columns
+    contents.extend(jbox(b"pclr", &[0, 2, 2, 7, 7, 0, 255, 255, 0]));
+    // CMP, MTYP and PCOL for each channel: component 1 used directly, then
+    // component 0 through each palette column, then a reserved type
+    contents.extend(jbox(
+        b"cmap",
+        &[0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 1, 2, 0],
+    ));
+    let data = jpx_file(&[jbox(b"jpch", &contents)]);
+
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    let codestream_header = &jpx.codestream_header_boxes()[0];
+    assert!(codestream_header.palette_box.is_some());
+    let cmap = codestream_header.component_mapping_box.as_ref().unwrap();
+    let mapping: Vec<(u16, ComponentMapType, u8)> = cmap
+        .component_map()
+        .iter()
+        .map(|map| (map.component(), map.mapping_type(), map.palette()))
+        .collect();
+    assert_eq!(
+        mapping,
+        vec![
+            (1, ComponentMapType::Direct, 0),
+            (0, ComponentMapType::Palette, 0),
+            (0, ComponentMapType::Palette, 1),
+            (1, ComponentMapType::Reserved { value: [2] }, 0),
+        ]
+    );
+    assert_eq!(cmap.component_map()[0].mapping_type_u8(), 0);
+    assert_eq!(cmap.component_map()[2].mapping_type_u8(), 1);
+    assert_eq!(cmap.component_map()[3].mapping_type_u8(), 2);
+}
+
+#[test]
+fn test_palette_box_bit_depths() {
+    let mut contents = image_header(64, 128, 1, 7);
+    // Palette box, NE = 2, NPC = 4, with unsigned 16 bit, signed 12 bit,
+    // unsigned 20 bit and unsigned 1 bit columns
+    contents.extend(jbox(
+        b"pclr",
+        &[
+            0, 2, 4, 15, 0x8B, 19, 0, //
+            0x12, 0x34, 0x0F, 0xFF, 0x0F, 0xFF, 0xFF, 0x01, //
+            0xFF, 0xFF, 0x07, 0xFF, 0x00, 0x00, 0x01, 0x00,
+        ],
+    ));
+    let data = jpx_file(&[jbox(b"jpch", &contents)]);
+
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    let palette = jpx.codestream_header_boxes()[0]
+        .palette_box
+        .as_ref()
+        .unwrap();
+    assert_eq!(palette.num_entries(), 2);
+    assert_eq!(palette.num_components(), 4);
+    assert_eq!(
+        palette.bit_depth(0),
+        Some(&BitDepth::Unsigned { value: 16 })
+    );
+    assert_eq!(palette.bit_depth(1), Some(&BitDepth::Signed { value: 12 }));
+    assert_eq!(palette.bit_depth(2).unwrap().num_bytes(), 3);
+    let rows: Vec<&[u32]> = palette.rows().collect();
+    assert_eq!(rows[0], &[0x1234, 0xFFFF_FFFF, 0xF_FFFF, 1]);
+    assert_eq!(rows[1], &[0xFFFF, 0x7FF, 1, 0]);
+    assert_eq!(*palette.entry(0, 1).unwrap() as i32, -1);
+}
+
+#[test]
+fn test_palette_box_bit_depth_errors() {
+    // 33 bit column, within the limit of 38 bits
+    let mut contents = image_header(64, 128, 1, 7);
+    contents.extend(jbox(b"pclr", &[0, 1, 1, 32, 0, 0, 0, 0, 0]));
+    let data = jpx_file(&[jbox(b"jpch", &contents)]);
+    let error = decode_jp2(&mut Cursor::new(&data)).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unsupported palette bit depth 33 at offset 119"
+    );
+
+    // 39 bit column
+    let mut contents = image_header(64, 128, 1, 7);
+    contents.extend(jbox(b"pclr", &[0, 1, 1, 38, 0, 0, 0, 0, 0]));
+    let data = jpx_file(&[jbox(b"jpch", &contents)]);
+    let error = decode_jp2(&mut Cursor::new(&data)).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "malformed box type [112, 99, 108, 114] at offset 119"
+    );
+}
+
+#[test]
+fn test_compositing_layer_header_box() {
+    let mut colours = enumerated_colour(1, 0, 17);
+    colours.extend(enumerated_colour(0, 0, 16));
+    let mut contents = jbox(b"cgrp", &colours);
+    // Opacity box, OTyp = 0 and no chroma key
+    contents.extend(jbox(b"opct", &[0]));
+    // N = 2, then Cn, Typ and Asoc for each channel
+    contents.extend(jbox(b"cdef", &[0, 2, 0, 0, 0, 0, 0, 1, 0, 1, 0, 1, 0, 0]));
+    let data = jpx_file(&[jbox(b"jplh", &contents), jbox(b"jplh", &[])]);
+
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert!(jpx.codestream_header_boxes().is_empty());
+    assert_eq!(jpx.compositing_layer_boxes().len(), 2);
+
+    let layer = &jpx.compositing_layer_boxes()[0];
+    assert_eq!(layer.identifier(), *b"jplh");
+    assert_eq!(layer.length(), contents.len() as u64);
+    let colour_spaces: Vec<(i8, Option<EnumeratedColourSpaces>)> = layer
+        .colour_specification_boxes
+        .iter()
+        .map(|colour| (colour.precedence(), colour.enumerated_colour_space()))
+        .collect();
+    assert_eq!(
+        colour_spaces,
+        vec![
+            (1, Some(EnumeratedColourSpaces::Greyscale)),
+            (0, Some(EnumeratedColourSpaces::sRGB))
+        ]
+    );
+    assert_eq!(
+        layer
+            .preferred_colour_specification()
+            .unwrap()
+            .enumerated_colour_space(),
+        Some(EnumeratedColourSpaces::Greyscale)
+    );
+    let channels = layer.channel_definition_box.as_ref().unwrap().channels();
+    assert_eq!(channels.len(), 2);
+    assert_eq!(channels[0].channel_type(), ChannelTypes::ColourImageData);
+    assert_eq!(channels[1].channel_type(), ChannelTypes::Opacity);
+    assert!(layer.resolution_box.is_none());
+    assert_eq!(layer.unknown_boxes.len(), 1);
+    assert_eq!(layer.unknown_boxes[0].identifier(), *b"opct");
+
+    let empty_layer = &jpx.compositing_layer_boxes()[1];
+    assert_eq!(empty_layer.length(), 0);
+    assert!(empty_layer.colour_specification_boxes.is_empty());
+    assert!(empty_layer.channel_definition_box.is_none());
+    assert!(empty_layer.preferred_colour_specification().is_none());
+}
+
+#[test]
+fn test_colour_group_box_unexpected() {
+    let contents = jbox(b"cgrp", &jbox(b"cdef", &[0, 0]));
+    let data = jpx_file(&[jbox(b"jplh", &contents)]);
+
+    let result = decode_jp2(&mut Cursor::new(&data));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "unexpected box type [99, 100, 101, 102] at offset 105"
+    );
+}
+
+#[test]
+fn test_free_and_media_data_boxes() {
+    let data = jpx_file(&[jbox(b"free", &[0; 6]), jbox(b"mdat", &[1, 2, 3])]);
+
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert!(jpx.header_box().is_some());
+    assert_eq!(jpx.contiguous_codestreams_boxes().len(), 1);
+    let skipped: Vec<([u8; 4], u64, u64)> = jpx
+        .unknown_boxes()
+        .iter()
+        .map(|unknown| (unknown.identifier(), unknown.offset(), unknown.length()))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![(BOX_TYPE_FREE, 89, 6), (BOX_TYPE_MEDIA_DATA, 103, 3)]
+    );
+}
+
+#[test]
+fn test_preferred_colour_specification() {
+    let colour_space = |colours: &[Vec<u8>]| {
+        let data = jpx_file_with_colours(colours, &[]);
+        let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+        let header_box = jpx.header_box().as_ref().unwrap();
+        assert_eq!(header_box.colour_specification_boxes.len(), colours.len());
+        header_box
+            .preferred_colour_specification()
+            .unwrap()
+            .enumerated_colour_space()
+            .unwrap()
+    };
+
+    // Highest precedence, even when negative or after a lower one
+    assert_eq!(
+        colour_space(&[enumerated_colour(-2, 1, 16), enumerated_colour(-1, 3, 17)]),
+        EnumeratedColourSpaces::Greyscale
+    );
+    assert_eq!(
+        colour_space(&[enumerated_colour(0, 0, 16), enumerated_colour(2, 0, 18)]),
+        EnumeratedColourSpaces::sYCC
+    );
+    // Equal precedence, most accurate approximation with 0 ranked last
+    assert_eq!(
+        colour_space(&[
+            enumerated_colour(1, 0, 16),
+            enumerated_colour(1, 4, 17),
+            enumerated_colour(1, 2, 18),
+            enumerated_colour(0, 1, 16)
+        ]),
+        EnumeratedColourSpaces::sYCC
+    );
+    // Equal precedence and approximation, first in the file
+    assert_eq!(
+        colour_space(&[enumerated_colour(1, 3, 17), enumerated_colour(1, 3, 16)]),
+        EnumeratedColourSpaces::Greyscale
+    );
+}
+
+#[test]
+fn test_preview_codestream() {
+    // A 4 by 8 preview codestream, followed by the codestream that has the
+    // 16 by 32 size of the JP2 Header box
+    let data = jpx_file(&[
+        jbox(b"jpch", &image_header(4, 8, 3, 7)),
+        jbox(b"jpch", &[]),
+        jbox(b"jp2c", &CODESTREAM),
+    ]);
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(jpx.contiguous_codestreams_boxes().len(), 2);
+    let preview = jpx.preview_codestream().unwrap();
+    assert_eq!(
+        preview.offset(),
+        jpx.contiguous_codestreams_boxes()[0].offset()
+    );
+
+    // Codestreams of the same size
+    let data = jpx_file(&[jbox(b"jp2c", &CODESTREAM)]);
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(jpx.contiguous_codestreams_boxes().len(), 2);
+    assert!(jpx.preview_codestream().is_none());
+
+    // A single codestream
+    let data = jpx_file(&[]);
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert!(jpx.preview_codestream().is_none());
+}
+
+#[test]
+fn test_association_metadata() {
+    // GMLJP2 style, with the GML in a nested labelled association
+    let mut gml_root = jbox(b"lbl ", b"gml.root-instance");
+    gml_root.extend(jbox(b"xml ", b"<gml:FeatureCollection/>"));
+    let mut gml = jbox(b"lbl ", b"gml.data");
+    gml.extend(jbox(b"asoc", &gml_root));
+
+    // Unlabelled, with a vendor UUID box and a Number List box
+    let mut vendor = jbox(b"nlst", &[0, 0, 0, 0]);
+    let mut uuid = [0x11; 16].to_vec();
+    uuid.push(1);
+    vendor.extend(jbox(b"uuid", &uuid));
+
+    let data = jpx_file(&[
+        jbox(b"jp2i", b"<IPR/>"),
+        jbox(b"asoc", &gml),
+        jbox(b"xml ", b"<top/>"),
+        jbox(b"asoc", &vendor),
+    ]);
+    let jpx = decode_jp2(&mut Cursor::new(&data)).unwrap();
+    assert!(jpx.unknown_boxes().is_empty());
+    let associations = jpx.association_boxes();
+    assert_eq!(associations.len(), 2);
+    assert_eq!(associations[0].identifier(), *b"asoc");
+    assert_eq!(associations[0].label(), Some("gml.data"));
+    assert_eq!(associations[0].association_boxes.len(), 1);
+    let nested = &associations[0].association_boxes[0];
+    assert_eq!(nested.label(), Some("gml.root-instance"));
+    assert_eq!(nested.xml_boxes[0].format(), "<gml:FeatureCollection/>");
+    assert!(associations[1].label().is_none());
+    assert_eq!(associations[1].unknown_boxes[0].identifier(), *b"nlst");
+
+    let metadata = jpx.metadata();
+    let categories: Vec<MetadataCategory> = metadata
+        .items()
+        .iter()
+        .map(|item| item.category())
+        .collect();
+    assert_eq!(
+        categories,
+        [
+            MetadataCategory::Rights,
+            MetadataCategory::Xml,
+            MetadataCategory::Geospatial,
+            MetadataCategory::VendorUuid
+        ]
+    );
+
+    let geospatial: Vec<_> = metadata.geospatial().collect();
+    assert_eq!(geospatial.len(), 1);
+    assert_eq!(geospatial[0].labels(), ["gml.data", "gml.root-instance"]);
+    assert_eq!(geospatial[0].label(), Some("gml.root-instance"));
+    match geospatial[0].source() {
+        MetadataSource::Xml(xml_box) => assert_eq!(xml_box.offset(), nested.xml_boxes[0].offset()),
+        source => panic!("expected an XML box, got {:?}", source),
+    }
+
+    let rights: Vec<_> = metadata.rights().collect();
+    match rights[0].source() {
+        MetadataSource::IntellectualProperty(jp2i) => assert_eq!(jp2i.format(), "<IPR/>"),
+        source => panic!("expected the Intellectual Property box, got {:?}", source),
+    }
+    assert!(metadata.xml().next().unwrap().labels().is_empty());
+    let vendor: Vec<_> = metadata.vendor_uuids().collect();
+    assert!(vendor[0].label().is_none());
+    match vendor[0].source() {
+        MetadataSource::Uuid(uuid_box) => assert_eq!(uuid_box.data(), &[1]),
+        source => panic!("expected a UUID box, got {:?}", source),
+    }
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn test_content_hash() {
+    // SHA-256 of the SOC and EOC markers
+    let expected = [
+        0x44, 0xd9, 0xb4, 0xad, 0xe6, 0xa0, 0xaf, 0x73, 0xe4, 0x01, 0x99, 0x34, 0x55, 0x6b, 0x3c,
+        0x75, 0xe6, 0x14, 0x08, 0xf9, 0xb2, 0x28, 0xe5, 0x4c, 0x88, 0xef, 0x45, 0xbf, 0x81, 0x1e,
+        0x52, 0xdc,
+    ];
+
+    // The same codestream, in a box with an XLBox field
+    let mut xl_box = 1u32.to_be_bytes().to_vec();
+    xl_box.extend_from_slice(b"jp2c");
+    xl_box.extend_from_slice(&(CODESTREAM.len() as u64 + 16).to_be_bytes());
+    xl_box.extend_from_slice(&CODESTREAM);
+
+    let mut data = jpx_file(&[xl_box]);
+    // Bytes after the last box, which are not part of either codestream
+    data.extend_from_slice(&jbox(b"free", &[0xFF; 4]));
+    let mut reader = Cursor::new(&data);
+    let jpx = decode_jp2(&mut reader).unwrap();
+    let codestreams = jpx.contiguous_codestreams_boxes();
+    assert_eq!(codestreams.len(), 2);
+    assert_eq!(codestreams[0].length(), 4);
+    assert_eq!(codestreams[0].content_hash(&mut reader).unwrap(), expected);
+    assert_eq!(codestreams[1].content_hash(&mut reader).unwrap(), expected);
+}
@@ -1,8 +1,13 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::{self, File},
+    io::{BufReader, Cursor},
+    path::Path,
+};
 
 use jp2::{
-    decode_jp2, BitDepth, ChannelTypes, ColourSpecificationMethods, EnumeratedColourSpaces,
-    JBox as _, JP2File,
+    decode_jp2, decode_jp2_slice, decode_jp2_with_options, is_jp2, BitDepth, Brand, ChannelTypes,
+    ColourSpecificationMethods, ComponentMapType, DecodeOptions, EnumeratedColourSpaces, JBox as _,
+    JP2Error, JP2File, JP2Warning, MetadataSource, RestrictedIccProfile, ToneReproductionCurve,
 };
 
 struct ExpectedConfiguration {
@@ -53,6 +58,11 @@ fn test_sample_file1() {
         },
     );
 
+    let file_type = boxes.file_type_box().as_ref().unwrap();
+    assert_eq!(file_type.major_brand(), Brand::Jp2);
+    assert_eq!(file_type.brands(), vec![Brand::J2kCodestream, Brand::Jp2]);
+    assert_eq!(Brand::J2kCodestream.bytes(), [0, 0, 0, 1]);
+
     let header_box = boxes.header_box().as_ref().unwrap();
     assert!(header_box.channel_definition_box.is_none());
     assert!(header_box.palette_box.is_none());
@@ -180,32 +190,83 @@ fn test_sample_file4() {
     assert_eq!(boxes.uuid_boxes().len(), 0);
 }
 
-#[ignore = "uses unsupported Part 2 extensions"]
+// A JPX file that can be read as JP2, with a Reader Requirements box
 #[test]
 fn test_sample_file5() {
-    let boxes = test_sample_jp2_file(
-        "file5.jp2",
-        ExpectedConfiguration {
-            compatibility_list: vec!["\0\0\0\u{1}".into(), "jp2 ".into()],
-            width: 640,
-            height: 480,
-            num_components: 3,
-            bit_depth: 16,
-            colourspace: EnumeratedColourSpaces::sRGB,
-            colour_specification_method: ColourSpecificationMethods::EnumeratedColourSpace,
-            has_unexpected_approx_set: true,
-        },
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../samples")
+        .join("file5.jp2");
+    let mut reader = BufReader::new(File::open(path).expect("file should exist"));
+    let boxes = decode_jp2(&mut reader).unwrap();
+
+    let file_type = boxes.file_type_box().as_ref().unwrap();
+    assert_eq!(file_type.brand(), "jpx ");
+    assert_eq!(
+        file_type.compatibility_list(),
+        vec!["\0\0\0\u{3}", "jp2 ", "jpx ", "jpbx"]
     );
+    assert_eq!(file_type.major_brand(), Brand::Jpx);
+    assert_eq!(
+        file_type.brands(),
+        vec![
+            Brand::Other([0, 0, 0, 3]),
+            Brand::Jp2,
+            Brand::Jpx,
+            Brand::Other(*b"jpbx")
+        ]
+    );
+
+    assert_eq!(boxes.unknown_boxes().len(), 1);
+    let reader_requirements = boxes.unknown_boxes().first().unwrap();
+    assert_eq!(reader_requirements.identifier(), *b"rreq");
+    assert_eq!(reader_requirements.offset(), 52);
+    assert_eq!(reader_requirements.length(), 16);
 
     let header_box = boxes.header_box().as_ref().unwrap();
-    assert!(header_box.channel_definition_box.is_none());
-    assert!(header_box.palette_box.is_none());
-    assert!(header_box.component_mapping_box.is_none());
+    let image_header_box = &header_box.image_header_box;
+    assert_eq!(image_header_box.width(), 768);
+    assert_eq!(image_header_box.height(), 512);
+    assert_eq!(image_header_box.components_num(), 3);
+    assert_eq!(image_header_box.components_bits(), 8);
 
-    assert_eq!(boxes.xml_boxes().len(), 2);
-    let xml0 = boxes.xml_boxes().first().unwrap();
-    assert_eq!(xml0.identifier(), *b"xml ");
+    // A restricted ICC profile, then a Part 2 enumerated colourspace
+    assert_eq!(header_box.colour_specification_boxes.len(), 2);
+    let colour_specification_box = header_box.colour_specification_boxes.first().unwrap();
+    assert_eq!(
+        colour_specification_box.method(),
+        ColourSpecificationMethods::RestrictedICCProfile
+    );
+    let profile =
+        RestrictedIccProfile::decode(colour_specification_box.restricted_icc_profile().unwrap())
+            .unwrap();
+    assert_eq!(profile.profile_class(), *b"scnr");
+    assert_eq!(profile.colour_space(), *b"RGB ");
+    assert_eq!(profile.num_channels(), 3);
+    assert_eq!(profile.white_point(), Some([0.964_202_9, 1.0, 0.824_905_4]));
+    assert_eq!(
+        profile.colorants(),
+        Some([
+            [0.797_699, 0.287_994_38, 0.0],
+            [0.135_192_87, 0.711_898_8, 0.0],
+            [0.031_295_776, 0.000_106_811_52, 0.824_905_4]
+        ])
+    );
+    for channel in 0..3 {
+        assert_eq!(
+            profile.tone_reproduction_curve(channel),
+            Some(&ToneReproductionCurve::Gamma(1.800_781_3))
+        );
+    }
+    assert!(profile.tone_reproduction_curve(3).is_none());
+    let colour_specification_box = header_box.colour_specification_boxes.last().unwrap();
+    assert_eq!(colour_specification_box.precedence(), 1);
+    assert_eq!(
+        colour_specification_box.enumerated_colour_space(),
+        Some(EnumeratedColourSpaces::Reserved)
+    );
 
+    assert_eq!(boxes.contiguous_codestreams_boxes().len(), 1);
+    assert_eq!(boxes.xml_boxes().len(), 0);
     assert_eq!(boxes.uuid_boxes().len(), 0);
 }
 
@@ -235,30 +296,68 @@ fn test_sample_file6() {
     assert_eq!(boxes.uuid_boxes().len(), 0);
 }
 
-#[ignore = "uses unsupported Part 2 extensions"]
+// A JPX file that can be read as JP2, with a Reader Requirements box
 #[test]
 fn test_sample_file7() {
-    let boxes = test_sample_jp2_file(
-        "file7.jp2",
-        ExpectedConfiguration {
-            compatibility_list: vec!["\0\0\0\u{1}".into(), "jp2 ".into()],
-            width: 640,
-            height: 480,
-            num_components: 3,
-            bit_depth: 16,
-            colourspace: EnumeratedColourSpaces::sRGB,
-            colour_specification_method: ColourSpecificationMethods::EnumeratedColourSpace,
-            has_unexpected_approx_set: true,
-        },
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../samples")
+        .join("file7.jp2");
+    let mut reader = BufReader::new(File::open(path).expect("file should exist"));
+    let boxes = decode_jp2(&mut reader).unwrap();
+
+    let file_type = boxes.file_type_box().as_ref().unwrap();
+    assert_eq!(file_type.brand(), "jpx ");
+    assert_eq!(
+        file_type.compatibility_list(),
+        vec!["\0\0\0\u{3}", "jp2 ", "jpx ", "jpxb"]
     );
 
+    assert_eq!(boxes.unknown_boxes().len(), 1);
+    let reader_requirements = boxes.unknown_boxes().first().unwrap();
+    assert_eq!(reader_requirements.identifier(), *b"rreq");
+    assert_eq!(reader_requirements.offset(), 52);
+    assert_eq!(reader_requirements.length(), 16);
+
     let header_box = boxes.header_box().as_ref().unwrap();
-    assert!(header_box.channel_definition_box.is_none());
-    assert!(header_box.palette_box.is_none());
-    assert!(header_box.component_mapping_box.is_none());
+    let image_header_box = &header_box.image_header_box;
+    assert_eq!(image_header_box.width(), 480);
+    assert_eq!(image_header_box.height(), 640);
+    assert_eq!(image_header_box.components_num(), 3);
+    assert_eq!(image_header_box.components_bits(), 16);
 
-    assert_eq!(boxes.xml_boxes().len(), 2);
+    // A restricted ICC profile, then a Part 2 enumerated colourspace
+    assert_eq!(header_box.colour_specification_boxes.len(), 2);
+    let colour_specification_box = header_box.colour_specification_boxes.first().unwrap();
+    assert_eq!(
+        colour_specification_box.method(),
+        ColourSpecificationMethods::RestrictedICCProfile
+    );
+    let profile =
+        RestrictedIccProfile::decode(colour_specification_box.restricted_icc_profile().unwrap())
+            .unwrap();
+    assert_eq!(profile.colour_space(), *b"RGB ");
+    assert_eq!(
+        profile.white_point(),
+        Some([0.964_187_6, 1.0, 0.824_890_14])
+    );
+    match profile.tone_reproduction_curve(1) {
+        Some(ToneReproductionCurve::Table(table)) => {
+            assert_eq!(table.len(), 2048);
+            assert_eq!(table[0], 0);
+            assert_eq!(table[1024], 3373);
+            assert_eq!(table[2047], 65535);
+        }
+        curve => panic!("expected a sampled curve, got {:?}", curve),
+    }
+    let colour_specification_box = header_box.colour_specification_boxes.last().unwrap();
+    assert_eq!(colour_specification_box.precedence(), 1);
+    assert_eq!(
+        colour_specification_box.enumerated_colour_space(),
+        Some(EnumeratedColourSpaces::Reserved)
+    );
 
+    assert_eq!(boxes.contiguous_codestreams_boxes().len(), 1);
+    assert_eq!(boxes.xml_boxes().len(), 0);
     assert_eq!(boxes.uuid_boxes().len(), 0);
 }
 
@@ -283,6 +382,19 @@ fn test_sample_file8() {
     assert!(header_box.palette_box.is_none());
     assert!(header_box.component_mapping_box.is_none());
 
+    let colour_specification_box = header_box.colour_specification_boxes.first().unwrap();
+    let profile =
+        RestrictedIccProfile::decode(colour_specification_box.restricted_icc_profile().unwrap())
+            .unwrap();
+    assert_eq!(profile.colour_space(), *b"GRAY");
+    assert_eq!(profile.num_channels(), 1);
+    assert_eq!(profile.white_point(), Some([0.964_202_9, 1.0, 0.824_905_4]));
+    assert!(profile.colorants().is_none());
+    assert_eq!(
+        profile.tone_reproduction_curve(0),
+        Some(&ToneReproductionCurve::Gamma(1.800_781_3))
+    );
+
     assert_eq!(boxes.xml_boxes().len(), 2);
     let xml0 = boxes.xml_boxes().first().unwrap();
     assert_eq!(xml0.identifier(), *b"xml ");
@@ -382,6 +494,14 @@ fn test_sample_file9() {
     assert_eq!(*pclr.entry(255, 2).unwrap(), 0xf5);
     assert!(pclr.entry(255, 3).is_none());
 
+    assert_eq!(pclr.rows().count(), 256);
+    assert_eq!(pclr.rows().nth(214).unwrap(), &[0xa5, 0x84, 0x5c]);
+    let green: Vec<u32> = pclr.entries_for_component(1).collect();
+    assert_eq!(green.len(), 256);
+    assert_eq!(&green[..3], &[0, 0xff, 0x0c]);
+    assert_eq!(green[252], 0x0b);
+    assert_eq!(pclr.entries_for_component(3).count(), 0);
+
     assert!(pclr.entry(256, 0).is_none());
     assert!(pclr.entry(256, 1).is_none());
     assert!(pclr.entry(256, 2).is_none());
@@ -405,13 +525,25 @@ fn test_sample_file9() {
     assert_eq!(cmap.identifier(), *b"cmap");
     assert_eq!(cmap.component_map().len(), 3);
     assert_eq!(cmap.component_map()[0].component(), 0);
-    assert_eq!(cmap.component_map()[0].mapping_type(), 1);
+    assert_eq!(
+        cmap.component_map()[0].mapping_type(),
+        ComponentMapType::Palette
+    );
+    assert_eq!(cmap.component_map()[0].mapping_type_u8(), 1);
     assert_eq!(cmap.component_map()[0].palette(), 0);
     assert_eq!(cmap.component_map()[1].component(), 0);
-    assert_eq!(cmap.component_map()[1].mapping_type(), 1);
+    assert_eq!(
+        cmap.component_map()[1].mapping_type(),
+        ComponentMapType::Palette
+    );
+    assert_eq!(cmap.component_map()[1].mapping_type_u8(), 1);
     assert_eq!(cmap.component_map()[1].palette(), 1);
     assert_eq!(cmap.component_map()[2].component(), 0);
-    assert_eq!(cmap.component_map()[2].mapping_type(), 1);
+    assert_eq!(
+        cmap.component_map()[2].mapping_type(),
+        ComponentMapType::Palette
+    );
+    assert_eq!(cmap.component_map()[2].mapping_type_u8(), 1);
     assert_eq!(cmap.component_map()[2].palette(), 2);
 
     assert_eq!(boxes.xml_boxes().len(), 0);
@@ -525,7 +657,7 @@ fn test_sample_zoo2() {
     assert_eq!(boxes.uuid_boxes().len(), 1);
 }
 
-fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File {
+fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File<'static> {
     let path = Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("../samples")
         .join(filename);
@@ -540,6 +672,7 @@ fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2F
     let signature = boxes.signature_box().as_ref().unwrap();
     assert_eq!(signature.identifier(), *b"jP  ");
     assert_eq!(signature.signature(), *b"\x0d\x0a\x87\x0a");
+    assert!(signature.is_valid());
 
     assert!(boxes.file_type_box().is_some());
     let file_type = boxes.file_type_box().as_ref().unwrap();
@@ -558,11 +691,22 @@ fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2F
     assert_eq!(image_header_box.components_num(), expected.num_components);
     assert_eq!(image_header_box.compression_type(), 7);
     assert_eq!(image_header_box.colourspace_unknown(), 0);
+    assert!(!image_header_box.is_colourspace_unknown());
     assert_eq!(image_header_box.intellectual_property(), 0);
     assert_eq!(image_header_box.components_bits(), expected.bit_depth);
-    assert_eq!(image_header_box.values_are_signed(), false);
+    assert!(!image_header_box.values_are_signed());
 
     assert!(header_box.bits_per_component_box.is_none());
+    assert!(!image_header_box.components_bits_varies());
+    assert_eq!(
+        header_box.component_bit_depths(),
+        vec![
+            BitDepth::Unsigned {
+                value: expected.bit_depth
+            };
+            expected.num_components as usize
+        ]
+    );
 
     assert_eq!(header_box.colour_specification_boxes.len(), 1);
     let colour_specification_box = header_box.colour_specification_boxes.first().unwrap();
@@ -577,6 +721,10 @@ fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2F
     } else {
         assert_eq!(colour_specification_box.colourspace_approximation(), 0u8);
     }
+    assert_eq!(
+        colour_specification_box.is_approximation_conformant(),
+        !expected.has_unexpected_approx_set
+    );
     match expected.colour_specification_method {
         ColourSpecificationMethods::EnumeratedColourSpace => {
             assert!(colour_specification_box.enumerated_colour_space().is_some());
@@ -585,17 +733,26 @@ fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2F
                 colour_specification_box.enumerated_colour_space().unwrap(),
                 expected.colourspace
             );
+            assert!(!expected.colourspace.is_reserved());
+            if header_box.palette_box.is_none() && header_box.channel_definition_box.is_none() {
+                assert_eq!(
+                    expected.colourspace.channels_expected(),
+                    Some(expected.num_components)
+                );
+            }
         }
         ColourSpecificationMethods::RestrictedICCProfile => {
+            assert!(expected.colourspace.is_reserved());
             assert!(colour_specification_box.enumerated_colour_space().is_none());
             assert!(colour_specification_box.restricted_icc_profile().is_some());
-            assert!(
-                colour_specification_box
-                    .restricted_icc_profile()
-                    .unwrap()
-                    .len()
-                    > 0
-            );
+            assert!(!colour_specification_box
+                .restricted_icc_profile()
+                .unwrap()
+                .is_empty());
+            assert!(RestrictedIccProfile::decode(
+                colour_specification_box.restricted_icc_profile().unwrap()
+            )
+            .is_ok());
         }
         ColourSpecificationMethods::Reserved { value } => {
             panic!(
@@ -613,6 +770,8 @@ fn test_sample_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2F
     assert!(codestream_box.length() > 0);
     assert!(codestream_box.offset() > 0);
 
+    assert_eq!(boxes.validate().unwrap(), vec![]);
+
     boxes
 }
 
@@ -656,9 +815,170 @@ fn test_geojp2() {
     assert_eq!(uuid.data()[0], b'I');
     assert_eq!(uuid.data()[1], b'I');
     assert_eq!(uuid.data().len(), 356);
+
+    let metadata = boxes.metadata();
+    assert_eq!(metadata.items().len(), 2);
+    let geospatial: Vec<_> = metadata.geospatial().collect();
+    assert_eq!(geospatial.len(), 1);
+    match geospatial[0].source() {
+        MetadataSource::Uuid(uuid_box) => assert_eq!(uuid_box.offset(), 85),
+        source => panic!("expected the UUID box, got {:?}", source),
+    }
+    assert_eq!(metadata.xml().count(), 1);
+    assert_eq!(metadata.rights().count(), 0);
+    assert_eq!(metadata.vendor_uuids().count(), 0);
+}
+
+#[test]
+fn test_decode_slice() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = fs::read(path).expect("file should exist");
+    let boxes = decode_jp2_slice(&data).unwrap();
+    assert_eq!(boxes.length(), data.len() as u64);
+    let image_header_box = &boxes.header_box().as_ref().unwrap().image_header_box;
+    assert_eq!(image_header_box.width(), 100);
+    assert_eq!(image_header_box.height(), 24);
+
+    // XML and UUID contents are borrowed from the input, not copied
+    let xml = boxes.xml_boxes().first().unwrap();
+    let xml_payload = xml.payload(&data).unwrap();
+    assert_eq!(xml.xml(), xml.format().as_bytes());
+    assert_eq!(xml.xml().as_ptr(), xml_payload.as_ptr());
+    let uuid = boxes.uuid_boxes().first().unwrap();
+    let uuid_payload = uuid.payload(&data).unwrap();
+    assert_eq!(&uuid_payload[..16], uuid.uuid());
+    assert_eq!(uuid.data(), &uuid_payload[16..]);
+    assert_eq!(uuid.data().as_ptr(), uuid_payload[16..].as_ptr());
+    let codestream = boxes.contiguous_codestreams_boxes().first().unwrap();
+    assert_eq!(codestream.payload(&data).unwrap()[..2], [0xFF, 0x4F]);
+
+    // A box outside of the data
+    assert!(xml.payload(&data[..500]).is_none());
+
+    // The owned copy outlives the input
+    let owned = decode_jp2_slice(&data.clone()).unwrap().into_owned();
+    assert_eq!(owned.xml_boxes()[0].xml(), xml_payload);
+    assert_eq!(owned.uuid_boxes()[0].data(), &uuid_payload[16..]);
+}
+
+#[test]
+fn test_decode_slice_box_length() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let mut data = fs::read(path).expect("file should exist");
+    let boxes = decode_jp2_slice(&data).unwrap();
+    let xml_offset = boxes.xml_boxes()[0].offset();
+
+    // An XML box claiming nearly 4 GiB is rejected without reading or
+    // allocating its contents
+    let header = xml_offset as usize - 8;
+    data[header..header + 4].copy_from_slice(&0xFFFF_FFF0u32.to_be_bytes());
+    let error = decode_jp2_slice(&data).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<JP2Error>(),
+        Some(JP2Error::BoxMalformed { box_type, offset })
+            if box_type == b"xml " && *offset == xml_offset
+    ));
+    assert!(decode_jp2(&mut Cursor::new(&data)).is_err());
 }
 
-fn test_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File {
+#[test]
+fn test_decode_malformed_box_lengths() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = fs::read(path).expect("file should exist");
+    let is_malformed_file_type = |data: &[u8]| {
+        let error = decode_jp2(&mut Cursor::new(data)).unwrap_err();
+        matches!(
+            error.downcast_ref::<JP2Error>(),
+            Some(JP2Error::BoxMalformed { box_type, .. }) if box_type == b"ftyp"
+        )
+    };
+
+    // Reserved box length
+    let mut reserved = data.clone();
+    reserved[12..16].copy_from_slice(&5u32.to_be_bytes());
+    assert!(is_malformed_file_type(&reserved));
+
+    // Extended box length shorter than the box header
+    let mut extended = data[..12].to_vec();
+    extended.extend_from_slice(&1u32.to_be_bytes());
+    extended.extend_from_slice(b"ftyp");
+    extended.extend_from_slice(&8u64.to_be_bytes());
+    extended.extend_from_slice(&data[16..]);
+    assert!(is_malformed_file_type(&extended));
+
+    // File Type box too short for its brand and minor version
+    let mut short = data.clone();
+    short[12..16].copy_from_slice(&12u32.to_be_bytes());
+    assert!(is_malformed_file_type(&short));
+
+    // Compatibility list entries that are not ISO 646 are reported lossily
+    let mut brand = data;
+    brand[28..32].copy_from_slice(&[0xFF, 0xFE, b'2', b' ']);
+    let error = decode_jp2(&mut Cursor::new(&brand)).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<JP2Error>(),
+        Some(JP2Error::NotCompatible { compatibility_list })
+            if compatibility_list == &["\u{FFFD}\u{FFFD}2 "]
+    ));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_decode_mmap() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = fs::read(&path).expect("file should exist");
+    let mapped = jp2::decode_jp2_mmap(&path).unwrap();
+    assert_eq!(mapped.data(), data.as_slice());
+    assert_eq!(mapped.file().length(), data.len() as u64);
+    assert_eq!(mapped.file().xml_boxes().len(), 1);
+    let xml = &mapped.file().xml_boxes()[0];
+    assert_eq!(
+        xml.xml().as_ptr(),
+        xml.payload(mapped.data()).unwrap().as_ptr()
+    );
+
+    let codestream = mapped.codestream(0).unwrap();
+    assert_eq!(codestream.len(), 145);
+    assert_eq!(codestream[..2], [0xFF, 0x4F]);
+    assert_eq!(codestream[143..], [0xFF, 0xD9]);
+    assert!(mapped.codestream(1).is_none());
+
+    assert!(jp2::decode_jp2_mmap(path.with_extension("missing")).is_err());
+}
+
+#[test]
+fn test_decode_truncated() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = fs::read(path).expect("file should exist");
+    let boxes = decode_jp2_slice(&data).unwrap();
+    assert!(boxes.is_complete());
+    let codestream = boxes.contiguous_codestreams_boxes().first().unwrap();
+    assert!(!codestream.is_truncated());
+    assert_eq!(codestream.length(), 145);
+
+    // Part way through the 145 byte codestream
+    let boxes = decode_jp2_slice(&data[..700]).unwrap();
+    assert!(!boxes.is_complete());
+    assert_eq!(boxes.length(), 700);
+    assert!(boxes.header_box().is_some());
+    assert_eq!(boxes.xml_boxes().len(), 1);
+    let codestream = boxes.contiguous_codestreams_boxes().first().unwrap();
+    assert!(codestream.is_truncated());
+    assert_eq!(codestream.offset(), 600);
+    assert_eq!(codestream.length(), 100);
+}
+
+fn test_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File<'static> {
     let path = Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("tests")
         .join(filename);
@@ -687,11 +1007,22 @@ fn test_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File {
     assert_eq!(image_header_box.components_num(), expected.num_components);
     assert_eq!(image_header_box.compression_type(), 7);
     assert_eq!(image_header_box.colourspace_unknown(), 0);
+    assert!(!image_header_box.is_colourspace_unknown());
     assert_eq!(image_header_box.intellectual_property(), 0);
     assert_eq!(image_header_box.components_bits(), expected.bit_depth);
-    assert_eq!(image_header_box.values_are_signed(), false);
+    assert!(!image_header_box.values_are_signed());
 
     assert!(header_box.bits_per_component_box.is_none());
+    assert!(!image_header_box.components_bits_varies());
+    assert_eq!(
+        header_box.component_bit_depths(),
+        vec![
+            BitDepth::Unsigned {
+                value: expected.bit_depth
+            };
+            expected.num_components as usize
+        ]
+    );
 
     assert_eq!(header_box.colour_specification_boxes.len(), 1);
     let colour_specification_box = header_box.colour_specification_boxes.first().unwrap();
@@ -720,6 +1051,8 @@ fn test_jp2_file(filename: &str, expected: ExpectedConfiguration) -> JP2File {
     assert!(codestream_box.length() > 0);
     assert!(codestream_box.offset() > 0);
 
+    assert_eq!(boxes.validate().unwrap(), vec![]);
+
     boxes
 }
 
@@ -742,7 +1075,7 @@ fn test_j2pi() {
     assert_eq!(image_header_box.components_num(), 1);
     assert_eq!(image_header_box.intellectual_property(), 1);
     assert_eq!(image_header_box.components_bits(), 8);
-    assert_eq!(image_header_box.values_are_signed(), false);
+    assert!(!image_header_box.values_are_signed());
 
     assert_eq!(boxes.contiguous_codestreams_boxes().len(), 1);
 
@@ -757,6 +1090,50 @@ fn test_j2pi() {
     assert_eq!(boxes.uuid_boxes().len(), 0);
 }
 
+#[cfg(feature = "xml")]
+#[test]
+fn test_j2pi_rights() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("j2pi.jp2");
+    let data = fs::read(path).expect("file should exist");
+    let boxes = decode_jp2_slice(&data).unwrap();
+    let jp2i = boxes.intellectual_property_box().as_ref().unwrap();
+
+    let rights = jp2i.rights().unwrap();
+    assert_eq!(rights.use_restriction(), Some("unclassified"));
+    assert_eq!(rights.management_type(), Some("SWE"));
+    assert_eq!(
+        rights.protection_countries(),
+        ["SWE", "FRA", "USA", "GBR", "ARE", "ZAF", "DEU", "ITA", "CZE"]
+    );
+
+    // The IPR XML without the exploitation elements
+    let offset = jp2i.offset() as usize;
+    let mut data = data[..offset].to_vec();
+    let xml = b"<jp:IPR xmlns:jp=\"http://www.jpeg.org/jpx/1.0/xml\"/>";
+    data[offset - 8..offset - 4].copy_from_slice(&(xml.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(xml);
+    let boxes = decode_jp2_slice(&data).unwrap();
+    let rights = boxes.intellectual_property_box().as_ref().unwrap().rights();
+    assert_eq!(rights.unwrap(), jp2::IntellectualPropertyRights::default());
+
+    // Another root element
+    let xml = b"<IPR/>\n";
+    data.truncate(offset);
+    data[offset - 8..offset - 4].copy_from_slice(&(xml.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(xml);
+    let boxes = decode_jp2_slice(&data).unwrap();
+    let error = boxes.intellectual_property_box().as_ref().unwrap().rights();
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        format!(
+            "malformed box type [106, 112, 50, 105] at offset {}",
+            offset
+        )
+    );
+}
+
 #[test]
 fn test_res_boxes() {
     let path = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -776,7 +1153,7 @@ fn test_res_boxes() {
     assert_eq!(image_header_box.components_num(), 1);
     assert_eq!(image_header_box.intellectual_property(), 0);
     assert_eq!(image_header_box.components_bits(), 8);
-    assert_eq!(image_header_box.values_are_signed(), false);
+    assert!(!image_header_box.values_are_signed());
 
     assert!(header_box.channel_definition_box.is_none());
     assert!(header_box.resolution_box.is_some());
@@ -801,6 +1178,9 @@ fn test_res_boxes() {
     assert_eq!(resc.horizontal_capture_grid_resolution_exponent(), 0);
     assert_eq!(resc.vertical_resolution_capture(), 20.0);
     assert_eq!(resc.horizontal_resolution_capture(), 25.0);
+    let (horizontal_dpi, vertical_dpi) = resc.capture_dpi();
+    assert!((horizontal_dpi - 0.635).abs() < 1e-9);
+    assert!((vertical_dpi - 0.508).abs() < 1e-9);
 
     assert!(res.default_display_resolution_box().is_some());
     let resd = res.default_display_resolution_box().as_ref().unwrap();
@@ -821,6 +1201,9 @@ fn test_res_boxes() {
     assert_eq!(resd.horizontal_display_grid_resolution_exponent(), 0);
     assert_eq!(resd.vertical_display_grid_resolution(), 300.0);
     assert_eq!(resd.horizontal_display_grid_resolution(), 375.0);
+    let (horizontal_dpi, vertical_dpi) = resd.display_dpi();
+    assert!((horizontal_dpi - 9.525).abs() < 1e-9);
+    assert!((vertical_dpi - 7.62).abs() < 1e-9);
 
     assert_eq!(boxes.contiguous_codestreams_boxes().len(), 1);
 
@@ -856,9 +1239,10 @@ fn test_hirise_modified() {
     assert_eq!(image_header_box.components_num(), 3);
     assert_eq!(image_header_box.compression_type(), 7);
     assert_eq!(image_header_box.colourspace_unknown(), 1);
+    assert!(image_header_box.is_colourspace_unknown());
     assert_eq!(image_header_box.intellectual_property(), 0);
     assert_eq!(image_header_box.components_bits(), 10);
-    assert_eq!(image_header_box.values_are_signed(), false);
+    assert!(!image_header_box.values_are_signed());
 
     assert!(header_box.bits_per_component_box.is_none());
 
@@ -884,10 +1268,25 @@ fn test_hirise_modified() {
 
     assert!(header_box.resolution_box.is_none());
 
+    // The colourspace is marked as unknown, so the sRGB colourspace is not
+    // assumed
+    assert!(header_box.known_colour_space().is_none());
+    assert_eq!(
+        boxes.validate().unwrap(),
+        vec![JP2Warning::ColourspaceUnknown {
+            colour_space: EnumeratedColourSpaces::sRGB
+        }]
+    );
+    assert_eq!(
+        boxes.validate().unwrap()[0].to_string(),
+        "colourspace marked as unknown, but specified as sRGB"
+    );
+
     assert_eq!(boxes.contiguous_codestreams_boxes().len(), 1);
     let codestream_box = boxes.contiguous_codestreams_boxes().first().unwrap();
     assert!(codestream_box.length() > 0);
     assert!(codestream_box.offset() > 0);
+    assert!(boxes.is_complete());
     assert_eq!(boxes.xml_boxes().len(), 0);
     assert_eq!(boxes.uuid_boxes().len(), 1);
     let uuid = boxes.uuid_boxes().first().unwrap();
@@ -937,3 +1336,76 @@ fn test_hirise_modified() {
     assert!(url.location().is_ok());
     assert_eq!(url.location().unwrap(), "ESP_053795_1905_COLOR.LBL");
 }
+
+#[test]
+fn test_enumerated_colour_spaces() {
+    assert_eq!(EnumeratedColourSpaces::sRGB.to_string(), "sRGB");
+    assert_eq!(EnumeratedColourSpaces::Greyscale.to_string(), "greyscale");
+    assert_eq!(EnumeratedColourSpaces::sYCC.to_string(), "sYCC");
+    assert_eq!(EnumeratedColourSpaces::Reserved.to_string(), "Reserved");
+
+    assert_eq!(EnumeratedColourSpaces::sRGB.channels_expected(), Some(3));
+    assert_eq!(
+        EnumeratedColourSpaces::Greyscale.channels_expected(),
+        Some(1)
+    );
+    assert_eq!(EnumeratedColourSpaces::sYCC.channels_expected(), Some(3));
+    assert_eq!(EnumeratedColourSpaces::Reserved.channels_expected(), None);
+
+    assert!(!EnumeratedColourSpaces::sRGB.is_reserved());
+    assert!(EnumeratedColourSpaces::Reserved.is_reserved());
+
+    assert_eq!(EnumeratedColourSpaces::sRGB.nominal_bit_depth(), Some(8));
+    assert_eq!(EnumeratedColourSpaces::sYCC.nominal_bit_depth(), Some(8));
+    assert_eq!(EnumeratedColourSpaces::Reserved.nominal_bit_depth(), None);
+}
+
+#[test]
+fn test_strict_approximation() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../samples/file1.jp2");
+    let data = fs::read(path).unwrap();
+    let lenient = decode_jp2_with_options(&mut Cursor::new(&data), DecodeOptions::default());
+    assert!(lenient.is_ok());
+
+    let strict = DecodeOptions { strict: true };
+    let result = decode_jp2_with_options(&mut Cursor::new(&data), strict);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("colourspace approximation 1 at offset"));
+}
+
+#[test]
+fn test_strict_nesting() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/hazard.jp2");
+    let mut data = fs::read(path).unwrap();
+    let strict = DecodeOptions { strict: true };
+    assert!(decode_jp2_with_options(&mut Cursor::new(&data), strict).is_ok());
+
+    // Colour Specification box outside the JP2 Header box
+    data.extend_from_slice(&[0, 0, 0, 15, b'c', b'o', b'l', b'r', 1, 0, 0, 0, 0, 0, 16]);
+    assert!(decode_jp2(&mut Cursor::new(&data)).is_ok());
+    let result = decode_jp2_with_options(&mut Cursor::new(&data), strict);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("unexpected box type"));
+}
+
+#[test]
+fn test_is_jp2() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/hazard.jp2");
+    let data = fs::read(path).unwrap();
+    let mut reader = Cursor::new(&data);
+    assert!(is_jp2(&mut reader));
+    assert_eq!(reader.position(), 0);
+    assert!(decode_jp2(&mut reader).is_ok());
+
+    // Codestream, signature box cut short and corrupted by a newline
+    // translation
+    assert!(!is_jp2(&mut Cursor::new(&[0xFF, 0x4F, 0xFF, 0x51])));
+    assert!(!is_jp2(&mut Cursor::new(&data[..11])));
+    let mut translated = data[..12].to_vec();
+    translated[8] = 0x0A;
+    assert!(!is_jp2(&mut Cursor::new(&translated)));
+}
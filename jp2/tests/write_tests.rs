@@ -0,0 +1,495 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use jp2::{
+    decode_jp2, BitDepth, CaptureResolutionBox, ColourSpecificationMethods,
+    DefaultDisplayResolutionBox, EnumeratedColourSpaces, JBox as _, JP2Error, JP2File, JP2Warning,
+    Jp2Builder, ResolutionSuperBox,
+};
+
+// SOC, EOC
+const CODESTREAM: [u8; 4] = [0xFF, 0x4F, 0xFF, 0xD9];
+
+#[test]
+fn test_builder_defaults_round_trip() {
+    let mut buffer = Vec::new();
+    Jp2Builder::new(64, 128, CODESTREAM.to_vec())
+        .write_to(&mut buffer)
+        .unwrap();
+
+    // 12 + 20 + (8 + 22 + 15) + (8 + 4)
+    assert_eq!(buffer.len(), 89);
+
+    let mut reader = Cursor::new(&buffer);
+    let jp2 = decode_jp2(&mut reader).unwrap();
+
+    let file_type_box = jp2.file_type_box().as_ref().unwrap();
+    assert_eq!(file_type_box.brand(), "jp2 ");
+    assert_eq!(file_type_box.min_version(), 0);
+    assert_eq!(file_type_box.compatibility_list(), vec!["jp2 ".to_string()]);
+
+    let header_box = jp2.header_box().as_ref().unwrap();
+    let image_header_box = &header_box.image_header_box;
+    assert_eq!(image_header_box.width(), 64);
+    assert_eq!(image_header_box.height(), 128);
+    assert_eq!(image_header_box.components_num(), 3);
+    assert_eq!(image_header_box.components_bits(), 8);
+    assert!(!image_header_box.values_are_signed());
+    assert_eq!(image_header_box.compression_type(), 7);
+    assert_eq!(image_header_box.colourspace_unknown(), 0);
+    assert_eq!(
+        header_box.known_colour_space(),
+        Some(EnumeratedColourSpaces::sRGB)
+    );
+    assert_eq!(image_header_box.intellectual_property(), 0);
+
+    assert_eq!(header_box.colour_specification_boxes.len(), 1);
+    let colour_specification_box = &header_box.colour_specification_boxes[0];
+    assert_eq!(
+        colour_specification_box.method(),
+        ColourSpecificationMethods::EnumeratedColourSpace
+    );
+    assert_eq!(colour_specification_box.precedence(), 0);
+    assert_eq!(colour_specification_box.colourspace_approximation(), 0);
+    assert_eq!(
+        colour_specification_box.enumerated_colour_space(),
+        Some(EnumeratedColourSpaces::sRGB)
+    );
+
+    assert_eq!(jp2.contiguous_codestreams_boxes().len(), 1);
+    let codestream_box = &jp2.contiguous_codestreams_boxes()[0];
+    assert_eq!(codestream_box.length(), CODESTREAM.len() as u64);
+    assert_eq!(
+        &buffer[codestream_box.offset as usize..],
+        CODESTREAM.as_slice()
+    );
+    assert_eq!(jp2.length(), buffer.len() as u64);
+}
+
+#[test]
+fn test_builder_greyscale_signed_round_trip() {
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .components_num(1)
+        .bit_depth(12, true)
+        .colour_space(EnumeratedColourSpaces::Greyscale)
+        .write_to(&mut buffer)
+        .unwrap();
+
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let header_box = jp2.header_box().as_ref().unwrap();
+    assert_eq!(header_box.image_header_box.components_num(), 1);
+    assert_eq!(header_box.image_header_box.components_bits(), 12);
+    assert!(header_box.image_header_box.values_are_signed());
+    assert_eq!(
+        header_box.colour_specification_boxes[0].enumerated_colour_space(),
+        Some(EnumeratedColourSpaces::Greyscale)
+    );
+}
+
+#[test]
+fn test_builder_invalid_bit_depth() {
+    let mut buffer = Vec::new();
+    let result = Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .bit_depth(39, false)
+        .write_to(&mut buffer);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "invalid value for bit_depth"
+    );
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_validate_channel_count() {
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .write_to(&mut buffer)
+        .unwrap();
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    assert_eq!(jp2.validate().unwrap(), vec![]);
+
+    // Greyscale with three components, the extra channels are unspecified
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .colour_space(EnumeratedColourSpaces::Greyscale)
+        .write_to(&mut buffer)
+        .unwrap();
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let warnings = jp2.validate().unwrap();
+    assert_eq!(
+        warnings,
+        vec![JP2Warning::UnassociatedChannels {
+            colour_space: EnumeratedColourSpaces::Greyscale,
+            expected: 1,
+            found: 3
+        }]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "greyscale requires 1 colour channels, 3 channels found without a channel definition"
+    );
+
+    // sRGB with one component and no palette
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .components_num(1)
+        .write_to(&mut buffer)
+        .unwrap();
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let error = jp2.validate().unwrap_err();
+    assert!(matches!(
+        error,
+        JP2Error::ChannelCountMismatch {
+            colour_space: EnumeratedColourSpaces::sRGB,
+            expected: 3,
+            found: 1
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "sRGB requires 3 colour channels but 1 found"
+    );
+}
+
+#[test]
+fn test_builder_resolution_round_trip() {
+    let capture = CaptureResolutionBox::new(20, 1, 25, 1, 0, 0).unwrap();
+    let display = DefaultDisplayResolutionBox::from_dpi(300.0, 72.0).unwrap();
+    let resolution = ResolutionSuperBox::new(Some(capture), Some(display)).unwrap();
+
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .resolution(resolution)
+        .write_to(&mut buffer)
+        .unwrap();
+
+    // 89 byte minimal file, with a 44 byte Resolution box
+    assert_eq!(buffer.len(), 89 + 44);
+
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let header_box = jp2.header_box().as_ref().unwrap();
+    let res = header_box.resolution_box.as_ref().unwrap();
+    assert_eq!(res.length(), 36);
+
+    let resc = res.capture_resolution_box().as_ref().unwrap();
+    assert_eq!(resc.length(), 10);
+    assert_eq!(resc.vertical_capture_grid_resolution_numerator(), 20);
+    assert_eq!(resc.vertical_capture_grid_resolution_denominator(), 1);
+    assert_eq!(resc.horizontal_capture_grid_resolution_numerator(), 25);
+    assert_eq!(resc.horizontal_capture_grid_resolution_denominator(), 1);
+    assert_eq!(resc.vertical_capture_grid_resolution_exponent(), 0);
+    assert_eq!(resc.horizontal_capture_grid_resolution_exponent(), 0);
+
+    // 300 dpi is 15000 / 127 * 10^2 points per metre, 72 dpi is 36000 / 127 * 10^1
+    let resd = res.default_display_resolution_box().as_ref().unwrap();
+    assert_eq!(resd.horizontal_display_grid_resolution_numerator(), 15000);
+    assert_eq!(resd.horizontal_display_grid_resolution_denominator(), 127);
+    assert_eq!(resd.horizontal_display_grid_resolution_exponent(), 2);
+    assert_eq!(resd.vertical_display_grid_resolution_numerator(), 36000);
+    assert_eq!(resd.vertical_display_grid_resolution_denominator(), 127);
+    assert_eq!(resd.vertical_display_grid_resolution_exponent(), 1);
+    let (horizontal_dpi, vertical_dpi) = resd.display_dpi();
+    assert!((horizontal_dpi - 300.0).abs() < 1e-9);
+    assert!((vertical_dpi - 72.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_resolution_from_dpi() {
+    let capture = CaptureResolutionBox::from_dpi(2400.0, 0.5).unwrap();
+    let resolution = ResolutionSuperBox::new(Some(capture), None).unwrap();
+    assert_eq!(resolution.length(), 18);
+    let capture = resolution.capture_resolution_box().as_ref().unwrap();
+    let (horizontal_dpi, vertical_dpi) = capture.capture_dpi();
+    assert!((horizontal_dpi - 2400.0).abs() < 1e-9);
+    assert!((vertical_dpi - 0.5).abs() < 1e-9);
+
+    assert!(CaptureResolutionBox::from_dpi(0.0, 300.0).is_err());
+    assert!(DefaultDisplayResolutionBox::from_dpi(300.0, f64::NAN).is_err());
+    assert!(CaptureResolutionBox::new(1, 0, 1, 1, 0, 0).is_err());
+    assert_eq!(
+        ResolutionSuperBox::new(None, None).unwrap_err().to_string(),
+        "invalid value for resolution"
+    );
+}
+
+// Resolution box of the capture and display resolutions, written and read
+// back
+fn resolution_round_trip(
+    capture: CaptureResolutionBox,
+    display: DefaultDisplayResolutionBox,
+) -> JP2File<'static> {
+    let resolution = ResolutionSuperBox::new(Some(capture), Some(display)).unwrap();
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .resolution(resolution)
+        .write_to(&mut buffer)
+        .unwrap();
+    decode_jp2(&mut Cursor::new(&buffer)).unwrap()
+}
+
+fn resolution_box<'a>(jp2: &'a JP2File) -> &'a ResolutionSuperBox {
+    let header_box = jp2.header_box().as_ref().unwrap();
+    header_box.resolution_box.as_ref().unwrap()
+}
+
+#[test]
+fn test_resolution_exponents() {
+    // 254 / 10 · 10^3 and 3 / 4 · 10^-2 points per metre, then 11811 / 1 ·
+    // 10^-2 and 5 / 2 · 10^5
+    let jp2 = resolution_round_trip(
+        CaptureResolutionBox::new(254, 10, 3, 4, 3, -2).unwrap(),
+        DefaultDisplayResolutionBox::new(11811, 1, 5, 2, -2, 5).unwrap(),
+    );
+    let res = resolution_box(&jp2);
+    let resc = res.capture_resolution_box().as_ref().unwrap();
+    assert_eq!(resc.vertical_capture_grid_resolution_exponent(), 3);
+    assert_eq!(resc.horizontal_capture_grid_resolution_exponent(), -2);
+    assert_eq!(resc.vertical_resolution_capture(), 25400.0);
+    assert_eq!(resc.horizontal_resolution_capture(), 0.0075);
+    let resd = res.default_display_resolution_box().as_ref().unwrap();
+    assert_eq!(resd.vertical_display_grid_resolution(), 118.11);
+    assert_eq!(resd.horizontal_display_grid_resolution(), 250000.0);
+    let (_, vertical_dpi) = resd.display_dpi();
+    assert!((vertical_dpi - 2.999_994).abs() < 1e-9);
+}
+
+#[test]
+fn test_resolution_extreme_exponents() {
+    // The largest and smallest exponents stay finite and non-zero
+    let jp2 = resolution_round_trip(
+        CaptureResolutionBox::new(u16::MAX, 1, 1, u16::MAX, 127, -128).unwrap(),
+        DefaultDisplayResolutionBox::new(1, 1, 1, 1, 22, -22).unwrap(),
+    );
+    let res = resolution_box(&jp2);
+    let resc = res.capture_resolution_box().as_ref().unwrap();
+    let vertical = resc.vertical_resolution_capture();
+    assert!(vertical.is_finite());
+    assert!((vertical / 6.5535e131 - 1.0).abs() < 1e-12);
+    let horizontal = resc.horizontal_resolution_capture();
+    assert!(horizontal > 0.0);
+    assert!((horizontal * 6.5535e132 - 1.0).abs() < 1e-12);
+
+    // Powers of ten up to 10^22 are exact
+    let resd = res.default_display_resolution_box().as_ref().unwrap();
+    assert_eq!(resd.vertical_display_grid_resolution(), 1e22);
+    assert_eq!(resd.horizontal_display_grid_resolution(), 1e-22);
+}
+
+#[test]
+fn test_components_bits_varies() {
+    let mut buffer = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .components_num(2)
+        .colour_space(EnumeratedColourSpaces::Greyscale)
+        .write_to(&mut buffer)
+        .unwrap();
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let header_box = jp2.header_box().as_ref().unwrap();
+    assert!(!header_box.image_header_box.components_bits_varies());
+    assert_eq!(
+        header_box.component_bit_depths(),
+        vec![BitDepth::Unsigned { value: 8 }; 2]
+    );
+
+    // Image Header box BPC field, at 12 + 20 + 8 + 8 + 10
+    buffer[58] = 255;
+    let missing = decode_jp2(&mut Cursor::new(&buffer));
+    assert_eq!(
+        missing.unwrap_err().to_string(),
+        "box type [98, 112, 99, 99] missing"
+    );
+
+    // Bits Per Component box with an unsigned 8 bit and a signed 12 bit
+    // component, at the end of the JP2 Header box
+    let bits_per_component = [0, 0, 0, 10, b'b', b'p', b'c', b'c', 0x07, 0x8B];
+    buffer.splice(77..77, bits_per_component.iter().copied());
+    buffer[35] += 10;
+
+    let jp2 = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let header_box = jp2.header_box().as_ref().unwrap();
+    assert!(header_box.image_header_box.components_bits_varies());
+    assert_eq!(header_box.image_header_box.components_bits(), 255);
+    assert!(header_box.bits_per_component_box.is_some());
+    assert_eq!(
+        header_box.component_bit_depths(),
+        vec![
+            BitDepth::Unsigned { value: 8 },
+            BitDepth::Signed { value: 12 }
+        ]
+    );
+}
+
+#[test]
+fn test_write_back() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = std::fs::read(path).expect("file should exist");
+    let mut reader = Cursor::new(&data);
+    let jp2 = decode_jp2(&mut reader).unwrap();
+
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    assert_eq!(buffer, data);
+
+    // The JP2 Header box, with its Image Header and Colour Specification
+    // boxes, and the XML box
+    let mut buffer = Vec::new();
+    let header_box = jp2.header_box().as_ref().unwrap();
+    header_box.write_back(&mut reader, &mut buffer).unwrap();
+    assert_eq!(buffer, &data[32..77]);
+    let mut buffer = Vec::new();
+    jp2.xml_boxes()[0]
+        .write_back(&mut reader, &mut buffer)
+        .unwrap();
+    assert_eq!(buffer, &data[457..592]);
+
+    // The reader must hold the box
+    let mut short = Cursor::new(&data[..600]);
+    let codestream_box = &jp2.contiguous_codestreams_boxes()[0];
+    assert!(codestream_box
+        .write_back(&mut short, &mut Vec::new())
+        .is_err());
+}
+
+#[test]
+fn test_write_back_box_headers() {
+    let mut data = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .write_to(&mut data)
+        .unwrap();
+    // Codestream box with an XLBox field, then a box with an LBox of 0 that
+    // extends to the end of the file
+    data.truncate(data.len() - 12);
+    data.extend_from_slice(&[0, 0, 0, 1, b'j', b'p', b'2', b'c']);
+    data.extend_from_slice(&20u64.to_be_bytes());
+    data.extend_from_slice(&CODESTREAM);
+    data.extend_from_slice(&[0, 0, 0, 0, b'f', b'r', b'e', b'e', 0xAA, 0xBB]);
+
+    let mut reader = Cursor::new(&data);
+    let jp2 = decode_jp2(&mut reader).unwrap();
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    assert_eq!(buffer, data);
+
+    // A single box is written with the shortest header
+    let mut buffer = Vec::new();
+    jp2.contiguous_codestreams_boxes()[0]
+        .write_back(&mut reader, &mut buffer)
+        .unwrap();
+    assert_eq!(
+        buffer,
+        [0, 0, 0, 12, b'j', b'p', b'2', b'c', 0xFF, 0x4F, 0xFF, 0xD9]
+    );
+    let mut buffer = Vec::new();
+    jp2.unknown_boxes()[0]
+        .write_back(&mut reader, &mut buffer)
+        .unwrap();
+    assert_eq!(buffer, [0, 0, 0, 10, b'f', b'r', b'e', b'e', 0xAA, 0xBB]);
+}
+
+#[test]
+fn test_edit_metadata_boxes() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = std::fs::read(path).expect("file should exist");
+    let mut reader = Cursor::new(&data);
+    let mut jp2 = decode_jp2(&mut reader).unwrap();
+
+    // Remove the GeoJP2 UUID box, of 380 bytes
+    let removed = jp2.remove_uuid_box(0).unwrap();
+    assert_eq!(removed.data().len(), 356);
+    assert!(jp2.uuid_boxes().is_empty());
+    assert_eq!(
+        jp2.remove_uuid_box(0).unwrap_err().to_string(),
+        "invalid value for index"
+    );
+
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    assert_eq!(buffer.len(), data.len() - 380);
+    assert_eq!(buffer[..77], data[..77]);
+    assert_eq!(buffer[77..], data[457..]);
+
+    let edited = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    assert!(edited.uuid_boxes().is_empty());
+    let image_header_box = &edited.header_box().as_ref().unwrap().image_header_box;
+    assert_eq!(image_header_box.width(), 100);
+    assert_eq!(image_header_box.height(), 24);
+    let codestream_box = &edited.contiguous_codestreams_boxes()[0];
+    assert_eq!(
+        codestream_box.payload(&buffer),
+        jp2.contiguous_codestreams_boxes()[0].payload(&data)
+    );
+
+    // Add an XML box after the existing one
+    jp2.add_xml_box("<test/>");
+    assert_eq!(jp2.xml_boxes()[1].format(), "<test/>");
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    let edited = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    assert_eq!(edited.xml_boxes().len(), 2);
+    assert_eq!(edited.xml_boxes()[0].format(), jp2.xml_boxes()[0].format());
+    assert_eq!(edited.xml_boxes()[1].format(), "<test/>");
+    assert_eq!(edited.xml_boxes()[1].offset(), 77 + 135 + 8);
+    assert_eq!(buffer.len(), data.len() - 380 + 15);
+}
+
+#[test]
+fn test_replace_uuid_box() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("geojp2.jp2");
+    let data = std::fs::read(path).expect("file should exist");
+    let mut reader = Cursor::new(&data);
+    let mut jp2 = decode_jp2(&mut reader).unwrap();
+
+    let uuid = [0x11; 16];
+    jp2.replace_uuid_box(0, uuid, vec![1, 2, 3]).unwrap();
+    assert!(jp2.replace_uuid_box(1, uuid, vec![]).is_err());
+    let mut buffer = Vec::new();
+    jp2.uuid_boxes()[0]
+        .write_back(&mut reader, &mut buffer)
+        .unwrap();
+    assert_eq!(buffer.len(), 27);
+
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    assert_eq!(buffer.len(), data.len() - 380 + 27);
+    let edited = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    let uuid_box = &edited.uuid_boxes()[0];
+    assert_eq!(uuid_box.uuid(), &uuid);
+    assert_eq!(uuid_box.data(), &vec![1, 2, 3]);
+    assert_eq!(uuid_box.offset(), 77 + 8);
+    assert_eq!(edited.xml_boxes()[0].format(), jp2.xml_boxes()[0].format());
+    assert_eq!(buffer[buffer.len() - 153..], data[592..]);
+}
+
+#[test]
+fn test_add_xml_box_after_box_to_end_of_file() {
+    let mut data = Vec::new();
+    Jp2Builder::new(1, 1, CODESTREAM.to_vec())
+        .write_to(&mut data)
+        .unwrap();
+    // Codestream box with an LBox of 0, extending to the end of the file
+    let codestream_offset = data.len() - 12;
+    data[codestream_offset..codestream_offset + 4].copy_from_slice(&[0; 4]);
+
+    let mut reader = Cursor::new(&data);
+    let mut jp2 = decode_jp2(&mut reader).unwrap();
+    // There is no XML box, so the new box precedes the codestream
+    jp2.add_xml_box("<a/>");
+    let mut buffer = Vec::new();
+    jp2.write(&mut reader, &mut buffer).unwrap();
+    assert_eq!(
+        buffer[codestream_offset..codestream_offset + 12],
+        *b"\0\0\0\x0cxml <a/>"
+    );
+    assert_eq!(buffer[codestream_offset + 12..], data[codestream_offset..]);
+
+    let edited = decode_jp2(&mut Cursor::new(&buffer)).unwrap();
+    assert_eq!(edited.xml_boxes()[0].format(), "<a/>");
+    assert_eq!(edited.contiguous_codestreams_boxes()[0].length(), 4);
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use jpc::coder::standard_decoder;
+use jpc::{decode_jpc, CodeBlockDecoder, SubBandType};
+
+// Compressed data for the J.10 LL and LH code-blocks, see
+// ITU-T T.800 (V4) | ISO/IEC 15444-1:2024 Section J.10
+const J10A_DATA: &[u8] = b"\x01\x8F\x0D\xC8\x75\x5D";
+const J10B_DATA: &[u8] = b"\x0F\xB1\x76";
+
+// 64x64 code-block with ten magnitude bit-planes, all of them coded
+const SYNTHETIC_SIZE: u32 = 64;
+const SYNTHETIC_MB: u8 = 10;
+const SYNTHETIC_PASSES: u8 = 1 + 3 * (SYNTHETIC_MB - 1);
+
+fn decode_code_block(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    subband: SubBandType,
+    no_passes: u8,
+    mb: u8,
+    zero_bit_planes: u8,
+) -> Vec<i32> {
+    let mut coder = standard_decoder(data);
+    let mut code_block = CodeBlockDecoder::new(width, height, subband, no_passes, mb).unwrap();
+    code_block.num_zero_bit_plane(zero_bit_planes).unwrap();
+    code_block.decode(&mut coder).unwrap();
+    code_block.coefficients()
+}
+
+// The MQ decoder accepts any input, so pseudo-random bytes stand in for a
+// large compressed code-block
+fn synthetic_data() -> Vec<u8> {
+    let mut state: u32 = 0x1234_5678;
+    (0..8192)
+        .map(|_| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            // Avoid marker codes, as an encoder would
+            (state >> 24) as u8 & 0x7F
+        })
+        .collect()
+}
+
+fn bench_code_block(c: &mut Criterion) {
+    c.bench_function("code_block_j10a", |b| {
+        b.iter(|| decode_code_block(black_box(J10A_DATA), 1, 5, SubBandType::LL, 16, 9, 3))
+    });
+    c.bench_function("code_block_j10b", |b| {
+        b.iter(|| decode_code_block(black_box(J10B_DATA), 1, 4, SubBandType::LH, 7, 10, 7))
+    });
+
+    let data = synthetic_data();
+    c.bench_function("code_block_synthetic_64x64", |b| {
+        b.iter(|| {
+            decode_code_block(
+                black_box(&data),
+                SYNTHETIC_SIZE,
+                SYNTHETIC_SIZE,
+                SubBandType::HH,
+                SYNTHETIC_PASSES,
+                SYNTHETIC_MB,
+                0,
+            )
+        })
+    });
+}
+
+fn bench_decode_jpc(c: &mut Criterion) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let data = fs::read(path).expect("file should exist");
+    c.bench_function("decode_jpc_tlm", |b| {
+        b.iter(|| decode_jpc(&mut Cursor::new(black_box(&data))).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_code_block, bench_decode_jpc);
+criterion_main!(benches);
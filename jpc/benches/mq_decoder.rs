@@ -0,0 +1,91 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use jpc::coder::{standard_decoder, MqDecoder, MqEncoder, NUM_CONTEXTS};
+
+// Compressed data and context sequence for the J.10 LL code-block, see
+// ITU-T T.800 (V4) | ISO/IEC 15444-1:2024 Table J.21
+const J10A_DATA: &[u8] = b"\x01\x8F\x0D\xC8\x75\x5D";
+const J10A_CONTEXTS: [usize; 35] = [
+    17, 18, 18, 9, 3, 3, 10, 3, 10, 15, 0, 9, 4, 10, 15, 15, 15, 16, 15, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+];
+
+// Compressed data and context sequence for the J.10 LH code-block
+const J10B_DATA: &[u8] = b"\x0F\xB1\x76";
+const J10B_CONTEXTS: [usize; 16] = [17, 18, 18, 9, 3, 0, 3, 3, 14, 0, 3, 10, 3, 10, 3, 16];
+
+// Number of decisions in the synthetic code-block
+const SYNTHETIC_DECISIONS: usize = 1 << 16;
+
+fn decode_all(decoder: &mut MqDecoder, contexts: &[usize]) -> u32 {
+    let mut ones = 0;
+    for cx in contexts {
+        ones += decoder.decode(*cx) as u32;
+    }
+    ones
+}
+
+// Pseudo-random decisions, biased towards zero, spread over all contexts
+fn synthetic_code_block() -> (Vec<u8>, Vec<usize>) {
+    let mut state: u32 = 0x1234_5678;
+    let mut encoder = MqEncoder::new(NUM_CONTEXTS);
+    encoder.init();
+    encoder.reset_contexts();
+    let mut contexts = Vec::with_capacity(SYNTHETIC_DECISIONS);
+    for _ in 0..SYNTHETIC_DECISIONS {
+        // xorshift32
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let cx = (state % NUM_CONTEXTS as u32) as usize;
+        let d = (state >> 8).is_multiple_of(8) as u8;
+        encoder.encode(cx, d);
+        contexts.push(cx);
+    }
+    (encoder.flush(), contexts)
+}
+
+fn bench_j10(c: &mut Criterion) {
+    c.bench_function("mq_decode_j10a", |b| {
+        b.iter(|| {
+            let mut decoder = standard_decoder(black_box(J10A_DATA));
+            decode_all(&mut decoder, &J10A_CONTEXTS)
+        })
+    });
+    c.bench_function("mq_decode_j10b", |b| {
+        b.iter(|| {
+            let mut decoder = standard_decoder(black_box(J10B_DATA));
+            decode_all(&mut decoder, &J10B_CONTEXTS)
+        })
+    });
+}
+
+fn bench_synthetic(c: &mut Criterion) {
+    let (data, contexts) = synthetic_code_block();
+    c.bench_function("mq_decode_synthetic", |b| {
+        b.iter(|| {
+            let mut decoder = standard_decoder(black_box(&data));
+            decode_all(&mut decoder, &contexts)
+        })
+    });
+
+    // Many small code-blocks through one decoder, as a whole image decode does
+    c.bench_function("mq_decode_reused_decoder", |b| {
+        b.iter_batched_ref(
+            || MqDecoder::new(NUM_CONTEXTS),
+            |decoder| {
+                let mut ones = 0;
+                for _ in 0..64 {
+                    decoder.reset_contexts();
+                    decoder.init(black_box(J10A_DATA));
+                    ones += decode_all(decoder, &J10A_CONTEXTS);
+                }
+                ones
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_j10, bench_synthetic);
+criterion_main!(benches);
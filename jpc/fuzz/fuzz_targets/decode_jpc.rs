@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Malformed input shall result in an error, never a panic, whether in
+    // the headers or in the packets and code-blocks
+    let mut reader = Cursor::new(data);
+    if let Ok(codestream) = jpc::decode_jpc(&mut reader) {
+        let _ = codestream.decode_image(&mut reader);
+    }
+});
@@ -0,0 +1,351 @@
+//! Codestream main header writing.
+//!
+//! This is the serialization counterpart to the main header parsing, covering
+//! the SOC, SIZ, COD, QCD and COM markers of ITU T.800 | ISO/IEC 15444-1
+//! Annex A.
+
+use std::convert::TryFrom;
+use std::error;
+use std::io;
+
+use super::{
+    CodestreamError, MarkerSymbol, ProgressionOrder, TransformationFilter, MARKER_SYMBOL_COD,
+    MARKER_SYMBOL_COM, MARKER_SYMBOL_QCD, MARKER_SYMBOL_SIZ, MARKER_SYMBOL_SOC,
+    MULTIPLE_COMPONENT_TRANSFORMATION_MULTIPLE, MULTIPLE_COMPONENT_TRANSFORMATION_NONE,
+};
+
+/// Builder for a codestream main header.
+///
+/// The header is written as SOC, SIZ, COD, QCD and an optional COM marker
+/// segment. Tile-parts and the EOC marker are left to the caller.
+///
+/// The defaults are a single tile covering the image, one quality layer,
+/// layer-resolution level-component-position progression, five decomposition
+/// levels with the 5-3 reversible filter, 64x64 code-blocks, the default
+/// precincts and two guard bits.
+///
+/// With the reversible filter, no quantization is signalled and the exponents
+/// are derived from the component precision and sub-band gain. With the
+/// irreversible filter, scalar derived quantization is signalled with a unit
+/// step size for the N<sub>L</sub>LL sub-band.
+#[derive(Debug)]
+pub struct CodestreamHeaderBuilder {
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    no_components: u16,
+    precision: u8,
+    values_are_signed: bool,
+    progression_order: ProgressionOrder,
+    no_layers: u16,
+    multiple_component_transformation: bool,
+    no_decomposition_levels: u8,
+    code_block_width_exponent: u8,
+    code_block_height_exponent: u8,
+    precinct_sizes: Option<Vec<(u8, u8)>>,
+    transformation: TransformationFilter,
+    guard_bits: u8,
+    comment: Option<String>,
+}
+
+impl CodestreamHeaderBuilder {
+    /// Create a builder for an image of the given size, number of components
+    /// and component precision in bits.
+    pub fn new(
+        width: u32,
+        height: u32,
+        no_components: u16,
+        precision: u8,
+    ) -> CodestreamHeaderBuilder {
+        CodestreamHeaderBuilder {
+            width,
+            height,
+            tile_width: width,
+            tile_height: height,
+            no_components,
+            precision,
+            values_are_signed: false,
+            progression_order: ProgressionOrder::LRLCPP,
+            no_layers: 1,
+            multiple_component_transformation: false,
+            no_decomposition_levels: 5,
+            code_block_width_exponent: 6,
+            code_block_height_exponent: 6,
+            precinct_sizes: None,
+            transformation: TransformationFilter::Reversible,
+            guard_bits: 2,
+            comment: None,
+        }
+    }
+
+    /// Set whether the component samples are signed.
+    pub fn values_are_signed(mut self, values_are_signed: bool) -> CodestreamHeaderBuilder {
+        self.values_are_signed = values_are_signed;
+        self
+    }
+
+    /// Set the reference tile size (XTsiz, YTsiz).
+    pub fn tile_size(mut self, tile_width: u32, tile_height: u32) -> CodestreamHeaderBuilder {
+        self.tile_width = tile_width;
+        self.tile_height = tile_height;
+        self
+    }
+
+    /// Set the progression order.
+    pub fn progression_order(
+        mut self,
+        progression_order: ProgressionOrder,
+    ) -> CodestreamHeaderBuilder {
+        self.progression_order = progression_order;
+        self
+    }
+
+    /// Set the number of quality layers.
+    pub fn no_layers(mut self, no_layers: u16) -> CodestreamHeaderBuilder {
+        self.no_layers = no_layers;
+        self
+    }
+
+    /// Set whether the multiple component transformation is used on
+    /// components 0, 1 and 2.
+    pub fn multiple_component_transformation(
+        mut self,
+        multiple_component_transformation: bool,
+    ) -> CodestreamHeaderBuilder {
+        self.multiple_component_transformation = multiple_component_transformation;
+        self
+    }
+
+    /// Set the number of decomposition levels, N<sub>L</sub>.
+    pub fn no_decomposition_levels(
+        mut self,
+        no_decomposition_levels: u8,
+    ) -> CodestreamHeaderBuilder {
+        self.no_decomposition_levels = no_decomposition_levels;
+        self
+    }
+
+    /// Set the code-block size as base two exponents, xcb and ycb.
+    ///
+    /// Each exponent shall be from 2 to 10 inclusive, and xcb + ycb shall be
+    /// no more than 12.
+    pub fn code_block_size(
+        mut self,
+        width_exponent: u8,
+        height_exponent: u8,
+    ) -> CodestreamHeaderBuilder {
+        self.code_block_width_exponent = width_exponent;
+        self.code_block_height_exponent = height_exponent;
+        self
+    }
+
+    /// Set the precinct size of each resolution level, starting with the
+    /// lowest, as base two exponents, PPx and PPy.
+    ///
+    /// There shall be one for each of the N<sub>L</sub> + 1 resolution
+    /// levels. Each exponent shall be no more than 15, and no less than 1
+    /// other than for resolution level 0.
+    pub fn precinct_sizes(mut self, precinct_sizes: &[(u8, u8)]) -> CodestreamHeaderBuilder {
+        self.precinct_sizes = Some(precinct_sizes.to_vec());
+        self
+    }
+
+    /// Set the wavelet transformation filter.
+    pub fn transformation(
+        mut self,
+        transformation: TransformationFilter,
+    ) -> CodestreamHeaderBuilder {
+        self.transformation = transformation;
+        self
+    }
+
+    /// Set the number of guard bits, from 0 to 7 inclusive.
+    pub fn guard_bits(mut self, guard_bits: u8) -> CodestreamHeaderBuilder {
+        self.guard_bits = guard_bits;
+        self
+    }
+
+    /// Add a Latin (ISO/IEC 8859-15) comment.
+    ///
+    /// Characters outside of the single byte range cannot be written.
+    pub fn comment(mut self, comment: &str) -> CodestreamHeaderBuilder {
+        self.comment = Some(comment.to_owned());
+        self
+    }
+
+    fn validate(&self) -> Result<(), CodestreamError> {
+        let invalid = |name| Err(CodestreamError::InvalidParameter { name });
+        if self.width == 0 || self.height == 0 {
+            return invalid("size");
+        }
+        if self.tile_width == 0 || self.tile_height == 0 {
+            return invalid("tile_size");
+        }
+        if !(1..=16384).contains(&self.no_components) {
+            return invalid("no_components");
+        }
+        if !(1..=38).contains(&self.precision) {
+            return invalid("precision");
+        }
+        // The quantization exponents are limited to five bits
+        let max_exponent = match self.transformation {
+            TransformationFilter::Reversible => self.precision + 2,
+            _ => self.precision,
+        };
+        if max_exponent > 31 {
+            return invalid("precision");
+        }
+        if self.no_layers == 0 {
+            return invalid("no_layers");
+        }
+        if self.multiple_component_transformation && self.no_components < 3 {
+            return invalid("multiple_component_transformation");
+        }
+        if self.no_decomposition_levels > 32 {
+            return invalid("no_decomposition_levels");
+        }
+        if !(2..=10).contains(&self.code_block_width_exponent)
+            || !(2..=10).contains(&self.code_block_height_exponent)
+            || self.code_block_width_exponent + self.code_block_height_exponent > 12
+        {
+            return invalid("code_block_size");
+        }
+        if let Some(precinct_sizes) = &self.precinct_sizes {
+            // Sub-band precincts are half the size of those of the
+            // resolution level, Equation B-16
+            let minimum = |r: usize| if r == 0 { 0 } else { 1 };
+            if precinct_sizes.len() != usize::from(self.no_decomposition_levels) + 1
+                || precinct_sizes.iter().enumerate().any(|(r, &(ppx, ppy))| {
+                    !(minimum(r)..=15).contains(&ppx) || !(minimum(r)..=15).contains(&ppy)
+                })
+            {
+                return invalid("precinct_sizes");
+            }
+        }
+        if self.guard_bits > 7 {
+            return invalid("guard_bits");
+        }
+        if let Some(comment) = &self.comment {
+            if comment.len() > u16::MAX as usize - 4 {
+                return invalid("comment");
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the main header to `writer`.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), Box<dyn error::Error>> {
+        self.validate()?;
+        let progression_order = match self.progression_order {
+            ProgressionOrder::Reserved { .. } => {
+                return Err(CodestreamError::InvalidParameter {
+                    name: "progression_order",
+                }
+                .into());
+            }
+            ref progression_order => progression_order.value(),
+        };
+        let transformation = match self.transformation {
+            TransformationFilter::Reserved { .. } => {
+                return Err(CodestreamError::InvalidParameter {
+                    name: "transformation",
+                }
+                .into());
+            }
+            ref transformation => transformation.value(),
+        };
+
+        writer.write_all(&MARKER_SYMBOL_SOC.0)?;
+
+        // SIZ, with the image and tile grids at the origin
+        let mut ssiz = self.precision - 1;
+        if self.values_are_signed {
+            ssiz |= 0x80;
+        }
+        let mut siz = Vec::with_capacity(36 + 3 * self.no_components as usize);
+        siz.extend_from_slice(&0u16.to_be_bytes());
+        siz.extend_from_slice(&self.width.to_be_bytes());
+        siz.extend_from_slice(&self.height.to_be_bytes());
+        siz.extend_from_slice(&0u32.to_be_bytes());
+        siz.extend_from_slice(&0u32.to_be_bytes());
+        siz.extend_from_slice(&self.tile_width.to_be_bytes());
+        siz.extend_from_slice(&self.tile_height.to_be_bytes());
+        siz.extend_from_slice(&0u32.to_be_bytes());
+        siz.extend_from_slice(&0u32.to_be_bytes());
+        siz.extend_from_slice(&self.no_components.to_be_bytes());
+        for _ in 0..self.no_components {
+            siz.extend_from_slice(&[ssiz, 1, 1]);
+        }
+        write_segment(writer, &MARKER_SYMBOL_SIZ, &siz)?;
+
+        // COD, with no SOP or EPH markers
+        let mut cod = Vec::with_capacity(10);
+        cod.push(u8::from(self.precinct_sizes.is_some()));
+        cod.push(progression_order);
+        cod.extend_from_slice(&self.no_layers.to_be_bytes());
+        cod.push(if self.multiple_component_transformation {
+            MULTIPLE_COMPONENT_TRANSFORMATION_MULTIPLE
+        } else {
+            MULTIPLE_COMPONENT_TRANSFORMATION_NONE
+        });
+        cod.push(self.no_decomposition_levels);
+        cod.push(self.code_block_width_exponent - 2);
+        cod.push(self.code_block_height_exponent - 2);
+        cod.push(0);
+        cod.push(transformation);
+        for &(ppx, ppy) in self.precinct_sizes.iter().flatten() {
+            cod.push((ppy << 4) | ppx);
+        }
+        write_segment(writer, &MARKER_SYMBOL_COD, &cod)?;
+
+        // QCD
+        let no_subbands = 3 * self.no_decomposition_levels as usize + 1;
+        let mut qcd = Vec::with_capacity(1 + 2 * no_subbands);
+        match self.transformation {
+            TransformationFilter::Reversible => {
+                qcd.push(self.guard_bits << 5);
+                // The N_L LL sub-band has no gain, followed by HL, LH and HH
+                // for each decomposition level
+                qcd.push(self.precision << 3);
+                for _ in 0..self.no_decomposition_levels {
+                    qcd.push((self.precision + 1) << 3);
+                    qcd.push((self.precision + 1) << 3);
+                    qcd.push((self.precision + 2) << 3);
+                }
+            }
+            _ => {
+                qcd.push((self.guard_bits << 5) | 1);
+                qcd.extend_from_slice(&((self.precision as u16) << 11).to_be_bytes());
+            }
+        }
+        write_segment(writer, &MARKER_SYMBOL_QCD, &qcd)?;
+
+        if let Some(comment) = &self.comment {
+            let mut com = Vec::with_capacity(2 + comment.len());
+            com.extend_from_slice(&1u16.to_be_bytes());
+            for c in comment.chars() {
+                match u8::try_from(c) {
+                    Ok(byte) => com.push(byte),
+                    Err(_) => {
+                        return Err(CodestreamError::InvalidParameter { name: "comment" }.into())
+                    }
+                }
+            }
+            write_segment(writer, &MARKER_SYMBOL_COM, &com)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write a marker, the segment length and the segment parameters.
+pub(crate) fn write_segment<W: io::Write>(
+    writer: &mut W,
+    marker: &MarkerSymbol,
+    parameters: &[u8],
+) -> io::Result<()> {
+    writer.write_all(&marker.0)?;
+    writer.write_all(&(parameters.len() as u16 + 2).to_be_bytes())?;
+    writer.write_all(parameters)
+}
@@ -1,12 +1,53 @@
 use log::{debug, info};
+use std::error;
+use std::fmt;
+use std::iter;
 
-use crate::coder::{Decoder, RUN_LEN, UNIFORM};
+use crate::coder::{
+    standard_decoder, Decoder, MqEncoder, RawDecoder, NUM_CONTEXTS, RUN_LEN, UNIFORM,
+};
 use crate::shared::SubBandType;
+#[cfg(feature = "stats")]
+use crate::stats::{CountingDecoder, DecodeStats};
+
+/// Selective arithmetic coding bypass bit of the code-block coding style,
+/// Table A.19.
+pub(crate) const CODE_BLOCK_STYLE_BYPASS: u8 = 0b0000_0001;
+
+// Coding passes before the first that the bypass codes raw, those of the
+// first four bit-planes, Section D.6
+const BYPASS_MQ_PASSES: usize = 10;
+
+/// Most magnitude bit-planes of a code-block, M<sub>b</sub>, whose
+/// coefficients fit in an i32.
+pub(crate) const MAX_MAGNITUDE_BIT_PLANES: u8 = 31;
+
+/// Whether coding pass `pass`, counted from 0, is coded raw rather than with
+/// the MQ-coder, for the code-block coding style `code_block_style`.
+///
+/// With the selective arithmetic coding bypass, the significance
+/// propagation and magnitude refinement passes after the first four
+/// bit-planes are raw, see Section D.6.
+pub(crate) fn is_raw_pass(code_block_style: u8, pass: usize) -> bool {
+    code_block_style & CODE_BLOCK_STYLE_BYPASS != 0
+        && pass >= BYPASS_MQ_PASSES
+        && (pass - BYPASS_MQ_PASSES) % 3 != 2
+}
+
+/// Whether coding pass `pass`, counted from 0, ends a codeword segment for
+/// the code-block coding style `code_block_style`, before the last pass.
+///
+/// With the selective arithmetic coding bypass, a segment ends at each
+/// change between the MQ-coder and raw passes, see Table D.9.
+pub(crate) fn ends_segment(code_block_style: u8, pass: usize) -> bool {
+    is_raw_pass(code_block_style, pass) != is_raw_pass(code_block_style, pass + 1)
+}
 
 #[derive(Debug, Clone)]
 enum Coeff {
-    // TODO i16 is probably wrong, might need generic
-    Significant { value: i16, is_negative: bool },
+    // Magnitude, wide enough for the bit-planes of a signed 32 bit output.
+    // TODO might need generic
+    Significant { value: u32, is_negative: bool },
     Insignificant(u8), // Insignificant at what bit-plane shift
 }
 
@@ -25,18 +66,188 @@ impl Coeff {
     }
 }
 
-struct CodeBlockDecodeError {}
+/// Decoded coefficient of a code-block, with the sign and magnitude kept
+/// separate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coefficient {
+    /// Coefficient that has become significant, with the magnitude bits
+    /// decoded so far.
+    Significant { magnitude: u32, is_negative: bool },
+
+    /// Coefficient that has not become significant.
+    ///
+    /// `bit_plane_shift` is the bit-plane in which a significance propagation
+    /// pass last coded the coefficient, or `None` if no such pass has.
+    Insignificant { bit_plane_shift: Option<u8> },
+}
+
+impl Coefficient {
+    /// Signed value of the coefficient, in two's complement, 0 if
+    /// insignificant.
+    ///
+    /// A magnitude of 2<sup>31</sup> is only representable when negative, as
+    /// `i32::MIN`.
+    pub fn value(&self) -> i32 {
+        match *self {
+            Coefficient::Significant {
+                magnitude,
+                is_negative: true,
+            } => (magnitude as i32).wrapping_neg(),
+            Coefficient::Significant { magnitude, .. } => magnitude as i32,
+            Coefficient::Insignificant { .. } => 0,
+        }
+    }
+
+    /// Coefficient with the sign and magnitude of a two's complement value.
+    ///
+    /// Zero is a coefficient that has not become significant, with no
+    /// significance propagation pass recorded.
+    pub fn from_value(value: i32) -> Coefficient {
+        match value {
+            0 => Coefficient::Insignificant {
+                bit_plane_shift: None,
+            },
+            _ => Coefficient::Significant {
+                magnitude: value.unsigned_abs(),
+                is_negative: value < 0,
+            },
+        }
+    }
+}
+
+/// Convert sign-magnitude coefficients to two's complement, for the inverse
+/// wavelet and component transforms.
+///
+/// This is the conversion [`CodeBlockDecoder::coefficients`] applies, see
+/// [`Coefficient::value`].
+pub fn to_twos_complement(coefficients: &[Coefficient]) -> Vec<i32> {
+    coefficients.iter().map(Coefficient::value).collect()
+}
+
+/// Convert two's complement values to sign-magnitude coefficients, the
+/// inverse of [`to_twos_complement`] for significant coefficients.
+pub fn from_twos_complement(values: &[i32]) -> Vec<Coefficient> {
+    values
+        .iter()
+        .copied()
+        .map(Coefficient::from_value)
+        .collect()
+}
+
+impl From<&Coeff> for Coefficient {
+    fn from(coeff: &Coeff) -> Self {
+        match *coeff {
+            // The sign is only meaningful once a magnitude bit is set
+            Coeff::Significant { value, is_negative } => Coefficient::Significant {
+                magnitude: value,
+                is_negative: is_negative && value != 0,
+            },
+            Coeff::Insignificant(u8::MAX) => Coefficient::Insignificant {
+                bit_plane_shift: None,
+            },
+            Coeff::Insignificant(bit_plane_shift) => Coefficient::Insignificant {
+                bit_plane_shift: Some(bit_plane_shift),
+            },
+        }
+    }
+}
+
+/// Errors from decoding the coefficients of a code-block.
+///
+/// These indicate that the compressed data was inconsistent with the code-block
+/// parameters, and replace what would otherwise be a panic on untrusted input.
+#[derive(Debug, PartialEq)]
+pub enum CodeBlockDecodeError {
+    /// Coefficient index is outside of the code-block
+    OutOfBounds { x: i32, y: i32 },
+    /// Coefficient is already significant
+    AlreadySignificant { x: i32, y: i32 },
+    /// Coefficient is required to be significant
+    NotSignificant { x: i32, y: i32 },
+    /// Decoder returned a value other than 0 or 1
+    InvalidSymbol { value: u8 },
+    /// Coding passes continue past the least significant bit-plane
+    BitPlaneUnderflow,
+    /// Output buffer does not have one entry per coefficient
+    OutputSize { expected: usize, actual: usize },
+    /// Code-block is wider or higher than 1024, or has more than 4096
+    /// coefficients
+    InvalidSize { width: u32, height: u32 },
+    /// Magnitude bit-planes, M<sub>b</sub>, exceed what an i32 coefficient
+    /// holds
+    InvalidMagnitudeBitPlanes { mb: u8 },
+    /// Compressed data ran out before the remaining coding passes
+    DataExhausted {
+        passes_decoded: u8,
+        bytes_consumed: usize,
+    },
+}
+
+impl error::Error for CodeBlockDecodeError {}
+impl fmt::Display for CodeBlockDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { x, y } => {
+                write!(f, "coefficient ({x}, {y}) is outside of the code-block")
+            }
+            Self::AlreadySignificant { x, y } => {
+                write!(f, "coefficient ({x}, {y}) is already significant")
+            }
+            Self::NotSignificant { x, y } => {
+                write!(f, "coefficient ({x}, {y}) is not significant")
+            }
+            Self::InvalidSymbol { value } => {
+                write!(f, "invalid decoded symbol {value}")
+            }
+            Self::BitPlaneUnderflow => {
+                write!(f, "coding passes exceed the available bit-planes")
+            }
+            Self::OutputSize { expected, actual } => {
+                write!(
+                    f,
+                    "output of {actual} coefficients, expected {expected} coefficients"
+                )
+            }
+            Self::InvalidSize { width, height } => {
+                write!(f, "invalid code-block size of {width}x{height}")
+            }
+            Self::InvalidMagnitudeBitPlanes { mb } => {
+                write!(f, "{mb} magnitude bit-planes exceed the supported 31")
+            }
+            Self::DataExhausted {
+                passes_decoded,
+                bytes_consumed,
+            } => {
+                write!(
+                    f,
+                    "compressed data exhausted after {passes_decoded} coding passes and {bytes_consumed} bytes"
+                )
+            }
+        }
+    }
+}
 
 /// decoder for codeblocks
 ///
 /// A CodeBlockDecoder produces coefficients from compressed data.
-struct CodeBlockDecoder {
+///
+/// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Annex D.
+#[derive(Debug)]
+pub struct CodeBlockDecoder {
     width: i32,
     height: i32,
     subband: SubBandType,
     no_passes: u8, // Max 164 from table B.4
     bit_plane_shift: u8,
+    // Whether the pass being decoded is raw, with signs not predicted
+    raw: bool,
     coefficients: Vec<Coeff>,
+    // Sign of each significant coefficient (1 or -1) or 0, with a border of
+    // one insignificant coefficient so neighbours can be read without
+    // bounds checks
+    significance: Vec<i8>,
+    #[cfg(feature = "stats")]
+    stats: DecodeStats,
 }
 
 /// Wrapper around an x, y coord
@@ -46,68 +257,438 @@ struct CoeffIndex {
     x: i32,
 }
 
+/// Signs of the eight neighbours of a coefficient, 1 or -1 if significant
+/// and 0 otherwise.
+///
+/// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Figure D.2
+#[derive(Debug, PartialEq)]
+struct Neighbours {
+    horizontal: [i8; 2],
+    vertical: [i8; 2],
+    diagonal: [i8; 4],
+}
+
+impl Neighbours {
+    /// Neighbours of the coefficient at `centre` in a buffer of signs with
+    /// rows of `stride` values, which has a border of one insignificant
+    /// coefficient around the code-block.
+    fn padded(signs: &[i8], stride: usize, centre: usize) -> Self {
+        Neighbours {
+            horizontal: [signs[centre - 1], signs[centre + 1]],
+            vertical: [signs[centre - stride], signs[centre + stride]],
+            diagonal: [
+                signs[centre - stride - 1],
+                signs[centre - stride + 1],
+                signs[centre + stride - 1],
+                signs[centre + stride + 1],
+            ],
+        }
+    }
+
+    fn significant(signs: &[i8]) -> u8 {
+        signs.iter().map(|sign| (*sign != 0) as u8).sum()
+    }
+
+    /// Context for the significance of a coefficient in `subband`.
+    fn significance_context(&self, subband: SubBandType) -> usize {
+        // Count significant neighbors
+        let h = Neighbours::significant(&self.horizontal); // horizontal contributions
+        let v = Neighbours::significant(&self.vertical); // vertical contributions
+        let d = Neighbours::significant(&self.diagonal); // diagonal contributions
+
+        // Compute context based on subband and neighbor counts
+        // Different formulas for LL / LH (vertical high pass), HL (horizontal high pass), HH (diagonal high pass) subbands
+        // ITU-T T.800 | ISO/IEC 15444-1 Table D.1
+        match subband {
+            SubBandType::LL | SubBandType::LH => match (h, v, d) {
+                (0, 0, 0) => 0,
+                (0, 0, 1) => 1,
+                (0, 0, _) => 2,
+                (0, 1, _) => 3,
+                (0, 2, _) => 4,
+                (1, 0, 0) => 5,
+                (1, 0, _) => 6,
+                (1, _, _) => 7,
+                // h = 2
+                (_, _, _) => 8,
+            },
+            SubBandType::HL => match (h, v, d) {
+                (0, 0, 0) => 0,
+                (0, 0, 1) => 1,
+                (0, 0, _) => 2,
+                (1, 0, _) => 3,
+                (2, 0, _) => 4,
+                (0, 1, 0) => 5,
+                (0, 1, _) => 6,
+                (_, 1, _) => 7,
+                // v = 2
+                (_, _, _) => 8,
+            },
+            SubBandType::HH => match (h + v, d) {
+                (0, 0) => 0,
+                (1, 0) => 1,
+                (a, 0) if a >= 2 => 2,
+                (0, 1) => 3,
+                (1, 1) => 4,
+                (a, 1) if a >= 2 => 5,
+                (0, 2) => 6,
+                (a, 2) if a >= 1 => 7,
+                // d >= 3
+                (_, _) => 8,
+            },
+        }
+    }
+
+    /// Context for the sign bit, with the bit to XOR it with.
+    ///
+    /// ITU-T T.800(V4) | ISO/IEC 15444-1:2024 section D.3.2
+    fn sign_context(&self) -> (usize, u8) {
+        let [h0, h1] = self.horizontal;
+        let [v0, v1] = self.vertical;
+
+        // Add up the contribution to a -1,0,1
+        let vc = (v0 + v1).signum();
+        let hc = (h0 + h1).signum();
+        // ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Table D.3
+        match (hc, vc) {
+            (1, 1) => (13, 0),
+            (1, 0) => (12, 0),
+            (1, -1) => (11, 0),
+            (0, 1) => (10, 0),
+            (0, 0) => (9, 0),
+            (0, -1) => (10, 1),
+            (-1, 1) => (11, 1),
+            (-1, 0) => (12, 1),
+            // (-1, -1)
+            (_, _) => (13, 1),
+        }
+    }
+
+    /// Context for a magnitude refinement bit, Table D.4.
+    fn magnitude_context(&self, first_refinement: bool) -> usize {
+        if !first_refinement {
+            return 16;
+        }
+        let significant = Neighbours::significant(&self.horizontal)
+            + Neighbours::significant(&self.vertical)
+            + Neighbours::significant(&self.diagonal);
+        if significant > 0 {
+            15
+        } else {
+            14
+        }
+    }
+}
+
 impl CodeBlockDecoder {
-    fn new(width: i32, height: i32, subband: SubBandType, no_passes: u8, mb: u8) -> Self {
-        Self {
-            width,
-            height,
+    /// Create a decoder for a code-block of `width` by `height` coefficients
+    /// in the given sub-band.
+    ///
+    /// `no_passes` is the number of coding passes included for the
+    /// code-block, and `mb` is the maximum number of magnitude bit-planes,
+    /// M<sub>b</sub> from Equation E-2.
+    ///
+    /// Fails for a code-block wider or higher than 1024, or with more than
+    /// 4096 coefficients, and for more than 31 magnitude bit-planes.
+    pub fn new(
+        width: u32,
+        height: u32,
+        subband: SubBandType,
+        no_passes: u8,
+        mb: u8,
+    ) -> Result<Self, CodeBlockDecodeError> {
+        let mut decoder = Self {
+            width: 0,
+            height: 0,
             subband,
             no_passes,
-            bit_plane_shift: mb - 1,
-            coefficients: vec![Coeff::Insignificant(u8::MAX); (width * height) as usize],
+            bit_plane_shift: 0,
+            raw: false,
+            coefficients: vec![],
+            significance: vec![],
+            #[cfg(feature = "stats")]
+            stats: DecodeStats::new(),
+        };
+        decoder.reset(width, height, subband, no_passes, mb)?;
+        Ok(decoder)
+    }
+
+    /// Reset the decoder for another code-block, keeping the allocated
+    /// buffers and the statistics.
+    ///
+    /// The parameters, and the sizes that fail, are the same as for
+    /// [`CodeBlockDecoder::new`].
+    pub fn reset(
+        &mut self,
+        width: u32,
+        height: u32,
+        subband: SubBandType,
+        no_passes: u8,
+        mb: u8,
+    ) -> Result<(), CodeBlockDecodeError> {
+        if width > 1024 || height > 1024 || width * height > 4096 {
+            return Err(CodeBlockDecodeError::InvalidSize { width, height });
+        }
+        if mb > MAX_MAGNITUDE_BIT_PLANES {
+            return Err(CodeBlockDecodeError::InvalidMagnitudeBitPlanes { mb });
         }
+        self.width = width as i32;
+        self.height = height as i32;
+        self.subband = subband;
+        self.no_passes = no_passes;
+        self.bit_plane_shift = mb.saturating_sub(1);
+        self.coefficients.clear();
+        self.coefficients
+            .resize((width * height) as usize, Coeff::Insignificant(u8::MAX));
+        self.significance.clear();
+        self.significance
+            .resize(((width + 2) * (height + 2)) as usize, 0);
+        Ok(())
     }
 
     /// Decode coefficients from the given compressed data.
-    fn decode(&mut self, coder: &mut dyn Decoder) -> Result<(), CodeBlockDecodeError> {
+    pub fn decode(&mut self, coder: &mut dyn Decoder) -> Result<(), CodeBlockDecodeError> {
         info!("Decoding code block for subband {:?}", self.subband);
 
-        // Start in CleanUp -> SignificancePropagation -> MagnitudeRefinement -> repeat ...
-        self.pass_cleanup(coder);
-        for _ in (1..self.no_passes).step_by(3) {
-            debug!("Beginning a pass set");
-            self.bit_plane_shift -= 1;
-            self.pass_significance(coder);
-            self.pass_refinement(coder);
-            self.pass_cleanup(coder);
-            debug!("coefficients: {:?}", self.coefficients);
+        // Empty code-blocks have no coding passes, and all coefficients
+        // remain insignificant
+        if self.no_passes == 0 {
+            return Ok(());
+        }
+
+        for (passes_decoded, pass) in State::sequence(self.no_passes).enumerate() {
+            self.decode_pass(passes_decoded, pass, coder)?;
+        }
+        debug!("coefficients: {:?}", self.coefficients);
+        Ok(())
+    }
+
+    /// Decode coefficients from the codeword segments of the compressed
+    /// data, for the code-block coding style `code_block_style`.
+    ///
+    /// Each segment covers the coding passes up to the next that
+    /// [ends_segment], with the MQ-coder initialised again for each, keeping
+    /// its contexts, and the raw passes of the selective arithmetic coding
+    /// bypass read as they are.
+    pub(crate) fn decode_segments(
+        &mut self,
+        code_block_style: u8,
+        segments: &[&[u8]],
+    ) -> Result<(), CodeBlockDecodeError> {
+        let mut segments = segments.iter().copied();
+        let mut mq = standard_decoder(&[]);
+        let mut raw = RawDecoder::new(&[]);
+        for (passes_decoded, pass) in State::sequence(self.no_passes).enumerate() {
+            self.raw = is_raw_pass(code_block_style, passes_decoded);
+            if passes_decoded == 0 || ends_segment(code_block_style, passes_decoded - 1) {
+                let segment = segments.next().unwrap_or_default();
+                if self.raw {
+                    raw = RawDecoder::new(segment);
+                } else {
+                    mq.init(segment);
+                }
+            }
+            let coder: &mut dyn Decoder = if self.raw { &mut raw } else { &mut mq };
+            let result = self.decode_pass(passes_decoded, pass, coder);
+            self.raw = false;
+            result?;
+        }
+        Ok(())
+    }
+
+    // Decode coding pass `pass`, after `passes_decoded` others
+    fn decode_pass(
+        &mut self,
+        passes_decoded: usize,
+        pass: State,
+        coder: &mut dyn Decoder,
+    ) -> Result<(), CodeBlockDecodeError> {
+        debug!("Beginning {:?} pass", pass);
+        if pass == State::SignificancePropagation {
+            // Start of the next bit-plane
+            if coder.is_exhausted() {
+                return Err(CodeBlockDecodeError::DataExhausted {
+                    passes_decoded: passes_decoded as u8,
+                    bytes_consumed: coder.bytes_consumed(),
+                });
+            }
+            self.bit_plane_shift = self
+                .bit_plane_shift
+                .checked_sub(1)
+                .ok_or(CodeBlockDecodeError::BitPlaneUnderflow)?;
+        }
+        #[cfg(feature = "stats")]
+        let mut counting = CountingDecoder::new(&mut *coder);
+        #[cfg(feature = "stats")]
+        let coder: &mut dyn Decoder = &mut counting;
+        match pass {
+            State::SignificancePropagation => self.pass_significance(coder)?,
+            State::MagnitudeRefinement => self.pass_refinement(coder)?,
+            State::CleanUp => self.pass_cleanup(coder)?,
+        }
+        #[cfg(feature = "stats")]
+        self.record_pass(pass, &counting.contexts);
+        Ok(())
+    }
+
+    /// Decode coefficients from the given compressed data into `out`, in
+    /// raster order.
+    ///
+    /// `out` shall have one entry for each coefficient of the code-block.
+    pub fn decode_into(
+        &mut self,
+        coder: &mut dyn Decoder,
+        out: &mut [i32],
+    ) -> Result<(), CodeBlockDecodeError> {
+        if out.len() != self.coefficients.len() {
+            return Err(CodeBlockDecodeError::OutputSize {
+                expected: self.coefficients.len(),
+                actual: out.len(),
+            });
         }
+        self.decode(coder)?;
+        self.write_coefficients(out);
         Ok(())
     }
-    /// Return coefficients
+
+    /// Statistics of every code-block decoded since the decoder was created.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    #[cfg(feature = "stats")]
+    fn record_pass(&mut self, pass: State, contexts: &[u64]) {
+        let decisions: u64 = contexts.iter().sum();
+        let stats = &mut self.stats;
+        match pass {
+            State::SignificancePropagation => {
+                stats.significance_passes += 1;
+                stats.significance_decisions += decisions;
+            }
+            State::MagnitudeRefinement => {
+                stats.refinement_passes += 1;
+                stats.refinement_decisions += decisions;
+            }
+            State::CleanUp => {
+                stats.cleanup_passes += 1;
+                stats.cleanup_decisions += decisions;
+            }
+        }
+        for (count, decisions) in stats.contexts.iter_mut().zip(contexts) {
+            *count += decisions;
+        }
+    }
+
+    /// Return coefficients, in raster order
     /// TODO return type is whak
     /// Note, return a copy, maybe need to decode more for this codeblock later and don't want to
     /// lose state
-    fn coefficients(&self) -> Vec<i32> {
-        self.coefficients
+    pub fn coefficients(&self) -> Vec<i32> {
+        let mut out = vec![0; self.coefficients.len()];
+        self.write_coefficients(&mut out);
+        out
+    }
+
+    /// Return coefficients in raster order, with the sign and magnitude
+    /// separate, and the state of insignificant coefficients.
+    ///
+    /// [`CodeBlockDecoder::coefficients`] gives the same coefficients as
+    /// signed values.
+    pub fn sign_magnitude_coefficients(&self) -> Vec<Coefficient> {
+        self.coefficients.iter().map(Coefficient::from).collect()
+    }
+
+    /// Bit-planes of the coefficients decoded so far, as text, for comparing
+    /// against the code-block examples of ITU-T T.800(V4) | ISO/IEC
+    /// 15444-1:2024 Annex J.
+    ///
+    /// The signs come first, then each bit-plane from the most significant
+    /// bit of any coefficient down to the bit-plane being decoded, each as a
+    /// grid of the code-block. A sign is `-` or `+` once the coefficient is
+    /// significant, and a bit is `1` or `0` from the bit-plane in which the
+    /// coefficient became significant. Otherwise, either is `.`.
+    ///
+    /// This is only available with the `debug-bitplanes` feature.
+    #[cfg(any(test, feature = "debug-bitplanes"))]
+    pub fn debug_bitplanes(&self) -> String {
+        let coefficients: Vec<Coefficient> =
+            self.coefficients.iter().map(Coefficient::from).collect();
+        let magnitude = |coefficient: &Coefficient| match *coefficient {
+            Coefficient::Significant { magnitude, .. } => Some(magnitude),
+            Coefficient::Insignificant { .. } => None,
+        };
+        let mut out = String::new();
+        let mut grid = |title: String, symbol: &dyn Fn(&Coefficient) -> char| {
+            out.push_str(&title);
+            out.push('\n');
+            for row in coefficients.chunks(self.width.max(1) as usize) {
+                let symbols: Vec<String> = row
+                    .iter()
+                    .map(|coefficient| symbol(coefficient).to_string())
+                    .collect();
+                out.push_str(&symbols.join(" "));
+                out.push('\n');
+            }
+        };
+        grid(String::from("sign"), &|coefficient| match *coefficient {
+            Coefficient::Significant {
+                is_negative: true, ..
+            } => '-',
+            Coefficient::Significant { .. } => '+',
+            Coefficient::Insignificant { .. } => '.',
+        });
+        // Significant coefficients have a magnitude of at least one
+        let top = coefficients
             .iter()
-            .map(|c| match c {
-                Coeff::Significant { value, is_negative } => {
-                    if *is_negative {
-                        -1 * value
-                    } else {
-                        *value
-                    }
-                }
-                Coeff::Insignificant(_) => 0,
-            } as i32)
-            .collect()
+            .filter_map(magnitude)
+            .map(|magnitude| 31 - magnitude.leading_zeros() as u8)
+            .max();
+        if let Some(top) = top {
+            for bit_plane in (self.bit_plane_shift..=top).rev() {
+                grid(
+                    format!("bit-plane {}", bit_plane),
+                    &|coefficient| match magnitude(coefficient)
+                        .map(|magnitude| magnitude >> bit_plane)
+                    {
+                        Some(bits) if bits != 0 => char::from(b'0' + (bits & 1) as u8),
+                        _ => '.',
+                    },
+                );
+            }
+        }
+        out
+    }
+
+    fn write_coefficients(&self, out: &mut [i32]) {
+        for (out, c) in out.iter_mut().zip(self.coefficients.iter()) {
+            *out = Coefficient::from(c).value();
+        }
     }
 
     /// Handle a cleanup pass
     ///
     /// Cleanup does cleanup and sign coding.
     /// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Section D.3.4
-    fn pass_cleanup(&mut self, coder: &mut dyn Decoder) {
+    fn pass_cleanup(&mut self, coder: &mut dyn Decoder) -> Result<(), CodeBlockDecodeError> {
         // Iterate coefficients in strips 4 tall across full width
         for by in (0..self.height).step_by(4) {
             for x in 0..self.width {
                 let mut offset_y: i32 = 0;
 
-                // Count insignificants in this column strip
+                // Count coefficients in this column strip left for this pass
+                // with a 0 context
                 let mut count_insig = 0;
                 for y in by..(by + 4).min(self.height) {
-                    count_insig += (!self.is_significant(CoeffIndex { y, x })) as i32;
+                    let idx = CoeffIndex { y, x };
+                    let visited = matches!(
+                        self.coeff_at(idx),
+                        Coeff::Insignificant(bs) if *bs == self.bit_plane_shift
+                    );
+                    count_insig += (!self.is_significant(idx)
+                        && !visited
+                        && self.significance_context(idx) == 0)
+                        as i32;
                 }
 
                 // Decision D8: Are four contiguous undecoded coefficients in a column each with a 0 context?
@@ -125,8 +706,10 @@ impl CodeBlockDecoder {
                         // two uniform context decodes
                         let a = coder.decode_bit(UNIFORM);
                         let b = coder.decode_bit(UNIFORM);
+                        if a > 1 || b > 1 {
+                            return Err(CodeBlockDecodeError::InvalidSymbol { value: a.max(b) });
+                        }
                         let c5 = 2 * a + b;
-                        assert!(c5 < 4, "Improper decode from mq coder");
 
                         // go forward s
                         offset_y += c5 as i32;
@@ -136,10 +719,10 @@ impl CodeBlockDecoder {
                         x,
                         y: by + offset_y,
                     };
-                    self.make_significant(nsi);
+                    self.make_significant(nsi)?;
 
                     // C2 decode sign bit
-                    self.decode_sign_bit(nsi, coder);
+                    self.decode_sign_bit(nsi, coder)?;
                     offset_y += 1;
                 }
 
@@ -147,19 +730,20 @@ impl CodeBlockDecoder {
                 for y in (by + offset_y)..(by + 4).min(self.height) {
                     let idx = CoeffIndex { x, y };
                     let newly_sig =
-                        !self.is_significant(idx) && self.significance_decode(idx, coder);
+                        !self.is_significant(idx) && self.significance_decode(idx, coder)?;
                     if newly_sig {
                         // C2 decode sign bit
-                        self.decode_sign_bit(idx, coder);
+                        self.decode_sign_bit(idx, coder)?;
                     }
                 }
             }
         }
         info!("completed cleanup pass");
+        Ok(())
     }
 
     /// Handle a significance propagation pass
-    fn pass_significance(&mut self, coder: &mut dyn Decoder) {
+    fn pass_significance(&mut self, coder: &mut dyn Decoder) -> Result<(), CodeBlockDecodeError> {
         // Iterate coefficients in strips 4 tall across full width
         for by in (0..self.height).step_by(4) {
             for x in 0..self.width {
@@ -172,21 +756,22 @@ impl CodeBlockDecoder {
                     if 0 == sig_ctx {
                         continue; // D2 yes
                     }
-                    let newly_sig = self.significance_decode_ctx(sig_ctx, idx, coder);
+                    let newly_sig = self.significance_decode_ctx(sig_ctx, idx, coder)?;
                     if newly_sig {
                         // C2
-                        self.decode_sign_bit(idx, coder);
+                        self.decode_sign_bit(idx, coder)?;
                     } else {
-                        *self.coeff_at_mut(idx) = Coeff::Insignificant(self.bit_plane_shift);
+                        *self.coeff_at_mut(idx)? = Coeff::Insignificant(self.bit_plane_shift);
                     }
                 }
             }
         }
         info!("completed significance pass");
+        Ok(())
     }
 
     /// Handle a magnitude refinement pass
-    fn pass_refinement(&mut self, coder: &mut dyn Decoder) {
+    fn pass_refinement(&mut self, coder: &mut dyn Decoder) -> Result<(), CodeBlockDecodeError> {
         // Iterate coefficients in strips 4 tall across full width
         for by in (0..self.height).step_by(4) {
             for x in 0..self.width {
@@ -196,17 +781,18 @@ impl CodeBlockDecoder {
                         continue; // D5 yes
                     }
                     // is bit set for this bit-plane
-                    let is_bit_set = self.is_bit_plane_set(idx);
+                    let is_bit_set = self.is_bit_plane_set(idx)?;
                     debug!("Is bit set: {}, for {:?}", is_bit_set, idx);
                     if is_bit_set {
                         continue; // D6 yes
                     }
                     // C3
-                    self.magnitude_decode(idx, coder);
+                    self.magnitude_decode(idx, coder)?;
                 }
             }
         }
         info!("completed refinement pass");
+        Ok(())
     }
 
     fn coeff_at(&self, idx: CoeffIndex) -> &Coeff {
@@ -220,124 +806,108 @@ impl CodeBlockDecoder {
         }
     }
 
-    fn coeff_at_mut(&mut self, idx: CoeffIndex) -> &mut Coeff {
+    fn coeff_at_mut(&mut self, idx: CoeffIndex) -> Result<&mut Coeff, CodeBlockDecodeError> {
         let CoeffIndex { x, y } = idx;
         let out_bounds = x < 0 || x >= self.width || y < 0 || y >= self.height;
-        assert!(!out_bounds, "Should not be trying to mutate out of bounds");
-        &mut self.coefficients[(self.width * idx.y + idx.x) as usize]
+        if out_bounds {
+            return Err(CodeBlockDecodeError::OutOfBounds { x, y });
+        }
+        Ok(&mut self.coefficients[(self.width * idx.y + idx.x) as usize])
     }
 
     fn significance_context(&self, idx: CoeffIndex) -> usize {
-        let CoeffIndex { x, y } = idx;
-        // mutables
-        let mut h = 0; // horizontal contributions
-        let mut v = 0; // vertical contributions
-        let mut d = 0; // diagonal contributions
-
-        // Count significant neighbors
-        h += self.is_significant(CoeffIndex { y, x: x - 1 }) as u8;
-        h += self.is_significant(CoeffIndex { y, x: x + 1 }) as u8;
-        v += self.is_significant(CoeffIndex { y: y - 1, x }) as u8;
-        v += self.is_significant(CoeffIndex { y: y + 1, x }) as u8;
-
-        // Diagonals (only if both adjacent orthogonal are insignificant)
-        d += self.is_significant(CoeffIndex { y: y - 1, x: x - 1 }) as u8;
-        d += self.is_significant(CoeffIndex { y: y - 1, x: x + 1 }) as u8;
-        d += self.is_significant(CoeffIndex { y: y + 1, x: x - 1 }) as u8;
-        d += self.is_significant(CoeffIndex { y: y + 1, x: x + 1 }) as u8;
-
+        let cx = self.neighbours(idx).significance_context(self.subband);
         debug!(
-            "For subband {:?}, idx: {:?}, found h={}, v={}, d={}",
-            self.subband, idx, h, v, d
+            "For subband {:?}, idx: {:?}, significance context {}",
+            self.subband, idx, cx
         );
-
-        // Compute context based on subband and neighbor counts
-        // Different formulas for LL / LH (vertical high pass), HL (horizontal high pass), HH (diagonal high pass) subbands
-        // ITU-T T.800 | ISO/IEC 15444-1 Table D.1
-        match self.subband {
-            SubBandType::LL | SubBandType::LH => match (h, v, d) {
-                (0, 0, 0) => 0,
-                (0, 0, 1) => 1,
-                (0, 0, _) => 2,
-                (0, 1, _) => 3,
-                (0, 2, _) => 4,
-                (1, 0, 0) => 5,
-                (1, 0, _) => 6,
-                (1, _, _) => 7,
-                (2, _, _) => 8,
-                (_, _, _) => panic!("Unknown significance context calculation"),
-            },
-            SubBandType::HL => match (h, v, d) {
-                (0, 0, 0) => 0,
-                (0, 0, 1) => 1,
-                (0, 0, _) => 2,
-                (1, 0, _) => 3,
-                (2, 0, _) => 4,
-                (0, 1, 0) => 5,
-                (0, 1, _) => 6,
-                (_, 1, _) => 7,
-                (_, 2, _) => 8,
-                (_, _, _) => panic!("Unknown significance context calculation"),
-            },
-            SubBandType::HH => match (h + v, d) {
-                (0, 0) => 0,
-                (1, 0) => 1,
-                (a, 0) if a >= 2 => 2,
-                (0, 1) => 3,
-                (1, 1) => 4,
-                (a, 1) if a >= 2 => 5,
-                (0, 2) => 6,
-                (a, 2) if a >= 1 => 7,
-                (_, b) if b >= 3 => 8,
-                (_, _) => panic!("Unknown significance context calculation"),
-            },
-        }
+        cx
     }
 
     /// Checks if the bit in this bit-plane was set
-    fn is_bit_plane_set(&self, idx: CoeffIndex) -> bool {
+    fn is_bit_plane_set(&self, idx: CoeffIndex) -> Result<bool, CodeBlockDecodeError> {
         match self.coeff_at(idx) {
             Coeff::Insignificant(_) => {
-                panic!("Attemping to check bit-plane of Insignificant coefficient")
+                Err(CodeBlockDecodeError::NotSignificant { x: idx.x, y: idx.y })
             }
-            Coeff::Significant { value, .. } => 1 == (0x1 & (value >> self.bit_plane_shift)),
+            Coeff::Significant { value, .. } => Ok(1 == (0x1 & (value >> self.bit_plane_shift))),
         }
     }
 
-    fn is_significant(&self, idx: CoeffIndex) -> bool {
+    fn in_bounds(&self, idx: CoeffIndex) -> bool {
         let CoeffIndex { x, y } = idx;
-        let out_bounds = x < 0 || x >= self.width || y < 0 || y >= self.height;
-        if out_bounds {
-            return false;
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    /// Index of a coefficient in the padded significance buffer
+    fn padded_index(&self, idx: CoeffIndex) -> usize {
+        ((idx.y + 1) * (self.width + 2) + idx.x + 1) as usize
+    }
+
+    /// Neighbours of a coefficient inside the code-block, read from the
+    /// padded significance buffer.
+    fn neighbours(&self, idx: CoeffIndex) -> Neighbours {
+        if !self.in_bounds(idx) {
+            return self.neighbours_checked(idx);
         }
-        match self.coeff_at(idx) {
-            Coeff::Insignificant(_) => false,
-            Coeff::Significant { .. } => true,
+        let stride = (self.width + 2) as usize;
+        Neighbours::padded(&self.significance, stride, self.padded_index(idx))
+    }
+
+    /// Neighbours of any coefficient, checking each against the code-block
+    /// bounds.
+    fn neighbours_checked(&self, idx: CoeffIndex) -> Neighbours {
+        let CoeffIndex { x, y } = idx;
+        let sign = |y, x| self.coeff_at(CoeffIndex { y, x }).sign_contribution();
+        Neighbours {
+            horizontal: [sign(y, x - 1), sign(y, x + 1)],
+            vertical: [sign(y - 1, x), sign(y + 1, x)],
+            diagonal: [
+                sign(y - 1, x - 1),
+                sign(y - 1, x + 1),
+                sign(y + 1, x - 1),
+                sign(y + 1, x + 1),
+            ],
+        }
+    }
+
+    fn is_significant(&self, idx: CoeffIndex) -> bool {
+        if !self.in_bounds(idx) {
+            return false;
         }
+        self.significance[self.padded_index(idx)] != 0
     }
 
     /// Turn a coefficient significant
-    fn make_significant(&mut self, idx: CoeffIndex) {
+    fn make_significant(&mut self, idx: CoeffIndex) -> Result<(), CodeBlockDecodeError> {
         debug!("Marking significant {:?}", idx);
         match self.coeff_at(idx) {
             Coeff::Insignificant(_) => {
-                *self.coeff_at_mut(idx) = Coeff::Significant {
+                debug_assert!(self.bit_plane_shift < 31, "magnitude overflows i32");
+                *self.coeff_at_mut(idx)? = Coeff::Significant {
                     value: 1 << self.bit_plane_shift,
                     is_negative: false,
                 };
+                let padded_index = self.padded_index(idx);
+                self.significance[padded_index] = 1;
+                Ok(())
             }
-            _ => panic!("tried to make a coefficient doubly significant"),
+            _ => Err(CodeBlockDecodeError::AlreadySignificant { x: idx.x, y: idx.y }),
         }
     }
 
     /// Decode the significance for a specific CoeffIndex from the decoder
-    fn significance_decode(&mut self, idx: CoeffIndex, decoder: &mut dyn Decoder) -> bool {
+    fn significance_decode(
+        &mut self,
+        idx: CoeffIndex,
+        decoder: &mut dyn Decoder,
+    ) -> Result<bool, CodeBlockDecodeError> {
         if let Coeff::Insignificant(bs) = self.coeff_at(idx) {
             if *bs == self.bit_plane_shift {
-                return false;
+                return Ok(false);
             }
         } else {
-            panic!("Should have checked if sig");
+            return Err(CodeBlockDecodeError::AlreadySignificant { x: idx.x, y: idx.y });
         }
         let cx = self.significance_context(idx);
         self.significance_decode_ctx(cx, idx, decoder)
@@ -349,148 +919,412 @@ impl CodeBlockDecoder {
         cx: usize,
         idx: CoeffIndex,
         decoder: &mut dyn Decoder,
-    ) -> bool {
+    ) -> Result<bool, CodeBlockDecodeError> {
         let sig = decoder.decode_bit(cx);
         debug!("significance {sig} for {idx:?}");
         if sig == 1 {
-            self.make_significant(idx);
-            true
+            self.make_significant(idx)?;
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
     /// Decode the magnitude bit for a specific CoeffIndex from the decoder
-    fn magnitude_decode(&mut self, idx: CoeffIndex, decoder: &mut dyn Decoder) {
+    fn magnitude_decode(
+        &mut self,
+        idx: CoeffIndex,
+        decoder: &mut dyn Decoder,
+    ) -> Result<(), CodeBlockDecodeError> {
         let cx = self.magnitude_context(idx);
         let b = decoder.decode_bit(cx);
-        *self.coeff_at_mut(idx) = match self.coeff_at(idx) {
+        *self.coeff_at_mut(idx)? = match self.coeff_at(idx) {
             Coeff::Insignificant(_) => {
-                panic!("Cannot set magnitude bit for an Insignificant coefficient")
+                return Err(CodeBlockDecodeError::NotSignificant { x: idx.x, y: idx.y });
             }
             Coeff::Significant { value, is_negative } => {
-                let value = value | (b << self.bit_plane_shift) as i16;
+                debug_assert!(self.bit_plane_shift < 31, "magnitude overflows i32");
+                // Shift in the full width of the magnitude
+                let value = value | (u32::from(b) << self.bit_plane_shift);
                 let is_negative = *is_negative;
                 Coeff::Significant { value, is_negative }
             }
         };
         debug!("Set bit {} for {:?}", b, idx);
+        Ok(())
     }
 
     /// Decode the sign bit for a specific CoeffIndex from the decoder
-    fn decode_sign_bit(&mut self, idx: CoeffIndex, decoder: &mut dyn Decoder) {
+    fn decode_sign_bit(
+        &mut self,
+        idx: CoeffIndex,
+        decoder: &mut dyn Decoder,
+    ) -> Result<(), CodeBlockDecodeError> {
         let (cx, xor) = self.sign_context(idx);
         let sign_bit = decoder.decode_bit(cx);
+        // Raw passes code the sign bit itself, without the prediction of
+        // Table D.3
+        let xor = if self.raw { 0 } else { xor };
         if let Coeff::Significant { value, .. } = self.coeff_at(idx) {
-            *self.coeff_at_mut(idx) = Coeff::Significant {
+            let is_negative = (sign_bit ^ xor) != 0;
+            *self.coeff_at_mut(idx)? = Coeff::Significant {
                 value: *value,
-                is_negative: (sign_bit ^ xor) != 0,
+                is_negative,
             };
+            let padded_index = self.padded_index(idx);
+            self.significance[padded_index] = if is_negative { -1 } else { 1 };
+            Ok(())
         } else {
-            panic!("Cannot set sign bit on coeff");
+            Err(CodeBlockDecodeError::NotSignificant { x: idx.x, y: idx.y })
         }
     }
 
-    fn num_zero_bit_plane(&mut self, arg: u8) {
-        self.bit_plane_shift -= arg;
+    /// Skip the number of missing most significant bit-planes, from the
+    /// packet header.
+    ///
+    /// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Section B.10.5.
+    pub fn num_zero_bit_plane(&mut self, arg: u8) -> Result<(), CodeBlockDecodeError> {
+        self.bit_plane_shift = self
+            .bit_plane_shift
+            .checked_sub(arg)
+            .ok_or(CodeBlockDecodeError::BitPlaneUnderflow)?;
+        Ok(())
     }
 
     /// Determine the context for sign bit decoding
     ///
     /// ITU-T T.800(V4) | ISO/IEC 15444-1:2024 section D.3.2
     fn sign_context(&self, idx: CoeffIndex) -> (usize, u8) {
-        let CoeffIndex { x, y } = idx;
+        self.neighbours(idx).sign_context()
+    }
 
-        let v0 = self.coeff_at(CoeffIndex { y: y - 1, x });
-        let v1 = self.coeff_at(CoeffIndex { y: y + 1, x });
-        let h0 = self.coeff_at(CoeffIndex { y, x: x - 1 });
-        let h1 = self.coeff_at(CoeffIndex { y, x: x + 1 });
+    fn magnitude_context(&self, idx: CoeffIndex) -> usize {
+        let first_refinement = match self.coeff_at(idx) {
+            Coeff::Significant { value, .. } => value >> (1 + self.bit_plane_shift) == 1,
+            Coeff::Insignificant(_) => true,
+        };
+        self.neighbours(idx).magnitude_context(first_refinement)
+    }
+}
 
-        debug!("v0 {v0:?} v1 {v1:?} h0 {v1:?} h1 {h1:?}");
+/// ColumnIndex type to help avoid indexing mistakes
+#[derive(Debug)]
+struct ColumnIndex {
+    pub base_y: i32,
+    pub x: i32,
+}
 
-        /// Add up the contribution to a -1,0,1
-        fn contribution(a: &Coeff, b: &Coeff) -> i8 {
-            let total = a.sign_contribution() + b.sign_contribution();
-            match total {
-                1 | 2 => 1,
-                0 => 0,
-                -1 | -2 => -1,
-                _ => panic!("Total should be in range -2..=2"),
-            }
+/// Parameters of a single code-block, as signalled in the codestream.
+///
+/// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Section B.10 for the values
+/// from the packet header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeBlockParameters {
+    /// Sub-band containing the code-block, which selects the significance
+    /// contexts of Table D.1.
+    pub subband: SubBandType,
+    /// Width of the code-block, at most 1024.
+    pub width: u32,
+    /// Height of the code-block, at most 1024, with no more than 4096
+    /// coefficients in total.
+    pub height: u32,
+    /// Number of coding passes included, from Table B.4.
+    pub no_passes: u8,
+    /// Number of missing most significant bit-planes, from Section B.10.5.
+    pub zero_bit_planes: u8,
+    /// Maximum number of magnitude bit-planes, M<sub>b</sub> from
+    /// Equation E-2.
+    pub mb: u8,
+}
+
+impl CodeBlockParameters {
+    /// Whether the code-block contributes no data.
+    ///
+    /// This is a code-block with no coding passes, either because the
+    /// inclusion tag tree has not included it yet, or because it is included
+    /// with all of its bit-planes missing. All of its coefficients are zero,
+    /// and `zero_bit_planes` may not have been signalled.
+    pub fn is_empty(&self) -> bool {
+        self.no_passes == 0
+    }
+}
+
+/// Decode the coefficients of a single code-block from its compressed data.
+///
+/// `data` is the code-block contribution from the packet bodies, for all of
+/// the included coding passes, with a single terminated codeword segment.
+///
+/// The coefficients are returned in raster order, so the coefficient at
+/// column `x` and row `y` of the code-block, relative to its upper left
+/// corner, is at index `y * width + x`.
+///
+/// Each coefficient is the signed integer q<sub>b</sub>(u,v) of Equation
+/// E-1, in two's complement rather than sign-magnitude, with the least
+/// significant bit as the last of the M<sub>b</sub> bit-planes. Bit-planes
+/// without a coding pass, when the passes are truncated, are zero. The
+/// coefficients are ready for dequantization, see Section E.1.1.
+///
+/// An empty code-block, see [`CodeBlockParameters::is_empty`], gives zero
+/// coefficients without decoding `data`.
+pub fn decode_code_block(
+    parameters: &CodeBlockParameters,
+    data: &[u8],
+) -> Result<Vec<i32>, CodeBlockDecodeError> {
+    let mut decoder = CodeBlockDecoder::new(0, 0, parameters.subband, 0, 0)?;
+    decode_code_block_with(&mut decoder, parameters, 0, data, &[data.len()])
+}
+
+/// Decode a code-block as [decode_code_block] does, reusing `decoder`, for
+/// the code-block coding style `code_block_style`.
+///
+/// `data` is made up of codeword segments of `segment_lengths`, see
+/// [CodeBlockDecoder::decode_segments]. Without code-block coding style
+/// options, it is a single segment.
+pub(crate) fn decode_code_block_with(
+    decoder: &mut CodeBlockDecoder,
+    parameters: &CodeBlockParameters,
+    code_block_style: u8,
+    data: &[u8],
+    segment_lengths: &[usize],
+) -> Result<Vec<i32>, CodeBlockDecodeError> {
+    let CodeBlockParameters {
+        subband,
+        width,
+        height,
+        no_passes,
+        zero_bit_planes,
+        mb,
+    } = *parameters;
+    decoder.reset(width, height, subband, no_passes, mb)?;
+    if parameters.is_empty() {
+        return Ok(vec![0; (width * height) as usize]);
+    }
+    decoder.num_zero_bit_plane(zero_bit_planes)?;
+    if code_block_style & CODE_BLOCK_STYLE_BYPASS == 0 {
+        let mut coder = standard_decoder(data);
+        decoder.decode(&mut coder)?;
+    } else {
+        let mut segments = Vec::with_capacity(segment_lengths.len());
+        let mut rest = data;
+        for &length in segment_lengths {
+            let (segment, remainder) = rest.split_at(length.min(rest.len()));
+            segments.push(segment);
+            rest = remainder;
         }
-        debug!(
-            "sign context vert {}, {}",
-            v0.sign_contribution(),
-            v1.sign_contribution()
-        );
-        debug!(
-            "sign context horz {}, {}",
-            h0.sign_contribution(),
-            h1.sign_contribution()
-        );
+        decoder.decode_segments(code_block_style, &segments)?;
+    }
+    Ok(decoder.coefficients())
+}
 
-        let vc = contribution(v0, v1);
-        let hc = contribution(h0, h1);
-        // ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Table D.3
-        let (ctx, xor) = match (hc, vc) {
-            (1, 1) => (13, 0),
-            (1, 0) => (12, 0),
-            (1, -1) => (11, 0),
-            (0, 1) => (10, 0),
-            (0, 0) => (9, 0),
-            (0, -1) => (10, 1),
-            (-1, 1) => (11, 1),
-            (-1, 0) => (12, 1),
-            (-1, -1) => (13, 1),
-            (_, _) => panic!("Invalid context values for sign_context"),
-        };
-        (ctx, xor)
+/// Encode the coefficients of a code-block, given in raster order, the
+/// inverse of [decode_code_block].
+///
+/// Every bit-plane from the most significant non-zero one is coded, with a
+/// cleanup pass for the first and all three coding passes for each of the
+/// others, in a single terminated codeword segment. Returns the parameters to
+/// signal in the packet header with the coded data, or `None` if a
+/// coefficient needs more than `mb` magnitude bit-planes. A code-block of zero
+/// coefficients has no coding passes and no data.
+pub(crate) fn encode_code_block(
+    subband: SubBandType,
+    width: u32,
+    height: u32,
+    mb: u8,
+    coefficients: &[i32],
+) -> Option<(CodeBlockParameters, Vec<u8>)> {
+    let magnitude = coefficients.iter().map(|c| c.unsigned_abs()).max();
+    let bit_planes = (32 - magnitude.unwrap_or(0).leading_zeros()) as u8;
+    let mut parameters = CodeBlockParameters {
+        subband,
+        width,
+        height,
+        no_passes: 0,
+        zero_bit_planes: mb.checked_sub(bit_planes)?,
+        mb,
+    };
+    if bit_planes == 0 {
+        return Some((parameters, vec![]));
     }
 
-    fn magnitude_context(&self, idx: CoeffIndex) -> usize {
-        if let Coeff::Significant { value, .. } = self.coeff_at(idx) {
-            let c = value.count_ones();
-            let sv = value >> (1 + self.bit_plane_shift);
-            if sv != 1 {
-                debug!("First refinement for idx {:?} w/ {}, c {}", idx, value, c);
-                return 16;
+    let mut encoder = CodeBlockEncoder::new(width as usize, height as usize, subband, coefficients);
+    let mut coder = MqEncoder::new(NUM_CONTEXTS);
+    coder.reset_contexts();
+    coder.init();
+    encoder.bit_plane_shift = bit_planes - 1;
+    encoder.pass_cleanup(&mut coder);
+    while encoder.bit_plane_shift > 0 {
+        encoder.bit_plane_shift -= 1;
+        encoder.pass_significance(&mut coder);
+        encoder.pass_refinement(&mut coder);
+        encoder.pass_cleanup(&mut coder);
+    }
+    parameters.no_passes = 3 * bit_planes - 2;
+    Some((parameters, coder.flush()))
+}
+
+/// Encoder of the coefficients of a code-block, which codes the decisions
+/// that [CodeBlockDecoder] decodes, in the same contexts.
+#[derive(Debug)]
+struct CodeBlockEncoder {
+    width: usize,
+    height: usize,
+    subband: SubBandType,
+    bit_plane_shift: u8,
+    magnitudes: Vec<u32>,
+    is_negative: Vec<bool>,
+    // Bit-plane in which a significance propagation pass last coded each
+    // coefficient, or u8::MAX
+    coded_in: Vec<u8>,
+    // Sign of each significant coefficient, with a border, as for the
+    // decoder
+    significance: Vec<i8>,
+}
+
+impl CodeBlockEncoder {
+    fn new(width: usize, height: usize, subband: SubBandType, coefficients: &[i32]) -> Self {
+        CodeBlockEncoder {
+            width,
+            height,
+            subband,
+            bit_plane_shift: 0,
+            magnitudes: coefficients.iter().map(|c| c.unsigned_abs()).collect(),
+            is_negative: coefficients.iter().map(|c| *c < 0).collect(),
+            coded_in: vec![u8::MAX; width * height],
+            significance: vec![0; (width + 2) * (height + 2)],
+        }
+    }
+
+    fn padded_index(&self, x: usize, y: usize) -> usize {
+        (y + 1) * (self.width + 2) + x + 1
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> Neighbours {
+        Neighbours::padded(&self.significance, self.width + 2, self.padded_index(x, y))
+    }
+
+    fn is_significant(&self, x: usize, y: usize) -> bool {
+        self.significance[self.padded_index(x, y)] != 0
+    }
+
+    /// Magnitude bit of a coefficient in the current bit-plane.
+    fn bit(&self, x: usize, y: usize) -> u8 {
+        ((self.magnitudes[y * self.width + x] >> self.bit_plane_shift) & 1) as u8
+    }
+
+    /// Encode the sign bit of a coefficient that has become significant
+    fn encode_sign_bit(&mut self, x: usize, y: usize, coder: &mut MqEncoder) {
+        let (cx, xor) = self.neighbours(x, y).sign_context();
+        let is_negative = self.is_negative[y * self.width + x];
+        coder.encode(cx, is_negative as u8 ^ xor);
+        let padded_index = self.padded_index(x, y);
+        self.significance[padded_index] = if is_negative { -1 } else { 1 };
+    }
+
+    /// Encode the significance of an insignificant coefficient, and its sign
+    /// if it becomes significant, returning whether it did
+    fn encode_significance(
+        &mut self,
+        cx: usize,
+        x: usize,
+        y: usize,
+        coder: &mut MqEncoder,
+    ) -> bool {
+        let bit = self.bit(x, y);
+        coder.encode(cx, bit);
+        if bit == 1 {
+            self.encode_sign_bit(x, y, coder);
+        }
+        bit == 1
+    }
+
+    /// Significance propagation pass, Section D.3.1
+    fn pass_significance(&mut self, coder: &mut MqEncoder) {
+        for by in (0..self.height).step_by(4) {
+            for x in 0..self.width {
+                for y in by..(by + 4).min(self.height) {
+                    if self.is_significant(x, y) {
+                        continue;
+                    }
+                    let cx = self.neighbours(x, y).significance_context(self.subband);
+                    if cx == 0 {
+                        continue;
+                    }
+                    if !self.encode_significance(cx, x, y, coder) {
+                        self.coded_in[y * self.width + x] = self.bit_plane_shift;
+                    }
+                }
             }
         }
-        let CoeffIndex { x, y } = idx;
-        let h0 = self.is_significant(CoeffIndex { y, x: x - 1 }) as u8;
-        let h1 = self.is_significant(CoeffIndex { y, x: x + 1 }) as u8;
-        let v0 = self.is_significant(CoeffIndex { y: y - 1, x }) as u8;
-        let v1 = self.is_significant(CoeffIndex { y: y + 1, x }) as u8;
-
-        let c = v0 + v1 + h0 + h1;
-        if c > 0 {
-            // early return if we know w/o diagonals
-            return 15;
-        }
-
-        let mut dc = 0u8;
-        // Diagonals (only if both adjacent orthogonal are insignificant)
-        dc += self.is_significant(CoeffIndex { y: y - 1, x: x - 1 }) as u8;
-        dc += self.is_significant(CoeffIndex { y: y - 1, x: x + 1 }) as u8;
-        dc += self.is_significant(CoeffIndex { y: y + 1, x: x - 1 }) as u8;
-        dc += self.is_significant(CoeffIndex { y: y + 1, x: x + 1 }) as u8;
-        if dc + c > 0 {
-            15
-        } else {
-            14
+    }
+
+    /// Magnitude refinement pass, Section D.3.3, for the coefficients that
+    /// became significant in an earlier bit-plane
+    fn pass_refinement(&mut self, coder: &mut MqEncoder) {
+        for by in (0..self.height).step_by(4) {
+            for x in 0..self.width {
+                for y in by..(by + 4).min(self.height) {
+                    let magnitude = self.magnitudes[y * self.width + x];
+                    if !self.is_significant(x, y) || magnitude >> self.bit_plane_shift == 1 {
+                        continue;
+                    }
+                    let first_refinement = magnitude >> (1 + self.bit_plane_shift) == 1;
+                    let cx = self.neighbours(x, y).magnitude_context(first_refinement);
+                    coder.encode(cx, self.bit(x, y));
+                }
+            }
         }
     }
-}
 
-/// ColumnIndex type to help avoid indexing mistakes
-#[derive(Debug)]
-struct ColumnIndex {
-    pub base_y: i32,
-    pub x: i32,
+    /// Cleanup pass, Section D.3.4, with run-length coding of columns of four
+    /// insignificant coefficients with no significant neighbours
+    fn pass_cleanup(&mut self, coder: &mut MqEncoder) {
+        for by in (0..self.height).step_by(4) {
+            let end = (by + 4).min(self.height);
+            for x in 0..self.width {
+                let left_for_pass = |encoder: &Self, y: usize| {
+                    !encoder.is_significant(x, y)
+                        && encoder.coded_in[y * encoder.width + x] != encoder.bit_plane_shift
+                };
+                let mut first = by;
+                let run_length = end - by == 4
+                    && (by..end).all(|y| {
+                        left_for_pass(self, y)
+                            && self.neighbours(x, y).significance_context(self.subband) == 0
+                    });
+                if run_length {
+                    match (by..end).find(|&y| self.bit(x, y) == 1) {
+                        None => {
+                            coder.encode(RUN_LEN, 0);
+                            continue;
+                        }
+                        Some(y) => {
+                            coder.encode(RUN_LEN, 1);
+                            let skipped = y - by;
+                            coder.encode(UNIFORM, (skipped >> 1) as u8);
+                            coder.encode(UNIFORM, (skipped & 1) as u8);
+                            self.encode_sign_bit(x, y, coder);
+                            first = y + 1;
+                        }
+                    }
+                }
+                for y in first..end {
+                    if left_for_pass(self, y) {
+                        let cx = self.neighbours(x, y).significance_context(self.subband);
+                        self.encode_significance(cx, x, y, coder);
+                    }
+                }
+            }
+        }
+    }
 }
 
-// Decoder State
-#[derive(Debug, Default)]
+/// Coding pass of the decoder.
+///
+/// The first bit-plane has only a cleanup pass, and each following bit-plane
+/// has significance propagation, magnitude refinement and cleanup passes.
+///
+/// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Section D.3
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 enum State {
     SignificancePropagation,
     #[default]
@@ -498,8 +1332,29 @@ enum State {
     MagnitudeRefinement,
 }
 
+impl State {
+    /// Pass following this one.
+    fn next(self) -> State {
+        match self {
+            State::CleanUp => State::SignificancePropagation,
+            State::SignificancePropagation => State::MagnitudeRefinement,
+            State::MagnitudeRefinement => State::CleanUp,
+        }
+    }
+
+    /// Sequence of `no_passes` coding passes, from the first bit-plane.
+    ///
+    /// The final bit-plane may have only some of its passes.
+    fn sequence(no_passes: u8) -> impl Iterator<Item = State> {
+        iter::successors(Some(State::default()), |state| Some(state.next()))
+            .take(no_passes as usize)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use crate::coder::{standard_decoder, Decoder};
 
     use super::*;
@@ -523,6 +1378,14 @@ mod tests {
             assert_eq!(exp_cx, cx, "incorrect cx during decode");
             out
         }
+
+        fn bytes_consumed(&self) -> usize {
+            0
+        }
+
+        fn is_exhausted(&self) -> bool {
+            false
+        }
     }
 
     /// Test decoding the codeblock from J.10 for LL using a mock mqcoder
@@ -575,9 +1438,9 @@ mod tests {
             index: 0,
         };
         // There are 16 coding passes in this example
-        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9);
+        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9).unwrap();
         // codeblock.mb(9);
-        codeblock.num_zero_bit_plane(3);
+        codeblock.num_zero_bit_plane(3).unwrap();
         // 9 - 3 = 6 bits to set
         // 6-1 = 5 => 1+5*3 = 16 coding passes
 
@@ -604,8 +1467,8 @@ mod tests {
         let mut coder = standard_decoder(bd);
 
         // There are 16 coding passes in this example
-        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9);
-        codeblock.num_zero_bit_plane(3);
+        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9).unwrap();
+        codeblock.num_zero_bit_plane(3).unwrap();
         // 9 - 3 = 6 bits to set
         // 6-1 = 5 => 1+5*3 = 16 coding passes
 
@@ -616,7 +1479,33 @@ mod tests {
 
         let coeffs = codeblock.coefficients();
         let exp_coeffs = vec![-26, -22, -30, -32, -19];
-        assert_eq!(coeffs, exp_coeffs, "Coefficients didn't match");
+        assert_eq!(
+            coeffs,
+            exp_coeffs,
+            "Coefficients didn't match\n{}",
+            codeblock.debug_bitplanes()
+        );
+
+        // -26, -22, -30, -32 and -19 down the column of the code-block
+        let bit_planes = codeblock.debug_bitplanes();
+        assert!(bit_planes.starts_with(
+            "sign\n-\n-\n-\n-\n-\n\
+             bit-plane 5\n.\n.\n.\n1\n.\n\
+             bit-plane 4\n1\n1\n1\n0\n1\n\
+             bit-plane 3\n1\n0\n1\n0\n0\n"
+        ));
+        assert!(bit_planes.ends_with("bit-plane 0\n0\n0\n0\n0\n1\n"));
+
+        let sign_magnitude = codeblock.sign_magnitude_coefficients();
+        assert_eq!(
+            sign_magnitude[0],
+            Coefficient::Significant {
+                magnitude: 26,
+                is_negative: true
+            }
+        );
+        let values: Vec<i32> = sign_magnitude.iter().map(Coefficient::value).collect();
+        assert_eq!(values, exp_coeffs);
     }
 
     /// Test decoding the codeblock from J.10 for LH using a mock mqcoder
@@ -647,9 +1536,9 @@ mod tests {
             index: 0,
         };
         // There are 7 coding passes in this example
-        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10);
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
         // codeblock.mb(10);
-        codeblock.num_zero_bit_plane(7);
+        codeblock.num_zero_bit_plane(7).unwrap();
         // 10 - 7 = 3 bits to set
         // 3 bits to set => 7 (=1cleanup+2bitplanes*3) coding passes
 
@@ -675,8 +1564,8 @@ mod tests {
         let bd = b"\x0F\xB1\x76";
         let mut coder = standard_decoder(bd);
 
-        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10);
-        codeblock.num_zero_bit_plane(7);
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
+        codeblock.num_zero_bit_plane(7).unwrap();
 
         assert!(
             codeblock.decode(&mut coder).is_ok(),
@@ -685,6 +1574,611 @@ mod tests {
 
         let coeffs = codeblock.coefficients();
         let exp_coeffs = vec![1, 5, 1, 0];
-        assert_eq!(coeffs, exp_coeffs, "Coefficients didn't match");
+        assert_eq!(
+            coeffs,
+            exp_coeffs,
+            "Coefficients didn't match\n{}",
+            codeblock.debug_bitplanes()
+        );
+        assert!(codeblock
+            .debug_bitplanes()
+            .starts_with("sign\n+\n+\n+\n.\nbit-plane 2\n.\n1\n.\n.\n"));
+
+        // The last coefficient never became significant
+        let sign_magnitude = codeblock.sign_magnitude_coefficients();
+        assert_eq!(sign_magnitude[3].value(), 0);
+        assert!(matches!(
+            sign_magnitude[3],
+            Coefficient::Insignificant { .. }
+        ));
+    }
+
+    #[test]
+    fn test_cb_decode_zero_refinement_bits() {
+        init_logger();
+
+        // A single coefficient becomes significant and negative in the first
+        // cleanup pass, then has a zero bit in each refinement pass
+        let mut coder = MockCoder {
+            exp: vec![(0, 1), (9, 1), (14, 0), (16, 0)],
+            index: 0,
+        };
+        let mut codeblock = CodeBlockDecoder::new(1, 1, SubBandType::LL, 7, 3).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(codeblock.coefficients(), vec![-4]);
+        assert_eq!(
+            codeblock.sign_magnitude_coefficients(),
+            vec![Coefficient::Significant {
+                magnitude: 4,
+                is_negative: true
+            }]
+        );
+    }
+
+    /// Code-blocks narrower or shorter than a stripe, where the cleanup pass
+    /// never uses run-length coding and the neighbours are clipped.
+    #[test]
+    fn test_cb_decode_small_sizes() {
+        // Each has -3 then 1 over two bit-planes: the first coefficient is
+        // significant and negative in the first cleanup pass, then the
+        // second is significant and positive in the significance propagation
+        // pass, its sign predicted negative from its neighbour, and the
+        // first is refined
+        let cases = [
+            (2, 1, SubBandType::LL, 5, 12),
+            (2, 1, SubBandType::HL, 3, 12),
+            (1, 2, SubBandType::LL, 3, 10),
+            (1, 2, SubBandType::HL, 5, 10),
+            (2, 1, SubBandType::HH, 1, 12),
+        ];
+        for (width, height, subband, context, sign_context) in cases {
+            let mut coder = MockCoder {
+                exp: vec![
+                    (0, 1),
+                    (9, 1),
+                    (context, 0),
+                    (context, 1),
+                    (sign_context, 1),
+                    (15, 1),
+                ],
+                index: 0,
+            };
+            let mut codeblock = CodeBlockDecoder::new(width, height, subband, 4, 2).unwrap();
+            codeblock.decode(&mut coder).unwrap();
+            assert_eq!(coder.index, coder.exp.len(), "{width}x{height} {subband:?}");
+            assert_eq!(
+                codeblock.coefficients(),
+                vec![-3, 1],
+                "{width}x{height} {subband:?}"
+            );
+        }
+
+        // A single coefficient has no neighbours
+        let mut coder = MockCoder {
+            exp: vec![(0, 0), (0, 1), (9, 0)],
+            index: 0,
+        };
+        let mut codeblock = CodeBlockDecoder::new(1, 1, SubBandType::HH, 4, 2).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(codeblock.coefficients(), vec![1]);
+    }
+
+    #[test]
+    fn test_cb_decode_high_magnitude() {
+        // With 16 magnitude bit-planes, the first bit-plane is bit 15, which
+        // does not fit in an i16
+        let mut coder = MockCoder {
+            exp: vec![(0, 1), (9, 1), (14, 1)],
+            index: 0,
+        };
+        let mut codeblock = CodeBlockDecoder::new(1, 1, SubBandType::LL, 4, 16).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(codeblock.coefficients(), vec![-49152]);
+        assert_eq!(
+            codeblock.sign_magnitude_coefficients(),
+            vec![Coefficient::Significant {
+                magnitude: 0xC000,
+                is_negative: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cb_zero_magnitude_has_no_sign() {
+        let mut codeblock = CodeBlockDecoder::new(1, 2, SubBandType::LL, 1, 3).unwrap();
+        codeblock.coefficients[0] = Coeff::Significant {
+            value: 0,
+            is_negative: true,
+        };
+        assert_eq!(codeblock.coefficients(), vec![0, 0]);
+        assert_eq!(
+            codeblock.sign_magnitude_coefficients()[0],
+            Coefficient::Significant {
+                magnitude: 0,
+                is_negative: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_twos_complement() {
+        let coefficients = [
+            Coefficient::Significant {
+                magnitude: 5,
+                is_negative: false,
+            },
+            Coefficient::Significant {
+                magnitude: 5,
+                is_negative: true,
+            },
+            Coefficient::Insignificant {
+                bit_plane_shift: Some(3),
+            },
+            Coefficient::Significant {
+                magnitude: 1 << 31,
+                is_negative: true,
+            },
+        ];
+        let values = to_twos_complement(&coefficients);
+        assert_eq!(values, vec![5, -5, 0, i32::MIN]);
+        assert_eq!(
+            from_twos_complement(&values)[2],
+            Coefficient::Insignificant {
+                bit_plane_shift: None
+            }
+        );
+        assert_eq!(from_twos_complement(&values)[3], coefficients[3]);
+    }
+
+    #[test]
+    fn test_encode_code_block() {
+        // Magnitude 5 has 3 bit-planes, so 7 coding passes
+        let (parameters, data) = encode_code_block(SubBandType::HL, 2, 1, 5, &[-5, 0]).unwrap();
+        assert_eq!(parameters.no_passes, 7);
+        assert_eq!(parameters.zero_bit_planes, 2);
+        assert_eq!(decode_code_block(&parameters, &data), Ok(vec![-5, 0]));
+
+        // Zero coefficients have no coding passes, and 8 needs 4 bit-planes
+        let (parameters, data) = encode_code_block(SubBandType::LL, 2, 2, 3, &[0; 4]).unwrap();
+        assert!(parameters.is_empty());
+        assert_eq!(parameters.zero_bit_planes, 3);
+        assert!(data.is_empty());
+        assert!(encode_code_block(SubBandType::LL, 1, 1, 3, &[8]).is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn test_twos_complement_round_trip(values in prop::collection::vec(any::<i32>(), 0..64)) {
+            prop_assert_eq!(to_twos_complement(&from_twos_complement(&values)), values);
+        }
+
+        #[test]
+        fn test_decode_small_code_blocks(
+            width in 1..=5u32,
+            height in 1..=5u32,
+            no_passes in 0..=28u8,
+            data in prop::collection::vec(any::<u8>(), 0..16),
+        ) {
+            // Any data decodes without panicking, either to coefficients
+            // within the magnitude bit-planes or to an error
+            let parameters = CodeBlockParameters {
+                subband: SubBandType::HH,
+                width,
+                height,
+                no_passes,
+                zero_bit_planes: 0,
+                mb: 10,
+            };
+            if let Ok(coefficients) = decode_code_block(&parameters, &data) {
+                prop_assert_eq!(coefficients.len(), (width * height) as usize);
+                prop_assert!(coefficients.iter().all(|value| value.abs() < 1 << 10));
+            }
+        }
+
+        #[test]
+        fn test_sign_magnitude_round_trip(
+            coefficients in prop::collection::vec(
+                (1..=i32::MAX as u32, any::<bool>()).prop_map(|(magnitude, is_negative)| {
+                    Coefficient::Significant { magnitude, is_negative }
+                }),
+                0..64,
+            )
+        ) {
+            let values = to_twos_complement(&coefficients);
+            prop_assert_eq!(from_twos_complement(&values), coefficients);
+        }
+
+        #[test]
+        fn test_encode_round_trip(
+            (width, height, coefficients) in (1..=8u32, 1..=8u32).prop_flat_map(|(width, height)| {
+                let coefficients = prop::collection::vec(-300..=300i32, (width * height) as usize);
+                (Just(width), Just(height), coefficients)
+            }),
+            subband in prop_oneof![
+                Just(SubBandType::LL),
+                Just(SubBandType::HL),
+                Just(SubBandType::LH),
+                Just(SubBandType::HH),
+            ],
+        ) {
+            let (parameters, data) =
+                encode_code_block(subband, width, height, 10, &coefficients).unwrap();
+            prop_assert_eq!(decode_code_block(&parameters, &data), Ok(coefficients));
+        }
+    }
+
+    /// Run-length coding is only used for a column whose coefficients all
+    /// have a 0 context, so not for one next to a significant coefficient.
+    #[test]
+    fn test_cb_cleanup_run_length_needs_zero_context() {
+        let mut coder = MockCoder {
+            exp: vec![
+                // First column, run-length coded with the first coefficient
+                // significant and positive
+                (17, 1),
+                (18, 0),
+                (18, 0),
+                (9, 0),
+                (3, 0),
+                (0, 0),
+                (0, 0),
+                // Second column, next to it, coded coefficient by coefficient
+                (5, 0),
+                (1, 0),
+                (0, 0),
+                (0, 0),
+            ],
+            index: 0,
+        };
+        let mut codeblock = CodeBlockDecoder::new(2, 4, SubBandType::LL, 1, 1).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(coder.exp.len(), coder.index);
+        assert_eq!(codeblock.coefficients(), vec![1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_cb_coeff_at_mut_out_of_bounds() {
+        let mut codeblock = CodeBlockDecoder::new(2, 2, SubBandType::LL, 1, 4).unwrap();
+        assert_eq!(
+            codeblock.coeff_at_mut(CoeffIndex { x: 2, y: 0 }).err(),
+            Some(CodeBlockDecodeError::OutOfBounds { x: 2, y: 0 })
+        );
+        assert_eq!(
+            codeblock.coeff_at_mut(CoeffIndex { x: 0, y: -1 }).err(),
+            Some(CodeBlockDecodeError::OutOfBounds { x: 0, y: -1 })
+        );
+    }
+
+    #[test]
+    fn test_cb_make_significant_twice() {
+        let mut codeblock = CodeBlockDecoder::new(2, 2, SubBandType::LL, 1, 4).unwrap();
+        let idx = CoeffIndex { x: 1, y: 1 };
+        assert!(codeblock.make_significant(idx).is_ok());
+        assert_eq!(
+            codeblock.make_significant(idx),
+            Err(CodeBlockDecodeError::AlreadySignificant { x: 1, y: 1 })
+        );
+    }
+
+    #[test]
+    fn test_cb_bit_plane_of_insignificant() {
+        let codeblock = CodeBlockDecoder::new(2, 2, SubBandType::LL, 1, 4).unwrap();
+        assert_eq!(
+            codeblock.is_bit_plane_set(CoeffIndex { x: 0, y: 0 }),
+            Err(CodeBlockDecodeError::NotSignificant { x: 0, y: 0 })
+        );
+    }
+
+    #[test]
+    fn test_cb_zero_bit_planes_underflow() {
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
+        assert_eq!(
+            codeblock.num_zero_bit_plane(10),
+            Err(CodeBlockDecodeError::BitPlaneUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_cb_decode_too_many_passes() {
+        // 3 bit-planes can only be decoded by 7 coding passes
+        let mut coder = standard_decoder(b"\x0F\xB1\x76");
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 10, 3).unwrap();
+        assert_eq!(
+            codeblock.decode(&mut coder).err(),
+            Some(CodeBlockDecodeError::BitPlaneUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_cb_decode_invalid_symbol() {
+        let mut coder = MockCoder {
+            exp: vec![(17, 1), (18, 2), (18, 0)],
+            index: 0,
+        };
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 1, 3).unwrap();
+        assert_eq!(
+            codeblock.decode(&mut coder).err(),
+            Some(CodeBlockDecodeError::InvalidSymbol { value: 2 })
+        );
+    }
+
+    #[test]
+    fn test_cb_padded_neighbours_match_checked() {
+        // Arbitrary compressed data, the MQ decoder accepts any input
+        let data: Vec<u8> = (0u32..256).map(|i| (i * 37 % 127) as u8).collect();
+        let mut coder = standard_decoder(&data);
+        // Odd dimensions, so the last stripe is partial
+        let mut codeblock = CodeBlockDecoder::new(9, 7, SubBandType::HH, 19, 7).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert!(codeblock.coefficients().iter().any(|c| *c > 0));
+        assert!(codeblock.coefficients().iter().any(|c| *c < 0));
+
+        // Including the border just outside the code-block
+        for y in -1..=7 {
+            for x in -1..=9 {
+                let idx = CoeffIndex { y, x };
+                assert_eq!(
+                    codeblock.neighbours(idx),
+                    codeblock.neighbours_checked(idx),
+                    "neighbours differ at {:?}",
+                    idx
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cb_decode_into_reused() {
+        let mut out = [0i32; 5];
+        let mut coder = standard_decoder(b"\x01\x8F\x0D\xC8\x75\x5D");
+        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9).unwrap();
+        codeblock.num_zero_bit_plane(3).unwrap();
+        codeblock.decode_into(&mut coder, &mut out).unwrap();
+        assert_eq!(out, [-26, -22, -30, -32, -19]);
+
+        // The J.10 LH code-block, with the same decoder
+        let mut out = [0i32; 4];
+        let mut coder = standard_decoder(b"\x0F\xB1\x76");
+        codeblock.reset(1, 4, SubBandType::LH, 7, 10).unwrap();
+        codeblock.num_zero_bit_plane(7).unwrap();
+        codeblock.decode_into(&mut coder, &mut out).unwrap();
+        assert_eq!(out, [1, 5, 1, 0]);
+        assert_eq!(codeblock.coefficients(), vec![1, 5, 1, 0]);
+    }
+
+    #[test]
+    fn test_cb_new_invalid() {
+        for &(width, height) in &[(100_000, 100_000), (2048, 1), (1, 1025), (64, 128)] {
+            assert_eq!(
+                CodeBlockDecoder::new(width, height, SubBandType::LL, 1, 4).err(),
+                Some(CodeBlockDecodeError::InvalidSize { width, height })
+            );
+        }
+        assert_eq!(
+            CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 32).err(),
+            Some(CodeBlockDecodeError::InvalidMagnitudeBitPlanes { mb: 32 })
+        );
+
+        // A failed reset leaves the decoder usable for a valid code-block
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
+        assert!(codeblock.reset(4096, 1, SubBandType::LH, 7, 10).is_err());
+        assert!(codeblock.reset(1, 4, SubBandType::LH, 7, 40).is_err());
+        let mut coder = standard_decoder(b"\x0F\xB1\x76");
+        codeblock.reset(1, 4, SubBandType::LH, 7, 10).unwrap();
+        codeblock.num_zero_bit_plane(7).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(codeblock.coefficients(), vec![1, 5, 1, 0]);
+    }
+
+    #[test]
+    fn test_cb_decode_into_wrong_size() {
+        let mut out = [0i32; 5];
+        let mut coder = standard_decoder(b"\x0F\xB1\x76");
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
+        assert_eq!(
+            codeblock.decode_into(&mut coder, &mut out),
+            Err(CodeBlockDecodeError::OutputSize {
+                expected: 4,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_cb_decode_bytes_consumed() {
+        let bd = b"\x01\x8F\x0D\xC8\x75\x5D";
+        let mut coder = standard_decoder(bd);
+        let mut codeblock = CodeBlockDecoder::new(1, 5, SubBandType::LL, 16, 9).unwrap();
+        codeblock.num_zero_bit_plane(3).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(coder.bytes_consumed(), bd.len());
+        assert!(!coder.is_exhausted());
+
+        let bd = b"\x0F\xB1\x76";
+        let mut coder = standard_decoder(bd);
+        let mut codeblock = CodeBlockDecoder::new(1, 4, SubBandType::LH, 7, 10).unwrap();
+        codeblock.num_zero_bit_plane(7).unwrap();
+        codeblock.decode(&mut coder).unwrap();
+        assert_eq!(coder.bytes_consumed(), bd.len());
+        assert!(!coder.is_exhausted());
+    }
+
+    #[test]
+    fn test_cb_decode_data_exhausted() {
+        // Far more coding passes than two bytes of data can hold
+        let mut coder = standard_decoder(b"\x01\x8F");
+        let mut codeblock = CodeBlockDecoder::new(32, 32, SubBandType::HH, 31, 11).unwrap();
+        assert!(matches!(
+            codeblock.decode(&mut coder),
+            Err(CodeBlockDecodeError::DataExhausted {
+                bytes_consumed: 2,
+                ..
+            })
+        ));
+    }
+
+    /// Counts the decisions made by the wrapped decoder
+    struct CountingCoder<D: Decoder> {
+        inner: D,
+        decisions: usize,
+    }
+
+    impl<D: Decoder> Decoder for CountingCoder<D> {
+        fn decode_bit(&mut self, cx: usize) -> u8 {
+            self.decisions += 1;
+            self.inner.decode_bit(cx)
+        }
+
+        fn bytes_consumed(&self) -> usize {
+            self.inner.bytes_consumed()
+        }
+
+        fn is_exhausted(&self) -> bool {
+            self.inner.is_exhausted()
+        }
+    }
+
+    #[test]
+    fn test_cb_decode_truncated_passes() {
+        // J.10 LH code-block, with the coding passes truncated. The
+        // decisions of each pass are from test_cb_decode_j10b_mocked
+        let cases: [(u8, usize, Vec<i32>); 8] = [
+            (0, 0, vec![0, 0, 0, 0]),
+            // Cleanup, for the first bit-plane
+            (1, 6, vec![0, 4, 0, 0]),
+            // Significance propagation, magnitude refinement and cleanup
+            (2, 8, vec![0, 4, 0, 0]),
+            (3, 9, vec![0, 4, 0, 0]),
+            (4, 10, vec![0, 4, 0, 0]),
+            // And for the last bit-plane
+            (5, 15, vec![1, 4, 1, 0]),
+            (6, 16, vec![1, 5, 1, 0]),
+            (7, 16, vec![1, 5, 1, 0]),
+        ];
+        for (no_passes, decisions, coefficients) in cases.iter() {
+            let mut coder = CountingCoder {
+                inner: standard_decoder(b"\x0F\xB1\x76"),
+                decisions: 0,
+            };
+            let mut codeblock =
+                CodeBlockDecoder::new(1, 4, SubBandType::LH, *no_passes, 10).unwrap();
+            codeblock.num_zero_bit_plane(7).unwrap();
+            codeblock.decode(&mut coder).unwrap();
+            assert_eq!(coder.decisions, *decisions, "{no_passes} passes");
+            assert_eq!(
+                codeblock.coefficients(),
+                *coefficients,
+                "{no_passes} passes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_cb_pass_sequence() {
+        use State::*;
+        assert_eq!(State::sequence(0).count(), 0);
+        assert_eq!(State::sequence(1).collect::<Vec<_>>(), vec![CleanUp]);
+        assert_eq!(
+            State::sequence(3).collect::<Vec<_>>(),
+            vec![CleanUp, SignificancePropagation, MagnitudeRefinement]
+        );
+        assert_eq!(
+            State::sequence(6).collect::<Vec<_>>(),
+            vec![
+                CleanUp,
+                SignificancePropagation,
+                MagnitudeRefinement,
+                CleanUp,
+                SignificancePropagation,
+                MagnitudeRefinement
+            ]
+        );
+        // 164 passes, the maximum from Table B.4, end part way through the
+        // 56th bit-plane
+        let passes: Vec<State> = State::sequence(164).collect();
+        assert_eq!(passes.iter().filter(|pass| **pass == CleanUp).count(), 55);
+        assert_eq!(passes.last(), Some(&SignificancePropagation));
+    }
+
+    #[test]
+    fn test_decode_code_block() {
+        let parameters = CodeBlockParameters {
+            subband: SubBandType::LL,
+            width: 1,
+            height: 5,
+            no_passes: 16,
+            zero_bit_planes: 3,
+            mb: 9,
+        };
+        assert_eq!(
+            decode_code_block(&parameters, b"\x01\x8F\x0D\xC8\x75\x5D"),
+            Ok(vec![-26, -22, -30, -32, -19])
+        );
+
+        let parameters = CodeBlockParameters {
+            width: 2048,
+            height: 1,
+            ..parameters
+        };
+        assert_eq!(
+            decode_code_block(&parameters, b""),
+            Err(CodeBlockDecodeError::InvalidSize {
+                width: 2048,
+                height: 1
+            })
+        );
+
+        // M_b of 37, from 7 guard bits and an exponent of 31
+        let parameters = CodeBlockParameters {
+            width: 1,
+            height: 5,
+            mb: 37,
+            ..parameters
+        };
+        assert_eq!(
+            decode_code_block(&parameters, b"\x01\x8F\x0D\xC8\x75\x5D"),
+            Err(CodeBlockDecodeError::InvalidMagnitudeBitPlanes { mb: 37 })
+        );
+    }
+
+    #[test]
+    fn test_decode_precinct_with_empty_code_blocks() {
+        // Precinct of four 1x5 code-blocks, in the order of the packet header,
+        // where the second is not yet included and the fourth is included
+        // with no coding passes and all bit-planes missing
+        let code_blocks = [
+            (16, 3, &b"\x01\x8F\x0D\xC8\x75\x5D"[..]),
+            // Zero bit-planes are not signalled, so may be anything
+            (0, u8::MAX, &b""[..]),
+            (16, 3, &b"\x01\x8F\x0D\xC8\x75\x5D"[..]),
+            (0, 9, &b"\xFF\xFF"[..]),
+        ];
+        let mut report = crate::TileComponentReport::new(0, 0, 1);
+        let decoded: Vec<Vec<i32>> = code_blocks
+            .iter()
+            .map(|&(no_passes, zero_bit_planes, data)| {
+                let parameters = CodeBlockParameters {
+                    subband: SubBandType::LL,
+                    width: 1,
+                    height: 5,
+                    no_passes,
+                    zero_bit_planes,
+                    mb: 9,
+                };
+                report.record_code_block(&parameters);
+                decode_code_block(&parameters, data).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                vec![-26, -22, -30, -32, -19],
+                vec![0; 5],
+                vec![-26, -22, -30, -32, -19],
+                vec![0; 5],
+            ]
+        );
+        assert_eq!(report.code_blocks_decoded, 2);
+        assert_eq!(report.coding_passes, 32);
     }
 }
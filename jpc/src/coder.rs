@@ -11,7 +11,10 @@ struct QeEntry {
 }
 
 /// Table C.2 - Qe values and probability estimation
-const QE_TABLE: [QeEntry; 47] = [
+///
+/// A `static` so that all coders share the one table, rather than each use
+/// of a `const` array being free to materialise a copy.
+static QE_TABLE: [QeEntry; 47] = [
     entry(0x5601, 1, 1, true),
     entry(0x3401, 2, 6, false),
     entry(0x1801, 3, 9, false),
@@ -125,8 +128,8 @@ impl MqEncoder {
     ///
     /// See ITU T.800 (V4) | ISO/IEC 15444-1:2024 Table D.7
     pub fn reset_contexts(&mut self) {
-        assert!(self.contexts.len() == 19);
-        for i in 0..19 {
+        assert!(self.contexts.len() == NUM_CONTEXTS);
+        for i in 0..NUM_CONTEXTS {
             self.contexts[i].index = 0;
             self.contexts[i].mps = 0;
         }
@@ -327,10 +330,35 @@ impl MqEncoder {
 
 pub trait Decoder {
     fn decode_bit(&mut self, cx: usize) -> u8;
+
+    /// Number of bytes of the compressed data read so far.
+    ///
+    /// This excludes the 1 bits fed in place of data once the end of the
+    /// data, or a marker, is reached.
+    fn bytes_consumed(&self) -> usize;
+
+    /// Whether the decoder has read further past the end of the compressed
+    /// data than a terminated segment needs, so that decisions now come only
+    /// from the 1 bits fed in place of data.
+    ///
+    /// This indicates that the data is truncated, or that more coding passes
+    /// were signalled than were coded.
+    fn is_exhausted(&self) -> bool;
 }
 
+/// Number of contexts used for code-block decoding, see Table D.7.
+pub const NUM_CONTEXTS: usize = 19;
+
+/// Bytes of 1 bits that may be fed past the end of a terminated segment.
+///
+/// The decoder reads a few bytes ahead of the decisions, and encoders may
+/// also discard trailing bytes that the decoder would synthesise, so this
+/// allows more than the two or three bytes normally read.
+const MAX_FILL_BYTES: usize = 8;
+
+/// Decoder initialised with the Table D.7 context states for `bytes`.
 pub fn standard_decoder(bytes: &[u8]) -> MqDecoder {
-    let mut decoder = MqDecoder::new(19);
+    let mut decoder = MqDecoder::new(NUM_CONTEXTS);
     decoder.reset_contexts();
     decoder.init(bytes);
     decoder
@@ -344,6 +372,7 @@ pub struct MqDecoder {
     ct: i32,                     // Bit counter
     buffer: Vec<u8>,             // Input buffer
     bp: usize,                   // Buffer pointer
+    fill: usize,                 // Bytes of 1s fed past the end of the data
     contexts: Vec<ContextState>, // Context states
 }
 
@@ -356,6 +385,7 @@ impl MqDecoder {
             ct: 0,
             buffer: Vec::new(),
             bp: 0,
+            fill: 0,
             contexts: vec![ContextState::default(); num_contexts],
         }
     }
@@ -363,9 +393,14 @@ impl MqDecoder {
     /// Initialize the decoder with compressed data (INITDEC procedure).
     ///
     /// See See ITU-T T.800 (V4) | ISO/IEC 15444-1:2024 Figure C.20.
+    ///
+    /// The input buffer is reused, so a decoder initialised again for the
+    /// next code-block only allocates if the data is longer.
     pub fn init(&mut self, data: &[u8]) {
-        self.buffer = data.to_vec();
+        self.buffer.clear();
+        self.buffer.extend_from_slice(data);
         self.bp = 0;
+        self.fill = 0;
         self.ct = 0;
         self.c = 0;
 
@@ -392,8 +427,8 @@ impl MqDecoder {
     ///
     /// See ITU T.800 (V4) | ISO/IEC 15444-1:2024 Section C.3.6 and Table D.7
     pub fn reset_contexts(&mut self) {
-        assert!(self.contexts.len() == 19);
-        for i in 0..19 {
+        assert!(self.contexts.len() == NUM_CONTEXTS);
+        for i in 0..NUM_CONTEXTS {
             self.contexts[i].index = 0;
             self.contexts[i].mps = 0;
         }
@@ -500,39 +535,31 @@ impl MqDecoder {
         }
     }
 
-    /// BYTEIN - Read a byte of compressed data
+    /// BYTEIN - Read a byte of compressed data.
+    ///
+    /// See ITU T.800 (V4) | ISO/IEC 15444-1:2024 Figure C.19. `bp` is the
+    /// next byte to read, so the byte the figure calls B is the previous one.
     fn byte_in(&mut self) {
-        if self.bp >= self.buffer.len() {
-            // End of data - feed 1s (0xFF in bits 15-8)
-            self.c += 0xFF00;
-            self.ct = 8;
-            return;
-        }
-
-        let b: u32 = self.buffer[self.bp].into();
-
-        if b == 0xFF {
-            if self.bp + 1 < self.buffer.len() {
-                let b1 = self.buffer[self.bp + 1];
-                if b1 > 0x8F {
-                    // Marker code detected - feed 1s
-                    self.c += 0xFF00;
-                    self.ct = 8;
-                } else {
-                    // Stuffed bit after 0xFF - increment BP and read 0xFF
-                    self.bp += 1;
-                    self.c += b << 9; //0xFF00;
-                    self.ct = 7;
-                }
-            } else {
+        let previous = self.bp.checked_sub(1).map(|bp| self.buffer[bp]);
+        match self.buffer.get(self.bp) {
+            Some(&b) if previous == Some(0xFF) && b <= 0x8F => {
+                // Stuffed bit after 0xFF, so only 7 bits of data
+                self.bp += 1;
+                self.c += (b as u32) << 9;
+                self.ct = 7;
+            }
+            Some(&b) if previous != Some(0xFF) => {
+                // Normal byte - insert into bits 15-8 of C_low
+                self.bp += 1;
+                self.c += (b as u32) << 8;
+                self.ct = 8;
+            }
+            _ => {
+                // End of data, or a marker code - feed 1s
                 self.c += 0xFF00;
                 self.ct = 8;
+                self.fill += 1;
             }
-        } else {
-            // Normal byte - insert into bits 15-8 of C_low
-            self.bp += 1;
-            self.c += b << 8;
-            self.ct = 8;
         }
     }
 }
@@ -541,6 +568,72 @@ impl Decoder for MqDecoder {
     fn decode_bit(&mut self, cx: usize) -> u8 {
         self.decode(cx)
     }
+
+    fn bytes_consumed(&self) -> usize {
+        self.bp
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.fill > MAX_FILL_BYTES
+    }
+}
+
+/// Decoder of the raw coding passes of the selective arithmetic coding
+/// bypass, which takes each decision as the next bit of the data, without a
+/// context.
+///
+/// See ITU T.800 (V4) | ISO/IEC 15444-1:2024 Section D.6. After a byte of
+/// 0xFF, the most significant bit of the next byte is a stuffed 0, and 1 bits
+/// are fed past the end of the data.
+#[derive(Debug)]
+pub(crate) struct RawDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    byte: u8,
+    // Bits of `byte` still to be read
+    bits: u8,
+    // Bytes of 1s fed past the end of the data
+    fill: usize,
+}
+
+impl<'a> RawDecoder<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        RawDecoder {
+            data,
+            position: 0,
+            byte: 0,
+            bits: 0,
+            fill: 0,
+        }
+    }
+}
+
+impl Decoder for RawDecoder<'_> {
+    fn decode_bit(&mut self, _cx: usize) -> u8 {
+        if self.bits == 0 {
+            self.bits = if self.byte == 0xFF { 7 } else { 8 };
+            self.byte = match self.data.get(self.position) {
+                Some(&b) => {
+                    self.position += 1;
+                    b
+                }
+                None => {
+                    self.fill += 1;
+                    0xFF
+                }
+            };
+        }
+        self.bits -= 1;
+        (self.byte >> self.bits) & 1
+    }
+
+    fn bytes_consumed(&self) -> usize {
+        self.position
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.fill > 0
+    }
 }
 
 #[cfg(test)]
@@ -549,6 +642,19 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_raw_decoder() {
+        // The bit after 0xFF is stuffed, and 1s follow the end of the data
+        let mut decoder = RawDecoder::new(&[0xA5, 0xFF, 0x2A]);
+        let bits: Vec<u8> = (0..28).map(|_| decoder.decode_bit(0)).collect();
+        assert_eq!(
+            bits,
+            [1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 1, 1, 1, 1]
+        );
+        assert_eq!(decoder.bytes_consumed(), 3);
+        assert!(decoder.is_exhausted());
+    }
+
     #[test]
     fn test_qe_table_integrity() {
         // Verify table has correct number of entries
@@ -558,17 +664,17 @@ mod tests {
         assert_eq!(QE_TABLE[0].qe, 0x5601);
         assert_eq!(QE_TABLE[0].nmps, 1);
         assert_eq!(QE_TABLE[0].nlps, 1);
-        assert_eq!(QE_TABLE[0].switch, true);
+        assert!(QE_TABLE[0].switch);
 
         assert_eq!(QE_TABLE[22].qe, 0x2401);
         assert_eq!(QE_TABLE[22].nmps, 23);
         assert_eq!(QE_TABLE[22].nlps, 20);
-        assert_eq!(QE_TABLE[22].switch, false);
+        assert!(!QE_TABLE[22].switch);
 
         assert_eq!(QE_TABLE[46].qe, 0x5601);
         assert_eq!(QE_TABLE[46].nmps, 46);
         assert_eq!(QE_TABLE[46].nlps, 46);
-        assert_eq!(QE_TABLE[46].switch, false);
+        assert!(!QE_TABLE[46].switch);
     }
 
     #[test]
@@ -726,8 +832,8 @@ mod tests {
 
         let mut decoded_sequences = vec![Vec::new(); 4];
         for _ in 0..8 {
-            for cx in 0..4 {
-                decoded_sequences[cx].push(decoder.decode(cx));
+            for (cx, decoded) in decoded_sequences.iter_mut().enumerate() {
+                decoded.push(decoder.decode(cx));
             }
         }
 
@@ -945,4 +1051,48 @@ mod tests {
         }
         assert_eq!(exp_bits, decoded);
     }
+
+    #[test]
+    fn test_decode_stuffed_byte() {
+        // 64 decisions in context 0, coded as a segment where 0xFF is followed
+        // by the stuffed byte 0x7F, which holds 7 bits of data
+        let compressed = [0x02, 0x20, 0xCA, 0xAD, 0xFF, 0x7F, 0xF5, 0x9E, 0xA5, 0x6B];
+        let decisions: u64 = 0x9E1B_9D8C_D7AC_B978;
+
+        let mut decoder = standard_decoder(&compressed);
+        for i in 0..64 {
+            let expected = (decisions >> (63 - i)) as u8 & 1;
+            assert_eq!(decoder.decode(0), expected, "decision {}", i);
+        }
+    }
+
+    #[test]
+    fn test_decode_stuffed_bytes() {
+        // Skewed pseudo-random decisions, so that the encoder output contains
+        // 0xFF bytes followed by stuffed bytes
+        let mut seed: u32 = 1;
+        let mut decisions = Vec::new();
+        for _ in 0..4000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let cx = (seed >> 16) as usize % NUM_CONTEXTS;
+            let d = ((seed >> 8) & 7 == 0) as u8;
+            decisions.push((cx, d));
+        }
+
+        let mut encoder = MqEncoder::new(NUM_CONTEXTS);
+        encoder.init();
+        encoder.reset_contexts();
+        for &(cx, d) in &decisions {
+            encoder.encode(cx, d);
+        }
+        let compressed = encoder.flush();
+        assert!(compressed.windows(2).any(|w| w[0] == 0xFF && w[1] <= 0x8F));
+
+        let mut decoder = standard_decoder(&compressed);
+        for &(cx, d) in &decisions {
+            assert_eq!(decoder.decode_bit(cx), d);
+        }
+        assert_eq!(decoder.bytes_consumed(), compressed.len());
+        assert!(!decoder.is_exhausted());
+    }
 }
@@ -0,0 +1,793 @@
+//! Decoding of the image from the packets of a codestream.
+//!
+//! This is the pipeline of Figure 1 of ITU-T T.800(V4) | ISO/IEC
+//! 15444-1:2024, run backwards: packets are read (Annex B), code-blocks
+//! decoded (Annex D), coefficients reconstructed (Annex E), the inverse
+//! wavelet transformation applied (Annex F), and the component
+//! transformation and the DC level shift undone (Annex G).
+
+use std::convert::TryFrom;
+use std::error;
+use std::io;
+#[cfg(feature = "stats")]
+use std::time::Instant;
+
+use jp2::EnumeratedColourSpaces;
+
+use super::{
+    code_block::{decode_code_block_with, CODE_BLOCK_STYLE_BYPASS, MAX_MAGNITUDE_BIT_PLANES},
+    inverse_component_transformation, num_precincts_1d, CodeBlockDecoder, CodeBlockParameters,
+    CodestreamError, ComponentInfo, ContiguousCodestream, DecodeReport, DecodedComponent,
+    DecodedImage, MultipleComponentTransformation, PacketDecodeError, ProgressionOrder,
+    QuantizationStyle, SubBandType, TileComponentReport, TransformationFilter, MARKER_SYMBOL_COD,
+    MARKER_SYMBOL_POC, MARKER_SYMBOL_PPM, MARKER_SYMBOL_QCD, MARKER_SYMBOL_RGN, MARKER_SYMBOL_SIZ,
+    MARKER_SYMBOL_SOT,
+};
+use crate::dwt::{inverse_dwt_53, resolution_bounds, subband_bounds, Band};
+use crate::packet::{Precinct, PrecinctSubBand};
+#[cfg(feature = "stats")]
+use crate::stats::DecodeStats;
+
+// EPH marker bit of Scod, Table A.13
+const SCOD_EPH: u8 = 0b0000_0100;
+
+/// Sub-band of a tile-component, with the code-blocks that partition it.
+#[derive(Debug, Clone)]
+pub(crate) struct SubBand {
+    pub(crate) subband: SubBandType,
+    pub(crate) band: Band,
+    /// Code-block exponents, xcb' and ycb'.
+    code_block_exponents: (u8, u8),
+    /// Index of the first code-block in each direction, counted from the
+    /// origin of the sub-band coordinates.
+    first_code_block: (u32, u32),
+    /// Number of code-blocks in each direction.
+    pub(crate) no_code_blocks: (u32, u32),
+    /// Number of magnitude bit-planes, M<sub>b</sub>.
+    pub(crate) mb: u8,
+}
+
+impl SubBand {
+    fn new(subband: SubBandType, band: Band, code_block_exponents: (u8, u8), mb: u8) -> Self {
+        // Code-blocks are anchored at multiples of their size, Section B.7
+        let (xcb, ycb) = code_block_exponents;
+        let partition = |b0: u32, b1: u32, exponent: u8| {
+            if b1 <= b0 {
+                return (0, 0);
+            }
+            let first = b0 >> exponent;
+            (first, ceil_div_pow2(b1, exponent) - first)
+        };
+        let (x, wide) = partition(band.x0, band.x1, xcb);
+        let (y, high) = partition(band.y0, band.y1, ycb);
+        SubBand {
+            subband,
+            band,
+            code_block_exponents,
+            first_code_block: (x, y),
+            no_code_blocks: (wide, high),
+            mb,
+        }
+    }
+
+    /// Bounds of code-block `index`, in raster order within the sub-band,
+    /// clipped to the sub-band.
+    pub(crate) fn code_block_bounds(&self, index: usize) -> (u32, u32, u32, u32) {
+        let (xcb, ycb) = self.code_block_exponents;
+        let x = self.first_code_block.0 + index as u32 % self.no_code_blocks.0;
+        let y = self.first_code_block.1 + index as u32 / self.no_code_blocks.0;
+        let band = &self.band;
+        (
+            (x << xcb).max(band.x0),
+            (y << ycb).max(band.y0),
+            ((x + 1) << xcb).min(band.x1),
+            ((y + 1) << ycb).min(band.y1),
+        )
+    }
+
+    /// Code-blocks of the precinct at column and row `precinct` of a
+    /// partition of the sub-band into precincts of `exponents`, as indices in
+    /// raster order within the sub-band, with the number of code-blocks in
+    /// each direction of the precinct.
+    ///
+    /// Code-blocks are no larger than precincts, Equation B-17, so each
+    /// code-block is in a single precinct.
+    pub(crate) fn precinct_code_blocks(
+        &self,
+        exponents: (u8, u8),
+        precinct: (u32, u32),
+    ) -> ((usize, usize), Vec<usize>) {
+        let (xcb, ycb) = self.code_block_exponents;
+        // Code-blocks of the precinct in one direction, counted from the
+        // first code-block of the sub-band
+        let range = |k: u32, exponent: u8, b0: u32, b1: u32, cb: u8, first: u32| {
+            let p0 = (u64::from(k) << exponent).max(u64::from(b0));
+            let p1 = ((u64::from(k) + 1) << exponent).min(u64::from(b1));
+            if p1 <= p0 {
+                return 0..0;
+            }
+            ((p0 >> cb) as u32 - first)..(p1.div_ceil(1 << cb) as u32 - first)
+        };
+        let band = &self.band;
+        let (x, y) = self.first_code_block;
+        let xs = range(precinct.0, exponents.0, band.x0, band.x1, xcb, x);
+        let ys = range(precinct.1, exponents.1, band.y0, band.y1, ycb, y);
+        if xs.is_empty() || ys.is_empty() {
+            return ((0, 0), Vec::new());
+        }
+        let wide = self.no_code_blocks.0;
+        let size = (xs.len(), ys.len());
+        let indices = ys
+            .flat_map(|y| xs.clone().map(move |x| (y * wide + x) as usize))
+            .collect();
+        (size, indices)
+    }
+}
+
+/// Partition of a resolution level into precincts, Section B.6.
+#[derive(Debug, Clone)]
+pub(crate) struct PrecinctPartition {
+    /// Precinct exponents, PPx and PPy.
+    exponents: (u8, u8),
+    /// Precinct exponents within the sub-bands, Equation B-16.
+    pub(crate) subband_exponents: (u8, u8),
+    /// Index of the first precinct in each direction, counted from the
+    /// origin of the resolution level coordinates.
+    first_precinct: (u32, u32),
+    /// Number of precincts in each direction.
+    no_precincts: (u32, u32),
+}
+
+impl PrecinctPartition {
+    fn new(bounds: &Band, exponents: (u8, u8), r: u8) -> Self {
+        let (ppx, ppy) = exponents;
+        let subband_exponents = if r == 0 {
+            exponents
+        } else {
+            (ppx - 1, ppy - 1)
+        };
+        PrecinctPartition {
+            exponents,
+            subband_exponents,
+            first_precinct: (bounds.x0 >> ppx, bounds.y0 >> ppy),
+            no_precincts: (
+                num_precincts_1d(bounds.x0.into(), bounds.x1.into(), ppx) as u32,
+                num_precincts_1d(bounds.y0.into(), bounds.y1.into(), ppy) as u32,
+            ),
+        }
+    }
+
+    /// Number of precincts, zero for an empty resolution level.
+    pub(crate) fn count(&self) -> usize {
+        let count = u64::from(self.no_precincts.0) * u64::from(self.no_precincts.1);
+        usize::try_from(count).unwrap_or(usize::MAX)
+    }
+
+    /// Column and row of precinct `p`, in raster order within the
+    /// resolution level, counted from the origin.
+    pub(crate) fn precinct(&self, p: usize) -> (u32, u32) {
+        (
+            self.first_precinct.0 + p as u32 % self.no_precincts.0,
+            self.first_precinct.1 + p as u32 / self.no_precincts.0,
+        )
+    }
+}
+
+// ⌈value / 2^exponent⌉
+fn ceil_div_pow2(value: u32, exponent: u8) -> u32 {
+    (u64::from(value).div_ceil(1 << exponent)) as u32
+}
+
+/// Coded data of a code-block, as read from the packets of a tile, with
+/// what is needed to decode it.
+///
+/// The bounds are in the coordinates of the sub-band, Equation B-15, with
+/// the upper left corner (`x0`, `y0`) included and the lower right corner
+/// (`x1`, `y1`) excluded, so the code-block is `parameters.width` by
+/// `parameters.height` coefficients.
+///
+/// `data` is the contribution of every packet read for the code-block,
+/// concatenated in the order of the packets, for `parameters.no_passes`
+/// coding passes. Without code-block coding style options, this is a single
+/// terminated codeword segment that [crate::decode_code_block] takes as it
+/// is. With the selective arithmetic coding bypass of `code_block_style`,
+/// `data` is split into the codeword segments of `segment_lengths`. A
+/// code-block that no packet included has no coding passes and no data, and
+/// all of its coefficients are zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodedCodeBlock {
+    /// Index of the tile, Isot.
+    pub tile_index: u16,
+    /// Index of the component within the image.
+    pub component_index: u16,
+    /// Resolution level, from 0 for the LL sub-band to N<sub>L</sub>.
+    pub resolution_level: u8,
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+    /// Sub-band, size, coding passes, missing bit-planes and M<sub>b</sub>.
+    pub parameters: CodeBlockParameters,
+    /// Quality layer of the first packet that included the code-block, or
+    /// `None` if no packet read included it.
+    pub first_layer: Option<u16>,
+    /// Coded data of the included coding passes.
+    pub data: Vec<u8>,
+    /// Code-block coding style, SPcod or SPcoc, Table A.19.
+    pub code_block_style: u8,
+    /// Length of each codeword segment of `data`.
+    pub segment_lengths: Vec<usize>,
+}
+
+/// Tile-component with its sub-bands and the code-blocks read from its
+/// packets.
+#[derive(Debug, Clone)]
+pub(crate) struct TileComponent {
+    tile_index: u16,
+    component_index: u16,
+    pub(crate) component: ComponentInfo,
+    /// Tile-component bounds, (tcx0, tcy0, tcx1, tcy1).
+    pub(crate) bounds: (u32, u32, u32, u32),
+    pub(crate) levels: u8,
+    /// Code-block coding style, Table A.19.
+    pub(crate) code_block_style: u8,
+    /// Sub-bands of each resolution level, LL alone for the lowest, then HL,
+    /// LH and HH.
+    pub(crate) resolutions: Vec<Vec<SubBand>>,
+    /// Precinct partition of each resolution level.
+    pub(crate) precinct_partitions: Vec<PrecinctPartition>,
+    /// Code-blocks of each resolution level in turn, and of each sub-band in
+    /// turn within it, in raster order within the sub-band.
+    code_blocks: Vec<CodedCodeBlock>,
+}
+
+impl TileComponent {
+    /// Bounds of resolution level `r`, Equation B-14, with zero samples.
+    pub(crate) fn resolution_bounds(&self, r: u8) -> Band {
+        resolution_bounds(self.bounds, self.levels, r)
+    }
+}
+
+/// Read the code-blocks of a codestream with a single tile, see
+/// [ContiguousCodestream::code_blocks].
+pub(crate) fn read_code_blocks<R: io::Read + io::Seek>(
+    codestream: &ContiguousCodestream,
+    reader: &mut R,
+) -> Result<Vec<CodedCodeBlock>, Box<dyn error::Error>> {
+    Ok(read_tile_packets(codestream, reader)?
+        .tile_components()
+        .into_iter()
+        .flat_map(|tile_component| tile_component.code_blocks)
+        .collect())
+}
+
+// Read the packets of the tile of a codestream
+fn read_tile_packets<R: io::Read + io::Seek>(
+    codestream: &ContiguousCodestream,
+    reader: &mut R,
+) -> Result<TilePackets, Box<dyn error::Error>> {
+    let mut tile_packets = tile_packets(codestream)?;
+    let data = codestream.tile_data(reader, tile_packets.tile_components[0].tile_index)?;
+    let mut offset = 0;
+    for index in 0..tile_packets.no_packets() {
+        offset = tile_packets.read_packet(index, &data, offset)?;
+    }
+    Ok(tile_packets)
+}
+
+/// Tile-components of a codestream with the precincts of each resolution
+/// level, and the packets that make them up, as far as they have been read.
+#[derive(Debug)]
+pub(crate) struct TilePackets {
+    /// Tile-component of each component in turn.
+    pub(crate) tile_components: Vec<TileComponent>,
+    /// Precincts of each resolution level of each tile-component, in raster
+    /// order, each with its own tag trees. An empty resolution level has no
+    /// precincts, and so no packets.
+    pub(crate) precincts: Vec<Vec<Vec<Precinct>>>,
+    /// Layer, component, resolution level and precinct of each packet, in
+    /// codestream order.
+    pub(crate) packets: Vec<(u16, u16, u8, usize)>,
+    layers: u16,
+    eph: bool,
+    /// Whether the first three components are transformed by the RCT.
+    pub(crate) mct: bool,
+}
+
+impl TilePackets {
+    /// Number of packets of the tile-component.
+    pub(crate) fn no_packets(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Number of quality layers.
+    pub(crate) fn no_layers(&self) -> u16 {
+        self.layers
+    }
+
+    /// Layer of packet `index`.
+    pub(crate) fn layer(&self, index: usize) -> u16 {
+        self.packets[index].0
+    }
+
+    /// Read packet `index` from `data` at `offset`, returning the offset
+    /// following it. The packets must be read in order.
+    pub(crate) fn read_packet(
+        &mut self,
+        index: usize,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<usize, PacketDecodeError> {
+        let (layer, c, r, p) = self.packets[index];
+        let style = self.tile_components[usize::from(c)].code_block_style;
+        self.precincts[usize::from(c)][usize::from(r)][p]
+            .read_packet(data, offset, layer, self.eph, style)
+    }
+
+    /// As [TilePackets::read_packet], but leaving the code-blocks as they were
+    /// if the packet cannot be read.
+    pub(crate) fn try_read_packet(
+        &mut self,
+        index: usize,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<usize, PacketDecodeError> {
+        let (layer, c, r, p) = self.packets[index];
+        let style = self.tile_components[usize::from(c)].code_block_style;
+        let precinct = &mut self.precincts[usize::from(c)][usize::from(r)][p];
+        let mut updated = precinct.clone();
+        let offset = updated.read_packet(data, offset, layer, self.eph, style)?;
+        *precinct = updated;
+        Ok(offset)
+    }
+
+    /// Tile-components with the code-blocks of the packets read so far.
+    pub(crate) fn tile_components(&self) -> Vec<TileComponent> {
+        self.tile_components
+            .iter()
+            .zip(&self.precincts)
+            .map(|(tile_component, precincts)| with_code_blocks(tile_component, precincts))
+            .collect()
+    }
+}
+
+// Tile-component with the code-blocks of `precincts`, the precincts of each
+// of its resolution levels
+fn with_code_blocks(tile_component: &TileComponent, precincts: &[Vec<Precinct>]) -> TileComponent {
+    let mut tile_component = tile_component.clone();
+    let resolutions = tile_component
+        .resolutions
+        .iter()
+        .zip(&tile_component.precinct_partitions)
+        .zip(precincts);
+    for (r, ((subbands, partition), precincts)) in (0..).zip(resolutions) {
+        for (s, subband) in subbands.iter().enumerate() {
+            // Code-blocks of the sub-band gathered from its precincts, in
+            // raster order within the sub-band
+            let (wide, high) = subband.no_code_blocks;
+            let mut code_blocks = vec![None; (wide * high) as usize];
+            for (p, precinct) in precincts.iter().enumerate() {
+                let (_, indices) = subband
+                    .precinct_code_blocks(partition.subband_exponents, partition.precinct(p));
+                for (index, code_block) in
+                    indices.into_iter().zip(&precinct.subbands[s].code_blocks)
+                {
+                    code_blocks[index] = Some(code_block);
+                }
+            }
+            for (index, code_block) in code_blocks.into_iter().enumerate() {
+                let code_block = match code_block {
+                    Some(code_block) => code_block,
+                    None => continue,
+                };
+                let (x0, y0, x1, y1) = subband.code_block_bounds(index);
+                tile_component.code_blocks.push(CodedCodeBlock {
+                    tile_index: tile_component.tile_index,
+                    component_index: tile_component.component_index,
+                    resolution_level: r,
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    parameters: CodeBlockParameters {
+                        subband: subband.subband,
+                        width: x1 - x0,
+                        height: y1 - y0,
+                        no_passes: code_block.no_passes,
+                        zero_bit_planes: code_block.zero_bit_planes,
+                        mb: subband.mb,
+                    },
+                    first_layer: code_block.first_layer,
+                    data: code_block.data.clone(),
+                    code_block_style: tile_component.code_block_style,
+                    segment_lengths: code_block.segment_lengths.clone(),
+                });
+            }
+        }
+    }
+    tile_component
+}
+
+/// Check that the codestream only uses what can be decoded, and lay out its
+/// tile-components, before any packet is read.
+pub(crate) fn tile_packets(
+    codestream: &ContiguousCodestream,
+) -> Result<TilePackets, Box<dyn error::Error>> {
+    let header = codestream.header();
+    let siz = header.image_and_tile_size_marker_segment();
+    let unsupported = |marker, offset| CodestreamError::UnsupportedFeature { marker, offset };
+
+    let components: Vec<_> = siz.components().collect();
+    if siz.num_tiles() != 1
+        || components.iter().any(|component| {
+            component.horizontal_separation() != 1
+                || component.vertical_separation() != 1
+                || component.precision() > 31
+        })
+    {
+        return Err(unsupported(MARKER_SYMBOL_SIZ, siz.offset()).into());
+    }
+    let tile = codestream.tile(0).ok_or(CodestreamError::MarkerMissing {
+        marker: MARKER_SYMBOL_SOT,
+    })?;
+
+    // The RCT transforms the first three components, Section G.2
+    let cod = tile.coding_style_marker_segment();
+    let mct = match cod.multiple_component_transformation() {
+        MultipleComponentTransformation::None => false,
+        MultipleComponentTransformation::Multiple if components.len() >= 3 => true,
+        _ => return Err(unsupported(MARKER_SYMBOL_COD, cod.offset()).into()),
+    };
+    if !header.region_of_interest_segments().is_empty() || tile.has_regions() {
+        return Err(unsupported(MARKER_SYMBOL_RGN, 0).into());
+    }
+    if header.progression_order_change_segment().is_some() {
+        return Err(unsupported(MARKER_SYMBOL_POC, 0).into());
+    }
+    for tile_part in tile.tile_parts() {
+        let sot = tile_part.start_of_tile_segment();
+        if codestream
+            .packed_packet_headers(sot.tile_index(), sot.tile_part_index())?
+            .is_some()
+        {
+            return Err(unsupported(MARKER_SYMBOL_PPM, 0).into());
+        }
+    }
+
+    // Tile-component bounds, the same as the tile without sub-sampling
+    let bounds = (
+        siz.tile_x_upper(0)?,
+        siz.tile_y_upper(0)?,
+        siz.tile_x_lower(0)?,
+        siz.tile_y_lower(0)?,
+    );
+    let layers = cod.no_layers();
+    // Samples, precinct sub-bands, code-blocks and packets so far, charged to
+    // the allocation limit before each resolution level is laid out
+    let (mut samples, mut precinct_subbands, mut code_blocks, mut no_packets) =
+        (0u64, 0u64, 0u64, 0u64);
+    let mut tile_components = Vec::new();
+    let mut precincts = Vec::new();
+    for (c, component) in (0..).zip(components) {
+        let parameters = tile.coding_style_parameters(c);
+        // Of the code-block coding style options of Table A.19, only the
+        // selective arithmetic coding bypass is supported
+        if parameters.transformation() != TransformationFilter::Reversible
+            || parameters.code_block_style() & !CODE_BLOCK_STYLE_BYPASS != 0
+        {
+            return Err(unsupported(MARKER_SYMBOL_COD, cod.offset()).into());
+        }
+        let quantization = tile.quantization_info(c);
+        if quantization.style != QuantizationStyle::NoQuantization {
+            return Err(unsupported(MARKER_SYMBOL_QCD, 0).into());
+        }
+
+        let mut tile_component = TileComponent {
+            tile_index: tile.index(),
+            component_index: c,
+            component,
+            bounds,
+            levels: parameters.no_decomposition_levels(),
+            code_block_style: parameters.code_block_style(),
+            resolutions: Vec::new(),
+            precinct_partitions: Vec::new(),
+            code_blocks: Vec::new(),
+        };
+        let levels = tile_component.levels;
+        let (tcx0, tcy0, tcx1, tcy1) = bounds;
+        samples += u64::from(tcx1 - tcx0) * u64::from(tcy1 - tcy0);
+        // Code-blocks of at most 2^10 in each direction and 2^12
+        // coefficients, Table A.18
+        let (xcb, ycb) = parameters.code_block_exponents();
+        if xcb > 10 || ycb > 10 || xcb + ycb > 12 {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_COD,
+                offset: cod.offset(),
+            }
+            .into());
+        }
+
+        // One precinct size for each resolution level, with PPx and PPy only
+        // 0 for the lowest, Section B.6
+        let precinct_sizes = parameters.precinct_sizes().unwrap_or_default();
+        if precinct_sizes.len() <= usize::from(levels)
+            || precinct_sizes.iter().skip(1).any(|precinct_size| {
+                precinct_size.width_exponent() == 0 || precinct_size.height_exponent() == 0
+            })
+        {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_COD,
+                offset: cod.offset(),
+            }
+            .into());
+        }
+
+        // Sub-bands of each resolution level, from Equation B-15, and the
+        // precincts that partition it
+        let mut resolution_precincts: Vec<Vec<Precinct>> = Vec::new();
+        for (r, precinct_size) in (0..=levels).zip(&precinct_sizes) {
+            let bounds = tile_component.resolution_bounds(r);
+            let partition = PrecinctPartition::new(
+                &bounds,
+                (
+                    precinct_size.width_exponent(),
+                    precinct_size.height_exponent(),
+                ),
+                r,
+            );
+            // Code-blocks are limited to the size of the precincts, Equation
+            // B-17
+            let (ppx, ppy) = partition.subband_exponents;
+            let code_block_exponents = (xcb.min(ppx), ycb.min(ppy));
+
+            // Coefficients of more magnitude bit-planes than an i32 holds
+            // are not supported
+            let mb = |subband| -> Result<u8, Box<dyn error::Error>> {
+                let mb = quantization.magnitude_bit_planes(r, subband)?;
+                if mb > MAX_MAGNITUDE_BIT_PLANES {
+                    return Err(unsupported(MARKER_SYMBOL_QCD, 0).into());
+                }
+                Ok(mb)
+            };
+            let subbands = if r == 0 {
+                vec![SubBand::new(
+                    SubBandType::LL,
+                    bounds,
+                    code_block_exponents,
+                    mb(SubBandType::LL)?,
+                )]
+            } else {
+                let [hl, lh, hh] = subband_bounds(&bounds);
+                vec![
+                    SubBand::new(
+                        SubBandType::HL,
+                        hl,
+                        code_block_exponents,
+                        mb(SubBandType::HL)?,
+                    ),
+                    SubBand::new(
+                        SubBandType::LH,
+                        lh,
+                        code_block_exponents,
+                        mb(SubBandType::LH)?,
+                    ),
+                    SubBand::new(
+                        SubBandType::HH,
+                        hh,
+                        code_block_exponents,
+                        mb(SubBandType::HH)?,
+                    ),
+                ]
+            };
+            let count = partition.count() as u64;
+            precinct_subbands = precinct_subbands.saturating_add(count * subbands.len() as u64);
+            for subband in &subbands {
+                let (wide, high) = subband.no_code_blocks;
+                code_blocks = code_blocks.saturating_add(u64::from(wide) * u64::from(high));
+            }
+            no_packets = no_packets.saturating_add(count * u64::from(layers));
+            codestream.limits.check_tile_allocation(
+                samples,
+                precinct_subbands,
+                code_blocks,
+                no_packets,
+            )?;
+            resolution_precincts.push(
+                (0..partition.count())
+                    .map(|p| Precinct {
+                        subbands: subbands
+                            .iter()
+                            .map(|subband| {
+                                let ((wide, high), _) = subband.precinct_code_blocks(
+                                    partition.subband_exponents,
+                                    partition.precinct(p),
+                                );
+                                PrecinctSubBand::new(wide, high)
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            );
+            tile_component.resolutions.push(subbands);
+            tile_component.precinct_partitions.push(partition);
+        }
+        tile_components.push(tile_component);
+        precincts.push(resolution_precincts);
+    }
+
+    // Packets in the progression order, Section B.12.1.1 to B.12.1.5. RPCL,
+    // PCRL and CPRL visit the precincts by the position of their upper left
+    // corner on the reference grid, clipped to the tile-component.
+    let mut packets: Vec<(u16, u16, u8, usize)> = Vec::new();
+    for (c, tile_component) in (0..).zip(&tile_components) {
+        for (r, partition) in (0..).zip(&tile_component.precinct_partitions) {
+            for p in 0..partition.count() {
+                packets.extend((0..layers).map(|layer| (layer, c, r, p)));
+            }
+        }
+    }
+    let position = |c: u16, r: u8, p: usize| {
+        let tile_component: &TileComponent = &tile_components[usize::from(c)];
+        let partition = &tile_component.precinct_partitions[usize::from(r)];
+        let (kx, ky) = partition.precinct(p);
+        let (ppx, ppy) = partition.exponents;
+        let (tcx0, tcy0, _, _) = tile_component.bounds;
+        let levels = tile_component.levels;
+        (
+            (u64::from(ky) << (ppy + levels - r)).max(tcy0.into()),
+            (u64::from(kx) << (ppx + levels - r)).max(tcx0.into()),
+        )
+    };
+    match cod.progression_order() {
+        ProgressionOrder::LRLCPP => packets.sort_by_key(|&(layer, c, r, p)| (layer, r, c, p)),
+        ProgressionOrder::RLLCPP => packets.sort_by_key(|&(layer, c, r, p)| (r, layer, c, p)),
+        ProgressionOrder::RLPCLP => {
+            packets.sort_by_key(|&(layer, c, r, p)| (r, position(c, r, p), c, layer))
+        }
+        ProgressionOrder::PCRLLP => {
+            packets.sort_by_key(|&(layer, c, r, p)| (position(c, r, p), c, r, layer))
+        }
+        ProgressionOrder::CPRLLP => {
+            packets.sort_by_key(|&(layer, c, r, p)| (c, position(c, r, p), r, layer))
+        }
+        ProgressionOrder::Reserved { .. } => {}
+    }
+    Ok(TilePackets {
+        tile_components,
+        precincts,
+        packets,
+        layers,
+        eph: cod.coding_style() & SCOD_EPH != 0,
+        mct,
+    })
+}
+
+/// Decode the image of a codestream with a single tile, see
+/// [ContiguousCodestream::decode_image].
+pub(crate) fn decode_image<R: io::Read + io::Seek>(
+    codestream: &ContiguousCodestream,
+    reader: &mut R,
+) -> Result<DecodedImage, Box<dyn error::Error>> {
+    let tile_packets = read_tile_packets(codestream, reader)?;
+    reconstruct_image(
+        tile_packets.tile_components(),
+        tile_packets.mct,
+        codestream.report().clone(),
+    )
+}
+
+/// Decode the code-blocks of each tile-component and reconstruct its
+/// samples, undoing the RCT of the first three if `mct` is set, as an image
+/// with `decode_report` and the report of each tile-component.
+pub(crate) fn reconstruct_image(
+    mut tile_components: Vec<TileComponent>,
+    mct: bool,
+    mut decode_report: DecodeReport,
+) -> Result<DecodedImage, Box<dyn error::Error>> {
+    let mut decoder = CodeBlockDecoder::new(0, 0, SubBandType::LL, 0, 0)?;
+    #[cfg(feature = "stats")]
+    let mut stats = DecodeStats::new();
+    let mut samples = Vec::new();
+    for tile_component in &mut tile_components {
+        let levels = tile_component.levels;
+        let mut report = TileComponentReport::new(
+            tile_component.tile_index,
+            tile_component.component_index,
+            levels + 1,
+        );
+        report.resolution_levels_decoded = levels + 1;
+        for code_block in &tile_component.code_blocks {
+            let parameters = &code_block.parameters;
+            #[cfg(feature = "stats")]
+            let start = Instant::now();
+            // Without quantization, the coefficients are reconstructed as
+            // they are decoded
+            let coefficients = decode_code_block_with(
+                &mut decoder,
+                parameters,
+                code_block.code_block_style,
+                &code_block.data,
+                &code_block.segment_lengths,
+            )?;
+            #[cfg(feature = "stats")]
+            stats.add_subband_time(parameters.subband, start.elapsed());
+            report.record_code_block(parameters);
+
+            let subbands =
+                &mut tile_component.resolutions[usize::from(code_block.resolution_level)];
+            let band = match parameters.subband {
+                SubBandType::LL | SubBandType::HL => &mut subbands[0].band,
+                SubBandType::LH => &mut subbands[1].band,
+                SubBandType::HH => &mut subbands[2].band,
+            };
+            let width = band.width();
+            let rows = coefficients.chunks_exact(parameters.width as usize);
+            for (y, row) in (code_block.y0..code_block.y1).zip(rows) {
+                let start = (y - band.y0) as usize * width + (code_block.x0 - band.x0) as usize;
+                band.data[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+
+        // Inverse discrete wavelet transformation, from the lowest
+        // resolution. Without decomposition levels, the tile-component is
+        // coded as the LL sub-band alone, which is taken as it is.
+        let resolutions: Vec<Vec<Band>> = std::mem::take(&mut tile_component.resolutions)
+            .into_iter()
+            .map(|subbands| subbands.into_iter().map(|subband| subband.band).collect())
+            .collect();
+        let band = match levels {
+            0 => resolutions.into_iter().flatten().next(),
+            _ => inverse_dwt_53(&resolutions),
+        }
+        .unwrap_or_else(|| tile_component.resolution_bounds(levels));
+        samples.push(band.data);
+        decode_report.push(report);
+    }
+    let transformation = if mct {
+        MultipleComponentTransformation::Multiple
+    } else {
+        MultipleComponentTransformation::None
+    };
+    inverse_component_transformation(
+        transformation,
+        TransformationFilter::Reversible,
+        &mut samples,
+    )?;
+
+    // Inverse DC level shift, Equation G-2, for unsigned samples
+    let components = tile_components
+        .iter()
+        .zip(samples)
+        .map(|(tile_component, samples)| {
+            let component = tile_component.component;
+            let precision = component.precision();
+            let signed = component.values_are_signed();
+            let (min, max, shift) = if signed {
+                (-(1i64 << (precision - 1)), (1i64 << (precision - 1)) - 1, 0)
+            } else {
+                (0, (1i64 << precision) - 1, 1i64 << (precision - 1))
+            };
+            let samples = samples
+                .iter()
+                .map(|&value| (i64::from(value) + shift).clamp(min, max) as i32)
+                .collect();
+            DecodedComponent::new(precision, signed, samples)
+        })
+        .collect();
+
+    // Greyscale for one or two components, and sRGB otherwise
+    let colour_space = match tile_components.len() {
+        1 | 2 => EnumeratedColourSpaces::Greyscale,
+        _ => EnumeratedColourSpaces::sRGB,
+    };
+    let (tcx0, tcy0, tcx1, tcy1) = tile_components[0].bounds;
+    #[cfg(feature = "stats")]
+    {
+        stats.merge(decoder.stats());
+        decode_report.merge_stats(&stats);
+    }
+    Ok(
+        DecodedImage::new(tcx1 - tcx0, tcy1 - tcy0, colour_space, components)
+            .with_report(decode_report),
+    )
+}
@@ -0,0 +1,452 @@
+//! Discrete wavelet transformation, inverse and forward.
+//!
+//! See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Annex F.
+
+/// Coefficients of a sub-band, or samples of a resolution level, in raster
+/// order, with the bounds of the area they cover in their own coordinates.
+///
+/// The upper left corner is (`x0`, `y0`) and the lower right corner, which
+/// is excluded, is (`x1`, `y1`), as in Equations B-14 and B-15.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Band {
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) x1: u32,
+    pub(crate) y1: u32,
+    pub(crate) data: Vec<i32>,
+}
+
+impl Band {
+    /// Band of zero coefficients.
+    pub(crate) fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        let mut band = Band {
+            x0,
+            y0,
+            x1,
+            y1,
+            data: vec![],
+        };
+        band.data = vec![0; band.width() * band.height()];
+        band
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        (self.x1 - self.x0) as usize
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        (self.y1 - self.y0) as usize
+    }
+}
+
+/// Bounds of resolution level `r` of a tile-component with `levels`
+/// decomposition levels and bounds (tcx0, tcy0, tcx1, tcy1), Equation B-14,
+/// with zero samples.
+pub(crate) fn resolution_bounds(tile_component: (u32, u32, u32, u32), levels: u8, r: u8) -> Band {
+    let (tcx0, tcy0, tcx1, tcy1) = tile_component;
+    let scale = levels - r;
+    let ceil_div = |a: u32| (u64::from(a).div_ceil(1 << scale)) as u32;
+    Band::new(
+        ceil_div(tcx0),
+        ceil_div(tcy0),
+        ceil_div(tcx1),
+        ceil_div(tcy1),
+    )
+}
+
+/// Bounds of the HL, LH and HH sub-bands of the decomposition level that a
+/// resolution level is split into, Equation B-15, with zero coefficients.
+///
+/// The LL sub-band is the resolution level below.
+pub(crate) fn subband_bounds(resolution: &Band) -> [Band; 3] {
+    let (u0, u1, v0, v1) = (resolution.x0, resolution.x1, resolution.y0, resolution.y1);
+    let low = |a: u32| a.div_ceil(2);
+    let high = |a: u32| a / 2;
+    [
+        Band::new(high(u0), low(v0), high(u1), low(v1)),
+        Band::new(low(u0), high(v0), low(u1), high(v1)),
+        Band::new(high(u0), high(v0), high(u1), high(v1)),
+    ]
+}
+
+/// Decompose the samples of a tile-component with `levels` decomposition
+/// levels of the reversible 5-3 filter, the FDWT procedure of Section F.4.1.
+///
+/// Returns the sub-bands of each resolution level, from the lowest: the
+/// N<sub>L</sub>LL sub-band alone, then HL, LH and HH for each resolution
+/// level in turn.
+pub(crate) fn forward_dwt_53(samples: &Band, levels: u8) -> Vec<Vec<Band>> {
+    let bounds = (samples.x0, samples.y0, samples.x1, samples.y1);
+    let mut resolutions = vec![Vec::new(); usize::from(levels) + 1];
+    let mut resolution = samples.clone();
+    for r in (1..=levels).rev() {
+        let mut ll = resolution_bounds(bounds, levels, r - 1);
+        let [mut hl, mut lh, mut hh] = subband_bounds(&resolution);
+        forward_53(&resolution, &mut ll, &mut hl, &mut lh, &mut hh);
+        resolutions[usize::from(r)] = vec![hl, lh, hh];
+        resolution = ll;
+    }
+    resolutions[0] = vec![resolution];
+    resolutions
+}
+
+/// Reconstruct the samples of a tile-component from its sub-bands, as
+/// [forward_dwt_53] gives them, the IDWT procedure of Section F.3.1.
+///
+/// The bounds of each resolution level are those of its sub-bands combined,
+/// as each bound of the LL and a high-pass sub-band add up to the bound of
+/// the resolution level in Equation B-15.
+pub(crate) fn inverse_dwt_53(resolutions: &[Vec<Band>]) -> Option<Band> {
+    let mut samples = resolutions.first()?.first()?.clone();
+    for subbands in &resolutions[1..] {
+        if let [hl, lh, hh] = &subbands[..] {
+            let mut resolution = Band::new(
+                samples.x0 + hl.x0,
+                samples.y0 + lh.y0,
+                samples.x1 + hl.x1,
+                samples.y1 + lh.y1,
+            );
+            inverse_53(&mut resolution, &samples, hl, lh, hh);
+            samples = resolution;
+        }
+    }
+    Some(samples)
+}
+
+/// Reconstruct the samples of a resolution level from the LL sub-band of the
+/// decomposition level, which is the resolution level below, and its HL, LH
+/// and HH sub-bands, with the reversible 5-3 filter.
+///
+/// `resolution` gives the bounds of the resolution level, and the data is
+/// overwritten. This is the 2D_SR procedure of Section F.3.2.
+pub(crate) fn inverse_53(resolution: &mut Band, ll: &Band, hl: &Band, lh: &Band, hh: &Band) {
+    interleave(resolution, ll, hl, lh, hh);
+
+    // HOR_SR, then VER_SR
+    let width = resolution.width();
+    if width > 0 {
+        for row in resolution.data.chunks_exact_mut(width) {
+            inverse_53_1d(row, resolution.x0);
+        }
+    }
+    let mut column = vec![0; resolution.height()];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = resolution.data[y * width + x];
+        }
+        inverse_53_1d(&mut column, resolution.y0);
+        for (y, value) in column.iter().enumerate() {
+            resolution.data[y * width + x] = *value;
+        }
+    }
+}
+
+/// Place the coefficients of each sub-band at the even or odd columns and
+/// rows of the resolution level, the 2D_INTERLEAVE procedure of Section
+/// F.3.3.
+fn interleave(resolution: &mut Band, ll: &Band, hl: &Band, lh: &Band, hh: &Band) {
+    let width = resolution.width();
+    for (band, x_offset, y_offset) in [(ll, 0, 0), (hl, 1, 0), (lh, 0, 1), (hh, 1, 1)] {
+        let band_width = band.width();
+        if band_width == 0 {
+            continue;
+        }
+        for (v, row) in (band.y0..).zip(band.data.chunks_exact(band_width)) {
+            let y = (2 * v + y_offset - resolution.y0) as usize;
+            for (u, value) in (band.x0..).zip(row) {
+                let x = (2 * u + x_offset - resolution.x0) as usize;
+                resolution.data[y * width + x] = *value;
+            }
+        }
+    }
+}
+
+/// Reconstruct a row or column of interleaved coefficients in place, with
+/// the reversible 5-3 filter of Equation F-5, where `i0` is the coordinate of
+/// the first value.
+///
+/// The signal is extended symmetrically at each end, as in Section F.3.7.
+/// This is the 1D_SR procedure of Section F.3.6.
+fn inverse_53_1d(values: &mut [i32], i0: u32) {
+    let n = values.len();
+    if n == 1 {
+        if i0 % 2 == 1 {
+            values[0] /= 2;
+        }
+        return;
+    }
+    // Periodic symmetric extension, only ever needed one value past the end
+    let extend = |i: isize| -> usize {
+        if i < 0 {
+            (-i) as usize
+        } else if i as usize >= n {
+            2 * (n - 1) - i as usize
+        } else {
+            i as usize
+        }
+    };
+    // Index of the first value at an even, or odd, coordinate
+    let first_even = (i0 % 2) as usize;
+    let first_odd = 1 - first_even;
+
+    for i in (first_even..n).step_by(2) {
+        let i = i as isize;
+        values[i as usize] -= (values[extend(i - 1)] + values[extend(i + 1)] + 2) >> 2;
+    }
+    for i in (first_odd..n).step_by(2) {
+        let i = i as isize;
+        values[i as usize] += (values[extend(i - 1)] + values[extend(i + 1)]) >> 1;
+    }
+}
+
+/// Decompose the samples of a resolution level into the LL sub-band of the
+/// decomposition level, which is the resolution level below, and its HL, LH
+/// and HH sub-bands, with the reversible 5-3 filter. This is the inverse of
+/// [inverse_53].
+///
+/// The sub-bands give the bounds, and their data is overwritten. This is the
+/// 2D_SD procedure of Section F.4.2.
+pub(crate) fn forward_53(
+    resolution: &Band,
+    ll: &mut Band,
+    hl: &mut Band,
+    lh: &mut Band,
+    hh: &mut Band,
+) {
+    let mut samples = resolution.clone();
+
+    // VER_SD, then HOR_SD
+    let width = samples.width();
+    let mut column = vec![0; samples.height()];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = samples.data[y * width + x];
+        }
+        forward_53_1d(&mut column, samples.y0);
+        for (y, value) in column.iter().enumerate() {
+            samples.data[y * width + x] = *value;
+        }
+    }
+    if width > 0 {
+        for row in samples.data.chunks_exact_mut(width) {
+            forward_53_1d(row, samples.x0);
+        }
+    }
+
+    deinterleave(&samples, ll, hl, lh, hh);
+}
+
+/// Take the coefficients of each sub-band from the even or odd columns and
+/// rows of the resolution level, the 2D_DEINTERLEAVE procedure of Section
+/// F.4.5.
+fn deinterleave(resolution: &Band, ll: &mut Band, hl: &mut Band, lh: &mut Band, hh: &mut Band) {
+    let width = resolution.width();
+    for (band, x_offset, y_offset) in [(ll, 0, 0), (hl, 1, 0), (lh, 0, 1), (hh, 1, 1)] {
+        let band_width = band.width();
+        if band_width == 0 {
+            continue;
+        }
+        for (v, row) in (band.y0..).zip(band.data.chunks_exact_mut(band_width)) {
+            let y = (2 * v + y_offset - resolution.y0) as usize;
+            for (u, value) in (band.x0..).zip(row) {
+                let x = (2 * u + x_offset - resolution.x0) as usize;
+                *value = resolution.data[y * width + x];
+            }
+        }
+    }
+}
+
+/// Decompose a row or column of samples in place into interleaved
+/// coefficients with the reversible 5-3 filter of Equation F-9, where `i0` is
+/// the coordinate of the first value. This is the inverse of
+/// [inverse_53_1d].
+///
+/// The signal is extended symmetrically at each end, as in Section F.4.7.
+/// This is the 1D_SD procedure of Section F.4.6.
+fn forward_53_1d(values: &mut [i32], i0: u32) {
+    let n = values.len();
+    if n == 1 {
+        if i0 % 2 == 1 {
+            values[0] *= 2;
+        }
+        return;
+    }
+    // Periodic symmetric extension, only ever needed one value past the end
+    let extend = |i: isize| -> usize {
+        if i < 0 {
+            (-i) as usize
+        } else if i as usize >= n {
+            2 * (n - 1) - i as usize
+        } else {
+            i as usize
+        }
+    };
+    // Index of the first value at an even, or odd, coordinate
+    let first_even = (i0 % 2) as usize;
+    let first_odd = 1 - first_even;
+
+    for i in (first_odd..n).step_by(2) {
+        let i = i as isize;
+        values[i as usize] -= (values[extend(i - 1)] + values[extend(i + 1)]) >> 1;
+    }
+    for i in (first_even..n).step_by(2) {
+        let i = i as isize;
+        values[i as usize] += (values[extend(i - 1)] + values[extend(i + 1)] + 2) >> 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn band(x0: u32, y0: u32, x1: u32, y1: u32, data: &[i32]) -> Band {
+        Band {
+            x0,
+            y0,
+            x1,
+            y1,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_inverse_53_1d() {
+        // Low-pass -103, high-pass 10, from samples -108 and -98
+        let mut values = [-103, 10];
+        inverse_53_1d(&mut values, 0);
+        assert_eq!(values, [-108, -98]);
+
+        // Starting at an odd coordinate, the first value is high-pass
+        let mut values = [10, -103];
+        inverse_53_1d(&mut values, 1);
+        assert_eq!(values, [-98, -108]);
+
+        // Constant signal
+        let mut values = [7, 0, 7, 0, 7];
+        inverse_53_1d(&mut values, 0);
+        assert_eq!(values, [7; 5]);
+
+        // A single value at an odd coordinate was doubled
+        let mut values = [-6];
+        inverse_53_1d(&mut values, 3);
+        assert_eq!(values, [-3]);
+        let mut values = [-6];
+        inverse_53_1d(&mut values, 2);
+        assert_eq!(values, [-6]);
+    }
+
+    #[test]
+    fn test_forward_53_1d() {
+        // The values of test_inverse_53_1d, decomposed
+        let mut values = [-108, -98];
+        forward_53_1d(&mut values, 0);
+        assert_eq!(values, [-103, 10]);
+
+        let mut values = [-98, -108];
+        forward_53_1d(&mut values, 1);
+        assert_eq!(values, [10, -103]);
+
+        let mut values = [7; 5];
+        forward_53_1d(&mut values, 0);
+        assert_eq!(values, [7, 0, 7, 0, 7]);
+
+        let mut values = [-3];
+        forward_53_1d(&mut values, 3);
+        assert_eq!(values, [-6]);
+    }
+
+    #[test]
+    fn test_forward_53() {
+        // The 2x2 image of test_inverse_53
+        let resolution = band(0, 0, 2, 2, &[-118, -108, -98, -88]);
+        let mut subbands = [
+            Band::new(0, 0, 1, 1),
+            Band::new(0, 0, 1, 1),
+            Band::new(0, 0, 1, 1),
+            Band::new(0, 0, 1, 1),
+        ];
+        let [ll, hl, lh, hh] = &mut subbands;
+        forward_53(&resolution, ll, hl, lh, hh);
+        assert_eq!(
+            subbands.map(|band| band.data),
+            [vec![-103], vec![10], vec![20], vec![0]]
+        );
+    }
+
+    #[test]
+    fn test_inverse_53() {
+        // One level of a 2x2 image, with samples 10, 20, 30 and 40 after the
+        // DC level shift of -128
+        let mut resolution = Band::new(0, 0, 2, 2);
+        inverse_53(
+            &mut resolution,
+            &band(0, 0, 1, 1, &[-103]),
+            &band(0, 0, 1, 1, &[10]),
+            &band(0, 0, 1, 1, &[20]),
+            &band(0, 0, 1, 1, &[0]),
+        );
+        assert_eq!(resolution.data, vec![-118, -108, -98, -88]);
+
+        // 3x1 image at an odd horizontal offset, where HL has two columns
+        let mut resolution = Band::new(1, 0, 4, 1);
+        inverse_53(
+            &mut resolution,
+            &band(1, 0, 2, 1, &[5]),
+            &band(0, 0, 2, 1, &[0, 0]),
+            &band(1, 0, 2, 0, &[]),
+            &band(0, 0, 2, 0, &[]),
+        );
+        assert_eq!(resolution.data, vec![5, 5, 5]);
+    }
+
+    #[test]
+    fn test_forward_dwt_53() {
+        // A constant 5x3 image has only low-pass coefficients, in a 2x1 LL
+        // sub-band after two levels
+        let samples = band(0, 0, 5, 3, &[7; 15]);
+        let resolutions = forward_dwt_53(&samples, 2);
+        assert_eq!(resolutions.len(), 3);
+        assert_eq!(resolutions[0], vec![band(0, 0, 2, 1, &[7, 7])]);
+        assert_eq!(
+            resolutions[1],
+            vec![
+                band(0, 0, 1, 1, &[0]),
+                band(0, 0, 2, 1, &[0, 0]),
+                band(0, 0, 1, 1, &[0]),
+            ]
+        );
+        assert_eq!(resolutions[2][0], band(0, 0, 2, 2, &[0; 4]));
+        assert_eq!(inverse_dwt_53(&resolutions), Some(samples.clone()));
+
+        // No decomposition levels leaves the samples as the LL sub-band
+        assert_eq!(forward_dwt_53(&samples, 0), vec![vec![samples]]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_dwt_53_round_trip(
+            (x0, y0, width, height, data) in (0..5u32, 0..5u32, 1..24u32, 1..24u32)
+                .prop_flat_map(|(x0, y0, width, height)| {
+                    let data = prop::collection::vec(-4096..4096i32, (width * height) as usize);
+                    (Just(x0), Just(y0), Just(width), Just(height), data)
+                }),
+            levels in 0..6u8,
+        ) {
+            // The forward transformation is undone bit for bit, at any offset
+            let bounds = (x0, y0, x0 + width, y0 + height);
+            let samples = band(x0, y0, x0 + width, y0 + height, &data);
+            let resolutions = forward_dwt_53(&samples, levels);
+            prop_assert_eq!(resolutions.len(), usize::from(levels) + 1);
+            for (r, subbands) in (0..).zip(&resolutions[1..]) {
+                let resolution = resolution_bounds(bounds, levels, r + 1);
+                prop_assert_eq!(
+                    subbands.iter().map(|band| band.data.len()).collect::<Vec<_>>(),
+                    subband_bounds(&resolution).iter().map(|band| band.data.len()).collect::<Vec<_>>()
+                );
+            }
+            prop_assert_eq!(inverse_dwt_53(&resolutions), Some(samples));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Encoding of an image to a codestream.
+//!
+//! This is the pipeline of Figure 1 of ITU-T T.800(V4) | ISO/IEC
+//! 15444-1:2024, for the cases that [crate::decode] supports: the DC level
+//! shift (Annex G), the forward wavelet transformation (Annex F), code-block
+//! coding (Annex D) and packet writing (Annex B). With the reversible filter,
+//! there is no quantization (Annex E).
+
+use std::convert::TryFrom;
+use std::error;
+use std::io::Cursor;
+
+use super::{
+    builder::write_segment, code_block::encode_code_block, CodestreamError,
+    CodestreamHeaderBuilder, ContiguousCodestream, DecodeLimits, MARKER_SYMBOL_EOC,
+    MARKER_SYMBOL_SOD, MARKER_SYMBOL_SOT,
+};
+use crate::decode::{tile_packets, SubBand};
+use crate::dwt::forward_dwt_53;
+use crate::packet::{PrecinctCodeBlock, PrecinctSubBandWriter, PrecinctWriter};
+
+// Offset of Psot within the SOT marker segment, Figure A.16
+const PSOT_OFFSET: usize = 6;
+
+/// Encode the samples of a greyscale image, in raster order, as a codestream
+/// with the main header of `parameters`.
+///
+/// This handles a subset of the images that
+/// [ContiguousCodestream::decode_image] decodes: a single component in a
+/// single tile, with the reversible filter
+/// and no code-block coding style options, with any precinct sizes and in
+/// any progression order. Every coding pass of every code-block is included in
+/// the first quality layer, so the image is lossless from the first layer,
+/// and the packets of any further layers are empty.
+///
+/// Each sample shall be within the range of the component precision and
+/// signedness of `parameters`.
+pub fn encode_image(
+    samples: &[i32],
+    parameters: &CodestreamHeaderBuilder,
+) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    // Main header and tile-part header, with Psot filled in once the length
+    // of the packets is known
+    let mut codestream = Vec::new();
+    parameters.write_to(&mut codestream)?;
+    let sot_offset = codestream.len();
+    write_segment(
+        &mut codestream,
+        &MARKER_SYMBOL_SOT,
+        &[0, 0, 0, 0, 0, 0, 0, 1],
+    )?;
+    codestream.extend_from_slice(&MARKER_SYMBOL_SOD.0);
+
+    // The headers are read back to lay out the tile-component as a decoder
+    // would, which also checks that the image can be decoded
+    let header = ContiguousCodestream::decode_first_tile_part_header(
+        &mut Cursor::new(&codestream),
+        DecodeLimits::default(),
+    )?;
+    let mut tile_packets = tile_packets(&header)?;
+    let tile_component = match &mut tile_packets.tile_components[..] {
+        [tile_component] if tile_component.code_block_style == 0 => tile_component,
+        _ => return Err(CodestreamError::InvalidParameter { name: "components" }.into()),
+    };
+
+    // DC level shift, Equation G-1, for unsigned samples
+    let component = tile_component.component;
+    let precision = component.precision();
+    let (min, max, shift) = if component.values_are_signed() {
+        (-(1i64 << (precision - 1)), (1i64 << (precision - 1)) - 1, 0)
+    } else {
+        (0, (1i64 << precision) - 1, 1i64 << (precision - 1))
+    };
+    let levels = tile_component.levels;
+    let mut resolution = tile_component.resolution_bounds(levels);
+    if samples.len() != resolution.data.len()
+        || samples
+            .iter()
+            .any(|&sample| !(min..=max).contains(&i64::from(sample)))
+    {
+        return Err(CodestreamError::InvalidParameter { name: "samples" }.into());
+    }
+    for (value, &sample) in resolution.data.iter_mut().zip(samples) {
+        *value = (i64::from(sample) - shift) as i32;
+    }
+
+    // Forward discrete wavelet transformation, into the sub-bands laid out
+    // for the code-blocks
+    let resolutions = forward_dwt_53(&resolution, levels);
+    for (subbands, bands) in tile_component.resolutions.iter_mut().zip(resolutions) {
+        for (subband, band) in subbands.iter_mut().zip(bands) {
+            subband.band = band;
+        }
+    }
+
+    // Code-blocks of each sub-band, grouped into the precincts of each
+    // resolution level, and the packets
+    let mut precincts: Vec<Vec<PrecinctWriter>> = Vec::new();
+    for (subbands, partition) in tile_component
+        .resolutions
+        .iter()
+        .zip(&tile_component.precinct_partitions)
+    {
+        let mut code_blocks = subbands
+            .iter()
+            .map(encode_subband)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut resolution = Vec::new();
+        for p in 0..partition.count() {
+            let mut precinct = PrecinctWriter {
+                subbands: Vec::new(),
+            };
+            for (subband, code_blocks) in subbands.iter().zip(&mut code_blocks) {
+                let ((wide, high), indices) = subband
+                    .precinct_code_blocks(partition.subband_exponents, partition.precinct(p));
+                let code_blocks = indices
+                    .into_iter()
+                    .map(|index| std::mem::take(&mut code_blocks[index]))
+                    .collect();
+                precinct
+                    .subbands
+                    .push(PrecinctSubBandWriter::new(wide, high, code_blocks));
+            }
+            resolution.push(precinct);
+        }
+        precincts.push(resolution);
+    }
+    for &(layer, _, r, p) in &tile_packets.packets {
+        codestream.extend(precincts[usize::from(r)][p].write_packet(layer));
+    }
+
+    // Psot covers the tile-part from its SOT marker, or is 0 if too long
+    let tile_length = u32::try_from(codestream.len() - sot_offset).unwrap_or(0);
+    codestream[sot_offset + PSOT_OFFSET..sot_offset + PSOT_OFFSET + 4]
+        .copy_from_slice(&tile_length.to_be_bytes());
+    codestream.extend_from_slice(&MARKER_SYMBOL_EOC.0);
+    Ok(codestream)
+}
+
+// Encode the code-blocks of a sub-band, in raster order
+fn encode_subband(subband: &SubBand) -> Result<Vec<PrecinctCodeBlock>, CodestreamError> {
+    let (wide, high) = subband.no_code_blocks;
+    let band = &subband.band;
+    let mut code_blocks = Vec::new();
+    for index in 0..(wide * high) as usize {
+        let (x0, y0, x1, y1) = subband.code_block_bounds(index);
+        let mut coefficients = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for y in y0..y1 {
+            let start = (y - band.y0) as usize * band.width() + (x0 - band.x0) as usize;
+            coefficients.extend_from_slice(&band.data[start..start + (x1 - x0) as usize]);
+        }
+        let (parameters, data) =
+            encode_code_block(subband.subband, x1 - x0, y1 - y0, subband.mb, &coefficients)
+                .ok_or(CodestreamError::InvalidParameter { name: "samples" })?;
+        code_blocks.push(PrecinctCodeBlock::new(
+            parameters.zero_bit_planes,
+            parameters.no_passes,
+            data,
+        ));
+    }
+    Ok(code_blocks)
+}
@@ -0,0 +1,571 @@
+//! Reconstructed image samples.
+
+use std::error;
+use std::fmt;
+
+use jp2::{
+    BitDepth, ChannelDefinitionBox, ChannelTypes, ComponentMapType, ComponentMappingBox,
+    EnumeratedColourSpaces, PaletteBox,
+};
+
+use super::DecodeReport;
+
+// Kr and Kb from IEC 61966-2-1 Amendment 1, as used for sYCC
+const SYCC_CR_TO_R: f32 = 1.402;
+const SYCC_CB_TO_G: f32 = 0.344_136;
+const SYCC_CR_TO_G: f32 = 0.714_136;
+const SYCC_CB_TO_B: f32 = 1.772;
+
+/// Error values that may be returned when converting a decoded image.
+#[derive(Debug, PartialEq)]
+pub enum ImageError {
+    /// Colour space cannot be converted to sRGB without colour management
+    UnsupportedColourSpace {
+        colour_space: EnumeratedColourSpaces,
+    },
+    /// Fewer components than the colour space requires
+    ComponentsMissing { expected: usize, actual: usize },
+    /// Component does not have one sample per pixel of the image
+    ComponentSize {
+        component: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// Component precision is outside of 1 to 38 bits
+    InvalidPrecision { component: usize, precision: u8 },
+    /// Sample differs from the expected sample, at column `x` and row `y`
+    SampleMismatch {
+        component: usize,
+        x: u32,
+        y: u32,
+        expected: i32,
+        actual: i32,
+    },
+    /// Channel of a component mapping refers to a component or a palette
+    /// column that is not present
+    ComponentMappingInvalid { channel: usize },
+}
+
+impl error::Error for ImageError {}
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedColourSpace { colour_space } => {
+                write!(
+                    f,
+                    "colour space {colour_space:?} cannot be converted to sRGB"
+                )
+            }
+            Self::ComponentsMissing { expected, actual } => {
+                write!(f, "expected {expected} components, found {actual}")
+            }
+            Self::ComponentSize {
+                component,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "component {component} has {actual} samples, expected {expected}"
+            ),
+            Self::InvalidPrecision {
+                component,
+                precision,
+            } => write!(f, "component {component} has invalid precision {precision}"),
+            Self::SampleMismatch {
+                component,
+                x,
+                y,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "component {component} has sample {actual} at ({x}, {y}), expected {expected}"
+            ),
+            Self::ComponentMappingInvalid { channel } => write!(
+                f,
+                "channel {channel} maps to a component or palette column that is not present"
+            ),
+        }
+    }
+}
+
+/// Reconstructed samples of a single component.
+///
+/// The samples are in raster order, after the inverse DC level shift, so
+/// unsigned components range from 0 to 2<sup>precision</sup> - 1 and signed
+/// components are centred on zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedComponent {
+    precision: u8,
+    signed: bool,
+    samples: Vec<i32>,
+}
+
+impl DecodedComponent {
+    pub fn new(precision: u8, signed: bool, samples: Vec<i32>) -> DecodedComponent {
+        DecodedComponent {
+            precision,
+            signed,
+            samples,
+        }
+    }
+
+    /// Bit depth of the samples, including the sign bit for signed samples.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Whether the samples are signed.
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// Samples in raster order.
+    pub fn samples(&self) -> &[i32] {
+        &self.samples
+    }
+
+    // Sample normalised to 0 to 1, with signed samples offset to be unsigned
+    fn normalised(&self, index: usize) -> f32 {
+        let mut value = i64::from(self.samples[index]);
+        if self.signed {
+            value += 1 << (self.precision - 1);
+        }
+        let max = (1_i64 << self.precision) - 1;
+        value.clamp(0, max) as f32 / max as f32
+    }
+
+    // Chroma sample normalised to -0.5 to 0.5
+    fn normalised_chroma(&self, index: usize) -> f32 {
+        let max = ((1_i64 << self.precision) - 1) as f32;
+        let offset = if self.signed {
+            0.0
+        } else {
+            (1_i64 << (self.precision - 1)) as f32
+        };
+        (self.samples[index] as f32 - offset) / max
+    }
+}
+
+/// Range and mean of the samples of a component.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleStats {
+    pub min: i32,
+    pub max: i32,
+    pub mean: f64,
+}
+
+/// Decoded image.
+///
+/// The components share the dimensions of the image, with any component
+/// subsampling already undone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedImage {
+    width: u32,
+    height: u32,
+    colour_space: EnumeratedColourSpaces,
+    components: Vec<DecodedComponent>,
+    // Component of the opacity of the whole image
+    opacity_component: Option<usize>,
+    report: DecodeReport,
+}
+
+impl DecodedImage {
+    pub fn new(
+        width: u32,
+        height: u32,
+        colour_space: EnumeratedColourSpaces,
+        components: Vec<DecodedComponent>,
+    ) -> DecodedImage {
+        DecodedImage {
+            width,
+            height,
+            colour_space,
+            components,
+            opacity_component: None,
+            report: DecodeReport::default(),
+        }
+    }
+
+    /// Apply the Channel Definition box of the file the image was decoded
+    /// from.
+    ///
+    /// The components are reordered so that the channels of colour image data
+    /// come first, in the order of the colours they are associated with,
+    /// followed by an opacity channel associated with the whole image, which
+    /// is then included in the output of [DecodedImage::to_srgb8]. Any other
+    /// components follow in their original order.
+    ///
+    /// The channel indices are component indices, as for a file without a
+    /// Component Mapping box, or after [DecodedImage::with_palette]. Channels
+    /// for components the image does not have are ignored.
+    pub fn with_channel_definition(mut self, channel_definition: &ChannelDefinitionBox) -> Self {
+        let mut colours: Vec<(u16, usize)> = Vec::new();
+        let mut opacity = None;
+        for channel in channel_definition.channels() {
+            let component = channel.channel_index() as usize;
+            if component >= self.components.len() {
+                continue;
+            }
+            match (channel.channel_type(), channel.channel_association()) {
+                (ChannelTypes::ColourImageData, association @ 1..=0xFFFE) => {
+                    colours.push((association, component));
+                }
+                (ChannelTypes::Opacity, 0) => opacity = Some(component),
+                _ => {}
+            }
+        }
+        colours.sort_by_key(|(association, _)| *association);
+
+        let mut order: Vec<usize> = Vec::with_capacity(self.components.len());
+        let components = colours
+            .into_iter()
+            .map(|(_, component)| component)
+            .chain(opacity)
+            .chain(0..self.components.len());
+        for component in components {
+            if !order.contains(&component) {
+                order.push(component);
+            }
+        }
+        self.opacity_component =
+            opacity.and_then(|opacity| order.iter().position(|&c| c == opacity));
+        let mut components: Vec<Option<DecodedComponent>> =
+            self.components.drain(..).map(Some).collect();
+        self.components = order
+            .into_iter()
+            .filter_map(|component| components[component].take())
+            .collect();
+        self
+    }
+
+    /// Apply the Palette box, and the Component Mapping box, of the file the
+    /// image was decoded from.
+    ///
+    /// Each channel of the component mapping becomes a component, either a
+    /// component of the image as it is, or the palette column for each of
+    /// its samples, with the bit depth of that column. Samples beyond the
+    /// last palette entry use the last entry. Without a Component Mapping
+    /// box, each palette column is applied to the first component.
+    ///
+    /// See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Sections I.5.3.4 and
+    /// I.5.3.5.
+    pub fn with_palette(
+        mut self,
+        palette: &PaletteBox,
+        component_mapping: Option<&ComponentMappingBox>,
+    ) -> Result<Self, ImageError> {
+        let mappings: Vec<(usize, Option<u8>)> = match component_mapping {
+            Some(component_mapping) => component_mapping
+                .component_map()
+                .iter()
+                .enumerate()
+                .map(|(channel, map)| match map.mapping_type() {
+                    ComponentMapType::Direct => Ok((usize::from(map.component()), None)),
+                    ComponentMapType::Palette => {
+                        Ok((usize::from(map.component()), Some(map.palette())))
+                    }
+                    ComponentMapType::Reserved { .. } => {
+                        Err(ImageError::ComponentMappingInvalid { channel })
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            None => (0..palette.num_components())
+                .map(|column| (0, Some(column)))
+                .collect(),
+        };
+        let last_entry = usize::from(palette.num_entries()).saturating_sub(1);
+        let mut channels = Vec::with_capacity(mappings.len());
+        for (channel, (component, column)) in mappings.into_iter().enumerate() {
+            let invalid = || ImageError::ComponentMappingInvalid { channel };
+            let component = self.components.get(component).ok_or_else(invalid)?;
+            let column = match column {
+                Some(column) => column,
+                None => {
+                    channels.push(component.clone());
+                    continue;
+                }
+            };
+            let bit_depth = palette.bit_depth(column).ok_or_else(invalid)?;
+            let (precision, signed) = match *bit_depth {
+                BitDepth::Signed { value } => (value, true),
+                BitDepth::Unsigned { value } | BitDepth::Reserved { value } => (value, false),
+            };
+            let values: Vec<i32> = palette
+                .entries_for_component(column)
+                .map(|value| {
+                    // Sign extension of the bit depth, within 32 bits
+                    let shift = 32 - u32::from(precision.min(32));
+                    if signed {
+                        ((value << shift) as i32) >> shift
+                    } else {
+                        value as i32
+                    }
+                })
+                .collect();
+            if values.is_empty() {
+                return Err(invalid());
+            }
+            let samples = component
+                .samples
+                .iter()
+                .map(|&sample| values[(sample.max(0) as usize).min(last_entry)])
+                .collect();
+            channels.push(DecodedComponent::new(precision, signed, samples));
+        }
+        self.components = channels;
+        self.opacity_component = None;
+        Ok(self)
+    }
+
+    /// Set the colour space of the image, such as from the Colour
+    /// Specification box of the file the image was decoded from.
+    pub fn with_colour_space(mut self, colour_space: EnumeratedColourSpaces) -> DecodedImage {
+        self.colour_space = colour_space;
+        self
+    }
+
+    /// Attach the report of the decode that produced the image.
+    pub fn with_report(mut self, report: DecodeReport) -> DecodedImage {
+        self.report = report;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Colour space the samples are in.
+    pub fn colour_space(&self) -> EnumeratedColourSpaces {
+        self.colour_space
+    }
+
+    /// Components, in codestream order unless reordered by a channel
+    /// definition.
+    pub fn components(&self) -> &[DecodedComponent] {
+        &self.components
+    }
+
+    /// Minimum, maximum and mean of the samples of component `c`, in a single
+    /// pass.
+    ///
+    /// This is `None` if there is no component `c` or it has no samples.
+    pub fn component_stats(&self, c: usize) -> Option<SampleStats> {
+        let samples = &self.components.get(c)?.samples;
+        let first = *samples.first()?;
+        let mut stats = SampleStats {
+            min: first,
+            max: first,
+            mean: 0.0,
+        };
+        let mut sum = 0_i64;
+        for &sample in samples {
+            stats.min = stats.min.min(sample);
+            stats.max = stats.max.max(sample);
+            sum += i64::from(sample);
+        }
+        stats.mean = sum as f64 / samples.len() as f64;
+        Some(stats)
+    }
+
+    /// What the decode produced, for each tile-component.
+    ///
+    /// This is empty if no report was attached.
+    pub fn report(&self) -> &DecodeReport {
+        &self.report
+    }
+
+    /// Whether [DecodedImage::to_srgb8] includes an opacity channel, from the
+    /// channel definition.
+    pub fn has_opacity(&self) -> bool {
+        self.opacity_component.is_some()
+    }
+
+    /// Check that the samples of each component are exactly those of
+    /// `expected`, in raster order, as for a lossless codestream.
+    ///
+    /// A different number of components or samples is reported first, then
+    /// the first sample that differs, with its position.
+    pub fn verify_lossless(&self, expected: &[Vec<i32>]) -> Result<(), ImageError> {
+        if expected.len() != self.components.len() {
+            return Err(ImageError::ComponentsMissing {
+                expected: expected.len(),
+                actual: self.components.len(),
+            });
+        }
+        let width = self.width.max(1) as usize;
+        for (c, (component, expected)) in self.components.iter().zip(expected).enumerate() {
+            if component.samples.len() != expected.len() {
+                return Err(ImageError::ComponentSize {
+                    component: c,
+                    expected: expected.len(),
+                    actual: component.samples.len(),
+                });
+            }
+            let samples = component.samples.iter().zip(expected);
+            if let Some((i, (&actual, &expected))) = samples
+                .enumerate()
+                .find(|(_, (actual, expected))| actual != expected)
+            {
+                return Err(ImageError::SampleMismatch {
+                    component: c,
+                    x: (i % width) as u32,
+                    y: (i / width) as u32,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Image as 8 bit sRGB, without colour management.
+    ///
+    /// For sRGB, the three colour components are interleaved as RGB. For sYCC,
+    /// the three colour components are converted to RGB using the matrix of
+    /// IEC 61966-2-1 Amendment 1, then interleaved. For greyscale, the colour
+    /// component is returned as a single channel. The colour components are
+    /// the first components, which follow the colour order of the channel
+    /// definition if one was applied.
+    ///
+    /// If the channel definition has an opacity channel for the whole image,
+    /// it follows the colour channels of each pixel, giving RGBA or greyscale
+    /// with alpha. Any further components are not included.
+    ///
+    /// Samples of other bit depths are scaled to 8 bits.
+    pub fn to_srgb8(&self) -> Result<Vec<u8>, ImageError> {
+        let pixels = self.width as usize * self.height as usize;
+        let opacity = self.check_opacity(pixels)?;
+        let channels = |colours: usize| colours + opacity.is_some() as usize;
+        let push_opacity = |output: &mut Vec<u8>, index: usize| {
+            if let Some(opacity) = opacity {
+                output.push(to_u8(opacity.normalised(index)));
+            }
+        };
+        match self.colour_space {
+            EnumeratedColourSpaces::sRGB => {
+                let [r, g, b] = self.check_components::<3>(pixels)?;
+                let mut output = Vec::with_capacity(pixels * channels(3));
+                for index in 0..pixels {
+                    output.push(to_u8(r.normalised(index)));
+                    output.push(to_u8(g.normalised(index)));
+                    output.push(to_u8(b.normalised(index)));
+                    push_opacity(&mut output, index);
+                }
+                Ok(output)
+            }
+            EnumeratedColourSpaces::sYCC => {
+                let [y, cb, cr] = self.check_components::<3>(pixels)?;
+                let mut output = Vec::with_capacity(pixels * channels(3));
+                for index in 0..pixels {
+                    let luma = y.normalised(index);
+                    let cb = cb.normalised_chroma(index);
+                    let cr = cr.normalised_chroma(index);
+                    output.push(to_u8(luma + SYCC_CR_TO_R * cr));
+                    output.push(to_u8(luma - SYCC_CB_TO_G * cb - SYCC_CR_TO_G * cr));
+                    output.push(to_u8(luma + SYCC_CB_TO_B * cb));
+                    push_opacity(&mut output, index);
+                }
+                Ok(output)
+            }
+            EnumeratedColourSpaces::Greyscale => {
+                let [grey] = self.check_components::<1>(pixels)?;
+                let mut output = Vec::with_capacity(pixels * channels(1));
+                for index in 0..pixels {
+                    output.push(to_u8(grey.normalised(index)));
+                    push_opacity(&mut output, index);
+                }
+                Ok(output)
+            }
+            colour_space => Err(ImageError::UnsupportedColourSpace { colour_space }),
+        }
+    }
+
+    /// Image as 8 bit sRGB with alpha, without colour management.
+    ///
+    /// Each pixel has red, green, blue and alpha channels. The colour channels
+    /// are converted as for [DecodedImage::to_srgb8], in the order given by
+    /// the associations of the channel definition, with greyscale repeated
+    /// over the three channels. Alpha is taken from the opacity channel, and
+    /// is fully opaque if there is none.
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, ImageError> {
+        let srgb = self.to_srgb8()?;
+        let colours = match self.colour_space {
+            EnumeratedColourSpaces::Greyscale => 1,
+            _ => 3,
+        };
+        let channels = colours + self.has_opacity() as usize;
+        let mut output = Vec::with_capacity(srgb.len() / channels * 4);
+        for pixel in srgb.chunks_exact(channels) {
+            if colours == 1 {
+                output.extend_from_slice(&[pixel[0]; 3]);
+            } else {
+                output.extend_from_slice(&pixel[..3]);
+            }
+            output.push(pixel.get(colours).copied().unwrap_or(u8::MAX));
+        }
+        Ok(output)
+    }
+
+    // The N colour components, each with a sample for every pixel
+    fn check_components<const N: usize>(
+        &self,
+        pixels: usize,
+    ) -> Result<[&DecodedComponent; N], ImageError> {
+        if self.components.len() < N {
+            return Err(ImageError::ComponentsMissing {
+                expected: N,
+                actual: self.components.len(),
+            });
+        }
+        for component in 0..N {
+            self.check_component(component, pixels)?;
+        }
+        Ok(std::array::from_fn(|index| &self.components[index]))
+    }
+
+    // The opacity component, if any, with a sample for every pixel
+    fn check_opacity(&self, pixels: usize) -> Result<Option<&DecodedComponent>, ImageError> {
+        match self.opacity_component {
+            Some(component) => self.check_component(component, pixels).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn check_component(
+        &self,
+        component: usize,
+        pixels: usize,
+    ) -> Result<&DecodedComponent, ImageError> {
+        let decoded = self
+            .components
+            .get(component)
+            .ok_or(ImageError::ComponentsMissing {
+                expected: component + 1,
+                actual: self.components.len(),
+            })?;
+        if !(1..=38).contains(&decoded.precision) {
+            return Err(ImageError::InvalidPrecision {
+                component,
+                precision: decoded.precision,
+            });
+        }
+        if decoded.samples.len() != pixels {
+            return Err(ImageError::ComponentSize {
+                component,
+                expected: pixels,
+                actual: decoded.samples.len(),
+            });
+        }
+        Ok(decoded)
+    }
+}
+
+// Normalised value, clamped and rounded to 8 bits
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
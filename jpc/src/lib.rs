@@ -2,18 +2,62 @@
 
 use log::{error, info};
 use std::cmp;
-use std::convert::TryInto;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
 use std::error;
 use std::fmt;
 use std::io;
 use std::str;
 
+mod builder;
 mod code_block;
+// Public with the `internals` feature, for the benchmarks and tests only, and
+// not part of the stable API
+#[cfg(feature = "internals")]
+#[doc(hidden)]
+pub mod coder;
+#[cfg(not(feature = "internals"))]
 mod coder;
+mod decode;
+mod dwt;
+mod encode;
+mod image;
+mod limits;
+mod mct;
+mod open;
+mod packet;
+mod part2;
+mod report;
 mod shared;
+#[cfg(feature = "stats")]
+mod stats;
+mod streaming;
 mod tag_tree;
 
-#[derive(Debug)]
+pub use builder::CodestreamHeaderBuilder;
+pub use code_block::{
+    decode_code_block, from_twos_complement, to_twos_complement, CodeBlockDecodeError,
+    CodeBlockDecoder, CodeBlockParameters, Coefficient,
+};
+pub use decode::CodedCodeBlock;
+pub use encode::encode_image;
+pub use image::{DecodedComponent, DecodedImage, ImageError, SampleStats};
+pub use limits::DecodeLimits;
+pub use mct::inverse_component_transformation;
+pub use open::open;
+pub use packet::PacketDecodeError;
+pub use part2::{
+    CollectionTransformType, ComponentCollection, ComponentCollectionSegment,
+    MultiComponentTransform, TransformArray, TransformArraySegment, TransformArrayType,
+    TransformCollection, TransformElementType, TransformOrderSegment, TransformStage,
+};
+pub use report::{DecodeReport, DecodeWarning, TileComponentReport};
+pub use shared::SubBandType;
+#[cfg(feature = "stats")]
+pub use stats::DecodeStats;
+pub use streaming::StreamingDecoder;
+
+#[derive(Debug, PartialEq)]
 enum CodestreamError {
     /// Marker generic error
     MarkerError {
@@ -45,20 +89,6 @@ enum CodestreamError {
         marker: MarkerSymbol,
         offset: u64,
     },
-    TileSizeOverflow {
-        image_horizontal_offset: u32,
-        image_vertical_offset: u32,
-        tile_horizontal_offset: u32,
-        tile_vertical_offset: u32,
-        reference_tile_width: u32,
-        reference_tile_height: u32,
-    },
-    TileGridOffsetOverflow {
-        tile_horizontal_offset: u32,
-        tile_vertical_offset: u32,
-        image_horizontal_offset: u32,
-        image_vertical_offset: u32,
-    },
     // Marker is known but feature is unsupported
     UnsupportedFeature {
         marker: MarkerSymbol,
@@ -67,6 +97,20 @@ enum CodestreamError {
     InputFormatError {
         error: String,
     },
+    /// Value supplied by the caller is out of range
+    InvalidParameter {
+        name: &'static str,
+    },
+    /// Reference grid or tile geometry cannot be represented
+    GridOverflow {
+        name: &'static str,
+    },
+    /// Value decoded from the codestream exceeds the decode limits
+    LimitExceeded {
+        name: &'static str,
+        value: u64,
+        limit: u64,
+    },
 }
 
 impl error::Error for CodestreamError {}
@@ -92,52 +136,137 @@ impl fmt::Display for CodestreamError {
             } => {
                 write!(f, "unexpected marker {actual_marker} expected {expected_marker} at byte offset {offset}",)
             }
-            Self::TileGridOffsetOverflow {
-                image_horizontal_offset,
-                image_vertical_offset,
-                tile_horizontal_offset,
-                tile_vertical_offset,
-            } => {
+            Self::MarkerMalformed { marker, offset } => {
+                write!(f, "malformed marker {marker} at byte offset {offset}",)
+            }
+            Self::UnsupportedFeature { marker, offset } => {
                 write!(
                     f,
-                    "tile grid offset overflow: XOSiz = {:?}, YOsiz = {:?}, XTOsiz = {:?}, YTOsiz = {:?}",
-                    image_horizontal_offset,
-                    image_vertical_offset,
-                    tile_horizontal_offset,
-                    tile_vertical_offset,
+                    "unsupported feature for marker {marker} at byte offset {offset}",
                 )
             }
-            // XTsiz + XTOsiz > XOsiz
-            // YTsiz + YTOsiz > YOsiz
-            Self::TileSizeOverflow {
-                image_horizontal_offset,
-                image_vertical_offset,
-                tile_horizontal_offset,
-                tile_vertical_offset,
-                reference_tile_width,
-                reference_tile_height,
-            } => {
+            Self::InputFormatError { error } => write!(f, "Unknown error in input: {}", error),
+            Self::InvalidParameter { name } => write!(f, "invalid value for {name}"),
+            Self::GridOverflow { name } => write!(f, "reference grid overflow computing {name}"),
+            Self::LimitExceeded { name, value, limit } => {
+                write!(f, "{name} of {value} exceeds decode limit of {limit}")
+            }
+        }
+    }
+}
+
+/// Error values that may be returned when decoding a SIZ marker segment.
+///
+/// The offset is the byte offset of the SIZ marker.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SizError {
+    /// Csiz is zero, where an image has at least one component.
+    NoComponents { offset: u64 },
+    /// Csiz does not match the number of components that fit in Lsiz.
+    ///
+    /// Lsiz is 38 bytes plus 3 bytes for each component.
+    ComponentCountMismatch {
+        declared: u16,
+        length_implies: u16,
+        offset: u64,
+    },
+    /// The image area offset, XOsiz or YOsiz, is not less than the reference
+    /// grid size, Xsiz or Ysiz, leaving no image area.
+    ///
+    /// `axis` is 'X' or 'Y'.
+    ImageOffsetOutOfRange {
+        axis: char,
+        image_offset: u32,
+        grid_size: u32,
+        offset: u64,
+    },
+    /// The tile grid offset, XTOsiz or YTOsiz, is greater than the image
+    /// area offset, XOsiz or YOsiz.
+    TileOffsetOutOfRange {
+        axis: char,
+        tile_offset: u32,
+        image_offset: u32,
+        offset: u64,
+    },
+    /// The first tile, ending at XTOsiz + XTsiz or YTOsiz + YTsiz, does not
+    /// reach past the image area offset, XOsiz or YOsiz, so holds none of
+    /// the image area.
+    FirstTileOutsideImage {
+        axis: char,
+        tile_end: u64,
+        image_offset: u32,
+        offset: u64,
+    },
+}
+
+impl error::Error for SizError {}
+impl fmt::Display for SizError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoComponents { offset } => {
                 write!(
                     f,
-                    "tile size overflow: XOSiz = {:?}, YOsiz = {:?}, XTOsiz = {:?}, YTOsiz = {:?}, XTsize = {:?}, YTsize = {:?}",
-                    image_horizontal_offset,
-                    image_vertical_offset,
-                    tile_horizontal_offset,
-                    tile_vertical_offset,
-                    reference_tile_width,
-                    reference_tile_height,
+                    "marker {MARKER_SYMBOL_SIZ} at byte offset {offset} has no components"
                 )
             }
-            Self::MarkerMalformed { marker, offset } => {
-                write!(f, "malformed marker {marker} at byte offset {offset}",)
-            }
-            Self::UnsupportedFeature { marker, offset } => {
+            Self::ComponentCountMismatch {
+                declared,
+                length_implies,
+                offset,
+            } => write!(
+                f,
+                "marker {MARKER_SYMBOL_SIZ} at byte offset {offset} declares {declared} components, but its length implies {length_implies}"
+            ),
+            Self::ImageOffsetOutOfRange {
+                axis,
+                image_offset,
+                grid_size,
+                offset,
+            } => write!(
+                f,
+                "marker {MARKER_SYMBOL_SIZ} at byte offset {offset} has {axis}Osiz {image_offset}, not less than {axis}siz {grid_size}"
+            ),
+            Self::TileOffsetOutOfRange {
+                axis,
+                tile_offset,
+                image_offset,
+                offset,
+            } => write!(
+                f,
+                "marker {MARKER_SYMBOL_SIZ} at byte offset {offset} has {axis}TOsiz {tile_offset}, greater than {axis}Osiz {image_offset}"
+            ),
+            Self::FirstTileOutsideImage {
+                axis,
+                tile_end,
+                image_offset,
+                offset,
+            } => write!(
+                f,
+                "marker {MARKER_SYMBOL_SIZ} at byte offset {offset} has {axis}TOsiz + {axis}Tsiz {tile_end}, not greater than {axis}Osiz {image_offset}"
+            ),
+        }
+    }
+}
+
+/// Error values that may be returned when decoding the main header.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// Marker segments that every main header shall have are absent, named
+    /// as by [Header::missing_required_markers].
+    MissingRequiredMarkers { markers: Vec<&'static str> },
+}
+
+impl error::Error for HeaderError {}
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingRequiredMarkers { markers } => {
                 write!(
                     f,
-                    "unsupported feature for marker {marker} at byte offset {offset}",
+                    "main header is missing required markers {}",
+                    markers.join(", ")
                 )
             }
-            Self::InputFormatError { error } => write!(f, "Unknown error in input: {}", error),
         }
     }
 }
@@ -152,7 +281,7 @@ impl From<io::Error> for CodestreamError {
 
 const COMPRESSION_TYPE_WAVELET: u8 = 7;
 
-#[derive(Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 struct MarkerSymbol([u8; 2]);
 impl MarkerSymbol {
     fn decode<R: io::Read + io::Seek>(reader: &mut R) -> io::Result<MarkerSymbol> {
@@ -160,6 +289,50 @@ impl MarkerSymbol {
         reader.read_exact(&mut marker_type.0)?;
         Ok(marker_type)
     }
+
+    // Name from Table A.2, or None for an unknown or reserved marker
+    fn name(&self) -> Option<&'static str> {
+        Some(match *self {
+            MARKER_SYMBOL_SOC => "SOC",
+            MARKER_SYMBOL_SOT => "SOT",
+            MARKER_SYMBOL_SOD => "SOD",
+            MARKER_SYMBOL_EOC => "EOC",
+            MARKER_SYMBOL_SIZ => "SIZ",
+            MARKER_SYMBOL_PRF => "PRF",
+            MARKER_SYMBOL_CAP => "CAP",
+            MARKER_SYMBOL_COD => "COD",
+            MARKER_SYMBOL_COC => "COC",
+            MARKER_SYMBOL_RGN => "RGN",
+            MARKER_SYMBOL_QCD => "QCD",
+            MARKER_SYMBOL_QCC => "QCC",
+            MARKER_SYMBOL_POC => "POC",
+            MARKER_SYMBOL_TLM => "TLM",
+            MARKER_SYMBOL_PLM => "PLM",
+            MARKER_SYMBOL_PLT => "PLT",
+            MARKER_SYMBOL_PPM => "PPM",
+            MARKER_SYMBOL_PPT => "PPT",
+            MARKER_SYMBOL_SOP => "SOP",
+            MARKER_SYMBOL_EPH => "EPH",
+            MARKER_SYMBOL_CRG => "CRG",
+            MARKER_SYMBOL_COM => "COM",
+            MARKER_SYMBOL_CPF => "CPF",
+            MARKER_SYMBOL_MCT => "MCT",
+            MARKER_SYMBOL_MCC => "MCC",
+            MARKER_SYMBOL_MCO => "MCO",
+            _ => return None,
+        })
+    }
+
+    // Whether the marker is followed by a marker segment, which starts with
+    // its length. Delimiting markers and 0xFF30 to 0xFF3F are marker only
+    // (Section A.1.3).
+    fn has_segment(&self) -> bool {
+        match *self {
+            MARKER_SYMBOL_SOC | MARKER_SYMBOL_SOD | MARKER_SYMBOL_EOC | MARKER_SYMBOL_EPH => false,
+            MarkerSymbol([0xFF, code]) => !(0x30..=0x3F).contains(&code),
+            _ => true,
+        }
+    }
 }
 
 impl fmt::Debug for MarkerSymbol {
@@ -173,32 +346,7 @@ impl fmt::Display for MarkerSymbol {
         write!(
             f,
             "{} (0x{:0>2X}{:0>2X})",
-            match *self {
-                MARKER_SYMBOL_SOC => "SOC",
-                MARKER_SYMBOL_SOT => "SOT",
-                MARKER_SYMBOL_SOD => "SOD",
-                MARKER_SYMBOL_EOC => "EOC",
-                MARKER_SYMBOL_SIZ => "SIZ",
-                MARKER_SYMBOL_PRF => "PRF",
-                MARKER_SYMBOL_CAP => "CAP",
-                MARKER_SYMBOL_COD => "COD",
-                MARKER_SYMBOL_COC => "COC",
-                MARKER_SYMBOL_RGN => "RGN",
-                MARKER_SYMBOL_QCD => "QCD",
-                MARKER_SYMBOL_QCC => "QCC",
-                MARKER_SYMBOL_POC => "POC",
-                MARKER_SYMBOL_TLM => "TLM",
-                MARKER_SYMBOL_PLM => "PLM",
-                MARKER_SYMBOL_PLT => "PLT",
-                MARKER_SYMBOL_PPM => "PPM",
-                MARKER_SYMBOL_PPT => "PPT",
-                MARKER_SYMBOL_SOP => "SOP",
-                MARKER_SYMBOL_EPH => "EPH",
-                MARKER_SYMBOL_CRG => "CRG",
-                MARKER_SYMBOL_COM => "COM",
-                MARKER_SYMBOL_CPF => "CPF",
-                _ => "Unknown Marker",
-            },
+            self.name().unwrap_or("Unknown Marker"),
             self.0[0],
             self.0[1]
         )
@@ -267,6 +415,15 @@ const MARKER_SYMBOL_COM: MarkerSymbol = MarkerSymbol([0xFF, 0x64]);
 /// Corresponding profile
 const MARKER_SYMBOL_CPF: MarkerSymbol = MarkerSymbol([0xFF, 0x59]);
 
+// Marker segments from ITU-T T.801 | ISO/IEC 15444-2 Table A.2, which are
+// recognised and skipped
+/// Multiple component transformation definition
+const MARKER_SYMBOL_MCT: MarkerSymbol = MarkerSymbol([0xFF, 0x74]);
+/// Multiple component collection
+const MARKER_SYMBOL_MCC: MarkerSymbol = MarkerSymbol([0xFF, 0x75]);
+/// Multiple component transformation ordering
+const MARKER_SYMBOL_MCO: MarkerSymbol = MarkerSymbol([0xFF, 0x77]);
+
 #[derive(Debug, PartialEq)]
 pub enum ProgressionOrder {
     // 0000 0000 Layer-resolution level-component-position progression
@@ -299,6 +456,51 @@ impl ProgressionOrder {
             _ => ProgressionOrder::Reserved { value },
         }
     }
+
+    fn value(&self) -> u8 {
+        match self {
+            ProgressionOrder::LRLCPP => 0b0000_0000,
+            ProgressionOrder::RLLCPP => 0b0000_0001,
+            ProgressionOrder::RLPCLP => 0b0000_0010,
+            ProgressionOrder::PCRLLP => 0b0000_0011,
+            ProgressionOrder::CPRLLP => 0b0000_0100,
+            ProgressionOrder::Reserved { value } => *value,
+        }
+    }
+}
+
+// Names from Table A.16
+impl fmt::Display for ProgressionOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgressionOrder::LRLCPP => f.write_str("LRCP"),
+            ProgressionOrder::RLLCPP => f.write_str("RLCP"),
+            ProgressionOrder::RLPCLP => f.write_str("RPCL"),
+            ProgressionOrder::PCRLLP => f.write_str("PCRL"),
+            ProgressionOrder::CPRLLP => f.write_str("CPRL"),
+            ProgressionOrder::Reserved { value } => write!(f, "reserved ({value})"),
+        }
+    }
+}
+
+// Progression order from its name, in either case, such as from a command
+// line argument
+impl str::FromStr for ProgressionOrder {
+    type Err = Box<dyn error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "LRCP" => Ok(ProgressionOrder::LRLCPP),
+            "RLCP" => Ok(ProgressionOrder::RLLCPP),
+            "RPCL" => Ok(ProgressionOrder::RLPCLP),
+            "PCRL" => Ok(ProgressionOrder::PCRLLP),
+            "CPRL" => Ok(ProgressionOrder::CPRLLP),
+            _ => Err(CodestreamError::InvalidParameter {
+                name: "progression order",
+            }
+            .into()),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -387,6 +589,43 @@ impl CodingBlockStyle {
     }
 }
 
+// Names from Table A.19
+impl fmt::Display for CodingBlockStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodingBlockStyle::NoSelectiveArithmeticCodingBypass => {
+                f.write_str("no selective arithmetic coding bypass")
+            }
+            CodingBlockStyle::SelectiveArithmeticCodingBypass => {
+                f.write_str("selective arithmetic coding bypass")
+            }
+            CodingBlockStyle::NoResetOfContextProbabilities => {
+                f.write_str("no reset of context probabilities")
+            }
+            CodingBlockStyle::ResetContextProbabilities => {
+                f.write_str("reset context probabilities")
+            }
+            CodingBlockStyle::NoTerminationOnEachCodingPass => {
+                f.write_str("no termination on each coding pass")
+            }
+            CodingBlockStyle::TerminationOnEachCodingPass => {
+                f.write_str("termination on each coding pass")
+            }
+            CodingBlockStyle::NoVerticallyCausalContext => {
+                f.write_str("no vertically causal context")
+            }
+            CodingBlockStyle::VerticallyCausalContext => f.write_str("vertically causal context"),
+            CodingBlockStyle::NoPredictableTermination => f.write_str("no predictable termination"),
+            CodingBlockStyle::PredictableTermination => f.write_str("predictable termination"),
+            CodingBlockStyle::NoSegmentationSymbolsAreUsed => {
+                f.write_str("no segmentation symbols")
+            }
+            CodingBlockStyle::SegmentationSymbolsAreUsed => f.write_str("segmentation symbols"),
+            CodingBlockStyle::Reserved { value } => write!(f, "reserved ({})", value[0]),
+        }
+    }
+}
+
 // A.13 – Coding style parameter values for the Scod parameter
 #[derive(Debug, PartialEq)]
 pub enum CodingStyleDefault {
@@ -492,6 +731,20 @@ impl MultipleComponentTransformation {
     }
 }
 
+// Names from Table A.17, where the transformation is the RCT with the 5-3
+// filter and the ICT with the 9-7 filter
+impl fmt::Display for MultipleComponentTransformation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultipleComponentTransformation::None => f.write_str("none"),
+            MultipleComponentTransformation::Multiple => f.write_str("RCT/ICT"),
+            MultipleComponentTransformation::Reserved { value } => {
+                write!(f, "reserved ({value})")
+            }
+        }
+    }
+}
+
 const TRANSFORMATION_FILTER_IRREVERSIBLE: [u8; 1] = [0];
 const TRANSFORMATION_FILTER_REVERSIBLE: [u8; 1] = [1];
 
@@ -514,6 +767,41 @@ impl TransformationFilter {
             _ => TransformationFilter::Reserved { value },
         }
     }
+
+    fn value(&self) -> u8 {
+        match self {
+            TransformationFilter::Irreversible => TRANSFORMATION_FILTER_IRREVERSIBLE[0],
+            TransformationFilter::Reversible => TRANSFORMATION_FILTER_REVERSIBLE[0],
+            TransformationFilter::Reserved { value } => value[0],
+        }
+    }
+}
+
+// Names from Table A.20
+impl fmt::Display for TransformationFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransformationFilter::Irreversible => f.write_str("9-7 irreversible"),
+            TransformationFilter::Reversible => f.write_str("5-3 reversible"),
+            TransformationFilter::Reserved { value } => write!(f, "reserved ({})", value[0]),
+        }
+    }
+}
+
+// Transformation filter from its name, or the filter taps alone
+impl str::FromStr for TransformationFilter {
+    type Err = Box<dyn error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "9-7 irreversible" | "9-7" => Ok(TransformationFilter::Irreversible),
+            "5-3 reversible" | "5-3" => Ok(TransformationFilter::Reversible),
+            _ => Err(CodestreamError::InvalidParameter {
+                name: "transformation filter",
+            }
+            .into()),
+        }
+    }
 }
 
 // A.4.2
@@ -563,6 +851,32 @@ pub struct StartOfTileSegment {
     no_tile_parts: [u8; 1],
 }
 
+impl StartOfTileSegment {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn tile_index(&self) -> u16 {
+        u16::from_be_bytes(self.tile_index)
+    }
+
+    pub fn tile_length(&self) -> u32 {
+        self.tile_length
+    }
+
+    pub fn tile_part_index(&self) -> u8 {
+        u8::from_be_bytes(self.tile_part_index)
+    }
+
+    pub fn no_tile_parts(&self) -> u8 {
+        u8::from_be_bytes(self.no_tile_parts)
+    }
+}
+
 // A.12
 //
 // Coding style default (COD)
@@ -594,6 +908,19 @@ pub struct CodingStyleMarkerSegment {
 }
 
 impl CodingStyleMarkerSegment {
+    /// Decode a COD marker segment from its bytes, starting with the length
+    /// field and excluding the marker.
+    ///
+    /// The segment is decoded as if its marker were at the start of a
+    /// codestream, so byte offsets are relative to the marker.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn error::Error>> {
+        ContiguousCodestream::decode_segment_bytes(
+            MARKER_SYMBOL_COD,
+            bytes,
+            |codestream, reader| codestream.decode_cod(reader),
+        )
+    }
+
     pub fn length(&self) -> u16 {
         self.length
     }
@@ -665,6 +992,10 @@ impl CodingStyleComponentSegment {
     pub fn component_coding_style(&self) -> CodingStyleComponent {
         CodingStyleComponent::new(self.coding_style[0])
     }
+
+    pub fn coding_style_parameters(&self) -> &CodingStyleParameters {
+        &self.coding_style_parameters
+    }
 }
 
 #[derive(Debug, Default)]
@@ -674,13 +1005,13 @@ pub struct CodingStyleParametersPrecinctSize {
 
 impl CodingStyleParametersPrecinctSize {
     pub fn height_exponent(&self) -> u8 {
-        // 4 LSBs are the precinct width exponent, PPx = value
-        self.value << 4 >> 4
+        // 4 MSBs are the precinct height exponent, PPy = value
+        self.value >> 4
     }
 
     pub fn width_exponent(&self) -> u8 {
-        // 4 MSBs are the precinct height exponent PPy = value
-        self.value >> 4
+        // 4 LSBs are the precinct width exponent, PPx = value
+        self.value & 0b0000_1111
     }
 }
 
@@ -724,6 +1055,15 @@ impl CodingStyleParameters {
     // being 2^10.
     //
     // Furthermore, the code-block size is restricted so that xcb + ycb <= 12.
+    /// Code-block width and height exponents, xcb and ycb, which a decoder
+    /// shall check before using the code-block size.
+    pub(crate) fn code_block_exponents(&self) -> (u8, u8) {
+        (
+            (self.code_block_width[0] & 0b00001111) + 2,
+            (self.code_block_height[0] & 0b00001111) + 2,
+        )
+    }
+
     pub fn code_block_width(&self) -> u16 {
         2u16.pow(((self.code_block_width[0] & 0b00001111) + 2) as u32)
     }
@@ -744,18 +1084,29 @@ impl CodingStyleParameters {
         TransformationFilter::new(self.transformation)
     }
 
+    // Table A.13 and A.23, the precinct bit of Scod or Scoc
     pub fn has_defined_precinct_size(&self) -> bool {
-        self.coding_style[0] & 0b1001 == 1
+        self.coding_style[0] & 0b0000_0001 == 1
     }
 
     pub fn has_default_precinct_size(&self) -> bool {
-        self.coding_style[0] & 0b1001 == 0
+        !self.has_defined_precinct_size()
     }
 
+    /// Precinct size of each resolution level, starting with the lowest.
+    ///
+    /// There is one for each of the N<sub>L</sub> + 1 resolution levels. If
+    /// precinct sizes are not defined, each has the default PPx = 15 and
+    /// PPy = 15.
     pub fn precinct_sizes(&self) -> Option<Vec<CodingStyleParametersPrecinctSize>> {
+        let resolution_levels = self.no_decomposition_levels() as usize + 1;
         // If entropy coder, precincts with PPx = 15 and PPy = 15
         if self.has_default_precinct_size() {
-            return Some(vec![CodingStyleParametersPrecinctSize { value: 255 }]);
+            return Some(
+                (0..resolution_levels)
+                    .map(|_| CodingStyleParametersPrecinctSize { value: 0xFF })
+                    .collect(),
+            );
         }
 
         Some(
@@ -964,6 +1315,82 @@ impl TilePartLength {
         self.tile_length
     }
 }
+/// Error values that may be returned when decoding a comment as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentDecodeError {
+    /// Comment has the binary registration value, so is not text, see
+    /// [CommentMarkerSegment::comment_bytes].
+    Binary,
+    /// Comment is not valid UTF-8 from the given byte, so may be ISO/IEC
+    /// 8859-15 text, see [CommentMarkerSegment::comment_latin1].
+    InvalidUtf8 { valid_up_to: usize },
+}
+
+impl error::Error for CommentDecodeError {}
+impl fmt::Display for CommentDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Binary => write!(f, "comment is binary, not text"),
+            Self::InvalidUtf8 { valid_up_to } => {
+                write!(f, "comment has invalid UTF-8 at byte {valid_up_to}")
+            }
+        }
+    }
+}
+
+/// First difference found between the TLM marker segments and the SOT marker
+/// segments of the tile-parts.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TlmMismatch {
+    /// Ptlm differs from Psot for the tile-part, in codestream order.
+    Length {
+        tile_part: usize,
+        tile_index: u16,
+        tlm_length: u32,
+        psot: u32,
+    },
+    /// Ttlm differs from Isot for the tile-part, in codestream order.
+    TileIndex {
+        tile_part: usize,
+        tlm_tile_index: u16,
+        sot_tile_index: u16,
+    },
+    /// Number of TLM entries differs from the number of tile-parts.
+    Count {
+        tlm_entries: usize,
+        tile_parts: usize,
+    },
+}
+
+impl error::Error for TlmMismatch {}
+impl fmt::Display for TlmMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length {
+                tile_part,
+                tile_index,
+                tlm_length,
+                psot,
+            } => write!(
+                f,
+                "tile-part {tile_part} of tile {tile_index} has TLM length {tlm_length} but Psot {psot}"
+            ),
+            Self::TileIndex {
+                tile_part,
+                tlm_tile_index,
+                sot_tile_index,
+            } => write!(
+                f,
+                "tile-part {tile_part} has TLM tile index {tlm_tile_index} but Isot {sot_tile_index}"
+            ),
+            Self::Count {
+                tlm_entries,
+                tile_parts,
+            } => write!(f, "TLM has {tlm_entries} entries for {tile_parts} tile-parts"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum TilePartParameterSize {
     TtlmNone,
@@ -982,7 +1409,7 @@ impl TilePartParameterSize {
             0 => tile_part_parameter_sizes.push(TilePartParameterSize::TtlmNone),
             1 => tile_part_parameter_sizes.push(TilePartParameterSize::Ttlm8Bit),
             2 => tile_part_parameter_sizes.push(TilePartParameterSize::Ttlm16Bit),
-            _ => {} // TODO: Add reserve values by removed known bits
+            _ => tile_part_parameter_sizes.push(TilePartParameterSize::Reserved { value }),
         }
 
         match (value >> 6) & 0b1 {
@@ -1080,7 +1507,19 @@ pub struct TilePacketLength {
     // If packet headers are stored with the packet, this length includes the
     // packet header. If packet headers are stored in the PPM or PPT, this
     // length does not include the packet header lengths.
-    packet_length: Vec<u8>,
+    packet_length: Vec<u32>,
+}
+
+impl TilePacketLength {
+    /// Marker segment index (Zplt).
+    pub fn segment_index(&self) -> u8 {
+        self.index[0]
+    }
+
+    /// Packet lengths (Iplt<sup>i</sup>), in bytes.
+    pub fn packet_lengths(&self) -> &[u32] {
+        &self.packet_length
+    }
 }
 
 // A.7.4
@@ -1101,11 +1540,15 @@ pub struct PackedPacketHeaderSegment {
 
     // Nppm^i: Number of bytes of Ippm information for the ith tile-part in the
     // order found in the codestream. One value for each tile-part (not tile).
-    number_of_bytes: [u8; 4],
-
+    //
     // Ippm^ij: Packet header for every packet in order in the tile-part.
     // The contents are exactly the packet header which would have been
     // distributed in the bit stream as described in B.10
+    //
+    // The series of Nppm and Ippm parameters is concatenated, in the order of
+    // increasing Zppm, with the series from other marker segments. A marker
+    // segment may end part way through the Ippm parameters of a tile-part,
+    // in which case the next marker segment continues them without an Nppm.
     data: Vec<u8>,
 }
 
@@ -1114,8 +1557,10 @@ impl PackedPacketHeaderSegment {
         u8::from_be_bytes(self.index) as usize
     }
 
-    pub fn number_of_bytes(&self) -> usize {
-        u32::from_be_bytes(self.number_of_bytes) as usize
+    /// Nppm and Ippm parameters following Zppm, which only form whole
+    /// tile-parts when concatenated with the other PPM marker segments.
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 }
 
@@ -1154,6 +1599,11 @@ impl TilePackedPacketHeaderSegment {
     pub fn index(&self) -> usize {
         u8::from_be_bytes(self.index) as usize
     }
+
+    /// Ippt parameters, the packet headers of the tile-part.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 // A.9.1
@@ -1196,6 +1646,192 @@ pub struct ComponentRegistrationSegment {
     vertical_offset: Vec<[u8; 2]>,
 }
 
+/// Table A.10 – Capability Rsiz parameter
+///
+/// Without the Part 2 bit, the low bits hold a single profile. With the Part 2
+/// bit, the low bits are flags for the extensions in use (ITU-T T.801 | ISO/IEC
+/// 15444-2 Table A.2).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecoderCapabilities {
+    /// 0000 0000 0000 0000 - Capabilities specified in this Recommendation only
+    Baseline,
+
+    /// 0000 0000 0000 0001 - Profile 0
+    Profile0,
+
+    /// 0000 0000 0000 0010 - Profile 1
+    Profile1,
+
+    /// 0000 0000 0000 0011 - 2K digital cinema profile
+    DigitalCinema2K,
+
+    /// 0000 0000 0000 0100 - 4K digital cinema profile
+    DigitalCinema4K,
+
+    /// 0000 0000 0000 0101 - Scalable 2K digital cinema profile
+    ScalableDigitalCinema2K,
+
+    /// 0000 0000 0000 0110 - Scalable 4K digital cinema profile
+    ScalableDigitalCinema4K,
+
+    /// 0000 0000 0000 0111 - Long-term storage profile
+    LongTermStorage,
+
+    /// 0000 0001 0000 xxxx - Broadcast contribution single tile profile
+    BroadcastSingleTile { level: u8 },
+
+    /// 0000 0010 0000 xxxx - Broadcast contribution multi-tile profile
+    BroadcastMultiTile { level: u8 },
+
+    /// 0000 0011 0000 xxxx - Broadcast contribution multi-tile reversible profile
+    BroadcastMultiTileReversible { level: u8 },
+
+    /// 0000 0100 xxxx xxxx - 2K IMF single tile lossy profile
+    Imf2K { main_level: u8, sub_level: u8 },
+
+    /// 0000 0101 xxxx xxxx - 4K IMF single tile lossy profile
+    Imf4K { main_level: u8, sub_level: u8 },
+
+    /// 0000 0110 xxxx xxxx - 8K IMF single tile lossy profile
+    Imf8K { main_level: u8, sub_level: u8 },
+
+    /// 0000 0111 xxxx xxxx - 2K IMF single/multi tile reversible profile
+    Imf2KReversible { main_level: u8, sub_level: u8 },
+
+    /// 0000 1000 xxxx xxxx - 4K IMF single/multi tile reversible profile
+    Imf4KReversible { main_level: u8, sub_level: u8 },
+
+    /// 0000 1001 xxxx xxxx - 8K IMF single/multi tile reversible profile
+    Imf8KReversible { main_level: u8, sub_level: u8 },
+
+    /// x1xx xxxx xxxx xxxx - CAP marker segment present, as required for high
+    /// throughput (HTJ2K) block coding
+    ExtendedCapabilities,
+
+    /// 1xxx xxxx xxxx xxxx - Capabilities specified in ITU-T T.801 | ISO/IEC
+    /// 15444-2
+    Part2,
+
+    /// 1xxx xxxx xxxx xxx1 - Variable DC offset
+    Part2DcOffset,
+
+    /// 1xxx xxxx xxxx xx1x - Variable scalar quantization
+    Part2VariableScalarQuantization,
+
+    /// 1xxx xxxx xxxx x1xx - Trellis coded quantization
+    Part2TrellisCodedQuantization,
+
+    /// 1xxx xxxx xxxx 1xxx - Visual masking
+    Part2VisualMasking,
+
+    /// 1xxx xxxx xxx1 xxxx - Single sample overlap
+    Part2SingleSampleOverlap,
+
+    /// 1xxx xxxx xx1x xxxx - Arbitrary decomposition styles
+    Part2ArbitraryDecomposition,
+
+    /// 1xxx xxxx x1xx xxxx - Arbitrary transformation kernels
+    Part2ArbitraryTransformationKernels,
+
+    /// 1xxx xxxx 1xxx xxxx - Whole sample symmetric transformation kernels
+    Part2WholeSampleSymmetricKernels,
+
+    /// 1xxx xxx1 xxxx xxxx - Multiple component transformations
+    Part2MultipleComponentTransformations,
+
+    /// 1xxx xx1x xxxx xxxx - Non-linear point transformations
+    Part2NonLinearPointTransformations,
+
+    /// 1xxx x1xx xxxx xxxx - Arbitrary shaped region of interest
+    Part2ArbitraryShapedRegionOfInterest,
+
+    /// All other values reserved, holding the unrecognised bits
+    Reserved { value: u16 },
+}
+
+impl DecoderCapabilities {
+    fn new(value: u16) -> Vec<DecoderCapabilities> {
+        let mut capabilities: Vec<DecoderCapabilities> = vec![];
+
+        if value & 0b_1000_0000_0000_0000 == 0 {
+            let profile = value & 0b_0011_1111_1111_1111;
+            let level = (profile & 0b_0000_1111) as u8;
+            let main_level = (profile & 0b_0000_1111) as u8;
+            let sub_level = ((profile >> 4) & 0b_0000_1111) as u8;
+            capabilities.push(match profile {
+                0x0000 => DecoderCapabilities::Baseline,
+                0x0001 => DecoderCapabilities::Profile0,
+                0x0002 => DecoderCapabilities::Profile1,
+                0x0003 => DecoderCapabilities::DigitalCinema2K,
+                0x0004 => DecoderCapabilities::DigitalCinema4K,
+                0x0005 => DecoderCapabilities::ScalableDigitalCinema2K,
+                0x0006 => DecoderCapabilities::ScalableDigitalCinema4K,
+                0x0007 => DecoderCapabilities::LongTermStorage,
+                0x0100..=0x010F => DecoderCapabilities::BroadcastSingleTile { level },
+                0x0200..=0x020F => DecoderCapabilities::BroadcastMultiTile { level },
+                0x0300..=0x030F => DecoderCapabilities::BroadcastMultiTileReversible { level },
+                0x0400..=0x04FF => DecoderCapabilities::Imf2K {
+                    main_level,
+                    sub_level,
+                },
+                0x0500..=0x05FF => DecoderCapabilities::Imf4K {
+                    main_level,
+                    sub_level,
+                },
+                0x0600..=0x06FF => DecoderCapabilities::Imf8K {
+                    main_level,
+                    sub_level,
+                },
+                0x0700..=0x07FF => DecoderCapabilities::Imf2KReversible {
+                    main_level,
+                    sub_level,
+                },
+                0x0800..=0x08FF => DecoderCapabilities::Imf4KReversible {
+                    main_level,
+                    sub_level,
+                },
+                0x0900..=0x09FF => DecoderCapabilities::Imf8KReversible {
+                    main_level,
+                    sub_level,
+                },
+                _ => DecoderCapabilities::Reserved { value: profile },
+            });
+        } else {
+            capabilities.push(DecoderCapabilities::Part2);
+
+            let extensions = [
+                DecoderCapabilities::Part2DcOffset,
+                DecoderCapabilities::Part2VariableScalarQuantization,
+                DecoderCapabilities::Part2TrellisCodedQuantization,
+                DecoderCapabilities::Part2VisualMasking,
+                DecoderCapabilities::Part2SingleSampleOverlap,
+                DecoderCapabilities::Part2ArbitraryDecomposition,
+                DecoderCapabilities::Part2ArbitraryTransformationKernels,
+                DecoderCapabilities::Part2WholeSampleSymmetricKernels,
+                DecoderCapabilities::Part2MultipleComponentTransformations,
+                DecoderCapabilities::Part2NonLinearPointTransformations,
+                DecoderCapabilities::Part2ArbitraryShapedRegionOfInterest,
+            ];
+            for (bit, extension) in extensions.iter().enumerate() {
+                if value & (1 << bit) != 0 {
+                    capabilities.push(*extension);
+                }
+            }
+
+            let unknown = value & 0b_0011_1000_0000_0000;
+            if unknown != 0 {
+                capabilities.push(DecoderCapabilities::Reserved { value: unknown });
+            }
+        }
+
+        if value & 0b_0100_0000_0000_0000 != 0 {
+            capabilities.push(DecoderCapabilities::ExtendedCapabilities);
+        }
+
+        capabilities
+    }
+}
+
 // A.5.1
 //
 // Image and tile size (SIZ)
@@ -1268,6 +1904,21 @@ pub struct ImageAndTileSizeMarkerSegment {
 }
 
 impl ImageAndTileSizeMarkerSegment {
+    /// Decode a SIZ marker segment from its bytes, starting with the length
+    /// field and excluding the marker.
+    ///
+    /// The segment is decoded as if its marker were at the start of a
+    /// codestream, so byte offsets are relative to the marker.
+    ///
+    /// The default [DecodeLimits] apply.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn error::Error>> {
+        ContiguousCodestream::decode_segment_bytes(
+            MARKER_SYMBOL_SIZ,
+            bytes,
+            |codestream, reader| codestream.decode_siz(reader),
+        )
+    }
+
     pub fn length(&self) -> u16 {
         self.length
     }
@@ -1280,6 +1931,12 @@ impl ImageAndTileSizeMarkerSegment {
         u16::from_be_bytes(self.decoder_capabilities)
     }
 
+    /// Capabilities signalled by Rsiz, as a profile or Part 2 extensions, and
+    /// whether the CAP marker segment is present.
+    pub fn capabilities(&self) -> Vec<DecoderCapabilities> {
+        DecoderCapabilities::new(self.decoder_capabilities())
+    }
+
     pub fn reference_grid_width(&self) -> u32 {
         u32::from_be_bytes(self.reference_grid_width)
     }
@@ -1334,77 +1991,160 @@ impl ImageAndTileSizeMarkerSegment {
         Ok(u8::from_be_bytes(*vertical_separation))
     }
 
+    /// Precision, sign and sub-sampling of each component, in component
+    /// index order.
+    pub fn components(&self) -> impl Iterator<Item = ComponentInfo> + '_ {
+        self.precision
+            .iter()
+            .zip(self.horizontal_separation.iter())
+            .zip(self.vertical_separation.iter())
+            .map(
+                |((ssiz, horizontal_separation), vertical_separation)| ComponentInfo {
+                    precision: (ssiz[0] & 0x7f) + 1,
+                    values_are_signed: (ssiz[0] & 0x80) == 0x80,
+                    horizontal_separation: horizontal_separation[0],
+                    vertical_separation: vertical_separation[0],
+                },
+            )
+    }
+
+    /// Number of tiles, numXtiles · numYtiles (Equation B-5).
+    ///
+    /// This is 0 if the tile size is zero or the tiles do not cover the
+    /// reference grid, which is not a valid codestream.
+    pub fn num_tiles(&self) -> u64 {
+        match (self.num_x_tiles(), self.num_y_tiles()) {
+            (Ok(x), Ok(y)) => u64::from(x) * u64::from(y),
+            _ => 0,
+        }
+    }
+
     // The number of tiles in the X direction (numXtiles) and the Y direction
     // (numYtiles) is the following
     //
-    // numXtiles = [(Xsiz - XTOsiz) / XTsiz]
-    // numYtiles = [(Ysiz - YTOsiz) / YTsiz]
-    fn num_x_tiles(&self) -> u32 {
-        (self.reference_grid_width() - self.tile_horizontal_offset()) / self.reference_tile_width()
+    // numXtiles = ⌈(Xsiz - XTOsiz) / XTsiz⌉
+    // numYtiles = ⌈(Ysiz - YTOsiz) / YTsiz⌉
+    fn num_x_tiles(&self) -> Result<u32, CodestreamError> {
+        num_tiles_1d(
+            self.reference_grid_width(),
+            self.tile_horizontal_offset(),
+            self.reference_tile_width(),
+        )
+        .ok_or(CodestreamError::GridOverflow { name: "numXtiles" })
     }
-    fn num_y_tiles(&self) -> u32 {
-        (self.reference_grid_height() - self.tile_vertical_offset()) / self.reference_tile_height()
+    fn num_y_tiles(&self) -> Result<u32, CodestreamError> {
+        num_tiles_1d(
+            self.reference_grid_height(),
+            self.tile_vertical_offset(),
+            self.reference_tile_height(),
+        )
+        .ok_or(CodestreamError::GridOverflow { name: "numYtiles" })
     }
 
     // Let p be the horizontal index of a tile, ranging from 0 to numXtiles -1
     // p = mod(t, numXTiles)
     // where t is the index of the tile
-    fn tile_horizontal_index(&self, t: u32) -> u32 {
-        t % self.num_x_tiles()
+    fn tile_horizontal_index(&self, t: u32) -> Result<u32, CodestreamError> {
+        Ok(t % self.num_x_tiles()?)
     }
 
     // Let q be the vertical index of a tile, ranging from 0 to numYtiles -1,
-    // q = [t / numXtiles]
+    // q = ⌊t / numXtiles⌋
     // where t is the index of the tile
-    fn tile_vertical_index(&self, t: u32) -> u32 {
-        t / self.num_x_tiles()
+    fn tile_vertical_index(&self, t: u32) -> Result<u32, CodestreamError> {
+        let q = t / self.num_x_tiles()?;
+        if q >= self.num_y_tiles()? {
+            return Err(CodestreamError::GridOverflow { name: "q" });
+        }
+        Ok(q)
     }
 
     // upper left x corner of the tile
     // tx_0(p,q) = max(XTOsiz + p · XTsiz, XOsiz)
-    fn tile_x_upper(&self, t: u32) -> u32 {
-        cmp::max(
-            self.tile_horizontal_offset()
-                + (self.tile_horizontal_index(t) * self.reference_tile_width()),
-            self.image_horizontal_offset(),
-        )
+    fn tile_x_upper(&self, t: u32) -> Result<u32, CodestreamError> {
+        let x = self.tile_horizontal_offset() as u64
+            + self.tile_horizontal_index(t)? as u64 * self.reference_tile_width() as u64;
+        u32::try_from(cmp::max(x, self.image_horizontal_offset() as u64))
+            .map_err(|_| CodestreamError::GridOverflow { name: "tx0" })
     }
 
     // upper left y corner of the tile
     // ty_0(p,q) = max(YTOsiz + q · YTsiz, YOsiz)
-    fn tile_y_upper(&self, t: u32) -> u32 {
-        cmp::max(
-            self.tile_vertical_offset()
-                + (self.tile_vertical_index(t) * self.reference_tile_height()),
-            self.image_vertical_offset(),
-        )
+    fn tile_y_upper(&self, t: u32) -> Result<u32, CodestreamError> {
+        let y = self.tile_vertical_offset() as u64
+            + self.tile_vertical_index(t)? as u64 * self.reference_tile_height() as u64;
+        u32::try_from(cmp::max(y, self.image_vertical_offset() as u64))
+            .map_err(|_| CodestreamError::GridOverflow { name: "ty0" })
+    }
+
+    // lower right x corner of the tile, exclusive
+    // tx_1(p,q) = min(XTOsiz + (p + 1) · XTsiz, Xsiz)
+    fn tile_x_lower(&self, t: u32) -> Result<u32, CodestreamError> {
+        let x = self.tile_horizontal_offset() as u64
+            + (self.tile_horizontal_index(t)? as u64 + 1) * self.reference_tile_width() as u64;
+        u32::try_from(cmp::min(x, self.reference_grid_width() as u64))
+            .map_err(|_| CodestreamError::GridOverflow { name: "tx1" })
+    }
+
+    // lower right y corner of the tile, exclusive
+    // ty_1(p,q) = min(YTOsiz + (q + 1) · YTsiz, Ysiz)
+    fn tile_y_lower(&self, t: u32) -> Result<u32, CodestreamError> {
+        let y = self.tile_vertical_offset() as u64
+            + (self.tile_vertical_index(t)? as u64 + 1) * self.reference_tile_height() as u64;
+        u32::try_from(cmp::min(y, self.reference_grid_height() as u64))
+            .map_err(|_| CodestreamError::GridOverflow { name: "ty1" })
+    }
+
+    fn tile_dimensions(&self, t: u32) -> Result<(u32, u32), CodestreamError> {
+        let width = self
+            .tile_x_lower(t)?
+            .checked_sub(self.tile_x_upper(t)?)
+            .ok_or(CodestreamError::GridOverflow { name: "tile width" })?;
+        let height = self
+            .tile_y_lower(t)?
+            .checked_sub(self.tile_y_upper(t)?)
+            .ok_or(CodestreamError::GridOverflow {
+                name: "tile height",
+            })?;
+        Ok((width, height))
     }
+}
 
-    // lower left x corner of the tile
-    // tx_1(p,q) = max(XTOsiz + (p + 1) · XTsiz, XOsiz)
-    fn tile_x_lower(&self, t: u32) -> u32 {
-        cmp::min(
-            self.tile_horizontal_offset()
-                + ((self.tile_horizontal_index(t) + 1) * self.reference_tile_width()),
-            self.image_horizontal_offset(),
-        ) - 1
+// Number of tiles along one axis of the reference grid, or None if the grid
+// is empty or the tile size is zero.
+fn num_tiles_1d(size: u32, tile_offset: u32, tile_size: u32) -> Option<u32> {
+    let extent = size.checked_sub(tile_offset)?;
+    if extent == 0 || tile_size == 0 {
+        return None;
     }
+    Some(extent.div_ceil(tile_size))
+}
+
+/// Ssiz, XRsiz and YRsiz of a single component from the SIZ marker segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentInfo {
+    precision: u8,
+    values_are_signed: bool,
+    horizontal_separation: u8,
+    vertical_separation: u8,
+}
 
-    // lower left y corner of the tile
-    // ty_1(p,q) = max(YTOsiz + (q + 1) · YTsiz, YOsiz)
-    fn tile_y_lower(&self, t: u32) -> u32 {
-        cmp::min(
-            self.tile_vertical_offset()
-                + ((self.tile_vertical_index(t) + 1) * self.reference_tile_height()),
-            self.image_vertical_offset(),
-        ) - 1
+impl ComponentInfo {
+    /// Component bit depth, from 1 to 38 bits.
+    pub fn precision(&self) -> u8 {
+        self.precision
     }
 
-    fn tile_dimensions(&self, t: u32) -> (u32, u32) {
-        (
-            self.tile_x_lower(t) - self.tile_x_upper(t),
-            self.tile_y_lower(t) - self.tile_y_upper(t),
-        )
+    pub fn values_are_signed(&self) -> bool {
+        self.values_are_signed
+    }
+
+    pub fn horizontal_separation(&self) -> u8 {
+        self.horizontal_separation
+    }
+
+    pub fn vertical_separation(&self) -> u8 {
+        self.vertical_separation
     }
 }
 
@@ -1522,21 +2262,38 @@ impl CorrespondingProfileMarkerSegment {
         self.offset
     }
 
-    /// Vector of Pcpf<sup>i</sup> values.
+    /// Vector of Pcpf<sup>i</sup> values, least significant first.
     pub fn pcpf_raw(&self) -> &[u16] {
         &self.pcpf
     }
 
     /// CPFnum.
     ///
-    /// This is computed from the Pcpf<sup>i</sup> integers.
-    pub fn cpf_num(&self) -> i32 {
-        let mut cpf_num = -1i32;
-        for i in 0..self.pcpf.len() {
-            let pcpf = self.pcpf.get(i).unwrap();
-            cpf_num += (pcpf * 2u16.pow((16 * i) as u32)) as i32;
+    /// This is computed from the Pcpf<sup>i</sup> integers, as the sum of
+    /// each Pcpf<sup>i</sup> · 2<sup>16(i - 1)</sup>, less 1. `None` if the
+    /// value does not fit in 64 bits, or the Pcpf<sup>i</sup> are all zero.
+    pub fn cpf_num(&self) -> Option<u64> {
+        let mut sum = 0u64;
+        for (i, &pcpf) in self.pcpf.iter().enumerate() {
+            if pcpf == 0 {
+                continue;
+            }
+            let shift = 16 * i as u32;
+            if shift >= u64::BITS {
+                return None;
+            }
+            sum |= u64::from(pcpf) << shift;
         }
-        cpf_num
+        sum.checked_sub(1)
+    }
+
+    /// Capabilities of the corresponding codestream of ITU-T T.800 |
+    /// ISO/IEC 15444-1, which CPFnum gives as its Rsiz value.
+    ///
+    /// `None` if CPFnum is not a valid Rsiz value.
+    pub fn corresponding_capabilities(&self) -> Option<Vec<DecoderCapabilities>> {
+        let rsiz = u16::try_from(self.cpf_num()?).ok()?;
+        Some(DecoderCapabilities::new(rsiz))
     }
 }
 
@@ -1563,6 +2320,19 @@ impl CommentRegistrationValue {
     }
 }
 
+// Names from Table A.44
+impl fmt::Display for CommentRegistrationValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommentRegistrationValue::Binary => f.write_str("binary"),
+            CommentRegistrationValue::Latin => f.write_str("ISO/IEC 8859-15 (Latin)"),
+            CommentRegistrationValue::Reserved { value } => {
+                write!(f, "reserved ({})", u16::from_be_bytes(*value))
+            }
+        }
+    }
+}
+
 // A.9.2
 //
 // Comment (COM)
@@ -1578,12 +2348,80 @@ pub struct CommentMarkerSegment {
 }
 
 impl CommentMarkerSegment {
+    /// Decode a COM marker segment from its bytes, starting with the length
+    /// field and excluding the marker.
+    ///
+    /// The segment is decoded as if its marker were at the start of a
+    /// codestream, so byte offsets are relative to the marker.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn error::Error>> {
+        ContiguousCodestream::decode_segment_bytes(
+            MARKER_SYMBOL_COM,
+            bytes,
+            |codestream, reader| codestream.decode_com(reader),
+        )
+    }
+
     pub fn registration_value(&self) -> CommentRegistrationValue {
         CommentRegistrationValue::new(self.registration_value)
     }
 
-    pub fn comment_utf8(&self) -> Result<&str, str::Utf8Error> {
-        str::from_utf8(&self.comment)
+    /// Comment decoded as UTF-8.
+    ///
+    /// Fails for the binary registration value, or if the comment is not
+    /// valid UTF-8, in which case it may be ISO/IEC 8859-15 text.
+    pub fn comment_utf8(&self) -> Result<&str, CommentDecodeError> {
+        if self.registration_value() == CommentRegistrationValue::Binary {
+            return Err(CommentDecodeError::Binary);
+        }
+        str::from_utf8(&self.comment).map_err(|error| CommentDecodeError::InvalidUtf8 {
+            valid_up_to: error.valid_up_to(),
+        })
+    }
+
+    /// Comment decoded as ISO/IEC 8859-1, where each byte is the Unicode code
+    /// point of the same value.
+    ///
+    /// ISO/IEC 8859-15 differs in eight code points, such as 0xA4 for the
+    /// euro sign, which are decoded as their ISO/IEC 8859-1 characters.
+    pub fn comment_latin1(&self) -> String {
+        self.comment.iter().map(|&byte| byte as char).collect()
+    }
+
+    /// Comment as unstructured bytes, for the binary registration value.
+    pub fn comment_bytes(&self) -> &[u8] {
+        &self.comment
+    }
+}
+
+/// Marker found while decoding, for a table of contents of the codestream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerOccurrence {
+    marker: [u8; 2],
+    offset: u64,
+    length: Option<u16>,
+}
+
+impl MarkerOccurrence {
+    /// Marker, such as 0xFF51 for SIZ.
+    pub fn marker(&self) -> u16 {
+        u16::from_be_bytes(self.marker)
+    }
+
+    /// Name of the marker, such as "SIZ", or "unknown" for a marker this
+    /// crate does not recognise.
+    pub fn name(&self) -> &'static str {
+        MarkerSymbol(self.marker).name().unwrap_or("unknown")
+    }
+
+    /// Byte offset of the marker.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the marker segment in bytes, not including the marker, or
+    /// `None` for a marker without a marker segment, such as SOC.
+    pub fn length(&self) -> Option<u16> {
+        self.length
     }
 }
 
@@ -1605,6 +2443,18 @@ pub enum QuantizationStyle {
     Reserved(u8),
 }
 
+// Names from Table A.28
+impl fmt::Display for QuantizationStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuantizationStyle::NoQuantization => f.write_str("no quantization"),
+            QuantizationStyle::ScalarDerived => f.write_str("scalar derived"),
+            QuantizationStyle::ScalarExpounded => f.write_str("scalar expounded"),
+            QuantizationStyle::Reserved(value) => write!(f, "reserved ({value})"),
+        }
+    }
+}
+
 impl QuantizationInfo {
     const SHIFT_GUARD: u8 = 5; // guard bits are encoded into top 3 bits of a u8
     const SHIFT_EXP: u8 = 3;
@@ -1657,6 +2507,100 @@ impl QuantizationInfo {
         }
     }
 
+    /// Number of magnitude bit-planes of a sub-band, M<sub>b</sub>.
+    ///
+    /// This is the bit budget of the coefficients of the sub-band, with the
+    /// guard bits and the exponent of its step size: M<sub>b</sub> = G +
+    /// ε<sub>b</sub> - 1, Equation E-2. Without quantization, ε<sub>b</sub>
+    /// is the component precision plus the gain of the sub-band, so the guard
+    /// bits are what allows the coefficients to exceed that range. A
+    /// code-block's zero bit-planes are counted down from M<sub>b</sub>.
+    ///
+    /// The sub-bands are as for [QuantizationInfo::step_size].
+    pub fn magnitude_bit_planes(
+        &self,
+        resolution_level: u8,
+        subband: SubBandType,
+    ) -> Result<u8, Box<dyn error::Error>> {
+        let step_size = self.step_size(resolution_level, subband)?;
+        Ok((self.guard_bits + step_size.exponent).saturating_sub(1))
+    }
+
+    /// Number of step sizes signalled, one for each sub-band unless the step
+    /// sizes are derived from the LL sub-band.
+    pub fn no_step_sizes(&self) -> usize {
+        match self.style {
+            QuantizationStyle::NoQuantization => self.values_bytes.len(),
+            QuantizationStyle::ScalarDerived => 1,
+            QuantizationStyle::ScalarExpounded => self.values_bytes.len() / 2,
+            QuantizationStyle::Reserved(_) => 0,
+        }
+    }
+
+    /// Step size of a sub-band.
+    ///
+    /// Resolution level 0 has the LL sub-band only, and each following
+    /// resolution level has the HL, LH and HH sub-bands, which is the order
+    /// the step sizes are signalled in. Derived step sizes are computed from
+    /// the LL step size with Equation E-5, where the exponent decreases by one
+    /// for each resolution level after the first.
+    ///
+    /// See ITU-T T.800(V4) or ISO/IEC 15444-1:2024 Section A.6.4 and E.1.1.
+    pub fn step_size(
+        &self,
+        resolution_level: u8,
+        subband: SubBandType,
+    ) -> Result<StepSize, Box<dyn error::Error>> {
+        let index = match (resolution_level, subband) {
+            (0, SubBandType::LL) => 0,
+            (0, _) | (_, SubBandType::LL) => {
+                return Err(CodestreamError::InvalidParameter { name: "subband" }.into())
+            }
+            (r, SubBandType::HL) => 3 * usize::from(r) - 2,
+            (r, SubBandType::LH) => 3 * usize::from(r) - 1,
+            (r, SubBandType::HH) => 3 * usize::from(r),
+        };
+        let out_of_range = CodestreamError::InvalidParameter {
+            name: "resolution_level",
+        };
+        match self.style {
+            QuantizationStyle::NoQuantization => {
+                let value = self.values_bytes.get(index).ok_or(out_of_range)?;
+                Ok(StepSize {
+                    exponent: Self::exponent_from_quant_value(value),
+                    mantissa: 0,
+                })
+            }
+            QuantizationStyle::ScalarDerived => {
+                let step_size = StepSize::from_value(self.values()[0]);
+                // E-5, with n_b = N_L - r + 1 for the high-pass sub-bands
+                let exponent = match resolution_level {
+                    0 => Some(step_size.exponent),
+                    r => (step_size.exponent + 1).checked_sub(r),
+                };
+                Ok(StepSize {
+                    exponent: exponent.ok_or(out_of_range)?,
+                    mantissa: step_size.mantissa,
+                })
+            }
+            QuantizationStyle::ScalarExpounded => {
+                let value = self
+                    .values_bytes
+                    .chunks_exact(2)
+                    .nth(index)
+                    .ok_or(out_of_range)?;
+                Ok(StepSize::from_value(u16::from_be_bytes([
+                    value[0], value[1],
+                ])))
+            }
+            QuantizationStyle::Reserved(_) => Err(CodestreamError::UnsupportedFeature {
+                marker: MARKER_SYMBOL_QCD,
+                offset: 0,
+            }
+            .into()),
+        }
+    }
+
     /// raw values
     pub fn values(&self) -> Vec<u16> {
         info!("Weird to grab raw values for quantization style");
@@ -1697,7 +2641,7 @@ impl QuantizationInfo {
         let style_code = qb & 0b11111; // 5 bits for style
         let style = match style_code {
             0 => {
-                if !(length - 2).is_multiple_of(3) {
+                if length < 2 || !(length - 2).is_multiple_of(3) {
                     Err(CodestreamError::InputFormatError {
                         error: String::from("Invalid length for quantization style"),
                     })?
@@ -1713,7 +2657,7 @@ impl QuantizationInfo {
                 QuantizationStyle::ScalarDerived
             }
             2 => {
-                if !(length - 3).is_multiple_of(6) {
+                if length < 3 || !(length - 3).is_multiple_of(6) {
                     Err(CodestreamError::InputFormatError {
                         error: String::from("Invalid length for quantization style"),
                     })?
@@ -1739,6 +2683,40 @@ impl QuantizationInfo {
     }
 }
 
+/// Quantization step size of a sub-band, as an exponent and mantissa.
+///
+/// Without quantization only the exponent is signalled, and the mantissa is
+/// 0.
+///
+/// See ITU-T T.800(V4) or ISO/IEC 15444-1:2024 Section A.6.4 and E.1.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepSize {
+    /// Exponent, ε<sub>b</sub>, 5 bits.
+    pub exponent: u8,
+    /// Mantissa, μ<sub>b</sub>, 11 bits.
+    pub mantissa: u16,
+}
+
+impl StepSize {
+    // Exponent in the 5 most significant bits, then the mantissa
+    fn from_value(value: u16) -> StepSize {
+        StepSize {
+            exponent: (value >> 11) as u8,
+            mantissa: value & 0x07FF,
+        }
+    }
+
+    /// Quantization step size, Δ<sub>b</sub> from Equation E-3, for a sub-band
+    /// with the given nominal dynamic range R<sub>b</sub>.
+    ///
+    /// The nominal dynamic range is the component precision plus the gain of
+    /// the sub-band, from Table E.1.
+    pub fn value(&self, nominal_dynamic_range: u8) -> f64 {
+        let exponent = i32::from(nominal_dynamic_range) - i32::from(self.exponent);
+        2f64.powi(exponent) * (1.0 + f64::from(self.mantissa) / 2048.0)
+    }
+}
+
 #[derive(Debug)]
 enum QuantizationValue {
     Reversible { value: [u8; 1] },
@@ -1792,6 +2770,19 @@ pub struct QuantizationDefaultMarkerSegment {
 }
 
 impl QuantizationDefaultMarkerSegment {
+    /// Decode a QCD marker segment from its bytes, starting with the length
+    /// field and excluding the marker.
+    ///
+    /// The segment is decoded as if its marker were at the start of a
+    /// codestream, so byte offsets are relative to the marker.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn error::Error>> {
+        ContiguousCodestream::decode_segment_bytes(
+            MARKER_SYMBOL_QCD,
+            bytes,
+            |codestream, reader| codestream.decode_qcd(reader),
+        )
+    }
+
     pub fn length(&self) -> u16 {
         self.length
     }
@@ -1811,6 +2802,15 @@ impl QuantizationDefaultMarkerSegment {
     pub fn quantization_values(&self) -> Vec<u16> {
         self.quantization_info.values()
     }
+
+    /// Step size of a sub-band, see [`QuantizationInfo::step_size`].
+    pub fn step_size(
+        &self,
+        resolution_level: u8,
+        subband: SubBandType,
+    ) -> Result<StepSize, Box<dyn error::Error>> {
+        self.quantization_info.step_size(resolution_level, subband)
+    }
 }
 
 // A.6.5
@@ -1844,6 +2844,15 @@ impl QuantizationComponentSegment {
     pub fn quantization_info(&self) -> &QuantizationInfo {
         &self.quantization_info
     }
+
+    /// Step size of a sub-band, see [`QuantizationInfo::step_size`].
+    pub fn step_size(
+        &self,
+        resolution_level: u8,
+        subband: SubBandType,
+    ) -> Result<StepSize, Box<dyn error::Error>> {
+        self.quantization_info.step_size(resolution_level, subband)
+    }
 }
 
 // Contiguous Codestream
@@ -1867,6 +2876,11 @@ pub struct ContiguousCodestream {
     length: u16,
     header: Header,
     tile_parts: Vec<TilePart>,
+    limits: DecodeLimits,
+    // Byte offset of the first SOT marker, following the main header
+    first_tile_part_offset: u64,
+    report: DecodeReport,
+    markers: Vec<MarkerOccurrence>,
 }
 
 impl ContiguousCodestream {
@@ -1874,6 +2888,379 @@ impl ContiguousCodestream {
         &self.header
     }
 
+    /// Tile-parts in codestream order.
+    pub fn tile_parts(&self) -> &[TilePart] {
+        &self.tile_parts
+    }
+
+    /// Markers in the main and tile-part headers, with the SOT and EOC
+    /// markers, in codestream order.
+    ///
+    /// Markers within the tile-part data, such as SOP and EPH, are not
+    /// included.
+    pub fn marker_map(&self) -> &[MarkerOccurrence] {
+        &self.markers
+    }
+
+    // Record the marker just read, with the length of its marker segment
+    fn record_marker<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        marker: MarkerSymbol,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let offset = reader.stream_position()? - 2;
+        let length = if marker.has_segment() {
+            let mut length = [0u8; 2];
+            reader.read_exact(&mut length)?;
+            reader.seek(io::SeekFrom::Current(-2))?;
+            Some(u16::from_be_bytes(length))
+        } else {
+            None
+        };
+        self.markers.push(MarkerOccurrence {
+            marker: marker.0,
+            offset,
+            length,
+        });
+        Ok(())
+    }
+
+    /// Tiles with at least one tile-part, in tile index order.
+    pub fn tiles(&self) -> Vec<Tile<'_>> {
+        let mut tiles: Vec<Tile<'_>> = Vec::new();
+        for tile_part in &self.tile_parts {
+            let index = tile_part.start_of_tile_segment().tile_index();
+            match tiles.iter_mut().find(|tile| tile.index == index) {
+                Some(tile) => tile.tile_parts.push(tile_part),
+                None => tiles.push(Tile {
+                    index,
+                    tile_parts: vec![tile_part],
+                    header: &self.header,
+                }),
+            }
+        }
+        tiles.sort_by_key(|tile| tile.index);
+        for tile in &mut tiles {
+            tile.tile_parts
+                .sort_by_key(|tile_part| tile_part.start_of_tile_segment().tile_part_index());
+        }
+        tiles
+    }
+
+    /// Tile `index`, if it has at least one tile-part.
+    pub fn tile(&self, index: u16) -> Option<Tile<'_>> {
+        self.tiles().into_iter().find(|tile| tile.index == index)
+    }
+
+    /// Report of decoding the codestream so far, with any warnings found
+    /// while reading the tile-parts.
+    pub fn report(&self) -> &DecodeReport {
+        &self.report
+    }
+
+    /// Whether the code-blocks use the high throughput (HTJ2K) block coder
+    /// of ITU-T T.814 | ISO/IEC 15444-15, rather than that of Annex D.
+    ///
+    /// This needs only the main header: Rsiz signals the CAP marker segment,
+    /// and the CAP marker segment has Pcap<sup>15</sup> set, with Ccap<sup>15</sup>
+    /// present.
+    pub fn is_high_throughput(&self) -> bool {
+        self.header
+            .image_and_tile_size_marker_segment()
+            .capabilities()
+            .contains(&DecoderCapabilities::ExtendedCapabilities)
+            && self
+                .header
+                .extended_capabilities_marker_segment()
+                .as_ref()
+                .and_then(|cap| cap.capabilities().get(14).copied().flatten())
+                .is_some()
+    }
+
+    // TLM entries of all marker segments, concatenated in Ztlm order
+    fn tlm_entries(&self) -> Vec<&TilePartLength> {
+        let mut segments: Vec<&TilePartLengthsSegment> =
+            self.header.tile_part_lengths_segments().iter().collect();
+        segments.sort_by_key(|segment| segment.segment_index());
+        segments
+            .into_iter()
+            .flat_map(|segment| segment.tile_part_lengths().iter())
+            .collect()
+    }
+
+    // Offset and length of a tile-part from the TLM marker segments, if any
+    fn tile_part_range_from_tlm(&self, tile: u16, part: u8) -> Option<(u64, u64)> {
+        let mut offset = self.first_tile_part_offset;
+        let mut parts_seen: HashMap<u16, u8> = HashMap::new();
+        for (i, entry) in self.tlm_entries().into_iter().enumerate() {
+            // Without Ttlm, there is one tile-part per tile in index order
+            let (entry_tile, entry_part) = match *entry.tile_index() {
+                Some(tile_index) => {
+                    let seen = parts_seen.entry(tile_index).or_insert(0);
+                    let entry_part = *seen;
+                    *seen = seen.saturating_add(1);
+                    (tile_index as usize, entry_part)
+                }
+                None => (i, 0),
+            };
+            if entry_tile == tile as usize && entry_part == part {
+                return Some((offset, entry.tile_length() as u64));
+            }
+            offset += entry.tile_length() as u64;
+        }
+        None
+    }
+
+    // Offset and length of a tile-part from the SOT marker segments
+    fn tile_part_range_from_sot<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+        tile: u16,
+        part: u8,
+    ) -> Result<(u64, u64), Box<dyn error::Error>> {
+        let tile_part = self.tile_part(tile, part)?;
+        let sot = tile_part.start_of_tile_segment();
+        let length = match sot.tile_length() {
+            // The tile-part continues to the EOC marker
+            0 => reader.seek(io::SeekFrom::End(-2))? - sot.offset(),
+            tile_length => tile_length as u64,
+        };
+        Ok((sot.offset(), length))
+    }
+
+    fn tile_part(&self, tile: u16, part: u8) -> Result<&TilePart, CodestreamError> {
+        self.tile_parts
+            .iter()
+            .find(|tile_part| {
+                let sot = tile_part.start_of_tile_segment();
+                sot.tile_index() == tile && sot.tile_part_index() == part
+            })
+            .ok_or(CodestreamError::InvalidParameter { name: "tile_part" })
+    }
+
+    /// Raw bytes of tile-part `part` of tile `tile`, from the SOT marker to
+    /// the end of the tile-part data, read from `reader` without decoding.
+    ///
+    /// The tile-part is located with the TLM marker segments when present,
+    /// and otherwise with the SOT marker segments found while decoding.
+    pub fn tile_part_bytes<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+        tile: u16,
+        part: u8,
+    ) -> Result<Vec<u8>, Box<dyn error::Error>> {
+        let (offset, length) = match self.tile_part_range_from_tlm(tile, part) {
+            Some(range) => range,
+            None => self.tile_part_range_from_sot(reader, tile, part)?,
+        };
+        let bytes = read_range(reader, offset, length)?;
+        if !bytes.starts_with(&MARKER_SYMBOL_SOT.0) {
+            return Err(CodestreamError::MarkerMissing {
+                marker: MARKER_SYMBOL_SOT,
+            }
+            .into());
+        }
+        Ok(bytes)
+    }
+
+    /// Packet data of tile `tile`, read from `reader`.
+    ///
+    /// The data of each tile-part, following the SOD marker, is concatenated
+    /// in TPsot order, giving the packets of the tile in progression order.
+    pub fn tile_data<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+        tile: u16,
+    ) -> Result<Vec<u8>, Box<dyn error::Error>> {
+        let tile = self
+            .tile(tile)
+            .ok_or(CodestreamError::InvalidParameter { name: "tile" })?;
+        let mut data = Vec::new();
+        for tile_part in tile.tile_parts() {
+            data.extend(read_range(
+                reader,
+                tile_part.data_offset(),
+                tile_part.data_length(),
+            )?);
+        }
+        Ok(data)
+    }
+
+    /// Raw bytes of packet `packet` within tile-part `part` of tile `tile`,
+    /// located with the PLT marker segments of the tile-part.
+    ///
+    /// If the packet headers are in PPM or PPT marker segments, the bytes do
+    /// not include the packet header.
+    pub fn packet_bytes<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+        tile: u16,
+        part: u8,
+        packet: usize,
+    ) -> Result<Vec<u8>, Box<dyn error::Error>> {
+        let tile_part = self.tile_part(tile, part)?;
+        let mut segments: Vec<&TilePacketLength> =
+            tile_part.packet_length_segments().iter().collect();
+        if segments.is_empty() {
+            return Err(CodestreamError::MarkerMissing {
+                marker: MARKER_SYMBOL_PLT,
+            }
+            .into());
+        }
+        segments.sort_by_key(|segment| segment.segment_index());
+        let packet_lengths: Vec<u32> = segments
+            .into_iter()
+            .flat_map(|segment| segment.packet_lengths().iter().cloned())
+            .collect();
+
+        let length = *packet_lengths
+            .get(packet)
+            .ok_or(CodestreamError::InvalidParameter { name: "packet" })?;
+        let offset = tile_part.data_offset()
+            + packet_lengths[..packet]
+                .iter()
+                .map(|&length| length as u64)
+                .sum::<u64>();
+        read_range(reader, offset, length as u64)
+    }
+
+    /// Decode the image, reading the tile data from `reader`.
+    ///
+    /// Only the simplest codestreams can be decoded so far: a single tile of
+    /// components without sub-sampling, with the reversible 5-3 filter and
+    /// optionally the RCT, no code-block coding style options other than the
+    /// selective arithmetic coding bypass, and no region of interest,
+    /// progression order changes or packed packet headers. Any precinct
+    /// sizes may be used. Anything else is reported as an unsupported feature
+    /// of the marker segment that signals it.
+    ///
+    /// The image is greyscale for one or two components and sRGB otherwise,
+    /// with the samples of the image area after the inverse component
+    /// transformation and DC level shift, and a report of what was decoded.
+    pub fn decode_image<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<DecodedImage, Box<dyn error::Error>> {
+        decode::decode_image(self, reader)
+    }
+
+    /// Code-blocks of the image, with their coded data, reading the tile
+    /// data from `reader`.
+    ///
+    /// This parses the packet headers and stops before decoding the
+    /// code-blocks, for decoding them separately. The codestream must be one
+    /// that [ContiguousCodestream::decode_image] supports. There is one entry
+    /// for every code-block of every sub-band, ordered by component, then by
+    /// resolution level, then by sub-band in the order LL, or HL, LH and HH, then in raster
+    /// order within the sub-band. See [CodedCodeBlock] for the layout of
+    /// each.
+    pub fn code_blocks<R: io::Read + io::Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Vec<CodedCodeBlock>, Box<dyn error::Error>> {
+        decode::read_code_blocks(self, reader)
+    }
+
+    /// Packet headers of tile-part `part` of tile `tile`, when they are held
+    /// in PPM or PPT marker segments rather than in the tile-part data.
+    ///
+    /// With PPM, the marker segments are concatenated in Zppm order and
+    /// divided into tile-parts, in codestream order, by the Nppm parameters.
+    /// With PPT, the marker segments of the tile-part are concatenated in
+    /// Zppt order. The packet headers are then read from the returned bytes,
+    /// and the packet bodies from the tile-part data. Returns `None` if the
+    /// packet headers are in the tile-part data.
+    pub fn packed_packet_headers(
+        &self,
+        tile: u16,
+        part: u8,
+    ) -> Result<Option<Vec<u8>>, Box<dyn error::Error>> {
+        let tile_part = self.tile_part(tile, part)?;
+
+        let mut ppm: Vec<&PackedPacketHeaderSegment> =
+            self.header.packed_packet_headers.iter().collect();
+        if !ppm.is_empty() {
+            ppm.sort_by_key(|segment| segment.index());
+            let malformed = || CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_PPM,
+                offset: ppm[0].offset - 2,
+            };
+            let data: Vec<u8> = ppm
+                .iter()
+                .flat_map(|segment| segment.data.iter().cloned())
+                .collect();
+            let position = self
+                .tile_parts
+                .iter()
+                .position(|other| std::ptr::eq(other, tile_part))
+                .unwrap();
+
+            let mut remaining = &data[..];
+            for _ in 0..position {
+                remaining = skip_ppm_tile_part(remaining).ok_or_else(malformed)?;
+            }
+            let rest = skip_ppm_tile_part(remaining).ok_or_else(malformed)?;
+            let headers = &remaining[4..remaining.len() - rest.len()];
+            return Ok(Some(headers.to_vec()));
+        }
+
+        let mut ppt: Vec<&TilePackedPacketHeaderSegment> =
+            tile_part.packed_packet_headers_segments().iter().collect();
+        if ppt.is_empty() {
+            return Ok(None);
+        }
+        ppt.sort_by_key(|segment| segment.index());
+        Ok(Some(
+            ppt.into_iter()
+                .flat_map(|segment| segment.data.iter().cloned())
+                .collect(),
+        ))
+    }
+
+    /// Check each TLM entry against the SOT marker segment of the
+    /// corresponding tile-part.
+    ///
+    /// The TLM marker segments are concatenated in Ztlm order, and the ith
+    /// entry describes the ith tile-part in the codestream. A Psot of 0, only
+    /// allowed for the last tile-part, is not compared. Some encoders write
+    /// inexact TLM marker segments, so this is not checked while decoding.
+    /// Without any TLM marker segments there is nothing to check.
+    pub fn validate_tlm(&self) -> Result<(), TlmMismatch> {
+        if self.header.tile_part_lengths_segments().is_empty() {
+            return Ok(());
+        }
+        let entries = self.tlm_entries();
+
+        if entries.len() != self.tile_parts.len() {
+            return Err(TlmMismatch::Count {
+                tlm_entries: entries.len(),
+                tile_parts: self.tile_parts.len(),
+            });
+        }
+
+        for (tile_part, (entry, part)) in entries.iter().zip(self.tile_parts.iter()).enumerate() {
+            let sot = part.start_of_tile_segment();
+            if let Some(tlm_tile_index) = *entry.tile_index() {
+                if tlm_tile_index != sot.tile_index() {
+                    return Err(TlmMismatch::TileIndex {
+                        tile_part,
+                        tlm_tile_index,
+                        sot_tile_index: sot.tile_index(),
+                    });
+                }
+            }
+            if sot.tile_length() != 0 && entry.tile_length() != sot.tile_length() {
+                return Err(TlmMismatch::Length {
+                    tile_part,
+                    tile_index: sot.tile_index(),
+                    tlm_length: entry.tile_length(),
+                    psot: sot.tile_length(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     // Length of marker segment in bytes (not including the marker).
     fn decode_length<R: io::Read + io::Seek>(
         &mut self,
@@ -1884,6 +3271,22 @@ impl ContiguousCodestream {
         Ok(u16::from_be_bytes(length))
     }
 
+    // Length of marker segment in bytes (not including the marker), which
+    // shall cover at least the fixed size parameters of the segment.
+    fn decode_length_minimum<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+        marker: MarkerSymbol,
+        minimum: u16,
+    ) -> Result<u16, Box<dyn error::Error>> {
+        let offset = reader.stream_position()? - 2;
+        let length = self.decode_length(reader)?;
+        if length < minimum {
+            return Err(CodestreamError::MarkerMalformed { marker, offset }.into());
+        }
+        Ok(length)
+    }
+
     fn decode_siz<R: io::Read + io::Seek>(
         &mut self,
         reader: &mut R,
@@ -1906,6 +3309,30 @@ impl ContiguousCodestream {
         reader.read_exact(&mut segment.tile_vertical_offset)?;
         reader.read_exact(&mut segment.no_components)?;
 
+        // Lsiz = 38 + 3 · Csiz
+        let offset = segment.offset - 2;
+        if segment.length < 38 || !(segment.length - 38).is_multiple_of(3) {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_SIZ,
+                offset,
+            }
+            .into());
+        }
+        if segment.no_components() == 0 {
+            return Err(SizError::NoComponents { offset }.into());
+        }
+        let length_implies = (segment.length - 38) / 3;
+        if segment.no_components() != length_implies {
+            return Err(SizError::ComponentCountMismatch {
+                declared: segment.no_components(),
+                length_implies,
+                offset,
+            }
+            .into());
+        }
+
+        self.limits.check_size(&segment)?;
+
         let no_components = segment.no_components();
 
         segment.precision = Vec::with_capacity(no_components as usize);
@@ -1927,45 +3354,87 @@ impl ContiguousCodestream {
             segment.vertical_separation.push(vertical_separation);
         }
 
-        // The tile grid offsets (XTOsiz, YTOsiz) are constrained to be no
-        // greater than the image area offsets. This is expressed by the
-        // following ranges
-        // 0 ≤ XTOsiz ≤ XOsiz
-        // 0 ≤ YTOsiz ≤ YOsiz
-        if segment.tile_horizontal_offset() > segment.image_horizontal_offset()
-            || segment.tile_vertical_offset() > segment.image_vertical_offset()
-        {
-            return Err(CodestreamError::TileGridOffsetOverflow {
-                tile_horizontal_offset: segment.tile_horizontal_offset(),
-                image_horizontal_offset: segment.image_horizontal_offset(),
-                tile_vertical_offset: segment.tile_vertical_offset(),
-                image_vertical_offset: segment.image_vertical_offset(),
+        self.limits.check_allocation(&segment)?;
+
+        // The image area offsets are within the reference grid, Equation B-1
+        // 0 ≤ XOsiz < Xsiz
+        // 0 ≤ YOsiz < Ysiz
+        let offset = segment.offset - 2;
+        let axes = [
+            (
+                'X',
+                segment.reference_grid_width(),
+                segment.image_horizontal_offset(),
+                segment.reference_tile_width(),
+                segment.tile_horizontal_offset(),
+            ),
+            (
+                'Y',
+                segment.reference_grid_height(),
+                segment.image_vertical_offset(),
+                segment.reference_tile_height(),
+                segment.tile_vertical_offset(),
+            ),
+        ];
+        for (axis, grid_size, image_offset, _, _) in axes {
+            if image_offset >= grid_size {
+                return Err(SizError::ImageOffsetOutOfRange {
+                    axis,
+                    image_offset,
+                    grid_size,
+                    offset,
+                }
+                .into());
             }
-            .into());
         }
 
-        // Also, the tile size plus the tile offset shall be greater than the image
-        // area offset. This ensures that the first tile (tile 0) will contain at least
-        // one reference grid point from the image area. This is expressed by the
-        // following ranges
-        //
-        // XTsiz + XTOsiz > XOsiz
-        // YTsiz + YTOsiz > YOsiz
-        if ((segment.reference_tile_width() + segment.tile_horizontal_offset())
-            < segment.image_horizontal_offset())
-            || ((segment.reference_tile_height() + segment.tile_vertical_offset())
-                < segment.image_vertical_offset())
+        // The tiles shall not be empty, and the component sub-sampling
+        // factors range from 1 to 255
+        if segment.reference_tile_width() == 0
+            || segment.reference_tile_height() == 0
+            || segment.horizontal_separation.contains(&[0])
+            || segment.vertical_separation.contains(&[0])
         {
-            return Err(CodestreamError::TileSizeOverflow {
-                reference_tile_width: segment.reference_tile_width(),
-                tile_horizontal_offset: segment.tile_horizontal_offset(),
-                image_horizontal_offset: segment.image_horizontal_offset(),
-                reference_tile_height: segment.reference_tile_height(),
-                tile_vertical_offset: segment.tile_vertical_offset(),
-                image_vertical_offset: segment.image_vertical_offset(),
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_SIZ,
+                offset,
             }
             .into());
         }
+
+        // The tile grid offsets are no greater than the image area offsets,
+        // and the first tile holds at least one reference grid point of the
+        // image area, Equation B-3
+        // 0 ≤ XTOsiz ≤ XOsiz, XTsiz + XTOsiz > XOsiz
+        // 0 ≤ YTOsiz ≤ YOsiz, YTsiz + YTOsiz > YOsiz
+        for (axis, _, image_offset, tile_size, tile_offset) in axes {
+            if tile_offset > image_offset {
+                return Err(SizError::TileOffsetOutOfRange {
+                    axis,
+                    tile_offset,
+                    image_offset,
+                    offset,
+                }
+                .into());
+            }
+            let tile_end = u64::from(tile_offset) + u64::from(tile_size);
+            if tile_end <= u64::from(image_offset) {
+                return Err(SizError::FirstTileOutsideImage {
+                    axis,
+                    tile_end,
+                    image_offset,
+                    offset,
+                }
+                .into());
+            }
+        }
+
+        // The number of tiles, numXtiles · numYtiles, shall be representable
+        segment
+            .num_x_tiles()?
+            .checked_mul(segment.num_y_tiles()?)
+            .ok_or(CodestreamError::GridOverflow { name: "numtiles" })?;
+
         info!("SIZ end at byte offset {}", reader.stream_position()?);
 
         Ok(segment)
@@ -1976,13 +3445,13 @@ impl ContiguousCodestream {
         reader: &mut R,
     ) -> Result<ExtendedCapabilitiesMarkerSegment, Box<dyn error::Error>> {
         log::info!("CAP start at byte offset {}", reader.stream_position()? - 2);
-        let mut segment = ExtendedCapabilitiesMarkerSegment::default();
-
         // Lcap
-        let mut marker_segment_length = [0u8; 2];
-        reader.read_exact(&mut marker_segment_length)?;
-        segment.length = u16::from_be_bytes(marker_segment_length);
-        segment.capabilities = Vec::<Option<u16>>::with_capacity(32);
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_CAP, 6)?;
+        let mut segment = ExtendedCapabilitiesMarkerSegment {
+            length,
+            capabilities: Vec::<Option<u16>>::with_capacity(32),
+            ..Default::default()
+        };
 
         // Pcap
         let mut capability_flags_present = [0u8; 4];
@@ -2021,27 +3490,27 @@ impl ContiguousCodestream {
         &mut self,
         reader: &mut R,
     ) -> Result<CorrespondingProfileMarkerSegment, Box<dyn error::Error>> {
-        log::info!("CPF start at byte offset {}", reader.stream_position()? - 2);
-        let mut segment = CorrespondingProfileMarkerSegment::default();
-
-        // Lcpf
-        let mut marker_segment_length_bytes = [0u8; 2];
-        reader.read_exact(&mut marker_segment_length_bytes)?;
-        segment.length = u16::from_be_bytes(marker_segment_length_bytes);
-
-        // Pcpf
-        let num_pfcp = (segment.length - 2) / 2;
-        if num_pfcp > 1 {
-            // Supporting more is possible, but we need a sanity check to prevent CPFnum overflow
-            log::error!("Only a single Pcpf value is supported at this time");
-            return Err(CodestreamError::UnsupportedFeature {
+        let offset = reader.stream_position()? - 2;
+        log::info!("CPF start at byte offset {offset}");
+        // Lcpf = 2 + 2 · N, for at least one Pcpf
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_CPF, 4)?;
+        if length % 2 != 0 {
+            return Err(CodestreamError::MarkerMalformed {
                 marker: MARKER_SYMBOL_CPF,
-                offset: self.offset,
+                offset,
             }
             .into());
         }
+        let num_pcpf = (length - 2) / 2;
+        let mut segment = CorrespondingProfileMarkerSegment {
+            offset: offset + 2,
+            length,
+            pcpf: Vec::with_capacity(num_pcpf as usize),
+        };
+
+        // Pcpf
         let mut pcpf_bytes = [0u8; 2];
-        for _ in 0..num_pfcp {
+        for _ in 0..num_pcpf {
             reader.read_exact(&mut pcpf_bytes)?;
             let pcpf = u16::from_be_bytes(pcpf_bytes);
             segment.pcpf.push(pcpf);
@@ -2216,7 +3685,7 @@ impl ContiguousCodestream {
         info!("POC start at byte offset {}", reader.stream_position()? - 2);
         let mut segment = ProgressionOrderChangeSegment {
             offset: reader.stream_position()?,
-            length: self.decode_length(reader)?,
+            length: self.decode_length_minimum(reader, MARKER_SYMBOL_POC, 2)?,
             ..Default::default()
         };
 
@@ -2261,20 +3730,18 @@ impl ContiguousCodestream {
     ) -> Result<PackedPacketHeaderSegment, Box<dyn error::Error>> {
         info!("PPM start at byte offset {}", reader.stream_position()? - 2);
         let offset = reader.stream_position()?;
-        let length = self.decode_length(reader)?;
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_PPM, 4)?;
+        // A marker segment may start with the continuation of the Ippm
+        // parameters from the previous one rather than with Nppm, so the
+        // series is only divided into tile-parts once all are read.
         let mut segment = PackedPacketHeaderSegment {
             offset,
             length,
             index: [0],
-            number_of_bytes: [0; 4],
-            // TODO: It is possible that the next PPM marker segment will not
-            // have an Nppm parameter after Zppm, but the continuation of the
-            // Ippm series from the last PPM marker segment.
-            data: vec![0; (length as usize) - 7],
+            data: vec![0; (length as usize) - 3],
         };
 
         reader.read_exact(&mut segment.index)?;
-        reader.read_exact(&mut segment.number_of_bytes)?;
         reader.read_exact(&mut segment.data)?;
         info!("PPM end at byte offset {}", reader.stream_position()?);
 
@@ -2287,7 +3754,7 @@ impl ContiguousCodestream {
     ) -> Result<TilePackedPacketHeaderSegment, Box<dyn error::Error>> {
         info!("PPT start at byte offset {}", reader.stream_position()? - 2);
         let offset = reader.stream_position()?;
-        let length = self.decode_length(reader)?;
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_PPT, 3)?;
         let mut segment = TilePackedPacketHeaderSegment {
             offset,
             length,
@@ -2310,13 +3777,23 @@ impl ContiguousCodestream {
         info!("TLM start at byte offset {}", reader.stream_position()? - 2);
         let mut segment = TilePartLengthsSegment {
             offset: reader.stream_position()?,
-            length: self.decode_length(reader)?,
+            length: self.decode_length_minimum(reader, MARKER_SYMBOL_TLM, 4)?,
             ..Default::default()
         };
         reader.read_exact(&mut segment.index)?;
         reader.read_exact(&mut segment.parameter_sizes)?;
 
         let parameter_sizes = segment.parameter_sizes();
+        if parameter_sizes
+            .iter()
+            .any(|size| matches!(size, TilePartParameterSize::Reserved { .. }))
+        {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_TLM,
+                offset: segment.offset - 2,
+            }
+            .into());
+        }
 
         let mut tile_part_size = 0;
         if parameter_sizes.contains(&TilePartParameterSize::Ttlm8Bit) {
@@ -2331,6 +3808,13 @@ impl ContiguousCodestream {
         }
 
         // number of tile lengths
+        if !(segment.length - 4).is_multiple_of(tile_part_size) {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_TLM,
+                offset: segment.offset - 2,
+            }
+            .into());
+        }
         let no_tile_part_lengths = (segment.length - 4) / tile_part_size;
 
         for _ in 0..no_tile_part_lengths {
@@ -2371,7 +3855,7 @@ impl ContiguousCodestream {
         reader: &mut R,
     ) -> Result<QuantizationDefaultMarkerSegment, Box<dyn error::Error>> {
         info!("QCD start at byte offset {}", reader.stream_position()? - 2);
-        let length = self.decode_length(reader)?;
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_QCD, 3)?;
         let quantization_style = QuantizationInfo::decode(reader, length - 2)?;
         info!("QCD end at byte offset {}", reader.stream_position()?);
 
@@ -2388,13 +3872,12 @@ impl ContiguousCodestream {
     ) -> Result<QuantizationComponentSegment, Box<dyn error::Error>> {
         info!("QCC start at byte offset {}", reader.stream_position()? - 2);
         let offset = reader.stream_position()?;
-        let length = self.decode_length(reader)?;
+        let len_comp = if no_components < 257 { 1 } else { 2 };
+        let length = self.decode_length_minimum(reader, MARKER_SYMBOL_QCC, 3 + len_comp)?;
 
         // Cqcc
         let component_index = self.decode_component_index(reader, no_components)?;
 
-        let len_comp = if no_components < 257 { 1 } else { 2 };
-
         let quantization_info = QuantizationInfo::decode(reader, length - (2 + len_comp))?;
 
         info!("QCC end at byte offset {}", reader.stream_position()?);
@@ -2472,13 +3955,44 @@ impl ContiguousCodestream {
         info!("PLT start at byte offset {}", reader.stream_position()? - 2);
         let mut segment = TilePacketLength {
             offset: reader.stream_position()?,
-            length: self.decode_length(reader)?,
+            length: self.decode_length_minimum(reader, MARKER_SYMBOL_PLT, 3)?,
             ..Default::default()
         };
 
         reader.read_exact(&mut segment.index)?;
 
-        self.decode_packet_length(reader, &mut segment.packet_length)?;
+        // Iplt values continue to the end of the marker segment
+        let end = segment.offset + segment.length as u64;
+        let mut packet_length: u32 = 0;
+        // Whether a byte with the high bit set is waiting for the last byte
+        // of its length
+        let mut pending = false;
+        while reader.stream_position()? < end {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            // Each byte holds 7 bits of the length, most significant first,
+            // with the high bit set on all but the last byte
+            packet_length =
+                packet_length
+                    .checked_mul(1 << 7)
+                    .ok_or(CodestreamError::MarkerMalformed {
+                        marker: MARKER_SYMBOL_PLT,
+                        offset: segment.offset - 2,
+                    })?
+                    | (byte[0] & 0x7F) as u32;
+            pending = byte[0] & 0x80 != 0;
+            if !pending {
+                segment.packet_length.push(packet_length);
+                packet_length = 0;
+            }
+        }
+        if pending {
+            return Err(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_PLT,
+                offset: segment.offset - 2,
+            }
+            .into());
+        }
 
         info!("PLT end at byte offset {}", reader.stream_position()?);
 
@@ -2522,13 +4036,10 @@ impl ContiguousCodestream {
         let mut segment = CommentMarkerSegment::default();
 
         // Length of marker segment in bytes (not including the marker).
-        let mut marker_segment_length = [0u8; 2];
-        reader.read_exact(&mut marker_segment_length)?;
+        let marker_segment_length = self.decode_length_minimum(reader, MARKER_SYMBOL_COM, 4)?;
         reader.read_exact(&mut segment.registration_value)?;
 
-        let comment_length = u16::from_be_bytes(marker_segment_length) as usize
-            - marker_segment_length.len()
-            - segment.registration_value.len();
+        let comment_length = marker_segment_length as usize - 2 - segment.registration_value.len();
 
         segment.comment = vec![0; comment_length];
 
@@ -2537,6 +4048,55 @@ impl ContiguousCodestream {
 
         Ok(segment)
     }
+
+    // Decode the parameters of an MCT, MCC or MCO marker segment, given the
+    // function to decode them with the byte offset of the marker
+    fn decode_part2_segment<R, T, F>(
+        &mut self,
+        reader: &mut R,
+        marker: MarkerSymbol,
+        decode: F,
+    ) -> Result<T, Box<dyn error::Error>>
+    where
+        R: io::Read + io::Seek,
+        F: FnOnce(u64, &[u8]) -> Result<T, CodestreamError>,
+    {
+        let offset = reader.stream_position()? - 2;
+        info!("{} start at byte offset {}", marker, offset);
+        let length = self.decode_length_minimum(reader, marker, 2)?;
+        let mut parameters = vec![0; usize::from(length) - 2];
+        reader.read_exact(&mut parameters)?;
+        Ok(decode(offset, &parameters)?)
+    }
+
+    // Decode a single marker segment from its bytes, which shall be exactly
+    // the length given by its length field, as if its marker were at the
+    // start of a codestream
+    fn decode_segment_bytes<T, F>(
+        marker: MarkerSymbol,
+        bytes: &[u8],
+        decode: F,
+    ) -> Result<T, Box<dyn error::Error>>
+    where
+        F: FnOnce(
+            &mut ContiguousCodestream,
+            &mut io::Cursor<Vec<u8>>,
+        ) -> Result<T, Box<dyn error::Error>>,
+    {
+        let malformed = || CodestreamError::MarkerMalformed { marker, offset: 0 };
+        if bytes.len() < 2 || usize::from(u16::from_be_bytes([bytes[0], bytes[1]])) != bytes.len() {
+            return Err(malformed().into());
+        }
+        let mut data = marker.0.to_vec();
+        data.extend_from_slice(bytes);
+        let mut reader = io::Cursor::new(data);
+        reader.set_position(2);
+        let segment = decode(&mut ContiguousCodestream::default(), &mut reader)?;
+        if reader.position() != reader.get_ref().len() as u64 {
+            return Err(malformed().into());
+        }
+        Ok(segment)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -2582,6 +4142,18 @@ pub struct Header {
 
     // COM (Optional, repeatable)
     comment_marker_segments: Vec<CommentMarkerSegment>,
+
+    // MCT (Optional, repeatable, ITU-T T.801 | ISO/IEC 15444-2)
+    transform_arrays: Vec<TransformArraySegment>,
+
+    // MCC (Optional, repeatable, ITU-T T.801 | ISO/IEC 15444-2)
+    component_collections: Vec<ComponentCollectionSegment>,
+
+    // MCO (Optional, ITU-T T.801 | ISO/IEC 15444-2)
+    transform_order: Option<TransformOrderSegment>,
+
+    // From MCT, MCC and MCO
+    multi_component_transform: Option<MultiComponentTransform>,
 }
 
 impl Header {
@@ -2620,6 +4192,25 @@ impl Header {
         self.coding_style_marker_segment.as_ref().unwrap()
     }
 
+    /// Names of the marker segments that every main header shall have, SIZ,
+    /// COD and QCD, that this header does not, in that order.
+    ///
+    /// A header decoded from a codestream has all of them, as decoding
+    /// fails with [HeaderError::MissingRequiredMarkers] otherwise.
+    pub fn missing_required_markers(&self) -> Vec<&'static str> {
+        let mut markers = vec![];
+        if self.image_and_tile_size_marker_segment.length == 0 {
+            markers.push("SIZ");
+        }
+        if self.coding_style_marker_segment.is_none() {
+            markers.push("COD");
+        }
+        if self.quantization_default_marker_segment.is_none() {
+            markers.push("QCD");
+        }
+        markers
+    }
+
     /// Coding style component (COC) segment
     ///
     /// Describes the coding style and number of decomposition levels for compressing
@@ -2712,6 +4303,45 @@ impl Header {
     pub fn comment_marker_segments(&self) -> &Vec<CommentMarkerSegment> {
         &self.comment_marker_segments
     }
+
+    /// Multiple component transformation definition (MCT) marker segments.
+    ///
+    /// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.7.
+    pub fn transform_array_segments(&self) -> &[TransformArraySegment] {
+        &self.transform_arrays
+    }
+
+    /// Multiple component collection (MCC) marker segments.
+    ///
+    /// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.8.
+    pub fn component_collection_segments(&self) -> &[ComponentCollectionSegment] {
+        &self.component_collections
+    }
+
+    /// Multiple component transformation ordering (MCO) marker segment.
+    ///
+    /// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.9.
+    pub fn transform_order_segment(&self) -> Option<&TransformOrderSegment> {
+        self.transform_order.as_ref()
+    }
+
+    /// Multiple component transformation of the MCT, MCC and MCO marker
+    /// segments, if there is an MCO marker segment.
+    ///
+    /// This is not applied by [ContiguousCodestream::decode_image].
+    pub fn multi_component_transform(&self) -> Option<&MultiComponentTransform> {
+        self.multi_component_transform.as_ref()
+    }
+
+    /// Whether any ITU-T T.801 | ISO/IEC 15444-2 marker segments are present
+    /// in the main header.
+    ///
+    /// The codestream cannot be fully decoded as Part 1 if so.
+    pub fn part2_markers_present(&self) -> bool {
+        !self.transform_arrays.is_empty()
+            || !self.component_collections.is_empty()
+            || self.transform_order.is_some()
+    }
 }
 
 // Many images have multiple components. This specification has a multiple component transformation to decorrelate threecomponents. This is the only function in this specification that relates components to each other
@@ -2746,14 +4376,268 @@ struct Image {}
 //
 // Images of lower resolution than the original are generated by decoding a
 // selected subset of these subbands.
-#[derive(Debug, Default)]
-struct Tile {}
+/// A tile, with the tile-parts it is divided into.
+///
+/// Tile-parts of different tiles may be interleaved in the codestream, so the
+/// tile-parts are gathered by Isot and ordered by TPsot.
+#[derive(Debug)]
+pub struct Tile<'a> {
+    index: u16,
+    tile_parts: Vec<&'a TilePart>,
+    header: &'a Header,
+}
+
+impl<'a> Tile<'a> {
+    /// Tile index (Isot).
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Tile-parts of the tile, in TPsot order.
+    pub fn tile_parts(&self) -> &[&'a TilePart] {
+        &self.tile_parts
+    }
+
+    /// Number of tile-parts of the tile.
+    ///
+    /// This is TNsot of the tile-parts when given. A TNsot of 0 means the
+    /// number is not specified, in which case it is the number of tile-parts
+    /// found in the codestream.
+    pub fn no_tile_parts(&self) -> usize {
+        self.tile_parts
+            .iter()
+            .map(|tile_part| tile_part.start_of_tile_segment().no_tile_parts())
+            .find(|&no_tile_parts| no_tile_parts != 0)
+            .map_or(self.tile_parts.len(), usize::from)
+    }
+
+    /// Whether each tile-part from TPsot 0 to the number of tile-parts is
+    /// present exactly once.
+    pub fn is_complete(&self) -> bool {
+        self.tile_parts.len() == self.no_tile_parts()
+            && self.tile_parts.iter().enumerate().all(|(i, tile_part)| {
+                usize::from(tile_part.start_of_tile_segment().tile_part_index()) == i
+            })
+    }
+
+    /// COD marker segment in effect for the tile, from the tile-part headers
+    /// or else the main header.
+    pub fn coding_style_marker_segment(&self) -> &'a CodingStyleMarkerSegment {
+        self.first_headers()
+            .find_map(|headers| headers.coding_style_marker_segment.as_ref())
+            .unwrap_or_else(|| self.header.coding_style_marker_segment())
+    }
+
+    /// Coding style parameters of the component in the tile.
+    ///
+    /// From A.6.1, a COC in the tile-part headers takes precedence over a COD
+    /// in the tile-part headers, then a COC in the main header, then the COD
+    /// in the main header.
+    pub fn coding_style_parameters(&self, component: u16) -> &'a CodingStyleParameters {
+        let for_component = |coc: &&CodingStyleComponentSegment| coc.component_index() == component;
+        if let Some(coc) = self
+            .first_headers()
+            .flat_map(|headers| headers.coding_style_component_segment.iter())
+            .find(for_component)
+        {
+            return coc.coding_style_parameters();
+        }
+        if let Some(cod) = self
+            .first_headers()
+            .find_map(|headers| headers.coding_style_marker_segment.as_ref())
+        {
+            return cod.coding_style_parameters();
+        }
+        match self
+            .header
+            .coding_style_component_segment()
+            .iter()
+            .find(for_component)
+        {
+            Some(coc) => coc.coding_style_parameters(),
+            None => self
+                .header
+                .coding_style_marker_segment()
+                .coding_style_parameters(),
+        }
+    }
+
+    /// Quantization of the component in the tile.
+    ///
+    /// From A.6.4 and A.6.5, a QCC in the tile-part headers takes precedence
+    /// over a QCD in the tile-part headers, then a QCC in the main header,
+    /// then the QCD in the main header.
+    pub fn quantization_info(&self, component: u16) -> &'a QuantizationInfo {
+        let for_component =
+            |qcc: &&QuantizationComponentSegment| qcc.component_index() == component;
+        if let Some(qcc) = self
+            .first_headers()
+            .flat_map(|headers| headers.quantization_component_segment.iter())
+            .find(for_component)
+        {
+            return qcc.quantization_info();
+        }
+        if let Some(qcd) = self
+            .first_headers()
+            .find_map(|headers| headers.quantization_default_marker_segment.as_ref())
+        {
+            return qcd.quantization_info();
+        }
+        match self
+            .header
+            .quantization_component_segments()
+            .iter()
+            .find(for_component)
+        {
+            Some(qcc) => qcc.quantization_info(),
+            None => self
+                .header
+                .quantization_default_marker_segment()
+                .quantization_info(),
+        }
+    }
+
+    /// Whether the tile-part headers have an RGN marker segment.
+    fn has_regions(&self) -> bool {
+        self.first_headers()
+            .any(|headers| !headers.regions.is_empty())
+    }
+
+    /// Number of packets in the tile.
+    ///
+    /// There is one packet for each layer of each precinct, over the
+    /// resolution levels of each component. This uses the tile-component
+    /// bounds (B.3), resolution bounds (B.5) and number of precincts (B.6) of
+    /// the coding style in effect for each component, and can be checked
+    /// against the PLT marker segments of a tile that has been fully read.
+    pub fn expected_packet_count(&self) -> Result<u64, Box<dyn error::Error>> {
+        let siz = self.header.image_and_tile_size_marker_segment();
+        let t = u32::from(self.index);
+        let (tx0, tx1) = (siz.tile_x_upper(t)?, siz.tile_x_lower(t)?);
+        let (ty0, ty1) = (siz.tile_y_upper(t)?, siz.tile_y_lower(t)?);
+
+        let mut precincts = 0;
+        for (component, info) in (0..).zip(siz.components()) {
+            // tcx0 = ⌈tx0 / XRsiz⌉, tcx1 = ⌈tx1 / XRsiz⌉, and likewise for y
+            let horizontal_separation = u64::from(info.horizontal_separation());
+            let vertical_separation = u64::from(info.vertical_separation());
+            let (tcx0, tcx1) = (
+                u64::from(tx0).div_ceil(horizontal_separation),
+                u64::from(tx1).div_ceil(horizontal_separation),
+            );
+            let (tcy0, tcy1) = (
+                u64::from(ty0).div_ceil(vertical_separation),
+                u64::from(ty1).div_ceil(vertical_separation),
+            );
+
+            let parameters = self.coding_style_parameters(component);
+            let levels = parameters.no_decomposition_levels();
+            let precinct_sizes = parameters.precinct_sizes().unwrap_or_default();
+            for (r, precinct_size) in (0..=levels).zip(precinct_sizes.iter()) {
+                // trx0 = ⌈tcx0 / 2^(NL - r)⌉, and likewise for the others
+                let scale = 1u64.checked_shl(u32::from(levels - r)).unwrap_or(u64::MAX);
+                let wide = num_precincts_1d(
+                    tcx0.div_ceil(scale),
+                    tcx1.div_ceil(scale),
+                    precinct_size.width_exponent(),
+                );
+                let high = num_precincts_1d(
+                    tcy0.div_ceil(scale),
+                    tcy1.div_ceil(scale),
+                    precinct_size.height_exponent(),
+                );
+                precincts += wide * high;
+            }
+        }
+        Ok(precincts * u64::from(self.coding_style_marker_segment().no_layers()))
+    }
+
+    // Main headers of the tile-parts, holding the COD, COC, QCD, QCC and RGN
+    // marker segments of the tile
+    fn first_headers(&self) -> impl Iterator<Item = &'a FirstTilePartHeaders> + '_ {
+        self.tile_parts
+            .iter()
+            .filter_map(|tile_part| tile_part.header.first_headers.as_ref())
+    }
+}
+
+// Number of precincts along one axis of a resolution level (Equation B-16)
+//
+// numprecincts = ⌈tr1 / 2^PP⌉ - ⌊tr0 / 2^PP⌋, or 0 if tr1 = tr0
+fn num_precincts_1d(tr0: u64, tr1: u64, exponent: u8) -> u64 {
+    if tr1 <= tr0 {
+        return 0;
+    }
+    let size = 1u64 << exponent;
+    tr1.div_ceil(size) - tr0 / size
+}
 
 /// A codestream is divided into tile-parts.
 #[derive(Debug)]
-struct TilePart {
+pub struct TilePart {
     header: TilePartHeader,
     data_offset: u64,
+    // Byte offset of the next SOT or EOC marker
+    data_end: u64,
+}
+
+impl TilePart {
+    pub fn start_of_tile_segment(&self) -> &StartOfTileSegment {
+        &self.header.start_of_tile_segment
+    }
+
+    /// Byte offset of the SOD marker, which ends the tile-part header.
+    pub fn sod_offset(&self) -> u64 {
+        self.data_offset - 2
+    }
+
+    /// Byte offset of the tile-part data, following the SOD marker.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Length of the tile-part data, the packets from the SOD marker to the
+    /// end of the tile-part.
+    ///
+    /// The tile-part ends at the next SOT marker, or at the EOC marker. This
+    /// is the end Psot gives, unless Psot does not match the markers, see
+    /// [DecodeWarning::TilePartLengthMismatch], or Psot is 0 for a last
+    /// tile-part that runs to the EOC marker.
+    pub fn data_length(&self) -> u64 {
+        self.data_end - self.data_offset
+    }
+
+    pub fn comment_marker_segments(&self) -> &[CommentMarkerSegment] {
+        &self.header.comment_marker_segments
+    }
+
+    /// PLT marker segments of the tile-part header, with the lengths of the
+    /// packets of the tile-part.
+    pub fn packet_length_segments(&self) -> &[TilePacketLength] {
+        &self.header.packet_lengths
+    }
+
+    pub fn packed_packet_headers_segments(&self) -> &[TilePackedPacketHeaderSegment] {
+        &self.header.packed_packet_headers
+    }
+
+    /// MCT marker segments of the tile-part header, see
+    /// [Header::transform_array_segments].
+    pub fn transform_array_segments(&self) -> &[TransformArraySegment] {
+        &self.header.transform_arrays
+    }
+
+    /// MCC marker segments of the tile-part header, see
+    /// [Header::component_collection_segments].
+    pub fn component_collection_segments(&self) -> &[ComponentCollectionSegment] {
+        &self.header.component_collections
+    }
+
+    /// MCO marker segment of the tile-part header, see
+    /// [Header::transform_order_segment].
+    pub fn transform_order_segment(&self) -> Option<&TransformOrderSegment> {
+        self.header.transform_order.as_ref()
+    }
 }
 
 /// A tile part header. Required for every tile part in the codestream. Contains the information
@@ -2770,15 +4654,23 @@ struct TilePartHeader {
     // POC (Optional, unless POC differ from main POC then Required)
     progression_order_change: Option<ProgressionOrderChangeSegment>,
 
-    // PPT (Optional)
-    packed_packet_headers: Option<TilePackedPacketHeaderSegment>,
+    // PPT (Optional, repeatable)
+    packed_packet_headers: Vec<TilePackedPacketHeaderSegment>,
 
-    // PLT (Optional)
-    // TODO double check there is only one per tile-part
-    packet_lengths: Option<PacketLengthSegment>,
+    // PLT (Optional, repeatable)
+    packet_lengths: Vec<TilePacketLength>,
 
     // COM (Optional, repeatable)
     comment_marker_segments: Vec<CommentMarkerSegment>,
+
+    // MCT (Optional, repeatable, ITU-T T.801 | ISO/IEC 15444-2)
+    transform_arrays: Vec<TransformArraySegment>,
+
+    // MCC (Optional, repeatable, ITU-T T.801 | ISO/IEC 15444-2)
+    component_collections: Vec<ComponentCollectionSegment>,
+
+    // MCO (Optional, ITU-T T.801 | ISO/IEC 15444-2)
+    transform_order: Option<TransformOrderSegment>,
 }
 
 impl TilePartHeader {
@@ -2787,9 +4679,12 @@ impl TilePartHeader {
             start_of_tile_segment,
             first_headers: None,
             progression_order_change: None,
-            packed_packet_headers: None,
-            packet_lengths: None,
+            packed_packet_headers: Vec::new(),
+            packet_lengths: Vec::new(),
             comment_marker_segments: Vec::new(),
+            transform_arrays: Vec::new(),
+            component_collections: Vec::new(),
+            transform_order: None,
         }
     }
 
@@ -2851,6 +4746,7 @@ impl ContiguousCodestream {
             .into());
         }
         info!("SOC start at byte offset {}", reader.stream_position()? - 2);
+        self.record_marker(reader, marker_type)?;
 
         // SIZ (Required as the second marker segment)
         marker_type = MarkerSymbol::decode(reader)?;
@@ -2862,6 +4758,7 @@ impl ContiguousCodestream {
             }
             .into());
         }
+        self.record_marker(reader, marker_type)?;
 
         header.image_and_tile_size_marker_segment = self.decode_siz(reader)?;
 
@@ -2869,138 +4766,214 @@ impl ContiguousCodestream {
 
         loop {
             match MarkerSymbol::decode(reader) {
-                Ok(marker_type) => match marker_type {
-                    // COC (Optional, no more than one COC per component)
-                    MARKER_SYMBOL_COC => {
-                        header
-                            .coding_style_component_segment
-                            .push(self.decode_coc(reader, no_components)?);
-                    }
-                    // QCD (Required)
-                    MARKER_SYMBOL_QCD => {
-                        header.quantization_default_marker_segment = Some(self.decode_qcd(reader)?);
+                Ok(marker_type) => {
+                    // The SOT marker is recorded with its tile-part
+                    if marker_type != MARKER_SYMBOL_SOT {
+                        self.record_marker(reader, marker_type)?;
                     }
+                    match marker_type {
+                        // COC (Optional, no more than one COC per component)
+                        MARKER_SYMBOL_COC => {
+                            header
+                                .coding_style_component_segment
+                                .push(self.decode_coc(reader, no_components)?);
+                        }
+                        // QCD (Required)
+                        MARKER_SYMBOL_QCD => {
+                            header.quantization_default_marker_segment =
+                                Some(self.decode_qcd(reader)?);
+                        }
 
-                    // COD (Required)
-                    MARKER_SYMBOL_COD => {
-                        header.coding_style_marker_segment = Some(self.decode_cod(reader)?);
-                    }
+                        // COD (Required)
+                        MARKER_SYMBOL_COD => {
+                            header.coding_style_marker_segment = Some(self.decode_cod(reader)?);
+                        }
 
-                    // QCC (Optional, no more than one QCC per component)
-                    MARKER_SYMBOL_QCC => {
-                        header
-                            .quantization_component_segments
-                            .push(self.decode_qcc(reader, no_components)?);
-                    }
+                        // QCC (Optional, no more than one QCC per component)
+                        MARKER_SYMBOL_QCC => {
+                            header
+                                .quantization_component_segments
+                                .push(self.decode_qcc(reader, no_components)?);
+                        }
 
-                    // RGN (Optional, no more than one RGN per component)
-                    MARKER_SYMBOL_RGN => {
-                        header.regions.push(self.decode_rgn(reader, no_components)?);
-                    }
+                        // RGN (Optional, no more than one RGN per component)
+                        MARKER_SYMBOL_RGN => {
+                            header.regions.push(self.decode_rgn(reader, no_components)?);
+                        }
 
-                    // POC (Required in main or tile for any progression order changes)
-                    MARKER_SYMBOL_POC => {
-                        header.progression_order_change =
-                            Some(self.decode_poc(reader, no_components)?);
-                    }
+                        // POC (Required in main or tile for any progression order changes)
+                        MARKER_SYMBOL_POC => {
+                            header.progression_order_change =
+                                Some(self.decode_poc(reader, no_components)?);
+                        }
 
-                    // PPM (Optional, either PPM or PPT or codestream packet headers required)
-                    MARKER_SYMBOL_PPM => {
-                        // TODO: If the PPM marker segment is present, all the packet headers shall be found in the
-                        // main header.
-                        header.packed_packet_headers.push(self.decode_ppm(reader)?);
-                    }
+                        // PPM (Optional, either PPM or PPT or codestream packet headers required)
+                        MARKER_SYMBOL_PPM => {
+                            // TODO: If the PPM marker segment is present, all the packet headers shall be found in the
+                            // main header.
+                            header.packed_packet_headers.push(self.decode_ppm(reader)?);
+                        }
 
-                    // TLM (Optional, repeatable)
-                    MARKER_SYMBOL_TLM => {
-                        header.tile_part_lengths.push(self.decode_tlm(reader)?);
-                    }
+                        // TLM (Optional, repeatable)
+                        MARKER_SYMBOL_TLM => {
+                            header.tile_part_lengths.push(self.decode_tlm(reader)?);
+                        }
 
-                    // PLM (Optional)
-                    MARKER_SYMBOL_PLM => {
-                        let packet_length = self.decode_plm(reader)?;
-                        header.packet_lengths.push(packet_length);
-                    }
+                        // PLM (Optional)
+                        MARKER_SYMBOL_PLM => {
+                            let packet_length = self.decode_plm(reader)?;
+                            header.packet_lengths.push(packet_length);
+                        }
 
-                    // CRG (Optional)
-                    MARKER_SYMBOL_CRG => {
-                        header.component_registration =
-                            Some(self.decode_crg(reader, no_components)?);
-                    }
+                        // CRG (Optional)
+                        MARKER_SYMBOL_CRG => {
+                            header.component_registration =
+                                Some(self.decode_crg(reader, no_components)?);
+                        }
 
-                    // COM (Optional, repeatable)
-                    MARKER_SYMBOL_COM => {
-                        let comment_marker_segment = self.decode_com(reader)?;
-                        header.comment_marker_segments.push(comment_marker_segment);
-                    }
+                        // COM (Optional, repeatable)
+                        MARKER_SYMBOL_COM => {
+                            let comment_marker_segment = self.decode_com(reader)?;
+                            header.comment_marker_segments.push(comment_marker_segment);
+                        }
 
-                    // CAP (Optional)
-                    // TODO: in strict mode, ensure this is the first marker segment after SIZ
-                    MARKER_SYMBOL_CAP => {
-                        header.extended_capabilities_marker_segment =
-                            Some(self.decode_cap(reader)?);
-                    }
+                        // CAP (Optional)
+                        // TODO: in strict mode, ensure this is the first marker segment after SIZ
+                        MARKER_SYMBOL_CAP => {
+                            header.extended_capabilities_marker_segment =
+                                Some(self.decode_cap(reader)?);
+                        }
 
-                    // CPF (Optional)
-                    // From ITU-T T.814 | ISO/IEC 15444-15
-                    MARKER_SYMBOL_CPF => {
-                        header.corresponding_profile_marker_segment =
-                            Some(self.decode_cpf(reader)?);
-                    }
+                        // CPF (Optional)
+                        // From ITU-T T.814 | ISO/IEC 15444-15
+                        MARKER_SYMBOL_CPF => {
+                            header.corresponding_profile_marker_segment =
+                                Some(self.decode_cpf(reader)?);
+                        }
 
-                    // Start of tile bit-stream
-                    MARKER_SYMBOL_SOT => {
-                        reader.seek(io::SeekFrom::Current(-2))?;
-                        break;
-                    }
+                        // MCT, MCC and MCO (Optional)
+                        // From ITU-T T.801 | ISO/IEC 15444-2
+                        MARKER_SYMBOL_MCT => {
+                            let segment = self.decode_part2_segment(
+                                reader,
+                                marker_type,
+                                TransformArraySegment::decode,
+                            )?;
+                            header.transform_arrays.push(segment);
+                        }
+                        MARKER_SYMBOL_MCC => {
+                            let segment = self.decode_part2_segment(
+                                reader,
+                                marker_type,
+                                ComponentCollectionSegment::decode,
+                            )?;
+                            header.component_collections.push(segment);
+                        }
+                        MARKER_SYMBOL_MCO => {
+                            let segment = self.decode_part2_segment(
+                                reader,
+                                marker_type,
+                                TransformOrderSegment::decode,
+                            )?;
+                            if header.transform_order.is_some() {
+                                return Err(CodestreamError::MarkerMalformed {
+                                    marker: marker_type,
+                                    offset: segment.offset(),
+                                }
+                                .into());
+                            }
+                            header.transform_order = Some(segment);
+                        }
 
-                    // Reserved markers
-                    // ITU-T H.800 or ISO/IEC 15444-1 2024, Section A.1.3 and Table A.1
-                    MarkerSymbol([0xff, 0x30])
-                    | MarkerSymbol([0xff, 0x31])
-                    | MarkerSymbol([0xff, 0x32])
-                    | MarkerSymbol([0xff, 0x33])
-                    | MarkerSymbol([0xff, 0x34])
-                    | MarkerSymbol([0xff, 0x35])
-                    | MarkerSymbol([0xff, 0x36])
-                    | MarkerSymbol([0xff, 0x37])
-                    | MarkerSymbol([0xff, 0x38])
-                    | MarkerSymbol([0xff, 0x39])
-                    | MarkerSymbol([0xff, 0x3A])
-                    | MarkerSymbol([0xff, 0x3B])
-                    | MarkerSymbol([0xff, 0x3C])
-                    | MarkerSymbol([0xff, 0x3D])
-                    | MarkerSymbol([0xff, 0x3E])
-                    | MarkerSymbol([0xff, 0x3F]) => {
-                        // Reserved as marker only, not a segment
-                        info!("Skipping marker: {:?}", marker_type);
-                    }
+                        // Start of tile bit-stream
+                        MARKER_SYMBOL_SOT => {
+                            reader.seek(io::SeekFrom::Current(-2))?;
+                            break;
+                        }
 
-                    _ => {
-                        log::error!("unknown marker type: {marker_type:?}");
-                        return Err(CodestreamError::MarkerUnknown {
-                            marker: marker_type,
-                            offset: reader.stream_position()? - 2,
+                        // Reserved markers
+                        // ITU-T H.800 or ISO/IEC 15444-1 2024, Section A.1.3 and Table A.1
+                        MarkerSymbol([0xff, 0x30])
+                        | MarkerSymbol([0xff, 0x31])
+                        | MarkerSymbol([0xff, 0x32])
+                        | MarkerSymbol([0xff, 0x33])
+                        | MarkerSymbol([0xff, 0x34])
+                        | MarkerSymbol([0xff, 0x35])
+                        | MarkerSymbol([0xff, 0x36])
+                        | MarkerSymbol([0xff, 0x37])
+                        | MarkerSymbol([0xff, 0x38])
+                        | MarkerSymbol([0xff, 0x39])
+                        | MarkerSymbol([0xff, 0x3A])
+                        | MarkerSymbol([0xff, 0x3B])
+                        | MarkerSymbol([0xff, 0x3C])
+                        | MarkerSymbol([0xff, 0x3D])
+                        | MarkerSymbol([0xff, 0x3E])
+                        | MarkerSymbol([0xff, 0x3F]) => {
+                            // Reserved as marker only, not a segment
+                            info!("Skipping marker: {:?}", marker_type);
+                        }
+
+                        _ => {
+                            log::error!("unknown marker type: {marker_type:?}");
+                            return Err(CodestreamError::MarkerUnknown {
+                                marker: marker_type,
+                                offset: reader.stream_position()? - 2,
+                            }
+                            .into());
                         }
-                        .into());
                     }
-                },
+                }
                 Err(e) => return Err(e.into()),
             }
         }
 
         // Required
-        if header.quantization_default_marker_segment.is_none() {
-            return Err(CodestreamError::MarkerMissing {
-                marker: MARKER_SYMBOL_QCD,
-            }
-            .into());
+        let markers = header.missing_required_markers();
+        if !markers.is_empty() {
+            return Err(HeaderError::MissingRequiredMarkers { markers }.into());
         }
-        if header.coding_style_marker_segment.is_none() {
-            return Err(CodestreamError::MarkerMissing {
-                marker: MARKER_SYMBOL_COD,
+
+        // A.6.4 and A.6.5
+        // Unless derived, there is a step size for each of the 3 N_L + 1
+        // sub-bands of the component the quantization applies to
+        for component in 0..no_components {
+            let levels = header
+                .coding_style_component_segment
+                .iter()
+                .find(|coc| coc.component_index() == component)
+                .map(|coc| coc.coding_style_parameters())
+                .unwrap_or_else(|| {
+                    header
+                        .coding_style_marker_segment()
+                        .coding_style_parameters()
+                })
+                .no_decomposition_levels();
+            let (marker, quantization_info) = match header
+                .quantization_component_segments
+                .iter()
+                .find(|qcc| qcc.component_index() == component)
+            {
+                Some(qcc) => (MARKER_SYMBOL_QCC, &qcc.quantization_info),
+                None => (
+                    MARKER_SYMBOL_QCD,
+                    header
+                        .quantization_default_marker_segment()
+                        .quantization_info(),
+                ),
+            };
+            let expected = 3 * usize::from(levels) + 1;
+            if quantization_info.style != QuantizationStyle::ScalarDerived
+                && quantization_info.no_step_sizes() != expected
+            {
+                return Err(CodestreamError::MarkerError {
+                    marker,
+                    error: format!(
+                        "component {component} has {} step sizes, expected {expected} for {levels} decomposition levels",
+                        quantization_info.no_step_sizes()
+                    ),
+                }
+                .into());
             }
-            .into());
         }
 
         // A.6.2
@@ -3045,24 +5018,30 @@ impl ContiguousCodestream {
             .into());
         }
 
+        // ITU-T T.801 | ISO/IEC 15444-2 Section J.2
+        header.multi_component_transform = MultiComponentTransform::new(
+            &header.transform_arrays,
+            &header.component_collections,
+            header.transform_order.as_ref(),
+        )?;
+
         Ok(header)
     }
 
     /// Decode a tile-part header
     ///
-    /// See ITU T.800 | ISO/IEC 15444-1 Figures A.4 and A.5
-    fn decode_tile_part<R: io::Read + io::Seek>(
+    /// See ITU T.800 | ISO/IEC 15444-1 Figures A.4 and A.5. The reader is left
+    /// after the SOD marker, at the start of the tile-part data.
+    fn decode_tile_part_header<R: io::Read + io::Seek>(
         &mut self,
         reader: &mut R,
-    ) -> Result<TilePart, Box<dyn error::Error>> {
+    ) -> Result<TilePartHeader, Box<dyn error::Error>> {
         // start of tile is always first
         let start_of_tile_segment = self.decode_sot(reader)?;
         let mut header = TilePartHeader::new(start_of_tile_segment);
 
         // todo determine if first header
-        if let _tiles_first_tile_part = true {
-            header.first_headers = Some(FirstTilePartHeaders::default())
-        }
+        header.first_headers = Some(FirstTilePartHeaders::default());
 
         let no_components = self
             .header
@@ -3071,11 +5050,11 @@ impl ContiguousCodestream {
 
         loop {
             let pos = reader.stream_position()?;
-            match MarkerSymbol::decode(reader)? {
+            let marker_type = MarkerSymbol::decode(reader)?;
+            self.record_marker(reader, marker_type)?;
+            match marker_type {
                 // COD (Optional)
                 MARKER_SYMBOL_COD => {
-                    header.first_headers()?.coding_style_marker_segment =
-                        Some(self.decode_cod(reader)?);
                     let cod = self.decode_cod(reader)?;
                     let prev = header
                         .first_headers()?
@@ -3094,7 +5073,10 @@ impl ContiguousCodestream {
                 MARKER_SYMBOL_COC => {
                     // TODO check that there is only a single COC per component
                     let coc = self.decode_coc(reader, no_components)?;
-                    header.first_headers()?.coding_style_component_segment = vec![coc];
+                    header
+                        .first_headers()?
+                        .coding_style_component_segment
+                        .push(coc);
                 }
 
                 // QCD (Optional)
@@ -3151,13 +5133,12 @@ impl ContiguousCodestream {
                         .into());
                     }
 
-                    header.packed_packet_headers = Some(self.decode_ppt(reader)?);
+                    header.packed_packet_headers.push(self.decode_ppt(reader)?);
                 }
 
                 // PLT (Optional)
                 MARKER_SYMBOL_PLT => {
-                    let packet_length_segment = self.decode_plm(reader)?;
-                    header.packet_lengths = Some(packet_length_segment);
+                    header.packet_lengths.push(self.decode_plt(reader)?);
                 }
 
                 // COM (Optional, repeatable)
@@ -3167,6 +5148,32 @@ impl ContiguousCodestream {
                         .push(self.decode_com(reader)?);
                 }
 
+                // MCT, MCC and MCO (Optional)
+                MARKER_SYMBOL_MCT => {
+                    let segment = self.decode_part2_segment(
+                        reader,
+                        marker_type,
+                        TransformArraySegment::decode,
+                    )?;
+                    header.transform_arrays.push(segment);
+                }
+                MARKER_SYMBOL_MCC => {
+                    let segment = self.decode_part2_segment(
+                        reader,
+                        marker_type,
+                        ComponentCollectionSegment::decode,
+                    )?;
+                    header.component_collections.push(segment);
+                }
+                MARKER_SYMBOL_MCO => {
+                    let segment = self.decode_part2_segment(
+                        reader,
+                        marker_type,
+                        TransformOrderSegment::decode,
+                    )?;
+                    header.transform_order = Some(segment);
+                }
+
                 // SOD
                 MARKER_SYMBOL_SOD => {
                     // Always last
@@ -3182,17 +5189,61 @@ impl ContiguousCodestream {
                 }
             }
         }
+        Ok(header)
+    }
+
+    // Tile-part header, then the extent of its data, up to the next SOT or EOC
+    // marker
+    fn decode_tile_part<R: io::Read + io::Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<TilePart, Box<dyn error::Error>> {
+        let header = self.decode_tile_part_header(reader)?;
 
         // Should have just seen the SOD marker
         let data_offset = reader.stream_position()?;
         let sot_offset = header.start_of_tile_segment.offset;
-        let data_end = sot_offset + header.start_of_tile_segment.tile_length as u64;
+        let tile_length = header.start_of_tile_segment.tile_length;
 
         // Seek past data, TODO read data
-        reader.seek(io::SeekFrom::Start(data_end))?;
+        let data_end = if tile_length == 0 {
+            // The last tile-part may contain all data until the EOC marker
+            reader.seek(io::SeekFrom::End(-2))?.max(data_offset)
+        } else {
+            let data_end = sot_offset + tile_length as u64;
+            if data_end < data_offset {
+                return Err(CodestreamError::MarkerMalformed {
+                    marker: MARKER_SYMBOL_SOT,
+                    offset: sot_offset,
+                }
+                .into());
+            }
+            reader.seek(io::SeekFrom::Start(data_end))?;
+            if !matches!(
+                MarkerSymbol::decode(reader),
+                Ok(MARKER_SYMBOL_SOT | MARKER_SYMBOL_EOC)
+            ) {
+                // Psot is wrong, so continue from the next marker that can
+                // follow a tile-part
+                let tile_part_end = find_tile_part_end(reader, data_offset)?;
+                self.report
+                    .push_warning(DecodeWarning::TilePartLengthMismatch {
+                        tile_part: self.tile_parts.len(),
+                        tile_index: header.start_of_tile_segment.tile_index(),
+                        expected: tile_length,
+                        actual: tile_part_end - sot_offset,
+                    });
+                reader.seek(io::SeekFrom::Start(tile_part_end))?;
+                tile_part_end
+            } else {
+                reader.seek(io::SeekFrom::Start(data_end))?;
+                data_end
+            }
+        };
         Ok(TilePart {
             header,
             data_offset,
+            data_end,
         })
     }
 
@@ -3202,10 +5253,13 @@ impl ContiguousCodestream {
     ) -> Result<(), Box<dyn error::Error>> {
         // The main header is found at the beginning of the codestream
         self.header = self.decode_main_header(reader)?;
+        self.first_tile_part_offset = reader.stream_position()?;
 
         // Grab tile-parts from stream
         loop {
-            match MarkerSymbol::decode(reader)? {
+            let marker_type = MarkerSymbol::decode(reader)?;
+            self.record_marker(reader, marker_type)?;
+            match marker_type {
                 MARKER_SYMBOL_SOT => {
                     info!("Handle tile-part. SOT");
                     let tile_part = self.decode_tile_part(reader)?;
@@ -3227,6 +5281,111 @@ impl ContiguousCodestream {
             }
         }
     }
+
+    /// Decode the main header and the header of the first tile-part, up to
+    /// its SOD marker, for decoding a codestream while its tile-part data is
+    /// still arriving.
+    ///
+    /// The extent of the tile-part data is taken from Psot, or is left empty
+    /// when Psot is 0, as it cannot be found yet.
+    pub(crate) fn decode_first_tile_part_header<R: io::Read + io::Seek>(
+        reader: &mut R,
+        limits: DecodeLimits,
+    ) -> Result<ContiguousCodestream, Box<dyn error::Error>> {
+        let mut codestream = ContiguousCodestream {
+            limits,
+            ..Default::default()
+        };
+        codestream.header = codestream.decode_main_header(reader)?;
+        codestream.first_tile_part_offset = reader.stream_position()?;
+        let marker_type = MarkerSymbol::decode(reader)?;
+        codestream.record_marker(reader, marker_type)?;
+        if marker_type != MARKER_SYMBOL_SOT {
+            return Err(CodestreamError::MarkerUnexpected {
+                actual_marker: marker_type,
+                expected_marker: MARKER_SYMBOL_SOT,
+                offset: reader.stream_position()?,
+            }
+            .into());
+        }
+        let header = codestream.decode_tile_part_header(reader)?;
+        let data_offset = reader.stream_position()?;
+        let data_end = match header.start_of_tile_segment.tile_length {
+            0 => data_offset,
+            tile_length => {
+                (header.start_of_tile_segment.offset + u64::from(tile_length)).max(data_offset)
+            }
+        };
+        codestream.tile_parts.push(TilePart {
+            header,
+            data_offset,
+            data_end,
+        });
+        Ok(codestream)
+    }
+}
+
+// Offset of the first SOT or EOC marker at or after `offset`, or the end of
+// the reader if there is none. Bit stuffing (Annex B.10.1 and D.6) keeps
+// these out of the packet data.
+fn find_tile_part_end<R: io::Read + io::Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<u64, Box<dyn error::Error>> {
+    match find_marker(reader, offset, &[MARKER_SYMBOL_SOT, MARKER_SYMBOL_EOC])? {
+        Some(marker_offset) => Ok(marker_offset),
+        None => Ok(reader.stream_position()?),
+    }
+}
+
+// Offset of the first of `markers` at or after `offset`, or None if there is
+// none before the end of the reader
+fn find_marker<R: io::Read + io::Seek>(
+    reader: &mut R,
+    offset: u64,
+    markers: &[MarkerSymbol],
+) -> Result<Option<u64>, Box<dyn error::Error>> {
+    reader.seek(io::SeekFrom::Start(offset))?;
+    let mut buffer = [0u8; 4096];
+    let mut buffer_offset = offset;
+    let mut previous = 0u8;
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            return Ok(None);
+        }
+        for (index, &byte) in buffer[..count].iter().enumerate() {
+            if markers.contains(&MarkerSymbol([previous, byte])) {
+                return Ok(Some(buffer_offset + index as u64 - 1));
+            }
+            previous = byte;
+        }
+        buffer_offset += count as u64;
+    }
+}
+
+// Concatenated PPM parameters after the Nppm and Ippm parameters of one
+// tile-part, or None if they run short.
+fn skip_ppm_tile_part(data: &[u8]) -> Option<&[u8]> {
+    let number_of_bytes = u32::from_be_bytes(data.get(0..4)?.try_into().unwrap()) as usize;
+    data.get(4..)?.get(number_of_bytes..)
+}
+
+// Read exactly `length` bytes at `offset`, without allocating more than the
+// reader holds.
+fn read_range<R: io::Read + io::Seek>(
+    reader: &mut R,
+    offset: u64,
+    length: u64,
+) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    reader.seek(io::SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    let mut range = io::Read::take(&mut *reader, length);
+    io::Read::read_to_end(&mut range, &mut bytes)?;
+    if (bytes.len() as u64) < length {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes)
 }
 
 // All components are defined with respect to the reference grid.
@@ -3272,7 +5431,172 @@ struct ImageArea {}
 pub fn decode_jpc<R: io::Read + io::Seek>(
     reader: &mut R,
 ) -> Result<ContiguousCodestream, Box<dyn error::Error>> {
-    let mut continuous_codestream = ContiguousCodestream::default();
+    decode_jpc_with_limits(reader, DecodeLimits::default())
+}
+
+/// Decode the codestream read from `reader`, and check that the image is
+/// reconstructed exactly, with the `expected` samples of each component in
+/// raster order.
+///
+/// This verifies a lossless codestream, which shall use the reversible filter
+/// for every tile-component, and which shall be one that
+/// [ContiguousCodestream::decode_image] supports. A difference is reported
+/// as an [ImageError], with the position of the first sample that differs,
+/// see [DecodedImage::verify_lossless].
+pub fn verify_lossless<R: io::Read + io::Seek>(
+    reader: &mut R,
+    expected: &[Vec<i32>],
+) -> Result<(), Box<dyn error::Error>> {
+    let codestream = decode_jpc(reader)?;
+    let no_components = codestream
+        .header()
+        .image_and_tile_size_marker_segment()
+        .no_components();
+    for tile in codestream.tiles() {
+        for component in 0..no_components {
+            if tile.coding_style_parameters(component).transformation()
+                != TransformationFilter::Reversible
+            {
+                return Err(CodestreamError::InvalidParameter {
+                    name: "transformation",
+                }
+                .into());
+            }
+        }
+    }
+    let image = codestream.decode_image(reader)?;
+    Ok(image.verify_lossless(expected)?)
+}
+
+/// Length of the codestream read from `reader`, from its current position to
+/// the end of the EOC marker.
+///
+/// Bytes following the EOC marker, such as padding after a codestream
+/// embedded in a larger file, are not counted. The marker segments and the
+/// tile-parts, by their Psot, are followed to the EOC marker, and where they
+/// cannot be, the data is searched for it. Without an EOC marker, this is
+/// the length of the rest of the input.
+///
+/// The reader is left at an unspecified position.
+pub fn codestream_length<R: io::Read + io::Seek>(
+    reader: &mut R,
+) -> Result<u64, Box<dyn error::Error>> {
+    let start = reader.stream_position()?;
+    let mut offset = start;
+    // Offset of the last marker found, from which to search
+    let mut marker_offset = start;
+    loop {
+        reader.seek(io::SeekFrom::Start(offset))?;
+        let marker = match MarkerSymbol::decode(reader) {
+            Ok(marker) if marker.0[0] == 0xFF => marker,
+            _ => break,
+        };
+        marker_offset = offset;
+        match marker {
+            MARKER_SYMBOL_EOC => return Ok(offset + 2 - start),
+            MARKER_SYMBOL_SOC => offset += 2,
+            MARKER_SYMBOL_SOT => {
+                // Lsot, Isot, then Psot, which is 0 for a last tile-part that
+                // runs to the EOC marker
+                let mut sot = [0u8; 8];
+                if reader.read_exact(&mut sot).is_err() {
+                    break;
+                }
+                let tile_part_length = u32::from_be_bytes([sot[4], sot[5], sot[6], sot[7]]);
+                if tile_part_length == 0 {
+                    break;
+                }
+                offset += u64::from(tile_part_length);
+            }
+            marker if marker.has_segment() => {
+                let mut length = [0u8; 2];
+                if reader.read_exact(&mut length).is_err() {
+                    break;
+                }
+                offset += 2 + u64::from(u16::from_be_bytes(length));
+            }
+            _ => break,
+        }
+    }
+
+    match find_marker(reader, marker_offset, &[MARKER_SYMBOL_EOC])? {
+        Some(eoc_offset) => Ok(eoc_offset + 2 - start),
+        None => Ok(reader.seek(io::SeekFrom::End(0))? - start),
+    }
+}
+
+/// Format of JPEG 2000 input, see [detect_format].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jpeg2000Format {
+    /// JP2 file, or a JPX file, starting with the Signature box, to decode
+    /// with [decode_jpc_from_jp2] or [jp2::decode_jp2]
+    Jp2Boxed,
+    /// Codestream starting with the SOC marker, to decode with [decode_jpc]
+    RawCodestream,
+    Unknown,
+}
+
+/// Whether `reader` holds a JP2 file or a raw codestream, from its first
+/// bytes.
+///
+/// The reader is left where it was, so that the input can then be decoded.
+/// Input that cannot be read is [Jpeg2000Format::Unknown].
+pub fn detect_format<R: io::Read + io::Seek>(reader: &mut R) -> Jpeg2000Format {
+    if jp2::is_jp2(reader) {
+        return Jpeg2000Format::Jp2Boxed;
+    }
+    let start = match reader.stream_position() {
+        Ok(start) => start,
+        Err(_) => return Jpeg2000Format::Unknown,
+    };
+    let marker = MarkerSymbol::decode(reader);
+    if reader.seek(io::SeekFrom::Start(start)).is_err() {
+        return Jpeg2000Format::Unknown;
+    }
+    match marker {
+        Ok(MARKER_SYMBOL_SOC) => Jpeg2000Format::RawCodestream,
+        _ => Jpeg2000Format::Unknown,
+    }
+}
+
+/// Decode the codestream of the first Contiguous Codestream box of a JP2
+/// file read from `reader`.
+///
+/// Byte offsets within the codestream, such as those of the marker segments
+/// and tile-parts, are offsets within the file.
+pub fn decode_jpc_from_jp2<R: io::Read + io::Seek>(
+    reader: &mut R,
+) -> Result<ContiguousCodestream, Box<dyn error::Error>> {
+    let jp2 = jp2::decode_jp2(reader)?;
+    decode_first_codestream(reader, &jp2)
+}
+
+// Decode the codestream of the first Contiguous Codestream box of `jp2`,
+// the boxes of the JP2 file read from `reader`
+fn decode_first_codestream<R: io::Read + io::Seek>(
+    reader: &mut R,
+    jp2: &jp2::JP2File,
+) -> Result<ContiguousCodestream, Box<dyn error::Error>> {
+    let contiguous_codestream_box = jp2
+        .contiguous_codestreams_boxes()
+        .first()
+        .ok_or(jp2::JP2Error::BoxMissing { box_type: *b"jp2c" })?;
+    reader.seek(io::SeekFrom::Start(contiguous_codestream_box.offset))?;
+    let mut continuous_codestream = decode_jpc(reader)?;
+    continuous_codestream.offset = contiguous_codestream_box.offset;
+    Ok(continuous_codestream)
+}
+
+/// Decode a codestream, failing before any allocation if the SIZ marker
+/// segment would exceed `limits`.
+pub fn decode_jpc_with_limits<R: io::Read + io::Seek>(
+    reader: &mut R,
+    limits: DecodeLimits,
+) -> Result<ContiguousCodestream, Box<dyn error::Error>> {
+    let mut continuous_codestream = ContiguousCodestream {
+        limits,
+        ..Default::default()
+    };
     continuous_codestream.decode(reader)?;
 
     // Tile: A rectangular array of points on the reference grid, registered
@@ -3400,6 +5724,164 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_codestream_error_invalid_parameter() {
+        let e = CodestreamError::InvalidParameter { name: "guard_bits" };
+        assert_eq!(format!("{e}"), "invalid value for guard_bits");
+    }
+
+    fn siz_with_grid(
+        grid: (u32, u32),
+        offset: (u32, u32),
+        tile: (u32, u32),
+        tile_offset: (u32, u32),
+    ) -> ImageAndTileSizeMarkerSegment {
+        ImageAndTileSizeMarkerSegment {
+            reference_grid_width: grid.0.to_be_bytes(),
+            reference_grid_height: grid.1.to_be_bytes(),
+            image_horizontal_offset: offset.0.to_be_bytes(),
+            image_vertical_offset: offset.1.to_be_bytes(),
+            reference_tile_width: tile.0.to_be_bytes(),
+            reference_tile_height: tile.1.to_be_bytes(),
+            tile_horizontal_offset: tile_offset.0.to_be_bytes(),
+            tile_vertical_offset: tile_offset.1.to_be_bytes(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tile_geometry() {
+        // 3 x 2 tiles, partially covering the image area at the edges
+        let siz = siz_with_grid((100, 50), (10, 5), (40, 30), (0, 0));
+        assert_eq!(siz.num_x_tiles(), Ok(3));
+        assert_eq!(siz.num_y_tiles(), Ok(2));
+        assert_eq!(siz.tile_dimensions(0), Ok((30, 25)));
+        assert_eq!(siz.tile_dimensions(1), Ok((40, 25)));
+        assert_eq!(siz.tile_dimensions(2), Ok((20, 25)));
+        assert_eq!(siz.tile_dimensions(5), Ok((20, 20)));
+        assert_eq!(siz.tile_x_upper(4), Ok(40));
+        assert_eq!(siz.tile_y_upper(4), Ok(30));
+        assert_eq!(
+            siz.tile_dimensions(6),
+            Err(CodestreamError::GridOverflow { name: "q" })
+        );
+    }
+
+    #[test]
+    fn test_tile_geometry_near_u32_max() {
+        let max = u32::MAX;
+        let siz = siz_with_grid(
+            (max, max),
+            (max - 1, max - 1),
+            (max, max),
+            (max - 1, max - 1),
+        );
+        assert_eq!(siz.num_x_tiles(), Ok(1));
+        assert_eq!(siz.tile_x_lower(0), Ok(max));
+        assert_eq!(siz.tile_dimensions(0), Ok((1, 1)));
+
+        let siz = siz_with_grid((max, max), (0, 0), (1, 1), (0, 0));
+        assert_eq!(siz.num_x_tiles(), Ok(max));
+        assert_eq!(siz.tile_x_upper(max - 1), Ok(max - 1));
+        assert_eq!(siz.tile_dimensions(max - 1), Ok((1, 1)));
+
+        let siz = siz_with_grid((max, max), (0, 0), (0, 1), (0, 0));
+        assert_eq!(
+            siz.num_x_tiles(),
+            Err(CodestreamError::GridOverflow { name: "numXtiles" })
+        );
+    }
+
+    #[test]
+    fn test_codestream_error_grid_overflow() {
+        let e = CodestreamError::GridOverflow { name: "numtiles" };
+        assert_eq!(format!("{e}"), "reference grid overflow computing numtiles");
+    }
+
+    #[test]
+    fn test_siz_components() {
+        let siz = ImageAndTileSizeMarkerSegment {
+            precision: vec![[0x07], [0x8B], [0x00]],
+            horizontal_separation: vec![[1], [2], [4]],
+            vertical_separation: vec![[1], [2], [3]],
+            ..Default::default()
+        };
+        let components: Vec<ComponentInfo> = siz.components().collect();
+        assert_eq!(components.len(), 3);
+        assert_eq!(components[0].precision(), 8);
+        assert!(!components[0].values_are_signed());
+        assert_eq!(components[1].precision(), 12);
+        assert!(components[1].values_are_signed());
+        assert_eq!(components[1].horizontal_separation(), 2);
+        assert_eq!(components[1].vertical_separation(), 2);
+        assert_eq!(components[2].precision(), 1);
+        assert_eq!(components[2].horizontal_separation(), 4);
+        assert_eq!(components[2].vertical_separation(), 3);
+    }
+
+    #[test]
+    fn test_decoder_capabilities() {
+        assert_eq!(
+            DecoderCapabilities::new(0x0000),
+            vec![DecoderCapabilities::Baseline]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x0002),
+            vec![DecoderCapabilities::Profile1]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x4000),
+            vec![
+                DecoderCapabilities::Baseline,
+                DecoderCapabilities::ExtendedCapabilities
+            ]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x0203),
+            vec![DecoderCapabilities::BroadcastMultiTile { level: 3 }]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x0821),
+            vec![DecoderCapabilities::Imf4KReversible {
+                main_level: 1,
+                sub_level: 2
+            }]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x0010),
+            vec![DecoderCapabilities::Reserved { value: 0x0010 }]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0x8100),
+            vec![
+                DecoderCapabilities::Part2,
+                DecoderCapabilities::Part2MultipleComponentTransformations
+            ]
+        );
+        assert_eq!(
+            DecoderCapabilities::new(0xD801),
+            vec![
+                DecoderCapabilities::Part2,
+                DecoderCapabilities::Part2DcOffset,
+                DecoderCapabilities::Reserved { value: 0x1800 },
+                DecoderCapabilities::ExtendedCapabilities
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codestream_error_limit_exceeded() {
+        let e = CodestreamError::LimitExceeded {
+            name: "width",
+            value: 70000,
+            limit: 65536,
+        };
+        assert_eq!(
+            format!("{e}"),
+            "width of 70000 exceeds decode limit of 65536"
+        );
+    }
+
     #[test]
     fn test_codestream_error_missing_marker() {
         let e = CodestreamError::MarkerMissing {
@@ -3434,29 +5916,30 @@ mod tests {
     }
 
     #[test]
-    fn test_codestream_error_tilesize_overflow() {
-        let e = CodestreamError::TileSizeOverflow {
-            image_horizontal_offset: 1,
-            image_vertical_offset: 2,
-            tile_horizontal_offset: 3,
-            tile_vertical_offset: 4,
-            reference_tile_width: 5,
-            reference_tile_height: 6,
+    fn test_siz_error_first_tile_outside_image() {
+        let e = SizError::FirstTileOutsideImage {
+            axis: 'Y',
+            tile_end: 3,
+            image_offset: 4,
+            offset: 2,
         };
-        assert_eq!(format!("{e}"), "tile size overflow: XOSiz = 1, YOsiz = 2, XTOsiz = 3, YTOsiz = 4, XTsize = 5, YTsize = 6");
+        assert_eq!(
+            format!("{e}"),
+            "marker SIZ (0xFF51) at byte offset 2 has YTOsiz + YTsiz 3, not greater than YOsiz 4"
+        );
     }
 
     #[test]
-    fn test_codestream_error_tile_grid_offset_overflow() {
-        let e = CodestreamError::TileGridOffsetOverflow {
-            tile_horizontal_offset: 1,
-            tile_vertical_offset: 2,
-            image_horizontal_offset: 3,
-            image_vertical_offset: 4,
+    fn test_siz_error_tile_offset_out_of_range() {
+        let e = SizError::TileOffsetOutOfRange {
+            axis: 'X',
+            tile_offset: 5,
+            image_offset: 4,
+            offset: 2,
         };
         assert_eq!(
             format!("{e}"),
-            "tile grid offset overflow: XOSiz = 3, YOsiz = 4, XTOsiz = 1, YTOsiz = 2"
+            "marker SIZ (0xFF51) at byte offset 2 has XTOsiz 5, greater than XOsiz 4"
         );
     }
 
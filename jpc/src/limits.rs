@@ -0,0 +1,181 @@
+//! Resource limits for decoding untrusted codestreams.
+
+use std::cmp;
+
+use super::{CodestreamError, ImageAndTileSizeMarkerSegment};
+
+/// Bytes used to hold each decoded sample or wavelet coefficient.
+const BYTES_PER_SAMPLE: u64 = 4;
+
+/// Bytes charged for each sub-band of a precinct, with its tag trees, while
+/// the packets of a tile are read.
+const BYTES_PER_PRECINCT_SUBBAND: u64 = 256;
+
+/// Bytes charged for each code-block, with its tag tree nodes and coded data
+/// description, while the packets of a tile are read.
+const BYTES_PER_CODE_BLOCK: u64 = 256;
+
+/// Bytes charged for each packet of the progression order of a tile.
+const BYTES_PER_PACKET: u64 = 16;
+
+/// Limits checked against the SIZ marker segment, before any storage for the
+/// image is allocated.
+///
+/// The default limits are generous, allowing images of up to 2<sup>20</sup>
+/// samples on each side, the maximum of 16384 components, the 65535 tiles
+/// that Isot can index and 4 GiB of decoded samples, but are finite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum width of the image area on the reference grid, Xsiz - XOsiz.
+    pub max_width: u32,
+    /// Maximum height of the image area on the reference grid, Ysiz - YOsiz.
+    pub max_height: u32,
+    /// Maximum number of components, Csiz.
+    pub max_components: u16,
+    /// Maximum number of tiles, numXtiles · numYtiles.
+    pub max_tiles: u64,
+    /// Maximum number of bytes for the decoded samples of all components,
+    /// and, when decoding a tile, for the bookkeeping of its precincts,
+    /// code-blocks and packets.
+    pub max_alloc_bytes: u64,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_width: 1 << 20,
+            max_height: 1 << 20,
+            max_components: 16384,
+            max_tiles: 65535,
+            max_alloc_bytes: 1 << 32,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// No limits, for trusted input only.
+    pub fn unlimited() -> Self {
+        DecodeLimits {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_components: u16::MAX,
+            max_tiles: u64::MAX,
+            max_alloc_bytes: u64::MAX,
+        }
+    }
+
+    /// Check the image area, the number of components and the number of
+    /// tiles.
+    pub(crate) fn check_size(
+        &self,
+        segment: &ImageAndTileSizeMarkerSegment,
+    ) -> Result<(), CodestreamError> {
+        let width = segment
+            .reference_grid_width()
+            .saturating_sub(segment.image_horizontal_offset());
+        if width > self.max_width {
+            return Err(CodestreamError::LimitExceeded {
+                name: "width",
+                value: width as u64,
+                limit: self.max_width as u64,
+            });
+        }
+        let height = segment
+            .reference_grid_height()
+            .saturating_sub(segment.image_vertical_offset());
+        if height > self.max_height {
+            return Err(CodestreamError::LimitExceeded {
+                name: "height",
+                value: height as u64,
+                limit: self.max_height as u64,
+            });
+        }
+        if segment.no_components() > self.max_components {
+            return Err(CodestreamError::LimitExceeded {
+                name: "components",
+                value: segment.no_components() as u64,
+                limit: self.max_components as u64,
+            });
+        }
+        if segment.num_tiles() > self.max_tiles {
+            return Err(CodestreamError::LimitExceeded {
+                name: "tiles",
+                value: segment.num_tiles(),
+                limit: self.max_tiles,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the storage needed for the samples of all components, taking
+    /// the component sub-sampling into account.
+    pub(crate) fn check_allocation(
+        &self,
+        segment: &ImageAndTileSizeMarkerSegment,
+    ) -> Result<(), CodestreamError> {
+        let mut bytes: u64 = 0;
+        for (horizontal_separation, vertical_separation) in segment
+            .horizontal_separation
+            .iter()
+            .zip(segment.vertical_separation.iter())
+        {
+            // Component dimensions, see Equation B-2
+            let width = component_extent(
+                segment.image_horizontal_offset(),
+                segment.reference_grid_width(),
+                horizontal_separation[0],
+            );
+            let height = component_extent(
+                segment.image_vertical_offset(),
+                segment.reference_grid_height(),
+                vertical_separation[0],
+            );
+            bytes = bytes.saturating_add(
+                width
+                    .saturating_mul(height)
+                    .saturating_mul(BYTES_PER_SAMPLE),
+            );
+        }
+        if bytes > self.max_alloc_bytes {
+            return Err(CodestreamError::LimitExceeded {
+                name: "allocation",
+                value: bytes,
+                limit: self.max_alloc_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the storage needed to decode a tile, before its precincts are
+    /// laid out: the `samples` of its tile-components, and the bookkeeping
+    /// of its `precinct_subbands`, `code_blocks` and `packets`.
+    pub(crate) fn check_tile_allocation(
+        &self,
+        samples: u64,
+        precinct_subbands: u64,
+        code_blocks: u64,
+        packets: u64,
+    ) -> Result<(), CodestreamError> {
+        let bytes = samples
+            .saturating_mul(BYTES_PER_SAMPLE)
+            .saturating_add(precinct_subbands.saturating_mul(BYTES_PER_PRECINCT_SUBBAND))
+            .saturating_add(code_blocks.saturating_mul(BYTES_PER_CODE_BLOCK))
+            .saturating_add(packets.saturating_mul(BYTES_PER_PACKET));
+        if bytes > self.max_alloc_bytes {
+            return Err(CodestreamError::LimitExceeded {
+                name: "allocation",
+                value: bytes,
+                limit: self.max_alloc_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Number of samples between ⌈offset / separation⌉ and ⌈size / separation⌉
+fn component_extent(offset: u32, size: u32, separation: u8) -> u64 {
+    let separation = cmp::max(separation, 1) as u64;
+    let start = (offset as u64).div_ceil(separation);
+    let end = (size as u64).div_ceil(separation);
+    end.saturating_sub(start)
+}
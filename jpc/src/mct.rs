@@ -0,0 +1,119 @@
+//! Inverse component transformations.
+//!
+//! See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Annex G.
+
+use std::error;
+
+use super::{CodestreamError, MultipleComponentTransformation, TransformationFilter};
+
+// Factors of the inverse ICT, Equation G-11
+const ICT_CR_TO_I0: f64 = 1.402;
+const ICT_CB_TO_I1: f64 = 0.344_136;
+const ICT_CR_TO_I1: f64 = 0.714_136;
+const ICT_CB_TO_I2: f64 = 1.772;
+
+/// Undo the component transformation of a tile, given the samples of each
+/// of its components in turn, before the inverse DC level shift.
+///
+/// With [MultipleComponentTransformation::Multiple], components 0, 1 and 2
+/// are transformed, with the RCT for the reversible filter or the ICT for
+/// the irreversible filter, and any further components, such as an alpha
+/// channel, are left as they are. The ICT results are rounded to the
+/// nearest integer. With [MultipleComponentTransformation::None], no
+/// component is changed.
+///
+/// There shall be at least three components for a transformation, each of
+/// the first three with the same number of samples.
+pub fn inverse_component_transformation(
+    mct: MultipleComponentTransformation,
+    filter: TransformationFilter,
+    components: &mut [Vec<i32>],
+) -> Result<(), Box<dyn error::Error>> {
+    match mct {
+        MultipleComponentTransformation::None => return Ok(()),
+        MultipleComponentTransformation::Multiple => {}
+        MultipleComponentTransformation::Reserved { .. } => {
+            return Err(CodestreamError::InvalidParameter { name: "mct" }.into())
+        }
+    }
+    // Further components pass through
+    let (y0, y1, y2) = match components {
+        [y0, y1, y2, ..] => (y0, y1, y2),
+        _ => return Err(CodestreamError::InvalidParameter { name: "components" }.into()),
+    };
+    if y1.len() != y0.len() || y2.len() != y0.len() {
+        return Err(CodestreamError::InvalidParameter { name: "components" }.into());
+    }
+
+    let samples = y0.iter_mut().zip(y1.iter_mut()).zip(y2.iter_mut());
+    match filter {
+        TransformationFilter::Reversible => {
+            // Equation G-7
+            for ((y0, y1), y2) in samples {
+                let i1 = *y0 - ((*y2 + *y1) >> 2);
+                (*y0, *y1, *y2) = (*y2 + i1, i1, *y1 + i1);
+            }
+        }
+        TransformationFilter::Irreversible => {
+            // Equation G-11
+            for ((y0, y1), y2) in samples {
+                let (y, cb, cr) = (f64::from(*y0), f64::from(*y1), f64::from(*y2));
+                *y0 = (y + ICT_CR_TO_I0 * cr).round() as i32;
+                *y1 = (y - ICT_CB_TO_I1 * cb - ICT_CR_TO_I1 * cr).round() as i32;
+                *y2 = (y + ICT_CB_TO_I2 * cb).round() as i32;
+            }
+        }
+        TransformationFilter::Reserved { .. } => {
+            return Err(CodestreamError::InvalidParameter { name: "filter" }.into())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse_rct() {
+        // Forward RCT of (10, 20, 30) and (-128, 127, 0), Equation G-6
+        let mut components = vec![vec![20, 31], vec![10, -127], vec![-10, -255]];
+        inverse_component_transformation(
+            MultipleComponentTransformation::Multiple,
+            TransformationFilter::Reversible,
+            &mut components,
+        )
+        .unwrap();
+        assert_eq!(components, vec![vec![10, -128], vec![20, 127], vec![30, 0]]);
+    }
+
+    #[test]
+    fn test_inverse_ict() {
+        // Grey has no chrominance, and pure chrominance is scaled
+        let mut components = vec![vec![50, 0], vec![0, 100], vec![0, 0]];
+        inverse_component_transformation(
+            MultipleComponentTransformation::Multiple,
+            TransformationFilter::Irreversible,
+            &mut components,
+        )
+        .unwrap();
+        assert_eq!(components, vec![vec![50, 0], vec![50, -34], vec![50, 177]]);
+    }
+
+    #[test]
+    fn test_too_few_components() {
+        let mut components = vec![vec![0], vec![0]];
+        assert!(inverse_component_transformation(
+            MultipleComponentTransformation::Multiple,
+            TransformationFilter::Reversible,
+            &mut components,
+        )
+        .is_err());
+        assert!(inverse_component_transformation(
+            MultipleComponentTransformation::None,
+            TransformationFilter::Reversible,
+            &mut components,
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,79 @@
+//! Decoding of an image from a JP2 file or a raw codestream.
+//!
+//! This combines [detect_format], the JP2 boxes and
+//! [ContiguousCodestream::decode_image] into a single call.
+
+use std::error;
+use std::io;
+
+use jp2::EnumeratedColourSpaces;
+
+use super::{
+    decode_first_codestream, decode_jpc, detect_format, CodestreamError, ContiguousCodestream,
+    DecodedImage, Jpeg2000Format,
+};
+
+/// Decode the image of a JP2 file or of a raw codestream read from `reader`.
+///
+/// The format is found by [detect_format]. For a JP2 file, the first
+/// Contiguous Codestream box is decoded, then the Palette, Component
+/// Mapping and Channel Definition boxes of the JP2 Header box are applied,
+/// and the colour space is taken from its Colour Specification boxes. For a
+/// raw codestream, the colour space follows from the number of components,
+/// greyscale for one or two and sRGB otherwise.
+///
+/// Only the codestreams that [ContiguousCodestream::decode_image] supports
+/// can be decoded.
+pub fn open<R: io::Read + io::Seek>(reader: &mut R) -> Result<DecodedImage, Box<dyn error::Error>> {
+    match detect_format(reader) {
+        Jpeg2000Format::Jp2Boxed => open_jp2(reader),
+        Jpeg2000Format::RawCodestream => {
+            let codestream = decode_jpc(reader)?;
+            let image = codestream.decode_image(reader)?;
+            let colour_space = default_colour_space(&codestream);
+            Ok(image.with_colour_space(colour_space))
+        }
+        Jpeg2000Format::Unknown => Err(CodestreamError::InputFormatError {
+            error: String::from("neither a JP2 file nor a codestream"),
+        }
+        .into()),
+    }
+}
+
+// Decode the first codestream of a JP2 file, with the channels and the
+// colour space of its JP2 Header box
+fn open_jp2<R: io::Read + io::Seek>(reader: &mut R) -> Result<DecodedImage, Box<dyn error::Error>> {
+    let jp2 = jp2::decode_jp2(reader)?;
+    let codestream = decode_first_codestream(reader, &jp2)?;
+    let mut image = codestream.decode_image(reader)?;
+
+    let header_box = match jp2.header_box() {
+        Some(header_box) => header_box,
+        None => {
+            let colour_space = default_colour_space(&codestream);
+            return Ok(image.with_colour_space(colour_space));
+        }
+    };
+    if let Some(palette) = &header_box.palette_box {
+        image = image.with_palette(palette, header_box.component_mapping_box.as_ref())?;
+    }
+    if let Some(channel_definition) = &header_box.channel_definition_box {
+        image = image.with_channel_definition(channel_definition);
+    }
+    let colour_space = header_box
+        .known_colour_space()
+        .unwrap_or_else(|| default_colour_space(&codestream));
+    Ok(image.with_colour_space(colour_space))
+}
+
+// Colour space of a codestream without a file format, from Csiz
+fn default_colour_space(codestream: &ContiguousCodestream) -> EnumeratedColourSpaces {
+    match codestream
+        .header()
+        .image_and_tile_size_marker_segment()
+        .no_components()
+    {
+        1 | 2 => EnumeratedColourSpaces::Greyscale,
+        _ => EnumeratedColourSpaces::sRGB,
+    }
+}
@@ -0,0 +1,645 @@
+//! Packet headers and packet bodies.
+//!
+//! See ITU-T T.800(V4) | ISO/IEC 15444-1:2024 Section B.10.
+
+use std::error;
+use std::fmt;
+
+use crate::code_block::ends_segment;
+use crate::tag_tree::{TagTree, TagTreeEncoder};
+
+// Initial value of Lblock, Section B.10.7.1
+const LBLOCK_INITIAL: u8 = 3;
+
+// Most bits of a codeword segment length
+const MAX_LENGTH_BITS: u8 = 32;
+
+/// Error values that may be returned when reading a packet.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PacketDecodeError {
+    /// Packet header or packet body runs past the end of the data
+    DataExhausted { offset: usize },
+    /// EPH marker does not follow the packet header, although the coding
+    /// style signals that it is used
+    EphMissing { offset: usize },
+    /// Codeword segment length takes more than 32 bits
+    LengthOverflow { offset: usize },
+}
+
+impl error::Error for PacketDecodeError {}
+impl fmt::Display for PacketDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DataExhausted { offset } => {
+                write!(f, "packet data exhausted at byte offset {offset}")
+            }
+            Self::EphMissing { offset } => {
+                write!(f, "missing EPH marker at byte offset {offset}")
+            }
+            Self::LengthOverflow { offset } => {
+                write!(
+                    f,
+                    "codeword segment length of more than 32 bits at byte offset {offset}"
+                )
+            }
+        }
+    }
+}
+
+/// Reader of the bits of a packet header.
+///
+/// A byte following a byte of 0xFF has a 0 bit stuffed into its most
+/// significant bit, which is skipped. See Section B.10.1.
+#[derive(Debug)]
+struct PacketHeaderReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    byte: u8,
+    bits_left: u8,
+}
+
+impl<'a> PacketHeaderReader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        PacketHeaderReader {
+            data,
+            offset,
+            byte: 0,
+            bits_left: 0,
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, PacketDecodeError> {
+        if self.bits_left == 0 {
+            let byte = *self
+                .data
+                .get(self.offset)
+                .ok_or(PacketDecodeError::DataExhausted {
+                    offset: self.offset,
+                })?;
+            self.bits_left = if self.byte == 0xFF { 7 } else { 8 };
+            self.byte = byte;
+            self.offset += 1;
+        }
+        self.bits_left -= 1;
+        Ok((self.byte >> self.bits_left) & 1 == 1)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u32, PacketDecodeError> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | u32::from(self.read_bit()?);
+        }
+        Ok(value)
+    }
+
+    /// Offset of the byte following the packet header.
+    ///
+    /// The header ends on a byte boundary, and if its last byte is 0xFF, it
+    /// is followed by a byte holding the stuffed bit.
+    fn end(&self) -> usize {
+        if self.byte == 0xFF {
+            self.offset + 1
+        } else {
+            self.offset
+        }
+    }
+
+    /// Number of coding passes, from the codewords of Table B.4.
+    fn read_no_passes(&mut self) -> Result<u8, PacketDecodeError> {
+        if !self.read_bit()? {
+            return Ok(1);
+        }
+        if !self.read_bit()? {
+            return Ok(2);
+        }
+        match self.read_bits(2)? {
+            3 => {}
+            value => return Ok(3 + value as u8),
+        }
+        match self.read_bits(5)? {
+            31 => {}
+            value => return Ok(6 + value as u8),
+        }
+        Ok(37 + self.read_bits(7)? as u8)
+    }
+}
+
+/// Writer of the bits of a packet header, the counterpart of
+/// [PacketHeaderReader].
+///
+/// A 0 bit is stuffed into the most significant bit of each byte following a
+/// byte of 0xFF. See Section B.10.1.
+#[derive(Debug, Default)]
+struct PacketHeaderWriter {
+    data: Vec<u8>,
+    bits_left: u8,
+}
+
+impl PacketHeaderWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.bits_left == 0 {
+            self.bits_left = if self.data.last() == Some(&0xFF) {
+                7
+            } else {
+                8
+            };
+            self.data.push(0);
+        }
+        self.bits_left -= 1;
+        if let Some(byte) = self.data.last_mut() {
+            *byte |= (bit as u8) << self.bits_left;
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u8) {
+        for bit in (0..count).rev() {
+            self.write_bit((value >> bit) & 1 == 1);
+        }
+    }
+
+    /// Number of coding passes, from 1 to 164, as the codewords of Table
+    /// B.4.
+    fn write_no_passes(&mut self, no_passes: u8) {
+        match no_passes {
+            0 | 1 => self.write_bit(false),
+            2 => self.write_bits(0b10, 2),
+            3..=5 => self.write_bits(0b1100 | u32::from(no_passes - 3), 4),
+            6..=36 => self.write_bits((0b1111 << 5) | u32::from(no_passes - 6), 9),
+            _ => self.write_bits((0b1_1111_1111 << 7) | u32::from(no_passes - 37), 16),
+        }
+    }
+
+    /// Bytes of the packet header, padded with 0 bits to a byte boundary.
+    ///
+    /// If the last byte is 0xFF, it is followed by a byte holding the
+    /// stuffed bit.
+    fn finish(mut self) -> Vec<u8> {
+        if self.data.last() == Some(&0xFF) {
+            self.data.push(0);
+        }
+        self.data
+    }
+}
+
+/// Code-block of a precinct, with what the packets read so far have
+/// signalled for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PrecinctCodeBlock {
+    /// Layer of the packet that first included the code-block, if any. The
+    /// inclusion tag tree signals this layer, Section B.10.4, and each later
+    /// packet adds to the coding passes of the earlier ones.
+    pub(crate) first_layer: Option<u16>,
+    /// Number of missing most significant bit-planes, Section B.10.5.
+    pub(crate) zero_bit_planes: u8,
+    /// Number of coding passes included so far.
+    pub(crate) no_passes: u8,
+    /// Coded data of the included coding passes.
+    pub(crate) data: Vec<u8>,
+    /// Length of each codeword segment of `data`.
+    pub(crate) segment_lengths: Vec<usize>,
+    /// Lblock, Section B.10.7.1.
+    lblock: u8,
+}
+
+impl PrecinctCodeBlock {
+    /// Code-block to be written to packets, with its missing bit-planes,
+    /// coding passes and coded data.
+    pub(crate) fn new(zero_bit_planes: u8, no_passes: u8, data: Vec<u8>) -> Self {
+        PrecinctCodeBlock {
+            zero_bit_planes,
+            no_passes,
+            data,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PrecinctCodeBlock {
+    fn default() -> Self {
+        PrecinctCodeBlock {
+            first_layer: None,
+            zero_bit_planes: 0,
+            no_passes: 0,
+            data: vec![],
+            segment_lengths: vec![],
+            lblock: LBLOCK_INITIAL,
+        }
+    }
+}
+
+/// Code-blocks of a sub-band within a precinct, in raster order, with the
+/// inclusion and zero bit-plane tag trees over them.
+#[derive(Debug, Clone)]
+pub(crate) struct PrecinctSubBand {
+    width: usize,
+    pub(crate) code_blocks: Vec<PrecinctCodeBlock>,
+    inclusion: TagTree,
+    zero_bit_planes: TagTree,
+}
+
+impl PrecinctSubBand {
+    /// Sub-band with `width` by `height` code-blocks in the precinct.
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        PrecinctSubBand {
+            width,
+            code_blocks: vec![PrecinctCodeBlock::default(); width * height],
+            inclusion: TagTree::new(width, height),
+            zero_bit_planes: TagTree::new(width, height),
+        }
+    }
+}
+
+/// Precinct of a resolution level of a tile-component, with its sub-bands in
+/// the order their code-blocks appear in a packet: LL for resolution level 0,
+/// then HL, LH and HH for the other resolution levels.
+#[derive(Debug, Clone)]
+pub(crate) struct Precinct {
+    pub(crate) subbands: Vec<PrecinctSubBand>,
+}
+
+impl Precinct {
+    /// Read the packet of `layer` for the precinct from `data` at `offset`,
+    /// adding the coding passes and data it includes to the code-blocks.
+    ///
+    /// An SOP marker segment before the packet is skipped, and an EPH marker
+    /// after the packet header is required if `eph` is set. Returns the
+    /// offset following the packet.
+    ///
+    /// The coding passes of a code-block are split into codeword segments by
+    /// `code_block_style`, each with its own length, Section B.10.7.2.
+    pub(crate) fn read_packet(
+        &mut self,
+        data: &[u8],
+        offset: usize,
+        layer: u16,
+        eph: bool,
+        code_block_style: u8,
+    ) -> Result<usize, PacketDecodeError> {
+        let mut offset = offset;
+        // SOP marker and Lsop, Nsop
+        let marker_at = |offset: usize, marker: [u8; 2]| {
+            data.get(offset..)
+                .is_some_and(|data| data.starts_with(&marker))
+        };
+        // A packet header does not end with 0xFF, so a lone 0xFF at the end
+        // is the start of an SOP marker or of a header still to arrive
+        let sop_exhausted = if marker_at(offset, [0xFF, 0x91]) {
+            offset += 6;
+            offset > data.len()
+        } else {
+            offset + 1 == data.len() && data[offset] == 0xFF
+        };
+        if sop_exhausted {
+            return Err(PacketDecodeError::DataExhausted { offset: data.len() });
+        }
+
+        let mut reader = PacketHeaderReader::new(data, offset);
+        // Code-blocks included in this packet, with the length of the data of
+        // each codeword segment, and whether the first continues the last
+        // segment of an earlier packet
+        let mut lengths: Vec<(usize, usize, Vec<usize>, bool)> = Vec::new();
+        if reader.read_bit()? {
+            for (s, subband) in self.subbands.iter_mut().enumerate() {
+                for (c, code_block) in subband.code_blocks.iter_mut().enumerate() {
+                    let (x, y) = (c % subband.width, c / subband.width);
+                    let first_inclusion = code_block.first_layer.is_none();
+                    let included = if first_inclusion {
+                        subband
+                            .inclusion
+                            .decode(x, y, u32::from(layer) + 1, || reader.read_bit())?
+                    } else {
+                        reader.read_bit()?
+                    };
+                    if !included {
+                        continue;
+                    }
+                    if first_inclusion {
+                        code_block.first_layer = Some(layer);
+                        code_block.zero_bit_planes =
+                            subband
+                                .zero_bit_planes
+                                .decode_value(x, y, || reader.read_bit())?
+                                as u8;
+                    }
+                    let no_passes = reader.read_no_passes()?;
+                    let length_overflow =
+                        |reader: &PacketHeaderReader| PacketDecodeError::LengthOverflow {
+                            offset: reader.offset,
+                        };
+                    while reader.read_bit()? {
+                        if code_block.lblock >= MAX_LENGTH_BITS {
+                            return Err(length_overflow(&reader));
+                        }
+                        code_block.lblock += 1;
+                    }
+                    // Lblock + ⌊log2(passes)⌋ bits for the passes of each
+                    // segment, Section B.10.7
+                    let first = usize::from(code_block.no_passes);
+                    let mut segment_lengths = Vec::new();
+                    let mut segment_passes: u32 = 0;
+                    for pass in first..first + usize::from(no_passes) {
+                        segment_passes += 1;
+                        if pass + 1 == first + usize::from(no_passes)
+                            || ends_segment(code_block_style, pass)
+                        {
+                            let length_bits = code_block.lblock + segment_passes.ilog2() as u8;
+                            if length_bits > MAX_LENGTH_BITS {
+                                return Err(length_overflow(&reader));
+                            }
+                            segment_lengths.push(reader.read_bits(length_bits)? as usize);
+                            segment_passes = 0;
+                        }
+                    }
+                    let continues = first > 0 && !ends_segment(code_block_style, first - 1);
+                    code_block.no_passes = code_block.no_passes.saturating_add(no_passes);
+                    lengths.push((s, c, segment_lengths, continues));
+                }
+            }
+        }
+        offset = reader.end();
+
+        if eph {
+            if offset + 2 > data.len() {
+                return Err(PacketDecodeError::DataExhausted { offset: data.len() });
+            }
+            if !marker_at(offset, [0xFF, 0x92]) {
+                return Err(PacketDecodeError::EphMissing { offset });
+            }
+            offset += 2;
+        }
+
+        for (s, c, segment_lengths, continues) in lengths {
+            let code_block = &mut self.subbands[s].code_blocks[c];
+            for (i, length) in segment_lengths.into_iter().enumerate() {
+                let body = data
+                    .get(offset..offset + length)
+                    .ok_or(PacketDecodeError::DataExhausted { offset: data.len() })?;
+                code_block.data.extend_from_slice(body);
+                match code_block.segment_lengths.last_mut() {
+                    Some(last) if i == 0 && continues => *last += length,
+                    _ => code_block.segment_lengths.push(length),
+                }
+                offset += length;
+            }
+        }
+        Ok(offset)
+    }
+}
+
+/// Code-blocks of a sub-band within a precinct to be written to packets, in
+/// raster order, with the inclusion and zero bit-plane tag trees over them.
+#[derive(Debug)]
+pub(crate) struct PrecinctSubBandWriter {
+    width: usize,
+    code_blocks: Vec<PrecinctCodeBlock>,
+    inclusion: TagTreeEncoder,
+    zero_bit_planes: TagTreeEncoder,
+}
+
+impl PrecinctSubBandWriter {
+    /// Sub-band with `width` by `height` code-blocks in the precinct, each
+    /// with its zero bit-planes, coding passes and coded data.
+    ///
+    /// Every coding pass of a code-block is written in the packet of the
+    /// first layer, and a code-block without coding passes is never
+    /// included.
+    pub(crate) fn new(width: usize, height: usize, code_blocks: Vec<PrecinctCodeBlock>) -> Self {
+        let inclusion: Vec<u32> = code_blocks
+            .iter()
+            .map(|code_block| {
+                if code_block.no_passes > 0 {
+                    0
+                } else {
+                    u32::MAX
+                }
+            })
+            .collect();
+        let zero_bit_planes: Vec<u32> = code_blocks
+            .iter()
+            .map(|code_block| u32::from(code_block.zero_bit_planes))
+            .collect();
+        PrecinctSubBandWriter {
+            width,
+            code_blocks,
+            inclusion: TagTreeEncoder::new(width, height, &inclusion),
+            zero_bit_planes: TagTreeEncoder::new(width, height, &zero_bit_planes),
+        }
+    }
+}
+
+/// Precinct of a resolution level of a tile-component to be written to
+/// packets, the counterpart of [Precinct], with its sub-bands in the same
+/// order.
+#[derive(Debug)]
+pub(crate) struct PrecinctWriter {
+    pub(crate) subbands: Vec<PrecinctSubBandWriter>,
+}
+
+impl PrecinctWriter {
+    /// Write the packet of `layer` for the precinct, without SOP or EPH
+    /// markers, including the code-blocks whose first layer it is.
+    pub(crate) fn write_packet(&mut self, layer: u16) -> Vec<u8> {
+        let mut writer = PacketHeaderWriter::default();
+        let mut body = Vec::new();
+        let non_empty = self.subbands.iter().any(|subband| {
+            subband
+                .code_blocks
+                .iter()
+                .any(|code_block| code_block.first_layer.is_none() && code_block.no_passes > 0)
+        });
+        writer.write_bit(non_empty);
+        if non_empty {
+            for subband in &mut self.subbands {
+                for (c, code_block) in subband.code_blocks.iter_mut().enumerate() {
+                    let (x, y) = (c % subband.width, c / subband.width);
+                    if code_block.first_layer.is_some() {
+                        // No further coding passes
+                        writer.write_bit(false);
+                        continue;
+                    }
+                    let threshold = u32::from(layer) + 1;
+                    if !subband
+                        .inclusion
+                        .encode(x, y, threshold, |bit| writer.write_bit(bit))
+                    {
+                        continue;
+                    }
+                    code_block.first_layer = Some(layer);
+                    subband
+                        .zero_bit_planes
+                        .encode_value(x, y, |bit| writer.write_bit(bit));
+                    let no_passes = code_block.no_passes;
+                    writer.write_no_passes(no_passes);
+                    // Lblock + ⌊log2(passes)⌋ bits, Section B.10.7.1
+                    let length = code_block.data.len() as u64;
+                    while length >> (code_block.lblock + no_passes.ilog2() as u8) != 0 {
+                        writer.write_bit(true);
+                        code_block.lblock += 1;
+                    }
+                    writer.write_bit(false);
+                    writer.write_bits(length as u32, code_block.lblock + no_passes.ilog2() as u8);
+                    body.extend_from_slice(&code_block.data);
+                }
+            }
+        }
+        let mut packet = writer.finish();
+        packet.extend_from_slice(&body);
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_no_passes() {
+        // Codewords of Table B.4 for 1, 2, 4, 6, 36, 37 and 164 passes, with
+        // a bit stuffed after each 0xFF
+        let data = [
+            0b0101_1011,
+            0b1110_0000,
+            0b1111_1111,
+            0b0011_1111,
+            0b1110_0000,
+            0b0011_1111,
+            0b1111_1111,
+            0b0110_0000,
+        ];
+        let mut reader = PacketHeaderReader::new(&data, 0);
+        for expected in [1, 2, 4, 6, 36, 37, 164] {
+            assert_eq!(reader.read_no_passes(), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_write_no_passes() {
+        // The codewords that test_read_no_passes reads
+        let mut writer = PacketHeaderWriter::default();
+        for no_passes in [1, 2, 4, 6, 36, 37, 164] {
+            writer.write_no_passes(no_passes);
+        }
+        let data = writer.finish();
+        let mut reader = PacketHeaderReader::new(&data, 0);
+        for expected in [1, 2, 4, 6, 36, 37, 164] {
+            assert_eq!(reader.read_no_passes(), Ok(expected));
+        }
+        assert_eq!(reader.end(), data.len());
+    }
+
+    #[test]
+    fn test_write_bit_stuffing() {
+        // Bytes of the test_bit_stuffing header, with the byte following
+        // 0xFF holding 7 bits
+        let mut writer = PacketHeaderWriter::default();
+        writer.write_bits(0xFF, 8);
+        writer.write_bits(0x7F, 7);
+        assert_eq!(writer.finish(), vec![0xFF, 0x7F]);
+
+        let mut writer = PacketHeaderWriter::default();
+        writer.write_bits(0xFF, 8);
+        assert_eq!(writer.finish(), vec![0xFF, 0x00]);
+    }
+
+    #[test]
+    fn test_bit_stuffing() {
+        // The most significant bit after 0xFF is skipped, and a header ending
+        // on 0xFF is followed by the byte with the stuffed bit
+        let data = [0xFF, 0x7F, 0x00];
+        let mut reader = PacketHeaderReader::new(&data, 0);
+        assert_eq!(reader.read_bits(8), Ok(0xFF));
+        assert_eq!(reader.end(), 2);
+        assert_eq!(reader.read_bits(7), Ok(0x7F));
+        assert_eq!(reader.end(), 2);
+        assert_eq!(reader.read_bit(), Ok(false));
+        assert_eq!(
+            reader.read_bits(8),
+            Err(PacketDecodeError::DataExhausted { offset: 3 })
+        );
+    }
+
+    #[test]
+    fn test_read_packet() {
+        // One sub-band of two code-blocks. The first is included, with 2 zero
+        // bit-planes, 3 coding passes and 2 bytes, and the second is not.
+        //
+        // 1 non-empty, 11 included, 0011 zero bit-planes, 1100 passes,
+        // 0 Lblock unchanged, 0010 length, 0 not included, then padding
+        let header = [0b1110_0111, 0b1000_0010, 0b0000_0000];
+        let mut precinct = Precinct {
+            subbands: vec![PrecinctSubBand::new(2, 1)],
+        };
+        let mut data = header.to_vec();
+        data.extend_from_slice(&[0xAB, 0xCD, 0xEF]);
+        assert_eq!(precinct.read_packet(&data, 0, 0, false, 0), Ok(5));
+        let code_blocks = &precinct.subbands[0].code_blocks;
+        assert_eq!(code_blocks[0].first_layer, Some(0));
+        assert_eq!(code_blocks[0].zero_bit_planes, 2);
+        assert_eq!(code_blocks[0].no_passes, 3);
+        assert_eq!(code_blocks[0].data, vec![0xAB, 0xCD]);
+        assert_eq!(code_blocks[1], PrecinctCodeBlock::default());
+
+        // Empty packet, with SOP and EPH markers
+        let data = [0xFF, 0x91, 0x00, 0x04, 0x00, 0x01, 0x00, 0xFF, 0x92];
+        assert_eq!(precinct.read_packet(&data, 0, 1, true, 0), Ok(9));
+        assert_eq!(
+            precinct.read_packet(&data[..8], 0, 1, true, 0),
+            Err(PacketDecodeError::DataExhausted { offset: 8 })
+        );
+        assert_eq!(
+            precinct.read_packet(&data[..3], 0, 1, true, 0),
+            Err(PacketDecodeError::DataExhausted { offset: 3 })
+        );
+        let data = [0xFF, 0x91, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(
+            precinct.read_packet(&data, 0, 1, true, 0),
+            Err(PacketDecodeError::EphMissing { offset: 7 })
+        );
+        assert_eq!(precinct.subbands[0].code_blocks[0].no_passes, 3);
+    }
+
+    #[test]
+    fn test_write_packet() {
+        // Two sub-bands, with the second code-block of the first empty, and
+        // the packet of the second layer empty
+        let code_block = |zero_bit_planes, no_passes, data: &[u8]| {
+            PrecinctCodeBlock::new(zero_bit_planes, no_passes, data.to_vec())
+        };
+        let mut writer = PrecinctWriter {
+            subbands: vec![
+                PrecinctSubBandWriter::new(
+                    2,
+                    1,
+                    vec![code_block(2, 3, &[0xAB, 0xCD]), code_block(0, 0, &[])],
+                ),
+                PrecinctSubBandWriter::new(1, 1, vec![code_block(5, 19, &[0xFF; 300])]),
+            ],
+        };
+        let mut data = writer.write_packet(0);
+        let first_length = data.len();
+        data.extend(writer.write_packet(1));
+        assert_eq!(data.len(), first_length + 1);
+
+        let mut precinct = Precinct {
+            subbands: vec![PrecinctSubBand::new(2, 1), PrecinctSubBand::new(1, 1)],
+        };
+        assert_eq!(
+            precinct.read_packet(&data, 0, 0, false, 0),
+            Ok(first_length)
+        );
+        assert_eq!(
+            precinct.read_packet(&data, first_length, 1, false, 0),
+            Ok(data.len())
+        );
+        let code_blocks = &precinct.subbands[0].code_blocks;
+        assert_eq!(code_blocks[0].first_layer, Some(0));
+        assert_eq!(code_blocks[0].zero_bit_planes, 2);
+        assert_eq!(code_blocks[0].no_passes, 3);
+        assert_eq!(code_blocks[0].data, vec![0xAB, 0xCD]);
+        assert_eq!(code_blocks[1], PrecinctCodeBlock::default());
+        let code_block = &precinct.subbands[1].code_blocks[0];
+        assert_eq!(code_block.zero_bit_planes, 5);
+        assert_eq!(code_block.no_passes, 19);
+        assert_eq!(code_block.data, vec![0xFF; 300]);
+    }
+}
@@ -0,0 +1,728 @@
+//! Multiple component transformation marker segments.
+//!
+//! The MCT, MCC and MCO marker segments of ITU-T T.801 | ISO/IEC 15444-2
+//! Annex A describe an array-based or wavelet-based transformation between
+//! collections of components. These are decoded here into a
+//! [MultiComponentTransform], which is not yet applied to the decoded image.
+
+use std::convert::TryFrom;
+
+use super::{CodestreamError, MARKER_SYMBOL_MCC, MARKER_SYMBOL_MCO, MARKER_SYMBOL_MCT};
+
+/// Type of the array of an MCT marker segment, from bits 8 and 9 of Imct.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Table A.33.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformArrayType {
+    /// Dependency transformation, a triangular matrix
+    Dependency,
+    /// Decorrelation transformation, a matrix of the output components by
+    /// the input components
+    Decorrelation,
+    /// Offsets, one for each output component
+    Offset,
+    Reserved {
+        value: u8,
+    },
+}
+
+impl From<u16> for TransformArrayType {
+    fn from(imct: u16) -> Self {
+        match (imct >> 8) & 0x03 {
+            0 => Self::Dependency,
+            1 => Self::Decorrelation,
+            2 => Self::Offset,
+            value => Self::Reserved { value: value as u8 },
+        }
+    }
+}
+
+/// Type of the elements of an MCT marker segment, from bits 10 and 11 of
+/// Imct.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Table A.33.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformElementType {
+    /// 16-bit signed integer
+    Int16,
+    /// 32-bit signed integer
+    Int32,
+    /// 32-bit IEEE 754 floating point
+    Float32,
+    /// 64-bit IEEE 754 floating point
+    Float64,
+}
+
+impl TransformElementType {
+    // Size in bytes of each SPmct value
+    fn size(self) -> usize {
+        match self {
+            Self::Int16 => 2,
+            Self::Int32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    // Value of the big-endian bytes of an SPmct value
+    fn value(self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::Int16 => f64::from(i16::from_be_bytes([bytes[0], bytes[1]])),
+            Self::Int32 => f64::from(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            Self::Float32 => {
+                f64::from(f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            Self::Float64 => f64::from_be_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ]),
+        }
+    }
+}
+
+impl From<u16> for TransformElementType {
+    fn from(imct: u16) -> Self {
+        match (imct >> 10) & 0x03 {
+            0 => Self::Int16,
+            1 => Self::Int32,
+            2 => Self::Float32,
+            _ => Self::Float64,
+        }
+    }
+}
+
+/// Multiple component transformation definition (MCT) marker segment.
+///
+/// An array of a dependency or decorrelation matrix, or of offsets, which
+/// may be split over several marker segments with the same index.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.7.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformArraySegment {
+    offset: u64,
+    length: u16,
+    segment_index: u16,
+    imct: u16,
+    last_segment_index: u16,
+    values: Vec<f64>,
+}
+
+impl TransformArraySegment {
+    // Decode the parameters of the marker segment, following Lmct, given the
+    // byte offset of the marker
+    pub(crate) fn decode(offset: u64, parameters: &[u8]) -> Result<Self, CodestreamError> {
+        let malformed = || CodestreamError::MarkerMalformed {
+            marker: MARKER_SYMBOL_MCT,
+            offset,
+        };
+        if parameters.len() < 6 {
+            return Err(malformed());
+        }
+        let imct = u16::from_be_bytes([parameters[2], parameters[3]]);
+        let size = TransformElementType::from(imct).size();
+        let spmct = &parameters[6..];
+        if !spmct.len().is_multiple_of(size) {
+            return Err(malformed());
+        }
+        Ok(Self {
+            offset,
+            length: u16::try_from(parameters.len() + 2).map_err(|_| malformed())?,
+            segment_index: u16::from_be_bytes([parameters[0], parameters[1]]),
+            imct,
+            last_segment_index: u16::from_be_bytes([parameters[4], parameters[5]]),
+            values: spmct
+                .chunks_exact(size)
+                .map(|bytes| TransformElementType::from(imct).value(bytes))
+                .collect(),
+        })
+    }
+
+    /// Byte offset of the marker.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the marker segment in bytes, not including the marker.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// Index of this marker segment among those of the array, Zmct.
+    pub fn segment_index(&self) -> u16 {
+        self.segment_index
+    }
+
+    /// Index of the last marker segment of the array, Ymct.
+    pub fn last_segment_index(&self) -> u16 {
+        self.last_segment_index
+    }
+
+    /// Index of the array, from 1 to 255, that component collections refer
+    /// to, from bits 0 to 7 of Imct.
+    pub fn index(&self) -> u8 {
+        (self.imct & 0xFF) as u8
+    }
+
+    pub fn array_type(&self) -> TransformArrayType {
+        TransformArrayType::from(self.imct)
+    }
+
+    pub fn element_type(&self) -> TransformElementType {
+        TransformElementType::from(self.imct)
+    }
+
+    /// Values of this marker segment, SPmct, in raster order.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+/// Type of the transformation of a component collection, from bits 0 and 1
+/// of Xmcc.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Table A.37.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionTransformType {
+    /// Array-based dependency transformation
+    Dependency,
+    /// Array-based decorrelation transformation
+    Decorrelation,
+    /// Wavelet-based transformation
+    Wavelet,
+    Reserved {
+        value: u8,
+    },
+}
+
+impl From<u8> for CollectionTransformType {
+    fn from(xmcc: u8) -> Self {
+        match xmcc & 0x03 {
+            0 => Self::Dependency,
+            1 => Self::Decorrelation,
+            3 => Self::Wavelet,
+            value => Self::Reserved { value },
+        }
+    }
+}
+
+/// Component collection of an MCC marker segment, with the input
+/// components that are transformed into the output components.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Table A.36.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentCollection {
+    xmcc: u8,
+    input_components: Vec<u16>,
+    output_components: Vec<u16>,
+    tmcc: u32,
+}
+
+impl ComponentCollection {
+    pub fn transform_type(&self) -> CollectionTransformType {
+        CollectionTransformType::from(self.xmcc)
+    }
+
+    /// Input components, Cmcc.
+    pub fn input_components(&self) -> &[u16] {
+        &self.input_components
+    }
+
+    /// Output components, Wmcc.
+    pub fn output_components(&self) -> &[u16] {
+        &self.output_components
+    }
+
+    /// Transformation parameters, Tmcc.
+    pub fn parameters(&self) -> u32 {
+        self.tmcc
+    }
+
+    /// Index of the MCT array of the matrix of an array-based
+    /// transformation, from bits 0 to 7 of Tmcc, or 0 if there is none.
+    pub fn matrix_index(&self) -> u8 {
+        match self.transform_type() {
+            CollectionTransformType::Dependency | CollectionTransformType::Decorrelation => {
+                (self.tmcc & 0xFF) as u8
+            }
+            _ => 0,
+        }
+    }
+
+    /// Index of the MCT array of the offsets of an array-based
+    /// transformation, from bits 8 to 15 of Tmcc, or 0 if there are none.
+    pub fn offset_index(&self) -> u8 {
+        match self.transform_type() {
+            CollectionTransformType::Dependency | CollectionTransformType::Decorrelation => {
+                ((self.tmcc >> 8) & 0xFF) as u8
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether an array-based transformation is reversible, from bit 16 of
+    /// Tmcc.
+    pub fn is_reversible(&self) -> bool {
+        (self.tmcc >> 16) & 0x01 == 1
+    }
+}
+
+/// Multiple component collection (MCC) marker segment.
+///
+/// The component collections of a stage of the multiple component
+/// transformation.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentCollectionSegment {
+    offset: u64,
+    length: u16,
+    segment_index: u16,
+    index: u8,
+    last_segment_index: u16,
+    collections: Vec<ComponentCollection>,
+}
+
+impl ComponentCollectionSegment {
+    // Decode the parameters of the marker segment, following Lmcc, given the
+    // byte offset of the marker
+    pub(crate) fn decode(offset: u64, parameters: &[u8]) -> Result<Self, CodestreamError> {
+        let malformed = || CodestreamError::MarkerMalformed {
+            marker: MARKER_SYMBOL_MCC,
+            offset,
+        };
+        let mut reader = ParameterReader { parameters };
+        let segment_index = reader.u16().ok_or_else(malformed)?;
+        let index = reader.u8().ok_or_else(malformed)?;
+        let last_segment_index = reader.u16().ok_or_else(malformed)?;
+        let no_collections = reader.u16().ok_or_else(malformed)?;
+        let mut collections = Vec::with_capacity(usize::from(no_collections).min(parameters.len()));
+        for _ in 0..no_collections {
+            let xmcc = reader.u8().ok_or_else(malformed)?;
+            let input_components = reader.components().ok_or_else(malformed)?;
+            let output_components = reader.components().ok_or_else(malformed)?;
+            let tmcc = reader.bytes(3).ok_or_else(malformed)?;
+            collections.push(ComponentCollection {
+                xmcc,
+                input_components,
+                output_components,
+                tmcc: u32::from_be_bytes([0, tmcc[0], tmcc[1], tmcc[2]]),
+            });
+        }
+        if !reader.parameters.is_empty() {
+            return Err(malformed());
+        }
+        Ok(Self {
+            offset,
+            length: u16::try_from(parameters.len() + 2).map_err(|_| malformed())?,
+            segment_index,
+            index,
+            last_segment_index,
+            collections,
+        })
+    }
+
+    /// Byte offset of the marker.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the marker segment in bytes, not including the marker.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// Index of this marker segment among those of the stage, Zmcc.
+    pub fn segment_index(&self) -> u16 {
+        self.segment_index
+    }
+
+    /// Index of the last marker segment of the stage, Ymcc.
+    pub fn last_segment_index(&self) -> u16 {
+        self.last_segment_index
+    }
+
+    /// Index of the stage that the MCO marker segment refers to, Imcc.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn collections(&self) -> &[ComponentCollection] {
+        &self.collections
+    }
+}
+
+/// Multiple component transformation ordering (MCO) marker segment.
+///
+/// See ITU-T T.801 | ISO/IEC 15444-2 Section A.3.9.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformOrderSegment {
+    offset: u64,
+    length: u16,
+    stages: Vec<u8>,
+}
+
+impl TransformOrderSegment {
+    // Decode the parameters of the marker segment, following Lmco, given the
+    // byte offset of the marker
+    pub(crate) fn decode(offset: u64, parameters: &[u8]) -> Result<Self, CodestreamError> {
+        let malformed = || CodestreamError::MarkerMalformed {
+            marker: MARKER_SYMBOL_MCO,
+            offset,
+        };
+        // Lmco = 3 + Nmco
+        match parameters.split_first() {
+            Some((&no_stages, stages)) if stages.len() == usize::from(no_stages) => Ok(Self {
+                offset,
+                length: parameters.len() as u16 + 2,
+                stages: stages.to_vec(),
+            }),
+            _ => Err(malformed()),
+        }
+    }
+
+    /// Byte offset of the marker.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the marker segment in bytes, not including the marker.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// Index of the MCC marker segments of each stage, Imco, in the order
+    /// that the inverse transformation applies them.
+    pub fn stages(&self) -> &[u8] {
+        &self.stages
+    }
+}
+
+// Big-endian parameters of a marker segment, read from the front
+struct ParameterReader<'a> {
+    parameters: &'a [u8],
+}
+
+impl<'a> ParameterReader<'a> {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        if self.parameters.len() < length {
+            return None;
+        }
+        let (bytes, rest) = self.parameters.split_at(length);
+        self.parameters = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Nmcc or Mmcc, then the component indices, Cmcc or Wmcc, which are
+    // 16-bit if the most significant bit is set
+    fn components(&mut self) -> Option<Vec<u16>> {
+        let count = self.u16()?;
+        let wide = count & 0x8000 != 0;
+        let count = usize::from(count & 0x7FFF);
+        if wide {
+            let bytes = self.bytes(2 * count)?;
+            Some(
+                bytes
+                    .chunks_exact(2)
+                    .map(|index| u16::from_be_bytes([index[0], index[1]]))
+                    .collect(),
+            )
+        } else {
+            Some(
+                self.bytes(count)?
+                    .iter()
+                    .map(|&index| u16::from(index))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Array of an MCT marker segment, or of several with the same index, in
+/// raster order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformArray {
+    index: u8,
+    array_type: TransformArrayType,
+    element_type: TransformElementType,
+    values: Vec<f64>,
+}
+
+impl TransformArray {
+    /// Index of the array, Imct.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn array_type(&self) -> TransformArrayType {
+        self.array_type
+    }
+
+    pub fn element_type(&self) -> TransformElementType {
+        self.element_type
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+/// Component collection of a stage, with the arrays that it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformCollection {
+    collection: ComponentCollection,
+    matrix: Option<TransformArray>,
+    offsets: Option<TransformArray>,
+}
+
+impl TransformCollection {
+    pub fn collection(&self) -> &ComponentCollection {
+        &self.collection
+    }
+
+    pub fn transform_type(&self) -> CollectionTransformType {
+        self.collection.transform_type()
+    }
+
+    pub fn input_components(&self) -> &[u16] {
+        self.collection.input_components()
+    }
+
+    pub fn output_components(&self) -> &[u16] {
+        self.collection.output_components()
+    }
+
+    pub fn is_reversible(&self) -> bool {
+        self.collection.is_reversible()
+    }
+
+    /// Matrix of an array-based transformation, if any.
+    pub fn matrix(&self) -> Option<&TransformArray> {
+        self.matrix.as_ref()
+    }
+
+    /// Offsets of an array-based transformation, if any.
+    pub fn offsets(&self) -> Option<&TransformArray> {
+        self.offsets.as_ref()
+    }
+
+    /// Rows of the matrix of a decorrelation transformation, one for each
+    /// output component with a column for each input component.
+    ///
+    /// Returns `None` for other transformations, or if the matrix does not
+    /// have that many values.
+    pub fn decorrelation_matrix(&self) -> Option<Vec<&[f64]>> {
+        let columns = self.input_components().len();
+        match &self.matrix {
+            Some(matrix)
+                if self.transform_type() == CollectionTransformType::Decorrelation
+                    && columns > 0
+                    && matrix.values.len() == columns * self.output_components().len() =>
+            {
+                Some(matrix.values.chunks_exact(columns).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Stage of the multiple component transformation, from the MCC marker
+/// segments with the same index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformStage {
+    index: u8,
+    collections: Vec<TransformCollection>,
+}
+
+impl TransformStage {
+    /// Index of the MCC marker segments, Imcc.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn collections(&self) -> &[TransformCollection] {
+        &self.collections
+    }
+}
+
+/// Multiple component transformation of ITU-T T.801 | ISO/IEC 15444-2, from
+/// the MCT, MCC and MCO marker segments of a header.
+///
+/// This describes the transformation only, it is not applied when decoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiComponentTransform {
+    stages: Vec<TransformStage>,
+}
+
+impl MultiComponentTransform {
+    /// Describe the transformation of the marker segments of a header, or
+    /// `None` without an MCO marker segment.
+    ///
+    /// The arrays and the stages may each be split over several marker
+    /// segments, which shall all be present. A stage or an array that is
+    /// referred to, but not present, is malformed.
+    pub(crate) fn new(
+        arrays: &[TransformArraySegment],
+        collections: &[ComponentCollectionSegment],
+        order: Option<&TransformOrderSegment>,
+    ) -> Result<Option<Self>, CodestreamError> {
+        let order = match order {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        let mut stages = Vec::with_capacity(order.stages.len());
+        for &index in &order.stages {
+            let segments = joined(
+                collections,
+                |segment| segment.index == index,
+                |segment| (segment.segment_index, segment.last_segment_index),
+            )
+            .ok_or(CodestreamError::MarkerMalformed {
+                marker: MARKER_SYMBOL_MCO,
+                offset: order.offset,
+            })?;
+            let mut stage = TransformStage {
+                index,
+                collections: Vec::new(),
+            };
+            for segment in segments {
+                let malformed = || CodestreamError::MarkerMalformed {
+                    marker: MARKER_SYMBOL_MCC,
+                    offset: segment.offset,
+                };
+                for collection in &segment.collections {
+                    let matrix = array(arrays, collection.matrix_index()).ok_or_else(malformed)?;
+                    let offsets = array(arrays, collection.offset_index()).ok_or_else(malformed)?;
+                    stage.collections.push(TransformCollection {
+                        collection: collection.clone(),
+                        matrix,
+                        offsets,
+                    });
+                }
+            }
+            stages.push(stage);
+        }
+        Ok(Some(Self { stages }))
+    }
+
+    /// Stages, in the order that the inverse transformation applies them.
+    pub fn stages(&self) -> &[TransformStage] {
+        &self.stages
+    }
+}
+
+// Array of the MCT marker segments with an index, or None for index 0, or
+// Some(None) if the marker segments are not all present
+fn array(arrays: &[TransformArraySegment], index: u8) -> Option<Option<TransformArray>> {
+    if index == 0 {
+        return Some(None);
+    }
+    let segments = joined(
+        arrays,
+        |segment| segment.index() == index,
+        |segment| (segment.segment_index, segment.last_segment_index),
+    )?;
+    let first = segments[0];
+    Some(Some(TransformArray {
+        index,
+        array_type: first.array_type(),
+        element_type: first.element_type(),
+        values: segments
+            .iter()
+            .flat_map(|segment| segment.values.iter().copied())
+            .collect(),
+    }))
+}
+
+// Marker segments with an index in order of Z, which shall be each of 0 to
+// the Y of the first marker segment
+fn joined<T>(
+    segments: &[T],
+    matches: impl Fn(&T) -> bool,
+    indices: impl Fn(&T) -> (u16, u16),
+) -> Option<Vec<&T>> {
+    let mut joined: Vec<&T> = segments.iter().filter(|segment| matches(segment)).collect();
+    joined.sort_by_key(|segment| indices(segment).0);
+    let (_, last) = indices(joined.first()?);
+    let complete = joined.len() == usize::from(last) + 1
+        && joined
+            .iter()
+            .enumerate()
+            .all(|(z, segment)| usize::from(indices(segment).0) == z);
+    complete.then_some(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mct() {
+        // Zmct = 0, Imct = 0x0901 (decorrelation, 32-bit float, index 1),
+        // Ymct = 0, SPmct = 1.0, -0.5
+        let mut parameters = vec![0x00, 0x00, 0x09, 0x01, 0x00, 0x00];
+        parameters.extend_from_slice(&1.0f32.to_be_bytes());
+        parameters.extend_from_slice(&(-0.5f32).to_be_bytes());
+        let segment = TransformArraySegment::decode(10, &parameters).unwrap();
+        assert_eq!(segment.offset(), 10);
+        assert_eq!(segment.length(), 16);
+        assert_eq!(segment.index(), 1);
+        assert_eq!(segment.array_type(), TransformArrayType::Decorrelation);
+        assert_eq!(segment.element_type(), TransformElementType::Float32);
+        assert_eq!(segment.values(), &[1.0, -0.5]);
+
+        // A partial value
+        parameters.pop();
+        assert!(TransformArraySegment::decode(10, &parameters).is_err());
+    }
+
+    #[test]
+    fn test_decode_mcc() {
+        // Zmcc = 0, Imcc = 2, Ymcc = 0, Qmcc = 1, Xmcc = 1 (decorrelation),
+        // Nmcc = 2, Cmcc = 0 1, Mmcc = 0x8001, Wmcc = 0x0102,
+        // Tmcc = 0x010301 (reversible, offsets 3, matrix 1)
+        let parameters = [
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x02, 0x00, 0x01, 0x80, 0x01,
+            0x01, 0x02, 0x01, 0x03, 0x01,
+        ];
+        let segment = ComponentCollectionSegment::decode(0, &parameters).unwrap();
+        assert_eq!(segment.index(), 2);
+        assert_eq!(segment.length(), 21);
+        let collection = &segment.collections()[0];
+        assert_eq!(
+            collection.transform_type(),
+            CollectionTransformType::Decorrelation
+        );
+        assert_eq!(collection.input_components(), &[0, 1]);
+        assert_eq!(collection.output_components(), &[0x0102]);
+        assert_eq!(collection.matrix_index(), 1);
+        assert_eq!(collection.offset_index(), 3);
+        assert!(collection.is_reversible());
+
+        // Tmcc cut short, or trailing bytes
+        assert!(ComponentCollectionSegment::decode(0, &parameters[..18]).is_err());
+        let mut parameters = parameters.to_vec();
+        parameters.push(0);
+        assert!(ComponentCollectionSegment::decode(0, &parameters).is_err());
+    }
+
+    #[test]
+    fn test_transform_arrays_joined() {
+        // Index 1 split over two marker segments, given out of order
+        let second = TransformArraySegment::decode(0, &[0, 1, 0x01, 0x01, 0, 1, 0, 3, 0, 4]);
+        let first = TransformArraySegment::decode(0, &[0, 0, 0x01, 0x01, 0, 1, 0, 1, 0, 2]);
+        let arrays = [second.unwrap(), first.unwrap()];
+        let array = array(&arrays, 1).unwrap().unwrap();
+        assert_eq!(array.values(), &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(array.array_type(), TransformArrayType::Decorrelation);
+        assert_eq!(super::array(&arrays[..1], 1), None);
+        assert_eq!(super::array(&arrays, 2), None);
+        assert_eq!(super::array(&arrays, 0), Some(None));
+    }
+}
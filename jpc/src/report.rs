@@ -0,0 +1,182 @@
+//! Summary of what a decode produced.
+
+use std::fmt;
+
+use super::CodeBlockParameters;
+#[cfg(feature = "stats")]
+use super::DecodeStats;
+
+/// Non-fatal problems found while decoding.
+///
+/// These indicate a codestream that does not conform, but that decoding
+/// continued past.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeWarning {
+    /// Tile-part length mismatch.
+    ///
+    /// Psot does not match the number of bytes from the SOT marker to the
+    /// next SOT or EOC marker, which usually means a truncated or concatenated
+    /// codestream. Decoding continues from that marker.
+    TilePartLengthMismatch {
+        tile_part: usize,
+        tile_index: u16,
+        expected: u32,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TilePartLengthMismatch {
+                tile_part,
+                tile_index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tile-part {tile_part} of tile {tile_index} has Psot {expected} but {actual} bytes"
+            ),
+        }
+    }
+}
+
+/// Resolution levels, code-blocks and packets decoded for one
+/// tile-component.
+///
+/// A decode limited to fewer resolution levels or quality layers than the
+/// codestream holds decodes fewer code-blocks and skips packets, which is
+/// recorded here.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileComponentReport {
+    /// Index of the tile, Isot.
+    pub tile_index: u16,
+    /// Index of the component within the image.
+    pub component_index: u16,
+    /// Resolution levels in the codestream, N<sub>L</sub> + 1.
+    pub resolution_levels: u8,
+    /// Resolution levels that were reconstructed.
+    pub resolution_levels_decoded: u8,
+    /// Code-blocks with at least one coding pass that were decoded.
+    pub code_blocks_decoded: u32,
+    /// Coding passes decoded, over all code-blocks.
+    pub coding_passes: u64,
+    /// Packets skipped due to layer or resolution limits.
+    pub packets_skipped: u32,
+}
+
+impl TileComponentReport {
+    pub fn new(tile_index: u16, component_index: u16, resolution_levels: u8) -> Self {
+        TileComponentReport {
+            tile_index,
+            component_index,
+            resolution_levels,
+            ..Default::default()
+        }
+    }
+
+    /// Decomposition levels that were reconstructed, one fewer than the
+    /// resolution levels.
+    pub fn decomposition_levels_decoded(&self) -> u8 {
+        self.resolution_levels_decoded.saturating_sub(1)
+    }
+
+    /// Sub-bands that were reconstructed.
+    ///
+    /// The lowest resolution level has the LL sub-band only, and each further
+    /// level adds the HL, LH and HH sub-bands.
+    pub fn subbands_decoded(&self) -> u32 {
+        match self.resolution_levels_decoded {
+            0 => 0,
+            levels => 1 + 3 * (u32::from(levels) - 1),
+        }
+    }
+
+    /// Record a decoded code-block.
+    pub fn record_code_block(&mut self, parameters: &CodeBlockParameters) {
+        if !parameters.is_empty() {
+            self.code_blocks_decoded += 1;
+            self.coding_passes += u64::from(parameters.no_passes);
+        }
+    }
+
+    /// Record a packet skipped due to a layer or resolution limit.
+    pub fn record_packet_skipped(&mut self) {
+        self.packets_skipped += 1;
+    }
+}
+
+/// Summary of a decode, with a report for each tile-component and any
+/// warnings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeReport {
+    tile_components: Vec<TileComponentReport>,
+    warnings: Vec<DecodeWarning>,
+    #[cfg(feature = "stats")]
+    stats: DecodeStats,
+}
+
+impl DecodeReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the report for a tile-component.
+    pub fn push(&mut self, tile_component: TileComponentReport) {
+        self.tile_components.push(tile_component);
+    }
+
+    /// Reports for each tile-component, in the order they were decoded.
+    pub fn tile_components(&self) -> &[TileComponentReport] {
+        &self.tile_components
+    }
+
+    /// Report for the component of the tile, if it was decoded.
+    pub fn tile_component(
+        &self,
+        tile_index: u16,
+        component_index: u16,
+    ) -> Option<&TileComponentReport> {
+        self.tile_components.iter().find(|report| {
+            report.tile_index == tile_index && report.component_index == component_index
+        })
+    }
+
+    /// Add a warning.
+    pub fn push_warning(&mut self, warning: DecodeWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// Warnings, in the order they were found.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
+    /// Statistics of the code-blocks decoded, over all tile-components.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    /// Add statistics of decoded code-blocks.
+    #[cfg(feature = "stats")]
+    pub fn merge_stats(&mut self, stats: &DecodeStats) {
+        self.stats.merge(stats);
+    }
+
+    /// Code-blocks decoded, over all tile-components.
+    pub fn code_blocks_decoded(&self) -> u64 {
+        self.tile_components
+            .iter()
+            .map(|report| u64::from(report.code_blocks_decoded))
+            .sum()
+    }
+
+    /// Packets skipped, over all tile-components.
+    pub fn packets_skipped(&self) -> u64 {
+        self.tile_components
+            .iter()
+            .map(|report| u64::from(report.packets_skipped))
+            .sum()
+    }
+}
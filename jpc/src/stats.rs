@@ -0,0 +1,117 @@
+//! Statistics of decoding, for analysing codestreams.
+//!
+//! Collected only with the `stats` feature. The log messages of the
+//! code-block decoder remain for tracing single decisions.
+
+use std::time::Duration;
+
+use super::coder::{Decoder, NUM_CONTEXTS};
+use super::SubBandType;
+
+/// Coding passes and decisions decoded, by pass and by context, and the
+/// time spent decoding the code-blocks of each sub-band.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Significance propagation passes decoded.
+    pub significance_passes: u64,
+    /// Magnitude refinement passes decoded.
+    pub refinement_passes: u64,
+    /// Cleanup passes decoded.
+    pub cleanup_passes: u64,
+    /// Decisions decoded in significance propagation passes.
+    pub significance_decisions: u64,
+    /// Decisions decoded in magnitude refinement passes.
+    pub refinement_decisions: u64,
+    /// Decisions decoded in cleanup passes.
+    pub cleanup_decisions: u64,
+    /// Decisions decoded with each context, indexed as for the MQ decoder:
+    /// 0 to 8 for significance, 9 to 13 for sign, 14 to 16 for magnitude
+    /// refinement, then run-length and uniform, see Table D.7.
+    pub contexts: [u64; NUM_CONTEXTS],
+    // Time for LL, HL, LH and HH
+    subband_time: [Duration; 4],
+}
+
+impl DecodeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Coding passes decoded, over all pass types.
+    pub fn passes(&self) -> u64 {
+        self.significance_passes + self.refinement_passes + self.cleanup_passes
+    }
+
+    /// Decisions decoded, over all contexts.
+    pub fn decisions(&self) -> u64 {
+        self.contexts.iter().sum()
+    }
+
+    /// Time spent decoding the code-blocks of a sub-band type, over all
+    /// resolution levels.
+    pub fn subband_time(&self, subband: SubBandType) -> Duration {
+        self.subband_time[subband_index(subband)]
+    }
+
+    /// Add the time spent decoding a code-block of `subband`.
+    pub(crate) fn add_subband_time(&mut self, subband: SubBandType, elapsed: Duration) {
+        self.subband_time[subband_index(subband)] += elapsed;
+    }
+
+    /// Add the counts and times of `other`.
+    pub fn merge(&mut self, other: &DecodeStats) {
+        self.significance_passes += other.significance_passes;
+        self.refinement_passes += other.refinement_passes;
+        self.cleanup_passes += other.cleanup_passes;
+        self.significance_decisions += other.significance_decisions;
+        self.refinement_decisions += other.refinement_decisions;
+        self.cleanup_decisions += other.cleanup_decisions;
+        for (count, other) in self.contexts.iter_mut().zip(other.contexts.iter()) {
+            *count += other;
+        }
+        for (time, other) in self.subband_time.iter_mut().zip(other.subband_time.iter()) {
+            *time += *other;
+        }
+    }
+}
+
+fn subband_index(subband: SubBandType) -> usize {
+    match subband {
+        SubBandType::LL => 0,
+        SubBandType::HL => 1,
+        SubBandType::LH => 2,
+        SubBandType::HH => 3,
+    }
+}
+
+/// Decoder that counts the decisions of each context it passes on.
+pub(crate) struct CountingDecoder<'a> {
+    inner: &'a mut dyn Decoder,
+    pub(crate) contexts: [u64; NUM_CONTEXTS],
+}
+
+impl<'a> CountingDecoder<'a> {
+    pub(crate) fn new(inner: &'a mut dyn Decoder) -> Self {
+        CountingDecoder {
+            inner,
+            contexts: [0; NUM_CONTEXTS],
+        }
+    }
+}
+
+impl Decoder for CountingDecoder<'_> {
+    fn decode_bit(&mut self, cx: usize) -> u8 {
+        if let Some(count) = self.contexts.get_mut(cx) {
+            *count += 1;
+        }
+        self.inner.decode_bit(cx)
+    }
+
+    fn bytes_consumed(&self) -> usize {
+        self.inner.bytes_consumed()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.inner.is_exhausted()
+    }
+}
@@ -0,0 +1,185 @@
+//! Decoding of a codestream while its bytes arrive.
+//!
+//! Packets are read as soon as all of their bytes are present, and the image
+//! is reconstructed as each quality layer completes, for progressive display
+//! of a codestream received over a network.
+
+use std::error;
+use std::io::Cursor;
+
+use super::{
+    CodestreamError, ContiguousCodestream, DecodeLimits, DecodedImage, PacketDecodeError,
+    MARKER_SYMBOL_SOC, MARKER_SYMBOL_SOD, MARKER_SYMBOL_SOT,
+};
+use crate::decode::{reconstruct_image, tile_packets, TilePackets};
+
+/// Decoder of a codestream given in pieces, with the image reconstructed from
+/// the quality layers received so far.
+///
+/// The codestream must be one that [ContiguousCodestream::decode_image]
+/// supports, with its tile in a single tile-part. Once the main header and
+/// the tile-part header have arrived, each packet is read as soon as all of
+/// its bytes have. With the LRCP progression order, the packets of each layer
+/// come before those of the next, so the image improves as each layer
+/// completes. With the other progression orders, the layers complete
+/// together, with the last packet.
+#[derive(Debug, Default)]
+pub struct StreamingDecoder {
+    data: Vec<u8>,
+    state: Option<StreamingState>,
+    image: Option<DecodedImage>,
+}
+
+// Headers of the codestream, and the packets read from its tile-part
+#[derive(Debug)]
+struct StreamingState {
+    codestream: ContiguousCodestream,
+    packets: TilePackets,
+    // Byte offset of the tile-part data, and of its end if Psot gives it
+    data_offset: usize,
+    data_end: Option<usize>,
+    // Offset of the next packet within the tile-part data, and its index
+    offset: usize,
+    next_packet: usize,
+    layers_complete: u16,
+}
+
+impl StreamingDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next bytes of the codestream, and read every packet that they
+    /// complete.
+    ///
+    /// Returns the number of packets read. When this completes a layer, the
+    /// image is reconstructed, see [StreamingDecoder::image]. A codestream
+    /// that cannot be decoded is reported as soon as its headers have
+    /// arrived, as for [ContiguousCodestream::decode_image].
+    pub fn push(&mut self, bytes: &[u8]) -> Result<usize, Box<dyn error::Error>> {
+        self.data.extend_from_slice(bytes);
+        if self.state.is_none() {
+            match first_tile_part_header_end(&self.data) {
+                Some(header_end) => self.state = Some(self.decode_headers(header_end)?),
+                None => return Ok(0),
+            }
+        }
+        let state = match &mut self.state {
+            Some(state) => state,
+            None => return Ok(0),
+        };
+
+        // Tile-part data received so far, which is complete once Psot bytes
+        // have arrived
+        let received = self.data.len();
+        let end = state.data_end.map_or(received, |end| end.min(received));
+        let data = &self.data[state.data_offset.min(end)..end];
+        let data_complete = state.data_end.is_some_and(|end| end <= received);
+        let mut read = 0;
+        while state.next_packet < state.packets.no_packets() {
+            match state
+                .packets
+                .try_read_packet(state.next_packet, data, state.offset)
+            {
+                Ok(offset) => {
+                    state.offset = offset;
+                    state.next_packet += 1;
+                    read += 1;
+                }
+                Err(PacketDecodeError::DataExhausted { .. }) if !data_complete => break,
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // Layers with every packet read, which are those before the layer of
+        // the first packet still to be read
+        let layers_complete = (state.next_packet..state.packets.no_packets())
+            .map(|index| state.packets.layer(index))
+            .min()
+            .unwrap_or_else(|| state.packets.no_layers());
+        if layers_complete > state.layers_complete {
+            state.layers_complete = layers_complete;
+            self.image = Some(reconstruct_image(
+                state.packets.tile_components(),
+                state.packets.mct,
+                state.codestream.report().clone(),
+            )?);
+        }
+        Ok(read)
+    }
+
+    // Main header and tile-part header, with the layout of the packets to read
+    fn decode_headers(&self, header_end: usize) -> Result<StreamingState, Box<dyn error::Error>> {
+        let codestream = ContiguousCodestream::decode_first_tile_part_header(
+            &mut Cursor::new(&self.data[..header_end]),
+            DecodeLimits::default(),
+        )?;
+        let tile_part = &codestream.tile_parts()[0];
+        let sot = tile_part.start_of_tile_segment();
+        if sot.tile_part_index() != 0 || sot.no_tile_parts() > 1 {
+            return Err(CodestreamError::UnsupportedFeature {
+                marker: MARKER_SYMBOL_SOT,
+                offset: sot.offset(),
+            }
+            .into());
+        }
+        let data_end = (sot.tile_length() != 0).then_some(tile_part.data_end as usize);
+        Ok(StreamingState {
+            packets: tile_packets(&codestream)?,
+            data_offset: tile_part.data_offset as usize,
+            data_end,
+            offset: 0,
+            next_packet: 0,
+            layers_complete: 0,
+            codestream,
+        })
+    }
+
+    /// Codestream with its main header and tile-part header, once they have
+    /// arrived.
+    pub fn codestream(&self) -> Option<&ContiguousCodestream> {
+        self.state.as_ref().map(|state| &state.codestream)
+    }
+
+    /// Number of packets read so far.
+    pub fn packets_read(&self) -> usize {
+        self.state.as_ref().map_or(0, |state| state.next_packet)
+    }
+
+    /// Number of quality layers with every packet read.
+    pub fn layers_complete(&self) -> u16 {
+        self.state.as_ref().map_or(0, |state| state.layers_complete)
+    }
+
+    /// Whether every packet of the codestream has been read.
+    pub fn is_complete(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_some_and(|state| state.next_packet == state.packets.no_packets())
+    }
+
+    /// Image reconstructed from the layers complete so far, or `None` until
+    /// the first layer completes.
+    pub fn image(&self) -> Option<&DecodedImage> {
+        self.image.as_ref()
+    }
+}
+
+// Offset following the SOD marker of the first tile-part, once the main
+// header and the tile-part header have arrived, following the marker
+// segments by their lengths
+fn first_tile_part_header_end(data: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    loop {
+        let marker = data.get(offset..offset + 2)?;
+        match [marker[0], marker[1]] {
+            marker if marker == MARKER_SYMBOL_SOC.0 => offset += 2,
+            marker if marker == MARKER_SYMBOL_SOD.0 => return Some(offset + 2),
+            _ => {
+                let length = data.get(offset + 2..offset + 4)?;
+                let length = u16::from_be_bytes([length[0], length[1]]);
+                offset += 2 + usize::from(length);
+            }
+        }
+    }
+}
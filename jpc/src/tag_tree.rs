@@ -8,7 +8,176 @@ use log::{debug, info};
 /// below it is recorded. Figure B.12 shows an example of this representation. The notation, qi(m,
 /// n), is the value at the node that is mth from the left and nth from the top, at the ith level.
 /// Level 0 is the lowest level of the tag tree; it contains the top node.
-pub struct TagTree {}
+///
+/// Values are decoded one leaf at a time, as far as a threshold, which is how
+/// the inclusion and zero bit-plane information of a packet header is read.
+/// What has been learnt about each node is kept, so a leaf can be decoded
+/// further with a higher threshold, as for the inclusion information of a
+/// later layer.
+#[derive(Debug, Clone)]
+pub struct TagTree {
+    /// Width and nodes of each level, starting with the leaves.
+    levels: Vec<(usize, Vec<TagTreeNode>)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TagTreeNode {
+    /// Value is known to be at least this.
+    low: u32,
+    /// Value, once it has been decoded.
+    value: Option<u32>,
+}
+
+impl TagTree {
+    /// Create a tag tree with `width` by `height` leaves.
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut levels = Vec::new();
+        let (mut w, mut h) = (width, height);
+        loop {
+            let node = TagTreeNode {
+                low: 0,
+                value: None,
+            };
+            levels.push((w, vec![node; w * h]));
+            if w <= 1 && h <= 1 {
+                break;
+            }
+            w = w.div_ceil(2);
+            h = h.div_ceil(2);
+        }
+        Self { levels }
+    }
+
+    /// Decode the leaf at column `x` and row `y` as far as `threshold`,
+    /// returning whether its value is less than `threshold`.
+    ///
+    /// Bits are read with `read_bit`, from the root towards the leaf, only
+    /// until this is known. See Section B.10.2.
+    pub fn decode<E>(
+        &mut self,
+        x: usize,
+        y: usize,
+        threshold: u32,
+        mut read_bit: impl FnMut() -> Result<bool, E>,
+    ) -> Result<bool, E> {
+        let mut low = 0;
+        let mut value = None;
+        for (level, (width, nodes)) in self.levels.iter_mut().enumerate().rev() {
+            let node = &mut nodes[(y >> level) * *width + (x >> level)];
+            low = low.max(node.low);
+            while node.value.is_none() && low < threshold {
+                if read_bit()? {
+                    node.value = Some(low);
+                } else {
+                    low += 1;
+                }
+            }
+            node.low = low;
+            value = node.value;
+        }
+        Ok(value.is_some_and(|value| value < threshold))
+    }
+
+    /// Decode the value of the leaf at column `x` and row `y`.
+    pub fn decode_value<E>(
+        &mut self,
+        x: usize,
+        y: usize,
+        mut read_bit: impl FnMut() -> Result<bool, E>,
+    ) -> Result<u32, E> {
+        let mut threshold = 1;
+        while !self.decode(x, y, threshold, &mut read_bit)? {
+            threshold += 1;
+        }
+        Ok(threshold - 1)
+    }
+}
+
+/// An encoder of a 2d-array of natural numbers as a tag tree, the
+/// counterpart of [TagTree].
+///
+/// Leaves are encoded as far as a threshold, writing the bits that
+/// [TagTree::decode] reads for the same leaves and thresholds.
+#[derive(Debug, Clone)]
+pub struct TagTreeEncoder {
+    /// Width and nodes of each level, starting with the leaves.
+    levels: Vec<(usize, Vec<TagTreeEncoderNode>)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TagTreeEncoderNode {
+    value: u32,
+    /// Value is known by the decoder to be at least this.
+    low: u32,
+    /// Whether the value has been encoded.
+    known: bool,
+}
+
+impl TagTreeEncoder {
+    /// Create an encoder of `width` by `height` leaves, with the leaf values
+    /// in raster order.
+    pub fn new(width: usize, height: usize, values: &[u32]) -> Self {
+        let node = |value| TagTreeEncoderNode {
+            value,
+            low: 0,
+            known: false,
+        };
+        let mut levels = vec![(width, values.iter().copied().map(node).collect::<Vec<_>>())];
+        let (mut w, mut h) = (width, height);
+        while w > 1 || h > 1 {
+            // Each node is the minimum of the (up to four) nodes below it
+            let (next_w, next_h) = (w.div_ceil(2), h.div_ceil(2));
+            let below = &levels[levels.len() - 1].1;
+            let mut nodes = vec![node(u32::MAX); next_w * next_h];
+            for (i, child) in below.iter().enumerate() {
+                let parent = &mut nodes[(i / w / 2) * next_w + (i % w) / 2];
+                parent.value = parent.value.min(child.value);
+            }
+            levels.push((next_w, nodes));
+            w = next_w;
+            h = next_h;
+        }
+        Self { levels }
+    }
+
+    /// Encode the leaf at column `x` and row `y` as far as `threshold`,
+    /// returning whether its value is less than `threshold`.
+    ///
+    /// Bits are written with `write_bit`, from the root towards the leaf, only
+    /// until this is known. See Section B.10.2.
+    pub fn encode(
+        &mut self,
+        x: usize,
+        y: usize,
+        threshold: u32,
+        mut write_bit: impl FnMut(bool),
+    ) -> bool {
+        let mut low = 0;
+        let mut value = None;
+        for (level, (width, nodes)) in self.levels.iter_mut().enumerate().rev() {
+            let node = &mut nodes[(y >> level) * *width + (x >> level)];
+            low = low.max(node.low);
+            while !node.known && low < threshold {
+                if low == node.value {
+                    write_bit(true);
+                    node.known = true;
+                } else {
+                    write_bit(false);
+                    low += 1;
+                }
+            }
+            node.low = low;
+            value = node.known.then_some(node.value);
+        }
+        value.is_some_and(|value| value < threshold)
+    }
+
+    /// Encode the value of the leaf at column `x` and row `y`.
+    pub fn encode_value(&mut self, x: usize, y: usize, write_bit: impl FnMut(bool)) {
+        let value = self.levels[0].1[y * self.levels[0].0 + x].value;
+        self.encode(x, y, value + 1, write_bit);
+    }
+}
 
 /// A decoder from tag tree bits to numbers in the 2d-array.
 ///
@@ -128,6 +297,71 @@ mod tests {
             .try_init();
     }
 
+    // Bits from a string of 0 and 1, failing once they run out
+    fn bits(bits: &str) -> impl FnMut() -> Result<bool, ()> + '_ {
+        let mut bits = bits.chars();
+        move || bits.next().map(|bit| bit == '1').ok_or(())
+    }
+
+    /// The example of B.10.2, decoding the values of the first row in turn.
+    #[test]
+    fn test_tag_tree_given_example() {
+        let mut tt = TagTree::new(6, 3);
+        assert_eq!(4, tt.levels.len());
+        assert_eq!(Ok(1), tt.decode_value(0, 0, bits("01111")));
+        assert_eq!(Ok(3), tt.decode_value(1, 0, bits("001")));
+        assert_eq!(Ok(2), tt.decode_value(2, 0, bits("101")));
+        assert_eq!(Ok(3), tt.decode_value(3, 0, bits("001")));
+        assert_eq!(Ok(2), tt.decode_value(4, 0, bits("1011")));
+        assert_eq!(Ok(3), tt.decode_value(5, 0, bits("01")));
+    }
+
+    /// Inclusion information is decoded as far as the threshold of each
+    /// layer, and no further.
+    #[test]
+    fn test_tag_tree_threshold() {
+        let mut tt = TagTree::new(2, 1);
+        // Root is at least 1, so neither leaf is included in layer 0
+        assert_eq!(Ok(false), tt.decode(0, 0, 1, bits("0")));
+        assert_eq!(Ok(false), tt.decode(1, 0, 1, bits("")));
+        // Root is 1, and the first leaf is at least 2
+        assert_eq!(Ok(false), tt.decode(0, 0, 2, bits("10")));
+        // Second leaf is 1
+        assert_eq!(Ok(true), tt.decode(1, 0, 2, bits("1")));
+        assert_eq!(Ok(true), tt.decode(1, 0, 3, bits("")));
+        // First leaf is 2
+        assert_eq!(Ok(true), tt.decode(0, 0, 3, bits("1")));
+        assert_eq!(Err(()), TagTree::new(1, 1).decode(0, 0, 1, bits("")));
+    }
+
+    /// Encoding the example of B.10.2 gives the bits decoded above, and a
+    /// leaf encoded as far as a threshold gives the bits of
+    /// [TagTree::decode].
+    #[test]
+    fn test_tag_tree_encoder() {
+        let values = [1, 3, 2, 3, 2, 3, 2, 2, 1, 4, 3, 2, 2, 2, 2, 2, 1, 2];
+        let mut encoder = TagTreeEncoder::new(6, 3, &values);
+        for (x, expected) in ["01111", "001", "101", "001", "1011", "01"]
+            .iter()
+            .enumerate()
+        {
+            let mut written = String::new();
+            encoder.encode_value(x, 0, |bit| written.push(if bit { '1' } else { '0' }));
+            assert_eq!(&written, expected);
+        }
+
+        let mut encoder = TagTreeEncoder::new(2, 1, &[2, 1]);
+        let mut written = String::new();
+        let mut write_bit = |bit| written.push(if bit { '1' } else { '0' });
+        assert!(!encoder.encode(0, 0, 1, &mut write_bit));
+        assert!(!encoder.encode(1, 0, 1, &mut write_bit));
+        assert!(!encoder.encode(0, 0, 2, &mut write_bit));
+        assert!(encoder.encode(1, 0, 2, &mut write_bit));
+        assert!(encoder.encode(1, 0, 3, &mut write_bit));
+        assert!(encoder.encode(0, 0, 3, &mut write_bit));
+        assert_eq!(written, "01011");
+    }
+
     #[test]
     fn test_oner() {
         init_logger();
@@ -5,7 +5,7 @@ const DATA_FOLDER: &str = "openjpeg-data-39524bd3a601d90ed8e0177559400d23945f96a
 
 use std::{io::Cursor, path::PathBuf};
 
-use jpc::decode_jpc;
+use jpc::{decode_jpc, ContiguousCodestream};
 
 fn get_compliance_data_dir() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -31,85 +31,139 @@ fn test_data_load() {
     assert_eq!(content[..4], vec![0xFF, 0x4F, 0xFF, 0x51]);
 }
 
-/// Test p0 compliance tests. Only verify pass/fail to parse.
-///
-/// Treat as a ratchet and try to improve results.
-#[test]
-fn test_parse_p0_j2k_files() {
-    //init_logger();
-    let files = [
-        // (Expect parse?, file_name)
-        (true, "./input/conformance/p0_01.j2k"),
-        (true, "./input/conformance/p0_02.j2k"),
-        (true, "./input/conformance/p0_03.j2k"),
-        (true, "./input/conformance/p0_04.j2k"),
-        (true, "./input/conformance/p0_05.j2k"),
-        (true, "./input/conformance/p0_06.j2k"),
-        (false, "./input/conformance/p0_07.j2k"),
-        (true, "./input/conformance/p0_08.j2k"),
-        (true, "./input/conformance/p0_09.j2k"),
-        (true, "./input/conformance/p0_10.j2k"),
-        (true, "./input/conformance/p0_11.j2k"),
-        (true, "./input/conformance/p0_12.j2k"),
-        (true, "./input/conformance/p0_13.j2k"),
-        (true, "./input/conformance/p0_14.j2k"),
-        (true, "./input/conformance/p0_15.j2k"),
-        (true, "./input/conformance/p0_16.j2k"),
-    ];
-
-    for (pass, file) in files {
-        let p = get_compliance_data_dir().join(file);
+/// Expected result for a conformance file, from `compliance.json`.
+#[derive(Debug, serde::Deserialize)]
+struct Expectation {
+    /// File name within `input/conformance`
+    file: String,
+    /// Whether the file is expected to parse
+    parse: bool,
+    /// Why the file does not parse, or anything else worth recording
+    #[serde(default)]
+    note: Option<String>,
+}
+
+// Expectations for the files whose names start with `prefix`
+fn expectations(prefix: &str) -> Vec<Expectation> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("compliance.json");
+    let content = std::fs::read_to_string(path).expect("Failed to read expectations");
+    let expectations: Vec<Expectation> =
+        serde_json::from_str(&content).expect("Failed to parse expectations");
+    expectations
+        .into_iter()
+        .filter(|expectation| expectation.file.starts_with(prefix))
+        .collect()
+}
+
+// Markers of the main header, found by following the marker segment lengths
+// from SOC, for a codestream that could not be decoded
+fn scan_main_header(content: &[u8]) -> Vec<(u64, [u8; 2], Option<u16>)> {
+    let mut markers = Vec::new();
+    let mut offset = 0;
+    while offset + 2 <= content.len() && content[offset] == 0xFF {
+        let marker = [content[offset], content[offset + 1]];
+        // SOC, and SOD or EOC, have no marker segment
+        let length = match marker[1] {
+            0x4F | 0x93 | 0xD9 => None,
+            _ => content
+                .get(offset + 2..offset + 4)
+                .map(|length| u16::from_be_bytes([length[0], length[1]])),
+        };
+        markers.push((offset as u64, marker, length));
+        match (marker[1], length) {
+            // The main header ends at the first SOT
+            (0x90, _) | (0xD9, _) => break,
+            (_, Some(length)) => offset += 2 + usize::from(length),
+            (_, None) => offset += 2,
+        }
+    }
+    markers
+}
+
+// Print what the decoder made of a file whose result was not as expected
+fn print_diagnosis(
+    content: &[u8],
+    parse: &Result<ContiguousCodestream, Box<dyn std::error::Error>>,
+) {
+    match parse {
+        Ok(codestream) => {
+            println!("  marker map:");
+            for marker in codestream.marker_map() {
+                println!(
+                    "    {:>8} {} (0x{:04X}) {:?}",
+                    marker.offset(),
+                    marker.name(),
+                    marker.marker(),
+                    marker.length()
+                );
+            }
+        }
+        Err(error) => {
+            println!("  error: {}", error);
+            println!("  main header markers:");
+            for (offset, marker, length) in scan_main_header(content) {
+                println!(
+                    "    {:>8} 0x{:02X}{:02X} {:?}",
+                    offset, marker[0], marker[1], length
+                );
+            }
+        }
+    }
+}
+
+// Parse each file with expectations for `prefix`, and check the results
+// agree, printing a diagnosis for each that does not
+fn check_expectations(prefix: &str) {
+    let expectations = expectations(prefix);
+    assert!(!expectations.is_empty());
+
+    let mut unexpected = Vec::new();
+    for expectation in &expectations {
+        let p = get_compliance_data_dir()
+            .join("input/conformance")
+            .join(&expectation.file);
         println!(
-            "Trying to parse: {:?} expecting {}",
-            p.file_name(),
-            if pass { "pass" } else { "fail" }
+            "Trying to parse: {} expecting {}",
+            expectation.file,
+            if expectation.parse { "pass" } else { "fail" }
         );
+        if let Some(note) = &expectation.note {
+            println!("  note: {}", note);
+        }
         let content = std::fs::read(&p).expect("Failed to read test data");
         assert!(!content.is_empty());
 
-        let parse = decode_jpc(&mut Cursor::new(content));
-        assert_eq!(
-            parse.is_ok(),
-            pass,
-            "Unexpected result, update test for {}",
-            p.file_name().unwrap().display()
-        );
+        let parse = decode_jpc(&mut Cursor::new(&content));
+        if parse.is_ok() != expectation.parse {
+            print_diagnosis(&content, &parse);
+            unexpected.push(expectation.file.as_str());
+        }
     }
+    assert!(
+        unexpected.is_empty(),
+        "Unexpected result, update compliance.json for {}",
+        unexpected.join(", ")
+    );
+}
+
+/// Test p0 compliance tests. Only verify pass/fail to parse.
+///
+/// Treat as a ratchet and try to improve results. The expected results are
+/// in `compliance.json`.
+#[test]
+fn test_parse_p0_j2k_files() {
+    //init_logger();
+    check_expectations("p0_");
 }
 
 /// Test p1 compliance tests. Only verify pass/fail to parse.
 ///
-/// Treat as a ratchet and try to improve results.
+/// Treat as a ratchet and try to improve results. The expected results are
+/// in `compliance.json`.
 #[test]
 fn test_parse_p1_j2k_files() {
     //init_logger();
-    let files = [
-        // (Expect parse?, file_name)
-        (true, "./input/conformance/p1_01.j2k"),
-        (true, "./input/conformance/p1_02.j2k"),
-        (true, "./input/conformance/p1_03.j2k"),
-        (true, "./input/conformance/p1_04.j2k"),
-        (true, "./input/conformance/p1_05.j2k"),
-        (true, "./input/conformance/p1_06.j2k"),
-        (true, "./input/conformance/p1_07.j2k"),
-    ];
-
-    for (pass, file) in files {
-        let p = get_compliance_data_dir().join(file);
-        println!(
-            "Trying to parse: {:?} expecting {}",
-            p.file_name(),
-            if pass { "pass" } else { "fail" }
-        );
-        let content = std::fs::read(&p).expect("Failed to read test data");
-        assert!(!content.is_empty());
-
-        let parse = decode_jpc(&mut Cursor::new(content));
-        assert_eq!(
-            parse.is_ok(),
-            pass,
-            "Unexpected result, update test for {}",
-            p.file_name().unwrap().display()
-        );
-    }
+    check_expectations("p1_");
 }
@@ -0,0 +1,712 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use jp2::EnumeratedColourSpaces;
+use jpc::coder::{MqEncoder, NUM_CONTEXTS};
+use jpc::{
+    decode_code_block, decode_jpc, encode_image, inverse_component_transformation, verify_lossless,
+    CodestreamHeaderBuilder, ImageError, MultipleComponentTransformation, SampleStats,
+    StreamingDecoder, SubBandType, TransformationFilter,
+};
+
+// Coded data of a code-block, from the decisions of its coding passes, each a
+// context and a bit
+fn code_block(decisions: &[(usize, u8)]) -> Vec<u8> {
+    let mut encoder = MqEncoder::new(NUM_CONTEXTS);
+    encoder.reset_contexts();
+    encoder.init();
+    for &(context, bit) in decisions {
+        encoder.encode(context, bit);
+    }
+    encoder.flush()
+}
+
+// Packet header from a string of 0 and 1, padded to a byte boundary
+fn packet_header(bits: &str) -> Vec<u8> {
+    let bits: Vec<u8> = bits.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let header: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .chain([b'0'; 8].iter())
+                .take(8)
+                .fold(0, |value, bit| (value << 1) | (bit - b'0'))
+        })
+        .collect();
+    // Bytes of 0xFF would need a stuffed bit
+    assert!(!header.contains(&0xFF));
+    header
+}
+
+// Codestream with a single tile-part holding `data`
+fn single_tile_part(builder: CodestreamHeaderBuilder, data: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    builder.write_to(&mut buffer).unwrap();
+    // SOT, Lsot = 10, Isot = 0, Psot, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&(14 + data.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(&[0x00, 0x01]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93]);
+    buffer.extend_from_slice(data);
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+    buffer
+}
+
+// 2x2 greyscale image of 10, 20, 30 and 40, with one decomposition level.
+//
+// After the DC level shift of -128, the forward 5-3 transformation of
+// Annex F.4 gives -103 for LL, 10 for HL, 20 for LH and 0 for HH, each a
+// 1x1 code-block. The exponents are 8 for LL, 9 for HL and LH, and 10 for HH,
+// so with two guard bits M_b is 9, 10 and 11.
+fn two_by_two() -> Vec<u8> {
+    two_by_two_with(1, 2)
+}
+
+// Coded data of the LL, HL and LH code-blocks of two_by_two
+fn two_by_two_code_blocks() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    // Magnitude 103 is 7 bits, so 2 zero bit-planes and 19 coding passes: the
+    // cleanup pass makes it significant and negative, then each magnitude
+    // refinement pass gives the next bit of 1100111
+    let ll = code_block(&[
+        (0, 1),
+        (9, 1),
+        (14, 1),
+        (16, 0),
+        (16, 0),
+        (16, 1),
+        (16, 1),
+        (16, 1),
+    ]);
+    // Magnitude 10 is 4 bits, so 6 zero bit-planes and 10 coding passes
+    let hl = code_block(&[(0, 1), (9, 0), (14, 0), (16, 1), (16, 0)]);
+    // Magnitude 20 is 5 bits, so 5 zero bit-planes and 13 coding passes
+    let lh = code_block(&[(0, 1), (9, 0), (14, 0), (16, 1), (16, 0), (16, 0)]);
+    (ll, hl, lh)
+}
+
+// The image of two_by_two, in LRCP order with `layers` layers. The first
+// layer has LL, the second has HL and LH, and any others are empty. Each
+// guard bit adds a zero bit-plane to every code-block.
+fn two_by_two_with(layers: u16, guard_bits: u8) -> Vec<u8> {
+    let (ll, hl, lh) = two_by_two_code_blocks();
+
+    // Zero bit-planes with two guard bits are 2 for LL, 6 for HL and 5 for LH
+    let zero_bit_planes = |two_guard_bits: u8| {
+        let count = usize::from(two_guard_bits + guard_bits - 2);
+        format!("{}1", "0".repeat(count))
+    };
+    let (ll_zero, hl_zero, lh_zero) = (zero_bit_planes(2), zero_bit_planes(6), zero_bit_planes(5));
+
+    // Resolution level 0: non-empty, LL included, zero bit-planes, passes,
+    // Lblock unchanged, length in 3 + 4 bits
+    let mut data = packet_header(&format!("1 1 {ll_zero} 1111 01101 0 {:07b}", ll.len()));
+    data.extend(&ll);
+    if layers == 1 {
+        // Resolution level 1: HL and LH included with lengths in 3 + 3 bits,
+        // and HH not included
+        data.extend(packet_header(&format!(
+            "1 1 {hl_zero} 1111 00100 0 {:06b} 1 {lh_zero} 1111 00111 0 {:06b} 0",
+            hl.len(),
+            lh.len()
+        )));
+    } else {
+        // Resolution level 1 is empty in the first layer, then in the second,
+        // resolution level 0 is empty and HL and LH are first included
+        data.push(0x00);
+        data.push(0x00);
+        data.extend(packet_header(&format!(
+            "1 01 {hl_zero} 1111 00100 0 {:06b} 01 {lh_zero} 1111 00111 0 {:06b} 00",
+            hl.len(),
+            lh.len()
+        )));
+    }
+    data.extend(&hl);
+    data.extend(&lh);
+    // Further layers are empty
+    data.extend(vec![0x00; 2 * usize::from(layers.saturating_sub(2))]);
+
+    single_tile_part(
+        CodestreamHeaderBuilder::new(2, 2, 1, 8)
+            .no_decomposition_levels(1)
+            .no_layers(layers)
+            .guard_bits(guard_bits),
+        &data,
+    )
+}
+
+#[test]
+fn test_decode_single_component() {
+    let buffer = two_by_two();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let image = codestream.decode_image(&mut reader).unwrap();
+
+    assert_eq!(image.width(), 2);
+    assert_eq!(image.height(), 2);
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::Greyscale);
+    assert_eq!(image.components().len(), 1);
+    let component = &image.components()[0];
+    assert_eq!(component.precision(), 8);
+    assert!(!component.signed());
+    assert_eq!(component.samples(), &[10, 20, 30, 40]);
+    assert_eq!(
+        image.component_stats(0),
+        Some(SampleStats {
+            min: 10,
+            max: 40,
+            mean: 25.0
+        })
+    );
+    assert_eq!(image.component_stats(1), None);
+
+    let report = image.report().tile_component(0, 0).unwrap();
+    assert_eq!(report.resolution_levels_decoded, 2);
+    assert_eq!(report.code_blocks_decoded, 3);
+    assert_eq!(report.coding_passes, 19 + 10 + 13);
+}
+
+#[test]
+fn test_verify_lossless() {
+    let buffer = two_by_two();
+    verify_lossless(&mut Cursor::new(&buffer), &[vec![10, 20, 30, 40]]).unwrap();
+
+    // The first sample that differs is reported
+    let error = verify_lossless(&mut Cursor::new(&buffer), &[vec![10, 20, 31, 41]]).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<ImageError>(),
+        Some(&ImageError::SampleMismatch {
+            component: 0,
+            x: 0,
+            y: 1,
+            expected: 31,
+            actual: 30,
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "component 0 has sample 30 at (0, 1), expected 31"
+    );
+    let error = verify_lossless(&mut Cursor::new(&buffer), &[vec![10, 20, 30]]).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<ImageError>(),
+        Some(&ImageError::ComponentSize {
+            component: 0,
+            expected: 3,
+            actual: 4,
+        })
+    );
+
+    // A codestream with the irreversible filter is not lossless
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 2, 1, 8).transformation(TransformationFilter::Irreversible),
+        &[],
+    );
+    assert!(verify_lossless(&mut Cursor::new(&buffer), &[vec![0; 4]]).is_err());
+}
+
+#[test]
+fn test_decode_layers() {
+    // The image of two_by_two in three layers, with the coding passes of LL
+    // and HL split between layers, and the first inclusion of HL in layer 1
+    // and of LH in layer 2. HH is never included.
+    let (ll, hl, lh) = two_by_two_code_blocks();
+    let (ll_first, hl_first) = (2, 1);
+
+    // Layer 0: LL included with 4 passes, length in 3 + 2 bits, and
+    // resolution level 1 empty
+    let mut data = packet_header(&format!("1 1 001 1101 0 {ll_first:05b}"));
+    data.extend(&ll[..ll_first]);
+    data.push(0x00);
+    // Layer 1: 15 more passes of LL, length in 3 + 3 bits. HL first included
+    // with 1 pass, length in 3 bits, and LH and HH not yet.
+    data.extend(packet_header(&format!(
+        "1 1 1111 01001 0 {:06b}",
+        ll.len() - ll_first
+    )));
+    data.extend(&ll[ll_first..]);
+    data.extend(packet_header(&format!(
+        "1 01 0000001 0 0 {hl_first:03b} 00 00"
+    )));
+    data.extend(&hl[..hl_first]);
+    // Layer 2: LL not included, 9 more passes of HL, and LH first included
+    // with all 13 passes, lengths in 3 + 3 bits
+    data.extend(packet_header("1 0"));
+    data.extend(packet_header(&format!(
+        "1 1 1111 00011 0 {:06b} 1 000001 1111 00111 0 {:06b} 0",
+        hl.len() - hl_first,
+        lh.len()
+    )));
+    data.extend(&hl[hl_first..]);
+    data.extend(&lh);
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 2, 1, 8)
+            .no_decomposition_levels(1)
+            .no_layers(3),
+        &data,
+    );
+
+    // Passes and data accumulate across layers
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+    let layers: Vec<_> = code_blocks
+        .iter()
+        .map(|code_block| (code_block.first_layer, code_block.parameters.no_passes))
+        .collect();
+    assert_eq!(
+        layers,
+        vec![(Some(0), 19), (Some(1), 10), (Some(2), 13), (None, 0)]
+    );
+    assert_eq!(code_blocks[0].data, ll);
+    assert_eq!(code_blocks[1].data, hl);
+    assert_eq!(code_blocks[1].segment_lengths, vec![hl.len()]);
+    assert_eq!(code_blocks[2].data, lh);
+
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!(image.components()[0].samples(), &[10, 20, 30, 40]);
+    let report = image.report().tile_component(0, 0).unwrap();
+    assert_eq!(report.coding_passes, 19 + 10 + 13);
+}
+
+#[test]
+fn test_decode_precincts() {
+    // 2x1 image of 10 and 40 without decomposition levels, in 1x1 precincts,
+    // which limit the code-blocks to 1x1. After the DC level shift, -118 and
+    // -88 are each 7 bits, so 2 zero bit-planes and 19 coding passes.
+    let left = code_block(&[
+        (0, 1),
+        (9, 1),
+        (14, 1),
+        (16, 1),
+        (16, 0),
+        (16, 1),
+        (16, 1),
+        (16, 0),
+    ]);
+    let right = code_block(&[
+        (0, 1),
+        (9, 1),
+        (14, 0),
+        (16, 1),
+        (16, 1),
+        (16, 0),
+        (16, 0),
+        (16, 0),
+    ]);
+
+    // A packet for each precinct, each with its own tag trees of a single
+    // code-block: non-empty, included, zero bit-planes, passes, Lblock
+    // unchanged, length in 3 + 4 bits
+    let mut data = Vec::new();
+    for coded in [&left, &right] {
+        data.extend(packet_header(&format!(
+            "1 1 001 1111 01101 0 {:07b}",
+            coded.len()
+        )));
+        data.extend(coded);
+    }
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 1, 1, 8)
+            .no_decomposition_levels(0)
+            .precinct_sizes(&[(0, 0)]),
+        &data,
+    );
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+    assert_eq!(code_blocks.len(), 2);
+    assert_eq!((code_blocks[1].x0, code_blocks[1].x1), (1, 2));
+    assert_eq!(code_blocks[1].data, right);
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!(image.components()[0].samples(), &[10, 40]);
+}
+
+#[test]
+fn test_decode_empty_packets() {
+    // Every packet is empty, so every coefficient is zero and every sample
+    // is at the middle of the range
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(3, 2, 1, 8).no_decomposition_levels(1),
+        &[0x00, 0x00],
+    );
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!(image.components()[0].samples(), &[128; 6]);
+    assert_eq!(image.report().code_blocks_decoded(), 0);
+}
+
+#[test]
+fn test_decode_unsupported() {
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 2, 1, 8).tile_size(1, 1),
+        &[],
+    );
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    assert_eq!(
+        codestream
+            .decode_image(&mut reader)
+            .unwrap_err()
+            .to_string(),
+        "unsupported feature for marker SIZ (0xFF51) at byte offset 4"
+    );
+
+    // Truncated packet data
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 2, 1, 8).no_decomposition_levels(1),
+        &[0x80],
+    );
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    assert_eq!(
+        codestream
+            .decode_image(&mut reader)
+            .unwrap_err()
+            .to_string(),
+        "packet data exhausted at byte offset 1"
+    );
+}
+
+#[test]
+fn test_code_blocks() {
+    let buffer = two_by_two();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+
+    // LL, then HL, LH and HH of resolution level 1, each 1x1
+    let subbands: Vec<_> = code_blocks
+        .iter()
+        .map(|code_block| (code_block.resolution_level, code_block.parameters.subband))
+        .collect();
+    assert_eq!(
+        subbands,
+        vec![
+            (0, SubBandType::LL),
+            (1, SubBandType::HL),
+            (1, SubBandType::LH),
+            (1, SubBandType::HH)
+        ]
+    );
+    let passes: Vec<_> = code_blocks
+        .iter()
+        .map(|code_block| {
+            let parameters = &code_block.parameters;
+            (
+                parameters.no_passes,
+                parameters.zero_bit_planes,
+                parameters.mb,
+            )
+        })
+        .collect();
+    assert_eq!(
+        passes,
+        vec![(19, 2, 9), (10, 6, 10), (13, 5, 10), (0, 0, 11)]
+    );
+
+    // Decoding the coded data separately gives the wavelet coefficients
+    let coefficients: Vec<_> = code_blocks
+        .iter()
+        .map(|code_block| decode_code_block(&code_block.parameters, &code_block.data).unwrap())
+        .collect();
+    assert_eq!(coefficients, vec![vec![-103], vec![10], vec![20], vec![0]]);
+    let hh = &code_blocks[3];
+    assert_eq!((hh.x0, hh.y0, hh.x1, hh.y1), (0, 0, 1, 1));
+    assert!(hh.data.is_empty());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_decode_stats() {
+    let buffer = two_by_two();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let image = codestream.decode_image(&mut reader).unwrap();
+    let stats = image.report().stats();
+
+    // 19, 10 and 13 passes, each starting with a cleanup pass
+    assert_eq!(stats.cleanup_passes, 7 + 4 + 5);
+    assert_eq!(stats.significance_passes, 6 + 3 + 4);
+    assert_eq!(stats.refinement_passes, 6 + 3 + 4);
+    assert_eq!(stats.passes(), 42);
+
+    // Each coefficient becomes significant in the first cleanup pass, and
+    // no other coefficient is there to propagate significance to
+    assert_eq!(stats.cleanup_decisions, 3 * 2);
+    assert_eq!(stats.significance_decisions, 0);
+    assert_eq!(stats.refinement_decisions, 6 + 3 + 4);
+    assert_eq!(stats.contexts[0], 3);
+    assert_eq!(stats.contexts[9], 3);
+    assert_eq!(stats.contexts[14], 3);
+    assert_eq!(stats.contexts[16], 10);
+    assert_eq!(stats.decisions(), 19);
+    assert!(stats.subband_time(SubBandType::LL) > std::time::Duration::ZERO);
+}
+
+#[test]
+fn test_inverse_component_transformation_four_components() {
+    // RGB with an alpha channel, where only the colour is transformed
+    let buffer = single_tile_part(
+        CodestreamHeaderBuilder::new(2, 1, 4, 8).multiple_component_transformation(true),
+        &[],
+    );
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let siz = codestream.header().image_and_tile_size_marker_segment();
+    assert_eq!(siz.no_components(), 4);
+    let tile = codestream.tile(0).unwrap();
+    let cod = tile.coding_style_marker_segment();
+    assert_eq!(
+        cod.multiple_component_transformation(),
+        MultipleComponentTransformation::Multiple
+    );
+
+    // Forward RCT of (10, 20, 30) and (-128, 127, 0), with alpha 100 and -28
+    let mut components = vec![
+        vec![20, 31],
+        vec![10, -127],
+        vec![-10, -255],
+        vec![100, -28],
+    ];
+    inverse_component_transformation(
+        cod.multiple_component_transformation(),
+        tile.coding_style_parameters(0).transformation(),
+        &mut components,
+    )
+    .unwrap();
+    assert_eq!(
+        components,
+        vec![vec![10, -128], vec![20, 127], vec![30, 0], vec![100, -28]]
+    );
+}
+
+#[test]
+fn test_streaming_decoder() {
+    let buffer = two_by_two_with(3, 2);
+    let mut reader = Cursor::new(&buffer);
+    let expected = decode_jpc(&mut reader)
+        .unwrap()
+        .decode_image(&mut reader)
+        .unwrap();
+    assert_eq!(expected.components()[0].samples(), &[10, 20, 30, 40]);
+
+    // One byte at a time, with the LL sub-band alone after the first layer
+    let mut decoder = StreamingDecoder::new();
+    let mut layers = Vec::new();
+    for byte in &buffer {
+        decoder.push(&[*byte]).unwrap();
+        if layers.last() != Some(&decoder.layers_complete()) {
+            layers.push(decoder.layers_complete());
+            if decoder.layers_complete() == 1 {
+                let image = decoder.image().unwrap();
+                assert_eq!(image.components()[0].samples(), &[25; 4]);
+            }
+        }
+    }
+    assert_eq!(layers, vec![0, 1, 2, 3]);
+    assert!(decoder.is_complete());
+    assert_eq!(decoder.packets_read(), 6);
+    assert_eq!(decoder.image().unwrap().components(), expected.components());
+
+    // All at once
+    let mut decoder = StreamingDecoder::new();
+    assert_eq!(decoder.push(&buffer[..10]).unwrap(), 0);
+    assert!(decoder.codestream().is_none());
+    assert!(decoder.image().is_none());
+    assert_eq!(decoder.push(&buffer[10..]).unwrap(), 6);
+    assert_eq!(decoder.layers_complete(), 3);
+    assert_eq!(decoder.image().unwrap().components(), expected.components());
+}
+
+#[test]
+fn test_streaming_decoder_markers() {
+    // One byte at a time, with the SOP and EPH markers of the packets split
+    // across pushes
+    for filename in ["eph.j2k", "sop.j2k"] {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(filename);
+        let buffer = fs::read(path).unwrap();
+        let mut reader = Cursor::new(&buffer);
+        let expected = decode_jpc(&mut reader)
+            .unwrap()
+            .decode_image(&mut reader)
+            .unwrap();
+
+        let mut decoder = StreamingDecoder::new();
+        for byte in &buffer {
+            decoder.push(&[*byte]).unwrap();
+        }
+        assert!(decoder.is_complete(), "{}", filename);
+        assert_eq!(
+            decoder.image().unwrap().components(),
+            expected.components(),
+            "{filename}"
+        );
+    }
+}
+
+#[test]
+fn test_decode_no_decomposition_levels() {
+    // The geometry of tlm.j2k: 2x1 unsigned 16 bit samples without
+    // decomposition levels, so a single LL code-block, M_b of 16 + 2 - 1, and
+    // no inverse wavelet transformation
+    let samples = [0x1234, 0xFEDC];
+    let buffer = encode_image(
+        &samples,
+        &CodestreamHeaderBuilder::new(2, 1, 1, 16).no_decomposition_levels(0),
+    )
+    .unwrap();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+    assert_eq!(code_blocks.len(), 1);
+    let code_block = &code_blocks[0];
+    assert_eq!(code_block.resolution_level, 0);
+    assert_eq!(
+        (code_block.x0, code_block.y0, code_block.x1, code_block.y1),
+        (0, 0, 2, 1)
+    );
+    assert_eq!(code_block.parameters.subband, SubBandType::LL);
+    assert_eq!(code_block.parameters.mb, 17);
+
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!((image.width(), image.height()), (2, 1));
+    assert_eq!(image.components()[0].samples(), &samples);
+    let report = image.report().tile_component(0, 0).unwrap();
+    assert_eq!(report.resolution_levels_decoded, 1);
+    assert_eq!(report.code_blocks_decoded, 1);
+}
+
+#[test]
+fn test_decode_guard_bits() {
+    // Each guard bit adds a magnitude bit-plane, which the packet headers
+    // signal as missing, so the samples are the same
+    for guard_bits in [1, 2, 3, 7] {
+        let buffer = two_by_two_with(1, guard_bits);
+        let mut reader = Cursor::new(&buffer);
+        let codestream = decode_jpc(&mut reader).unwrap();
+        let tile = codestream.tile(0).unwrap();
+        let quantization = tile.quantization_info(0);
+        assert_eq!(quantization.guard_bits, guard_bits);
+        assert_eq!(
+            quantization
+                .magnitude_bit_planes(0, SubBandType::LL)
+                .unwrap(),
+            7 + guard_bits
+        );
+        assert_eq!(
+            quantization
+                .magnitude_bit_planes(1, SubBandType::HH)
+                .unwrap(),
+            9 + guard_bits
+        );
+
+        let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+        assert_eq!(code_blocks[0].parameters.mb, 7 + guard_bits);
+        assert_eq!(code_blocks[0].parameters.zero_bit_planes, guard_bits);
+        let image = codestream.decode_image(&mut reader).unwrap();
+        assert_eq!(
+            image.components()[0].samples(),
+            &[10, 20, 30, 40],
+            "{guard_bits} guard bits"
+        );
+    }
+}
+
+#[test]
+fn test_decode_tlm() {
+    // tlm.j2k is 2x1 RGB with 16 bit samples, coded with the RCT and the
+    // selective arithmetic coding bypass, and no decomposition levels
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let buffer = fs::read(path).unwrap();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+
+    // Only Y0 has coded data: 43 coding passes, the first 10 in one codeword
+    // segment, then raw segments of two passes alternating with a cleanup pass
+    let code_blocks = codestream.code_blocks(&mut reader).unwrap();
+    assert_eq!(code_blocks.len(), 3);
+    let y0 = &code_blocks[0];
+    assert_eq!(y0.code_block_style, 0b1);
+    assert_eq!(y0.parameters.no_passes, 43);
+    assert_eq!(y0.segment_lengths.len(), 1 + 2 * 11);
+    assert_eq!(y0.segment_lengths[..5], [2, 1, 2, 1, 2]);
+    assert_eq!(y0.segment_lengths.iter().sum::<usize>(), y0.data.len());
+    for code_block in &code_blocks[1..] {
+        assert_eq!(code_block.parameters.no_passes, 0);
+        assert!(code_block.data.is_empty());
+    }
+
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!((image.width(), image.height()), (2, 1));
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::sRGB);
+    assert_eq!(image.components().len(), 3);
+    for component in image.components() {
+        assert_eq!(component.precision(), 16);
+        assert!(!component.signed());
+        assert_eq!(component.samples(), &[65535, 65535]);
+    }
+    let report = image.report().tile_component(0, 0).unwrap();
+    assert_eq!(report.coding_passes, 43);
+    assert_eq!(image.report().code_blocks_decoded(), 1);
+}
+
+#[test]
+fn test_decode_blue() {
+    // blue.j2k is a 128x64 RGB image with 8 bit samples, coded losslessly by
+    // OpenJPEG 2.5.0 with the RCT, the 5-3 filter, five decomposition levels
+    // and 32x32 code-blocks
+    // TODO: check against the samples of opj_decompress rather than those of
+    // this decoder
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("blue.j2k");
+    let buffer = fs::read(path).unwrap();
+    let mut reader = Cursor::new(&buffer);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let image = codestream.decode_image(&mut reader).unwrap();
+    assert_eq!((image.width(), image.height()), (128, 64));
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::sRGB);
+
+    // Sum, minimum, maximum and first 16 samples of each component
+    let expected: [(i64, i32, i32, [i32; 16]); 3] = [
+        (
+            32450,
+            0,
+            39,
+            [2, 0, 2, 8, 5, 5, 6, 6, 9, 2, 1, 4, 4, 11, 14, 3],
+        ),
+        (
+            200853,
+            0,
+            192,
+            [0, 2, 0, 0, 0, 37, 101, 97, 41, 5, 1, 1, 7, 39, 72, 53],
+        ),
+        (
+            1344521,
+            124,
+            243,
+            [
+                146, 141, 142, 140, 134, 175, 199, 202, 182, 152, 148, 148, 158, 182, 195, 178,
+            ],
+        ),
+    ];
+    assert_eq!(image.components().len(), 3);
+    for (component, (sum, min, max, first)) in image.components().iter().zip(expected.iter()) {
+        let samples = component.samples();
+        assert_eq!(samples.len(), 128 * 64);
+        assert_eq!(
+            samples.iter().map(|&sample| i64::from(sample)).sum::<i64>(),
+            *sum
+        );
+        assert_eq!(samples.iter().min(), Some(min));
+        assert_eq!(samples.iter().max(), Some(max));
+        assert_eq!(&samples[..16], first);
+    }
+}
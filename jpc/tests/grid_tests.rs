@@ -0,0 +1,209 @@
+use std::io::Cursor;
+
+use jpc::{decode_jpc_with_limits, DecodeLimits, SizError};
+
+// Codestream with a single component, given SIZ geometry
+// (Xsiz, Ysiz, XOsiz, YOsiz, XTsiz, YTsiz, XTOsiz, YTOsiz), a COD and QCD for
+// no decomposition levels, an empty tile-part and EOC.
+fn codestream(siz: [u32; 8]) -> Vec<u8> {
+    let mut buffer = vec![0xFF, 0x4F];
+    // SIZ, Lsiz = 41, Rsiz = 0
+    buffer.extend_from_slice(&[0xFF, 0x51, 0x00, 0x29, 0x00, 0x00]);
+    for value in siz.iter() {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+    // Csiz = 1, Ssiz = 7, XRsiz = 1, YRsiz = 1
+    buffer.extend_from_slice(&[0x00, 0x01, 0x07, 0x01, 0x01]);
+    // COD, Lcod = 12
+    buffer.extend_from_slice(&[0xFF, 0x52, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x01]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x04, 0x04, 0x00, 0x01]);
+    // QCD, Lqcd = 4
+    buffer.extend_from_slice(&[0xFF, 0x5C, 0x00, 0x04, 0x40, 0x40]);
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    // SOD, EOC
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+    buffer
+}
+
+fn decode_error(siz: [u32; 8]) -> String {
+    let data = codestream(siz);
+    decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited())
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn test_single_tile_grid() {
+    let data = codestream([16, 16, 0, 0, 16, 16, 0, 0]);
+    let codestream =
+        decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited()).unwrap();
+    let siz = codestream.header().image_and_tile_size_marker_segment();
+    assert_eq!(siz.reference_grid_width(), 16);
+    assert_eq!(siz.reference_tile_width(), 16);
+}
+
+#[test]
+fn test_tile_at_grid_limit() {
+    // One tile, whose lower right corner (XTOsiz + XTsiz) is beyond u32::MAX
+    let max = u32::MAX;
+    let data = codestream([max, max, max - 1, max - 1, max, max, max - 1, max - 1]);
+    assert!(decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited()).is_ok());
+}
+
+#[test]
+fn test_tile_count_overflow() {
+    // numXtiles · numYtiles = (2^32 - 1)^2
+    let max = u32::MAX;
+    assert_eq!(
+        decode_error([max, max, 0, 0, 1, 1, 0, 0]),
+        "reference grid overflow computing numtiles"
+    );
+    // Tile offsets near the grid size do not overflow the tile count
+    let data = codestream([max, max, max - 2, max - 2, 2, 2, max - 3, max - 3]);
+    assert!(decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited()).is_ok());
+}
+
+fn decode_siz_error(siz: [u32; 8]) -> SizError {
+    let data = codestream(siz);
+    *decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited())
+        .unwrap_err()
+        .downcast::<SizError>()
+        .unwrap()
+}
+
+#[test]
+fn test_empty_image_area() {
+    let max = u32::MAX;
+    assert_eq!(
+        decode_error([max, 16, max, 0, 16, 16, 0, 0]),
+        "marker SIZ (0xFF51) at byte offset 2 has XOsiz 4294967295, not less than Xsiz 4294967295"
+    );
+    assert_eq!(
+        decode_siz_error([16, 0, 0, 0, 16, 16, 0, 0]),
+        SizError::ImageOffsetOutOfRange {
+            axis: 'Y',
+            image_offset: 0,
+            grid_size: 0,
+            offset: 2
+        }
+    );
+}
+
+#[test]
+fn test_image_offset_out_of_range() {
+    // XOsiz beyond Xsiz
+    assert_eq!(
+        decode_siz_error([16, 16, 17, 0, 16, 16, 0, 0]),
+        SizError::ImageOffsetOutOfRange {
+            axis: 'X',
+            image_offset: 17,
+            grid_size: 16,
+            offset: 2
+        }
+    );
+    // YOsiz equal to Ysiz
+    assert_eq!(
+        decode_siz_error([16, 16, 0, 16, 16, 16, 0, 0]),
+        SizError::ImageOffsetOutOfRange {
+            axis: 'Y',
+            image_offset: 16,
+            grid_size: 16,
+            offset: 2
+        }
+    );
+}
+
+#[test]
+fn test_tile_offset_out_of_range() {
+    // XTOsiz beyond XOsiz
+    assert_eq!(
+        decode_siz_error([16, 16, 2, 2, 8, 8, 3, 0]),
+        SizError::TileOffsetOutOfRange {
+            axis: 'X',
+            tile_offset: 3,
+            image_offset: 2,
+            offset: 2
+        }
+    );
+    assert_eq!(
+        decode_error([16, 16, 2, 2, 8, 8, 0, 3]),
+        "marker SIZ (0xFF51) at byte offset 2 has YTOsiz 3, greater than YOsiz 2"
+    );
+    // Tile offsets equal to the image offsets are allowed
+    let data = codestream([16, 16, 2, 2, 8, 8, 2, 2]);
+    assert!(decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited()).is_ok());
+}
+
+#[test]
+fn test_first_tile_outside_image() {
+    // The first tile ends at XOsiz, before the image area
+    assert_eq!(
+        decode_siz_error([16, 16, 8, 0, 4, 4, 4, 0]),
+        SizError::FirstTileOutsideImage {
+            axis: 'X',
+            tile_end: 8,
+            image_offset: 8,
+            offset: 2
+        }
+    );
+    assert_eq!(
+        decode_error([16, 16, 0, 9, 4, 4, 0, 4]),
+        "marker SIZ (0xFF51) at byte offset 2 has YTOsiz + YTsiz 8, not greater than YOsiz 9"
+    );
+}
+
+#[test]
+fn test_zero_tile_size() {
+    assert_eq!(
+        decode_error([16, 16, 0, 0, 0, 16, 0, 0]),
+        "malformed marker SIZ (0xFF51) at byte offset 2"
+    );
+}
+
+// Decode with Csiz and Lsiz replaced
+fn decode_components_error(no_components: u16, length: u16) -> Box<dyn std::error::Error> {
+    let mut data = codestream([16, 16, 0, 0, 16, 16, 0, 0]);
+    data[4..6].copy_from_slice(&length.to_be_bytes());
+    data[40..42].copy_from_slice(&no_components.to_be_bytes());
+    decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited()).unwrap_err()
+}
+
+#[test]
+fn test_component_count() {
+    let error = decode_components_error(0, 38);
+    assert_eq!(
+        error.downcast_ref::<SizError>(),
+        Some(&SizError::NoComponents { offset: 2 })
+    );
+    assert_eq!(
+        error.to_string(),
+        "marker SIZ (0xFF51) at byte offset 2 has no components"
+    );
+
+    // Csiz of 3, with room for one component
+    let error = decode_components_error(3, 41);
+    assert_eq!(
+        error.downcast_ref::<SizError>(),
+        Some(&SizError::ComponentCountMismatch {
+            declared: 3,
+            length_implies: 1,
+            offset: 2
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "marker SIZ (0xFF51) at byte offset 2 declares 3 components, but its length implies 1"
+    );
+
+    // Lsiz that is not a whole number of components
+    assert_eq!(
+        decode_components_error(1, 42).to_string(),
+        "malformed marker SIZ (0xFF51) at byte offset 2"
+    );
+    assert_eq!(
+        decode_components_error(1, 37).to_string(),
+        "malformed marker SIZ (0xFF51) at byte offset 2"
+    );
+}
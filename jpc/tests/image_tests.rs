@@ -0,0 +1,335 @@
+use std::{fs::File, io::BufReader, io::Cursor, path::Path};
+
+use jp2::{decode_jp2, ChannelDefinitionBox, EnumeratedColourSpaces, JP2File};
+use jpc::{
+    CodeBlockParameters, DecodeReport, DecodedComponent, DecodedImage, ImageError, SubBandType,
+    TileComponentReport,
+};
+
+fn unsigned(precision: u8, samples: &[i32]) -> DecodedComponent {
+    DecodedComponent::new(precision, false, samples.to_vec())
+}
+
+#[test]
+fn test_srgb8() {
+    let image = DecodedImage::new(
+        2,
+        1,
+        EnumeratedColourSpaces::sRGB,
+        vec![
+            unsigned(8, &[255, 0]),
+            unsigned(8, &[128, 1]),
+            unsigned(8, &[0, 2]),
+        ],
+    );
+    assert_eq!(image.to_srgb8().unwrap(), vec![255, 128, 0, 0, 1, 2]);
+}
+
+#[test]
+fn test_srgb8_scaled() {
+    let image = DecodedImage::new(
+        3,
+        1,
+        EnumeratedColourSpaces::sRGB,
+        vec![
+            unsigned(12, &[4095, 2048, 0]),
+            unsigned(4, &[15, 8, 0]),
+            DecodedComponent::new(8, true, vec![127, 0, -128]),
+            // Opacity, not included in the output
+            unsigned(1, &[1, 1, 0]),
+        ],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap(),
+        vec![255, 255, 255, 128, 136, 128, 0, 0, 0]
+    );
+}
+
+#[test]
+fn test_sycc_to_srgb8() {
+    let image = DecodedImage::new(
+        3,
+        1,
+        EnumeratedColourSpaces::sYCC,
+        vec![
+            unsigned(8, &[76, 200, 29]),
+            unsigned(8, &[85, 128, 255]),
+            unsigned(8, &[255, 128, 107]),
+        ],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap(),
+        vec![254, 0, 0, 200, 200, 200, 0, 0, 254]
+    );
+
+    // Signed chroma is centred on zero
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::sYCC,
+        vec![
+            unsigned(8, &[200]),
+            DecodedComponent::new(8, true, vec![0]),
+            DecodedComponent::new(8, true, vec![0]),
+        ],
+    );
+    assert_eq!(image.to_srgb8().unwrap(), vec![200, 200, 200]);
+}
+
+#[test]
+fn test_greyscale_to_srgb8() {
+    let image = DecodedImage::new(
+        2,
+        2,
+        EnumeratedColourSpaces::Greyscale,
+        vec![unsigned(16, &[0, 257, 32768, 65535])],
+    );
+    assert_eq!(image.to_srgb8().unwrap(), vec![0, 1, 128, 255]);
+}
+
+fn jbox(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut buffer = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    buffer.extend_from_slice(box_type);
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+// Greyscale JP2 file with two components, 8 bits, where the channel
+// definition marks the first as the grey channel and the second as opacity.
+fn greyscale_alpha_file() -> Vec<u8> {
+    let mut buffer = jbox(b"jP  ", &[0x0D, 0x0A, 0x87, 0x0A]);
+    buffer.extend(jbox(b"ftyp", b"jp2 \0\0\0\0jp2 "));
+    // HEIGHT 1, WIDTH 2, NC 2, BPC 7, C 7, UnkC 0, IPR 0
+    let mut header = jbox(b"ihdr", &[0, 0, 0, 1, 0, 0, 0, 2, 0, 2, 7, 7, 0, 0]);
+    // METH 1, PREC 0, APPROX 0, EnumCS 17
+    header.extend(jbox(b"colr", &[1, 0, 0, 0, 0, 0, 17]));
+    // N 2, with Cn, Typ and Asoc for each channel, opacity listed first
+    header.extend(jbox(b"cdef", &[0, 2, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+    buffer.extend(jbox(b"jp2h", &header));
+    // SOC, EOC
+    buffer.extend(jbox(b"jp2c", &[0xFF, 0x4F, 0xFF, 0xD9]));
+    buffer
+}
+
+#[test]
+fn test_greyscale_alpha_to_srgb8() {
+    let mut reader = Cursor::new(greyscale_alpha_file());
+    let file = decode_jp2(&mut reader).unwrap();
+    let header_box = file.header_box().as_ref().unwrap();
+    let channel_definition = header_box.channel_definition_box.as_ref().unwrap();
+
+    let image = DecodedImage::new(
+        2,
+        1,
+        header_box.colour_specification_boxes[0]
+            .enumerated_colour_space()
+            .unwrap(),
+        vec![unsigned(8, &[10, 20]), unsigned(8, &[255, 0])],
+    );
+    assert!(!image.has_opacity());
+    assert_eq!(image.to_srgb8().unwrap(), vec![10, 20]);
+
+    let image = image.with_channel_definition(channel_definition);
+    assert!(image.has_opacity());
+    assert_eq!(image.to_srgb8().unwrap(), vec![10, 255, 20, 0]);
+}
+
+#[test]
+fn test_greyscale_alpha_to_rgba8() {
+    let mut reader = Cursor::new(greyscale_alpha_file());
+    let file = decode_jp2(&mut reader).unwrap();
+    let header_box = file.header_box().as_ref().unwrap();
+    let channel_definition = header_box.channel_definition_box.as_ref().unwrap();
+
+    let image = DecodedImage::new(
+        2,
+        1,
+        EnumeratedColourSpaces::Greyscale,
+        vec![unsigned(8, &[10, 20]), unsigned(8, &[255, 0])],
+    );
+    assert_eq!(
+        image.to_rgba8().unwrap(),
+        vec![10, 10, 10, 255, 20, 20, 20, 255]
+    );
+    let image = image.with_channel_definition(channel_definition);
+    assert_eq!(
+        image.to_rgba8().unwrap(),
+        vec![10, 10, 10, 255, 20, 20, 20, 0]
+    );
+}
+
+// file2.jp2, with a channel definition where components 0, 1 and 2 are
+// associated with colours 3, 2 and 1
+fn reversed_channels_file() -> JP2File<'static> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../samples/file2.jp2");
+    let mut reader = BufReader::new(File::open(path).expect("file should exist"));
+    decode_jp2(&mut reader).unwrap()
+}
+
+fn channel_definition<'a>(file: &'a JP2File) -> &'a ChannelDefinitionBox {
+    let header_box = file.header_box().as_ref().unwrap();
+    header_box.channel_definition_box.as_ref().unwrap()
+}
+
+#[test]
+fn test_reversed_channel_definition() {
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::sRGB,
+        vec![
+            unsigned(8, &[3]),
+            unsigned(8, &[2]),
+            unsigned(8, &[1]),
+            // Not described by the channel definition
+            unsigned(1, &[0]),
+        ],
+    )
+    .with_channel_definition(channel_definition(&reversed_channels_file()));
+    let samples: Vec<&[i32]> = image
+        .components()
+        .iter()
+        .map(|component| component.samples())
+        .collect();
+    assert_eq!(samples, vec![&[1][..], &[2], &[3], &[0]]);
+    assert_eq!(image.components()[3].precision(), 1);
+    assert_eq!(image.to_srgb8().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_reversed_channel_definition_to_rgba8() {
+    let file = reversed_channels_file();
+    let channel_definition = channel_definition(&file);
+
+    // Cr, Cb and Y components of red, then white
+    let image = DecodedImage::new(
+        2,
+        1,
+        EnumeratedColourSpaces::sYCC,
+        vec![
+            unsigned(8, &[255, 128]),
+            unsigned(8, &[85, 128]),
+            unsigned(8, &[76, 255]),
+        ],
+    )
+    .with_channel_definition(channel_definition);
+    assert!(!image.has_opacity());
+    assert_eq!(image.to_srgb8().unwrap(), vec![254, 0, 0, 255, 255, 255]);
+    assert_eq!(
+        image.to_rgba8().unwrap(),
+        vec![254, 0, 0, 255, 255, 255, 255, 255]
+    );
+
+    // As sRGB, the components are blue, green and red
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::sRGB,
+        vec![unsigned(8, &[3]), unsigned(8, &[2]), unsigned(8, &[1])],
+    )
+    .with_channel_definition(channel_definition);
+    assert_eq!(image.to_rgba8().unwrap(), vec![1, 2, 3, 255]);
+}
+
+#[test]
+fn test_to_srgb8_errors() {
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::Reserved,
+        vec![unsigned(8, &[0])],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap_err(),
+        ImageError::UnsupportedColourSpace {
+            colour_space: EnumeratedColourSpaces::Reserved
+        }
+    );
+
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::sRGB,
+        vec![unsigned(8, &[0]), unsigned(8, &[0])],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap_err().to_string(),
+        "expected 3 components, found 2"
+    );
+
+    let image = DecodedImage::new(
+        2,
+        1,
+        EnumeratedColourSpaces::Greyscale,
+        vec![unsigned(8, &[0])],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap_err(),
+        ImageError::ComponentSize {
+            component: 0,
+            expected: 2,
+            actual: 1
+        }
+    );
+
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::Greyscale,
+        vec![unsigned(0, &[0])],
+    );
+    assert_eq!(
+        image.to_srgb8().unwrap_err(),
+        ImageError::InvalidPrecision {
+            component: 0,
+            precision: 0
+        }
+    );
+}
+
+#[test]
+fn test_decode_report() {
+    let image = DecodedImage::new(
+        1,
+        1,
+        EnumeratedColourSpaces::Greyscale,
+        vec![unsigned(8, &[0])],
+    );
+    assert!(image.report().tile_components().is_empty());
+
+    // Six resolution levels, reduced to the lowest three
+    let mut tile_component = TileComponentReport::new(1, 0, 6);
+    tile_component.resolution_levels_decoded = 3;
+    for no_passes in [10, 0, 7].iter() {
+        tile_component.record_code_block(&CodeBlockParameters {
+            subband: SubBandType::HL,
+            width: 64,
+            height: 64,
+            no_passes: *no_passes,
+            zero_bit_planes: 2,
+            mb: 9,
+        });
+    }
+    tile_component.record_packet_skipped();
+    tile_component.record_packet_skipped();
+    let mut report = DecodeReport::new();
+    report.push(TileComponentReport::new(0, 0, 6));
+    report.push(tile_component);
+    let image = image.with_report(report);
+
+    let report = image.report();
+    assert_eq!(report.tile_components().len(), 2);
+    assert_eq!(report.code_blocks_decoded(), 2);
+    assert_eq!(report.packets_skipped(), 2);
+    let tile_component = report.tile_component(1, 0).unwrap();
+    assert_eq!(tile_component.resolution_levels, 6);
+    assert_eq!(tile_component.decomposition_levels_decoded(), 2);
+    assert_eq!(tile_component.subbands_decoded(), 7);
+    assert_eq!(tile_component.code_blocks_decoded, 2);
+    assert_eq!(tile_component.coding_passes, 17);
+
+    let tile_component = report.tile_component(0, 0).unwrap();
+    assert_eq!(tile_component.decomposition_levels_decoded(), 0);
+    assert_eq!(tile_component.subbands_decoded(), 0);
+    assert!(report.tile_component(0, 1).is_none());
+}
@@ -0,0 +1,149 @@
+use std::io::Cursor;
+
+use jpc::{decode_jpc, decode_jpc_with_limits, CodestreamHeaderBuilder, DecodeLimits};
+
+// Main header, a single empty tile-part and the EOC marker.
+fn codestream(width: u32, height: u32, no_components: u16) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(width, height, no_components, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    // SOD, EOC
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_default_limits() {
+    let limits = DecodeLimits::default();
+    assert_eq!(limits.max_width, 1 << 20);
+    assert_eq!(limits.max_height, 1 << 20);
+    assert_eq!(limits.max_components, 16384);
+    assert_eq!(limits.max_tiles, 65535);
+    assert_eq!(limits.max_alloc_bytes, 1 << 32);
+}
+
+#[test]
+fn test_within_limits() {
+    let data = codestream(1024, 512, 3);
+    assert!(decode_jpc(&mut Cursor::new(&data)).is_ok());
+}
+
+#[test]
+fn test_width_exceeds_default_limit() {
+    let data = codestream(u32::MAX, 1, 1);
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "width of 4294967295 exceeds decode limit of 1048576"
+    );
+
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), DecodeLimits::unlimited());
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_height_exceeds_limit() {
+    let data = codestream(16, 32, 1);
+    let limits = DecodeLimits {
+        max_height: 16,
+        ..Default::default()
+    };
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), limits);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "height of 32 exceeds decode limit of 16"
+    );
+}
+
+#[test]
+fn test_components_exceed_limit() {
+    let data = codestream(16, 16, 4);
+    let limits = DecodeLimits {
+        max_components: 3,
+        ..Default::default()
+    };
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), limits);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "components of 4 exceeds decode limit of 3"
+    );
+}
+
+#[test]
+fn test_allocation_exceeds_limit() {
+    // Within the default dimensions, but 3 * 2^20 * 2^20 * 4 bytes of samples
+    let data = codestream(1 << 20, 1 << 20, 3);
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "allocation of 13194139533312 exceeds decode limit of 4294967296"
+    );
+
+    // 16 * 16 * 4 bytes for each component
+    let data = codestream(16, 16, 3);
+    let limits = DecodeLimits {
+        max_alloc_bytes: 3 * 1024 - 1,
+        ..Default::default()
+    };
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), limits);
+    assert!(result.is_err());
+    let limits = DecodeLimits {
+        max_alloc_bytes: 3 * 1024,
+        ..Default::default()
+    };
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), limits);
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_precinct_allocation_exceeds_limit() {
+    // 2^26 samples of 4 bytes fit the default limit, but 1x1 precincts give
+    // 2^26 precincts, code-blocks and packets, of 256, 256 and 16 bytes
+    let mut data = Vec::new();
+    CodestreamHeaderBuilder::new(8192, 8192, 1, 8)
+        .no_decomposition_levels(0)
+        .precinct_sizes(&[(0, 0)])
+        .write_to(&mut data)
+        .unwrap();
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1, SOD, EOC
+    data.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    data.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let result = codestream.decode_image(&mut reader);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "allocation of 35701915648 exceeds decode limit of 4294967296"
+    );
+}
+
+#[test]
+fn test_tiles_exceed_limit() {
+    // 1 by 1 tiles over a 1024 by 1024 grid
+    let mut data = Vec::new();
+    CodestreamHeaderBuilder::new(1024, 1024, 1, 8)
+        .tile_size(1, 1)
+        .write_to(&mut data)
+        .unwrap();
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "tiles of 1048576 exceeds decode limit of 65535"
+    );
+
+    let data = codestream(1024, 512, 1);
+    let limits = DecodeLimits {
+        max_tiles: 0,
+        ..Default::default()
+    };
+    let result = decode_jpc_with_limits(&mut Cursor::new(&data), limits);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "tiles of 1 exceeds decode limit of 0"
+    );
+}
@@ -0,0 +1,99 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor},
+    path::Path,
+};
+
+use jp2::{decode_jp2, EnumeratedColourSpaces, Jp2Builder};
+use jpc::{decode_jpc_from_jp2, encode_image, open, CodestreamHeaderBuilder};
+
+fn sample_path(filename: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../samples")
+        .join(filename)
+}
+
+#[test]
+fn test_open_raw_codestream() {
+    let samples: Vec<i32> = (0..48).map(|i| (i * 5) % 256).collect();
+    let codestream = encode_image(&samples, &CodestreamHeaderBuilder::new(8, 6, 1, 8)).unwrap();
+    let image = open(&mut Cursor::new(&codestream)).unwrap();
+    assert_eq!((image.width(), image.height()), (8, 6));
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::Greyscale);
+    assert_eq!(image.components().len(), 1);
+    assert_eq!(image.components()[0].samples(), &samples[..]);
+}
+
+#[test]
+fn test_open_jp2() {
+    let samples: Vec<i32> = (0..48).map(|i| (i * 7) % 256).collect();
+    let codestream = encode_image(&samples, &CodestreamHeaderBuilder::new(8, 6, 1, 8)).unwrap();
+    let mut file = Vec::new();
+    Jp2Builder::new(8, 6, codestream)
+        .colour_space(EnumeratedColourSpaces::Greyscale)
+        .write_to(&mut file)
+        .unwrap();
+    let image = open(&mut Cursor::new(&file)).unwrap();
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::Greyscale);
+    assert_eq!(image.components()[0].samples(), &samples[..]);
+}
+
+#[test]
+fn test_open_jp2_palette() {
+    // file9.jp2 maps a single component through a palette of three columns
+    let image = open(&mut BufReader::new(
+        File::open(sample_path("file9.jp2")).unwrap(),
+    ))
+    .unwrap();
+    assert_eq!((image.width(), image.height()), (768, 512));
+    assert_eq!(image.colour_space(), EnumeratedColourSpaces::sRGB);
+    assert_eq!(image.components().len(), 3);
+
+    let mut reader = BufReader::new(File::open(sample_path("file9.jp2")).unwrap());
+    let indices = decode_jpc_from_jp2(&mut reader)
+        .unwrap()
+        .decode_image(&mut reader)
+        .unwrap();
+    let mut reader = BufReader::new(File::open(sample_path("file9.jp2")).unwrap());
+    let file = decode_jp2(&mut reader).unwrap();
+    let palette = file
+        .header_box()
+        .as_ref()
+        .unwrap()
+        .palette_box
+        .as_ref()
+        .unwrap();
+    for (column, component) in image.components().iter().enumerate() {
+        assert_eq!(component.precision(), 8);
+        assert!(!component.signed());
+        let expected: Vec<i32> = indices.components()[0]
+            .samples()
+            .iter()
+            .map(|&index| *palette.entry(index as u16, column as u8).unwrap() as i32)
+            .collect();
+        assert_eq!(component.samples(), &expected[..]);
+    }
+}
+
+#[test]
+fn test_open_unknown() {
+    let result = open(&mut Cursor::new(b"GIF89a"));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("neither a JP2 file nor a codestream"));
+}
+
+#[test]
+fn test_open_malformed_jp2() {
+    let samples: Vec<i32> = (0..48).map(|i| (i * 3) % 256).collect();
+    let codestream = encode_image(&samples, &CodestreamHeaderBuilder::new(8, 6, 1, 8)).unwrap();
+    let mut file = Vec::new();
+    Jp2Builder::new(8, 6, codestream)
+        .write_to(&mut file)
+        .unwrap();
+
+    // A File Type box with a reserved length is an error, not a panic
+    file[12..16].copy_from_slice(&5u32.to_be_bytes());
+    assert!(open(&mut Cursor::new(&file)).is_err());
+}
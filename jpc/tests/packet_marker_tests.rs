@@ -35,11 +35,11 @@ fn test_sop() {
     assert_eq!(siz.reference_tile_height(), 1);
     assert_eq!(siz.no_components(), 3);
     assert_eq!(siz.precision(0).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(0).unwrap(), false);
+    assert!(!siz.values_are_signed(0).unwrap());
     assert_eq!(siz.precision(1).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(1).unwrap(), false);
+    assert!(!siz.values_are_signed(1).unwrap());
     assert_eq!(siz.precision(2).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(2).unwrap(), false);
+    assert!(!siz.values_are_signed(2).unwrap());
     assert_eq!(siz.horizontal_separation(0).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(1).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(2).unwrap(), 1);
@@ -98,14 +98,8 @@ fn test_sop() {
         TransformationFilter::Reversible
     );
 
-    assert_eq!(
-        cod.coding_style_parameters().has_defined_precinct_size(),
-        false
-    );
-    assert_eq!(
-        cod.coding_style_parameters().has_default_precinct_size(),
-        true
-    );
+    assert!(!cod.coding_style_parameters().has_defined_precinct_size());
+    assert!(cod.coding_style_parameters().has_default_precinct_size());
     assert!(cod.coding_style_parameters().precinct_sizes().is_some());
     let precincts = cod.coding_style_parameters().precinct_sizes().unwrap();
     assert_eq!(precincts[0].width_exponent(), 15);
@@ -188,11 +182,11 @@ fn test_eph() {
     assert_eq!(siz.reference_tile_height(), 1);
     assert_eq!(siz.no_components(), 3);
     assert_eq!(siz.precision(0).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(0).unwrap(), false);
+    assert!(!siz.values_are_signed(0).unwrap());
     assert_eq!(siz.precision(1).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(1).unwrap(), false);
+    assert!(!siz.values_are_signed(1).unwrap());
     assert_eq!(siz.precision(2).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(2).unwrap(), false);
+    assert!(!siz.values_are_signed(2).unwrap());
     assert_eq!(siz.horizontal_separation(0).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(1).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(2).unwrap(), 1);
@@ -251,14 +245,8 @@ fn test_eph() {
         TransformationFilter::Reversible
     );
 
-    assert_eq!(
-        cod.coding_style_parameters().has_defined_precinct_size(),
-        false
-    );
-    assert_eq!(
-        cod.coding_style_parameters().has_default_precinct_size(),
-        true
-    );
+    assert!(!cod.coding_style_parameters().has_defined_precinct_size());
+    assert!(cod.coding_style_parameters().has_default_precinct_size());
     assert!(cod.coding_style_parameters().precinct_sizes().is_some());
 
     // COC
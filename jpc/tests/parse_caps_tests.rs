@@ -1,12 +1,17 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::{BufReader, Cursor},
+    path::Path,
+};
 
 fn init() {
     let _ = env_logger::builder().is_test(true).try_init();
 }
 
 use jpc::{
-    decode_jpc, CodingBlockStyle, CommentRegistrationValue, MultipleComponentTransformation,
-    ProgressionOrder, QuantizationStyle, TransformationFilter,
+    decode_jpc, CodestreamHeaderBuilder, CodingBlockStyle, CommentRegistrationValue,
+    DecoderCapabilities, MultipleComponentTransformation, ProgressionOrder, QuantizationStyle,
+    TransformationFilter,
 };
 
 #[test]
@@ -34,13 +39,20 @@ fn test_ds0_ht_01_b11_codestream() {
     assert_eq!(siz.offset(), 4);
     assert_eq!(siz.length(), 41);
     assert_eq!(siz.decoder_capabilities(), 0b0100_0000_0000_0000);
+    assert_eq!(
+        siz.capabilities(),
+        vec![
+            DecoderCapabilities::Baseline,
+            DecoderCapabilities::ExtendedCapabilities
+        ]
+    );
     assert_eq!(siz.image_horizontal_offset(), 0);
     assert_eq!(siz.image_vertical_offset(), 0);
     assert_eq!(siz.reference_tile_width(), 128);
     assert_eq!(siz.reference_tile_height(), 128);
     assert_eq!(siz.no_components(), 1);
     assert_eq!(siz.precision(0).unwrap(), 8);
-    assert_eq!(siz.values_are_signed(0).unwrap(), false);
+    assert!(!siz.values_are_signed(0).unwrap());
     assert_eq!(siz.horizontal_separation(0).unwrap(), 1);
     assert_eq!(siz.vertical_separation(0).unwrap(), 1);
 
@@ -101,7 +113,11 @@ fn test_ds0_ht_01_b11_codestream() {
     let cpf = maybe_cpf.as_ref().unwrap();
     assert_eq!(cpf.length(), 4);
     assert_eq!(cpf.pcpf_raw(), [2]);
-    assert_eq!(cpf.cpf_num(), 1); // From jpylyzer
+    assert_eq!(cpf.cpf_num(), Some(1)); // From jpylyzer
+    assert_eq!(
+        cpf.corresponding_capabilities(),
+        Some(vec![DecoderCapabilities::Profile0])
+    );
 
     // COD
     let cod = header.coding_style_marker_segment();
@@ -136,9 +152,16 @@ fn test_ds0_ht_01_b11_codestream() {
         TransformationFilter::Reversible
     );
 
-    // TODO: fix this
-    // assert_eq!(cod.coding_style_parameters().has_precinct_size(), true);
-    // assert!(cod.coding_style_parameters().precinct_sizes().is_some());
+    assert!(cod.coding_style_parameters().has_defined_precinct_size());
+    assert!(!cod.coding_style_parameters().has_default_precinct_size());
+    let precincts: Vec<(u8, u8)> = cod
+        .coding_style_parameters()
+        .precinct_sizes()
+        .unwrap()
+        .iter()
+        .map(|precinct| (precinct.width_exponent(), precinct.height_exponent()))
+        .collect();
+    assert_eq!(precincts, vec![(7, 7), (8, 8), (8, 8), (8, 8)]);
 
     // COC
     assert!(header.coding_style_component_segment().is_empty());
@@ -182,3 +205,70 @@ fn test_ds0_ht_01_b11_codestream() {
     assert!(com.comment_utf8().is_ok());
     assert_eq!(com.comment_utf8().unwrap(), "Kakadu-vxt7.11-Beta");
 }
+
+// Codestream with a CPF marker segment of the given Lcpf and Pcpf values
+// after SIZ, an empty tile-part and EOC.
+fn codestream_with_cpf(length: u16, pcpf: &[u16]) -> Vec<u8> {
+    let mut header = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut header)
+        .unwrap();
+    // SOC, then SIZ with Lsiz = 41
+    let siz_end = 2 + 2 + 41;
+    let mut buffer = header[..siz_end].to_vec();
+    buffer.extend_from_slice(&[0xFF, 0x59]);
+    buffer.extend_from_slice(&length.to_be_bytes());
+    for value in pcpf {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+    buffer.extend_from_slice(&header[siz_end..]);
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1, SOD, EOC
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_cpf_multiple_entries() {
+    // CPFnum of 0x0103, broadcast single tile at level 3, with a second
+    // Pcpf of zero
+    let data = codestream_with_cpf(6, &[0x0104, 0x0000]);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let cpf = codestream
+        .header()
+        .corresponding_profile_marker_segment()
+        .as_ref()
+        .unwrap();
+    assert_eq!(cpf.length(), 6);
+    assert_eq!(cpf.offset(), 47);
+    assert_eq!(cpf.pcpf_raw(), [0x0104, 0x0000]);
+    assert_eq!(cpf.cpf_num(), Some(0x0103));
+    assert_eq!(
+        cpf.corresponding_capabilities(),
+        Some(vec![DecoderCapabilities::BroadcastSingleTile { level: 3 }])
+    );
+
+    // CPFnum of 2 + 2^16 - 1, beyond Rsiz
+    let data = codestream_with_cpf(8, &[0x0002, 0x0001, 0x0000]);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let cpf = codestream
+        .header()
+        .corresponding_profile_marker_segment()
+        .as_ref()
+        .unwrap();
+    assert_eq!(cpf.cpf_num(), Some(0x1_0001));
+    assert_eq!(cpf.corresponding_capabilities(), None);
+}
+
+#[test]
+fn test_cpf_length() {
+    // No Pcpf, and an odd number of bytes
+    for (length, pcpf) in [(2, &[][..]), (5, &[0x0002, 0x0000][..])] {
+        let data = codestream_with_cpf(length, pcpf);
+        assert_eq!(
+            decode_jpc(&mut Cursor::new(&data)).unwrap_err().to_string(),
+            "malformed marker CPF (0xFF59) at byte offset 45"
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Seek},
+    path::Path,
+};
 
 use jpc::{
-    decode_jpc, CodingBlockStyle, CommentRegistrationValue, MultipleComponentTransformation,
-    ProgressionOrder, QuantizationStyle, TransformationFilter,
+    decode_jpc, decode_jpc_from_jp2, detect_format, CodingBlockStyle, CodingStyleMarkerSegment,
+    CommentRegistrationValue, DecoderCapabilities, Jpeg2000Format, MultipleComponentTransformation,
+    ProgressionOrder, QuantizationDefaultMarkerSegment, QuantizationStyle, StepSize, SubBandType,
+    TransformationFilter,
 };
 
 #[test]
@@ -29,23 +35,31 @@ fn test_blue() {
     assert_eq!(siz.offset(), 4);
     assert_eq!(siz.length(), 47);
     assert_eq!(siz.decoder_capabilities(), 0);
+    assert_eq!(siz.capabilities(), vec![DecoderCapabilities::Baseline]);
     assert_eq!(siz.image_horizontal_offset(), 0);
     assert_eq!(siz.image_vertical_offset(), 0);
     assert_eq!(siz.reference_tile_width(), 128);
     assert_eq!(siz.reference_tile_height(), 64);
     assert_eq!(siz.no_components(), 3);
     assert_eq!(siz.precision(0).unwrap(), 8);
-    assert_eq!(siz.values_are_signed(0).unwrap(), false);
+    assert!(!siz.values_are_signed(0).unwrap());
     assert_eq!(siz.precision(1).unwrap(), 8);
-    assert_eq!(siz.values_are_signed(1).unwrap(), false);
+    assert!(!siz.values_are_signed(1).unwrap());
     assert_eq!(siz.precision(2).unwrap(), 8);
-    assert_eq!(siz.values_are_signed(2).unwrap(), false);
+    assert!(!siz.values_are_signed(2).unwrap());
     assert_eq!(siz.horizontal_separation(0).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(1).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(2).unwrap(), 1);
     assert_eq!(siz.vertical_separation(0).unwrap(), 1);
     assert_eq!(siz.vertical_separation(1).unwrap(), 1);
     assert_eq!(siz.vertical_separation(2).unwrap(), 1);
+    assert_eq!(siz.components().count(), 3);
+    for component in siz.components() {
+        assert_eq!(component.precision(), 8);
+        assert!(!component.values_are_signed());
+        assert_eq!(component.horizontal_separation(), 1);
+        assert_eq!(component.vertical_separation(), 1);
+    }
 
     // CAP
     let maybe_cap = header.extended_capabilities_marker_segment();
@@ -90,9 +104,13 @@ fn test_blue() {
         TransformationFilter::Reversible
     );
 
-    // TODO: fix this
-    // assert_eq!(cod.coding_style_parameters().has_precinct_size(), true);
-    // assert!(cod.coding_style_parameters().precinct_sizes().is_some());
+    assert!(!cod.coding_style_parameters().has_defined_precinct_size());
+    assert!(cod.coding_style_parameters().has_default_precinct_size());
+    let precincts = cod.coding_style_parameters().precinct_sizes().unwrap();
+    assert_eq!(precincts.len(), 6);
+    assert!(precincts
+        .iter()
+        .all(|precinct| precinct.width_exponent() == 15 && precinct.height_exponent() == 15));
 
     // COC
     assert!(header.coding_style_component_segment().is_empty());
@@ -107,6 +125,13 @@ fn test_blue() {
         quant_info.exponents(),
         vec![8, 9, 9, 10, 9, 9, 10, 9, 9, 10, 9, 9, 10, 9, 9, 10]
     );
+    assert_eq!(quant_info.no_step_sizes(), 16);
+    let exponent = |r, subband| qcd.step_size(r, subband).unwrap().exponent;
+    assert_eq!(exponent(0, SubBandType::LL), 8);
+    assert_eq!(exponent(1, SubBandType::HL), 9);
+    assert_eq!(exponent(1, SubBandType::HH), 10);
+    assert_eq!(exponent(5, SubBandType::LH), 9);
+    assert_eq!(qcd.step_size(5, SubBandType::HH).unwrap().mantissa, 0);
 
     // QCC
     assert!(header.quantization_component_segments().is_empty());
@@ -139,3 +164,254 @@ fn test_blue() {
         "Created by OpenJPEG version 2.5.0"
     );
 }
+
+#[test]
+fn test_decode_from_jp2() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("jp2")
+        .join("tests")
+        .join("geojp2.jp2");
+    let file = File::open(path).expect("file should exist");
+    let mut reader = BufReader::new(file);
+    let codestream = decode_jpc_from_jp2(&mut reader).unwrap();
+    assert_eq!(codestream.offset(), 600);
+
+    let siz = codestream.header().image_and_tile_size_marker_segment();
+    assert_eq!(siz.offset(), 604);
+    assert_eq!(siz.reference_grid_width(), 100);
+    assert_eq!(siz.reference_grid_height(), 24);
+    assert_eq!(siz.no_components(), 1);
+    assert!(!codestream.tile_parts().is_empty());
+}
+
+#[test]
+fn test_decode_from_jp2_codestream() {
+    // A raw codestream is not a JP2 file
+    let filename = "blue.j2k";
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(filename);
+    let file = File::open(path).expect("file should exist");
+    let mut reader = BufReader::new(file);
+    assert!(decode_jpc_from_jp2(&mut reader).is_err());
+}
+
+#[test]
+fn test_detect_format() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("jp2")
+        .join("tests")
+        .join("geojp2.jp2");
+    let mut reader = BufReader::new(File::open(path).expect("file should exist"));
+    assert_eq!(detect_format(&mut reader), Jpeg2000Format::Jp2Boxed);
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert!(decode_jpc_from_jp2(&mut reader).is_ok());
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let mut reader = BufReader::new(File::open(path).expect("file should exist"));
+    assert_eq!(detect_format(&mut reader), Jpeg2000Format::RawCodestream);
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert!(decode_jpc(&mut reader).is_ok());
+
+    assert_eq!(
+        detect_format(&mut Cursor::new(b"GIF89a")),
+        Jpeg2000Format::Unknown
+    );
+    assert_eq!(
+        detect_format(&mut Cursor::new([0xFF])),
+        Jpeg2000Format::Unknown
+    );
+}
+
+#[test]
+fn test_defined_precinct_sizes() {
+    // Lcod = 15, Scod with precincts defined, LRCP, one layer, no MCT,
+    // N_L = 2, 64 by 64 code-blocks, 5-3 reversible filter, then PPy and PPx
+    // for each resolution level
+    let cod = CodingStyleMarkerSegment::from_bytes(&[
+        0, 15, 0x01, 0, 0, 1, 0, 2, 4, 4, 0, 1, 0x54, 0x65, 0x76,
+    ])
+    .unwrap();
+    let parameters = cod.coding_style_parameters();
+    assert!(parameters.has_defined_precinct_size());
+    assert!(!parameters.has_default_precinct_size());
+    let precincts: Vec<(u8, u8)> = parameters
+        .precinct_sizes()
+        .unwrap()
+        .iter()
+        .map(|precinct| (precinct.width_exponent(), precinct.height_exponent()))
+        .collect();
+    assert_eq!(precincts, vec![(4, 5), (5, 6), (6, 7)]);
+
+    // Default precincts, with SOP and EPH markers
+    let cod =
+        CodingStyleMarkerSegment::from_bytes(&[0, 12, 0x06, 0, 0, 1, 0, 2, 4, 4, 0, 1]).unwrap();
+    let parameters = cod.coding_style_parameters();
+    assert!(!parameters.has_defined_precinct_size());
+    assert!(parameters.has_default_precinct_size());
+    let precincts = parameters.precinct_sizes().unwrap();
+    assert_eq!(precincts.len(), 3);
+    assert_eq!(precincts[2].width_exponent(), 15);
+    assert_eq!(precincts[2].height_exponent(), 15);
+}
+
+#[test]
+fn test_display_names() {
+    assert_eq!(ProgressionOrder::LRLCPP.to_string(), "LRCP");
+    assert_eq!(ProgressionOrder::CPRLLP.to_string(), "CPRL");
+    assert_eq!(
+        ProgressionOrder::Reserved { value: 5 }.to_string(),
+        "reserved (5)"
+    );
+    assert_eq!(
+        TransformationFilter::Reversible.to_string(),
+        "5-3 reversible"
+    );
+    assert_eq!(
+        TransformationFilter::Irreversible.to_string(),
+        "9-7 irreversible"
+    );
+    assert_eq!(MultipleComponentTransformation::None.to_string(), "none");
+    assert_eq!(
+        MultipleComponentTransformation::Multiple.to_string(),
+        "RCT/ICT"
+    );
+    assert_eq!(
+        QuantizationStyle::ScalarExpounded.to_string(),
+        "scalar expounded"
+    );
+    assert_eq!(QuantizationStyle::Reserved(3).to_string(), "reserved (3)");
+    assert_eq!(
+        CodingBlockStyle::SelectiveArithmeticCodingBypass.to_string(),
+        "selective arithmetic coding bypass"
+    );
+    assert_eq!(
+        CommentRegistrationValue::Latin.to_string(),
+        "ISO/IEC 8859-15 (Latin)"
+    );
+    assert_eq!(
+        CommentRegistrationValue::Reserved { value: [1, 0] }.to_string(),
+        "reserved (256)"
+    );
+}
+
+#[test]
+fn test_from_str() {
+    for order in [
+        ProgressionOrder::LRLCPP,
+        ProgressionOrder::RLLCPP,
+        ProgressionOrder::RLPCLP,
+        ProgressionOrder::PCRLLP,
+        ProgressionOrder::CPRLLP,
+    ] {
+        assert_eq!(
+            order.to_string().parse::<ProgressionOrder>().unwrap(),
+            order
+        );
+    }
+    assert_eq!(
+        "rpcl".parse::<ProgressionOrder>().unwrap(),
+        ProgressionOrder::RLPCLP
+    );
+    assert_eq!(
+        "LRC".parse::<ProgressionOrder>().unwrap_err().to_string(),
+        "invalid value for progression order"
+    );
+
+    for filter in [
+        TransformationFilter::Irreversible,
+        TransformationFilter::Reversible,
+    ] {
+        assert_eq!(
+            filter.to_string().parse::<TransformationFilter>().unwrap(),
+            filter
+        );
+    }
+    assert_eq!(
+        "5-3".parse::<TransformationFilter>().unwrap(),
+        TransformationFilter::Reversible
+    );
+    assert!("reserved (2)".parse::<TransformationFilter>().is_err());
+}
+
+#[test]
+fn test_step_sizes() {
+    // Lqcd = 11, scalar expounded with 2 guard bits, for one decomposition
+    // level
+    let qcd = QuantizationDefaultMarkerSegment::from_bytes(&[
+        0, 11, 0x42, 0x40, 0x01, 0x48, 0x02, 0x48, 0x03, 0x50, 0x04,
+    ])
+    .unwrap();
+    assert_eq!(
+        qcd.step_size(0, SubBandType::LL).unwrap(),
+        StepSize {
+            exponent: 8,
+            mantissa: 1
+        }
+    );
+    assert_eq!(
+        qcd.step_size(1, SubBandType::LH).unwrap(),
+        StepSize {
+            exponent: 9,
+            mantissa: 3
+        }
+    );
+    assert_eq!(
+        qcd.step_size(1, SubBandType::HH).unwrap(),
+        StepSize {
+            exponent: 10,
+            mantissa: 4
+        }
+    );
+    assert_eq!(
+        qcd.step_size(2, SubBandType::HL).unwrap_err().to_string(),
+        "invalid value for resolution_level"
+    );
+    assert_eq!(
+        qcd.step_size(1, SubBandType::LL).unwrap_err().to_string(),
+        "invalid value for subband"
+    );
+    assert!(qcd.step_size(0, SubBandType::HL).is_err());
+
+    // Lqcd = 5, scalar derived from ε0 = 8, μ0 = 1024
+    let qcd = QuantizationDefaultMarkerSegment::from_bytes(&[0, 5, 0x41, 0x44, 0x00]).unwrap();
+    let ll = qcd.step_size(0, SubBandType::LL).unwrap();
+    assert_eq!(
+        ll,
+        StepSize {
+            exponent: 8,
+            mantissa: 1024
+        }
+    );
+    assert_eq!(ll.value(8), 1.5);
+    assert_eq!(ll.value(10), 6.0);
+    assert_eq!(qcd.step_size(1, SubBandType::HL).unwrap().exponent, 8);
+    assert_eq!(qcd.step_size(3, SubBandType::HH).unwrap().exponent, 6);
+    assert_eq!(qcd.step_size(3, SubBandType::HH).unwrap().mantissa, 1024);
+    assert!(qcd.step_size(10, SubBandType::HH).is_err());
+}
+
+#[test]
+fn test_step_size_count_mismatch() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("blue.j2k");
+    let mut data = std::fs::read(path).expect("file should exist");
+    // Four decomposition levels in the COD, for the QCD with the 16 step
+    // sizes of five
+    let cod = data
+        .windows(2)
+        .position(|marker| marker == [0xFF, 0x52])
+        .unwrap();
+    assert_eq!(data[cod + 9], 5);
+    data[cod + 9] = 4;
+    let error = decode_jpc(&mut std::io::Cursor::new(&data)).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "marker QCD (0xFF5C) error \"component 0 has 16 step sizes, expected 13 for 4 decomposition levels\""
+    );
+}
@@ -0,0 +1,153 @@
+use std::io::Cursor;
+
+use jpc::{
+    decode_jpc, CodestreamHeaderBuilder, CollectionTransformType, TransformArrayType,
+    TransformElementType,
+};
+
+// Main header, with the given marker segments following the Part 1 marker
+// segments, and a single tile-part with an empty MCO marker segment.
+fn codestream(segments: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(16, 16, 3, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    buffer.extend_from_slice(segments);
+    // SOT, Lsot = 10, Isot = 0, Psot = 19, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x13, 0x00, 0x01]);
+    // MCO, Lmco = 3, Nmco = 0
+    buffer.extend_from_slice(&[0xFF, 0x77, 0x00, 0x03, 0x00]);
+    // SOD, EOC
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_part2_markers_decoded() {
+    let mut segments = Vec::new();
+    // MCT, Lmct = 8, Zmct = 0, Imct = 0x0101, Ymct = 0 and no SPmct values
+    segments.extend_from_slice(&[0xFF, 0x74, 0x00, 0x08, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00]);
+    // MCC, Lmcc = 9, Zmcc = 0, Imcc = 0, Ymcc = 0 and Qmcc = 0, no component
+    // collections
+    segments.extend_from_slice(&[0xFF, 0x75, 0x00, 0x09, 0x00, 0x00, 0x00]);
+    segments.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    // MCO, Lmco = 4, Nmco = 1, Imco = 0
+    segments.extend_from_slice(&[0xFF, 0x77, 0x00, 0x04, 0x01, 0x00]);
+    let data = codestream(&segments);
+    let main_header_length = data.len() - segments.len() - 21;
+
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let header = codestream.header();
+    assert!(header.part2_markers_present());
+    let offset = main_header_length as u64;
+    let arrays = header.transform_array_segments();
+    assert_eq!(arrays.len(), 1);
+    assert_eq!((arrays[0].offset(), arrays[0].length()), (offset, 8));
+    assert_eq!(arrays[0].index(), 1);
+    assert_eq!(arrays[0].array_type(), TransformArrayType::Decorrelation);
+    assert_eq!(arrays[0].element_type(), TransformElementType::Int16);
+    assert!(arrays[0].values().is_empty());
+    let collections = header.component_collection_segments();
+    assert_eq!(collections.len(), 1);
+    assert_eq!(
+        (collections[0].offset(), collections[0].length()),
+        (offset + 10, 9)
+    );
+    assert!(collections[0].collections().is_empty());
+    let order = header.transform_order_segment().unwrap();
+    assert_eq!((order.offset(), order.length()), (offset + 21, 4));
+    assert_eq!(order.stages(), &[0]);
+    let transform = header.multi_component_transform().unwrap();
+    assert_eq!(transform.stages().len(), 1);
+    assert!(transform.stages()[0].collections().is_empty());
+
+    let tile_parts = codestream.tile_parts();
+    assert_eq!(tile_parts.len(), 1);
+    let order = tile_parts[0].transform_order_segment().unwrap();
+    assert_eq!(order.length(), 3);
+    assert!(order.stages().is_empty());
+}
+
+// MCT marker segment of 16-bit values, with the array type and index in Imct
+fn mct(imct: u16, values: &[i16]) -> Vec<u8> {
+    let mut segment = vec![0xFF, 0x74];
+    segment.extend_from_slice(&(8 + 2 * values.len() as u16).to_be_bytes());
+    segment.extend_from_slice(&[0x00, 0x00]);
+    segment.extend_from_slice(&imct.to_be_bytes());
+    segment.extend_from_slice(&[0x00, 0x00]);
+    for value in values {
+        segment.extend_from_slice(&value.to_be_bytes());
+    }
+    segment
+}
+
+#[test]
+fn test_multi_component_transform() {
+    let mut segments = Vec::new();
+    // Decorrelation matrix 1 and offsets 2
+    segments.extend(mct(0x0101, &[1, 0, 0, 0, 1, 1, 0, 0, 1]));
+    segments.extend(mct(0x0202, &[-128, 0, 0]));
+    // MCC, Lmcc = 23, Zmcc = 0, Imcc = 4, Ymcc = 0 and Qmcc = 1, Xmcc = 1,
+    // Nmcc = 3 and Cmcc = 0 1 2, Mmcc = 3 and Wmcc = 0 1 2, Tmcc = 0x000201
+    segments.extend_from_slice(&[0xFF, 0x75, 0x00, 0x17, 0x00, 0x00, 0x04, 0x00, 0x00]);
+    segments.extend_from_slice(&[0x00, 0x01, 0x01, 0x00, 0x03, 0x00, 0x01, 0x02]);
+    segments.extend_from_slice(&[0x00, 0x03, 0x00, 0x01, 0x02, 0x00, 0x02, 0x01]);
+    // MCO, Lmco = 4, Nmco = 1, Imco = 4
+    segments.extend_from_slice(&[0xFF, 0x77, 0x00, 0x04, 0x01, 0x04]);
+    let data = codestream(&segments);
+
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let transform = codestream.header().multi_component_transform().unwrap();
+    let stages = transform.stages();
+    assert_eq!(stages.len(), 1);
+    assert_eq!(stages[0].index(), 4);
+    let collection = &stages[0].collections()[0];
+    assert_eq!(
+        collection.transform_type(),
+        CollectionTransformType::Decorrelation
+    );
+    assert_eq!(collection.input_components(), &[0, 1, 2]);
+    assert_eq!(collection.output_components(), &[0, 1, 2]);
+    assert!(!collection.is_reversible());
+    let matrix: Vec<&[f64]> = vec![&[1.0, 0.0, 0.0], &[0.0, 1.0, 1.0], &[0.0, 0.0, 1.0]];
+    assert_eq!(collection.decorrelation_matrix(), Some(matrix));
+    let offsets = collection.offsets().unwrap();
+    assert_eq!(offsets.array_type(), TransformArrayType::Offset);
+    assert_eq!(offsets.values(), &[-128.0, 0.0, 0.0]);
+}
+
+#[test]
+fn test_multi_component_transform_missing_array() {
+    // MCC referring to matrix 1, which is not present
+    let mut segments = Vec::new();
+    segments.extend_from_slice(&[0xFF, 0x75, 0x00, 0x17, 0x00, 0x00, 0x04, 0x00, 0x00]);
+    segments.extend_from_slice(&[0x00, 0x01, 0x01, 0x00, 0x03, 0x00, 0x01, 0x02]);
+    segments.extend_from_slice(&[0x00, 0x03, 0x00, 0x01, 0x02, 0x00, 0x00, 0x01]);
+    segments.extend_from_slice(&[0xFF, 0x77, 0x00, 0x04, 0x01, 0x04]);
+    let data = codestream(&segments);
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("malformed marker MCC (0xFF75)"));
+}
+
+#[test]
+fn test_part1_codestream() {
+    let data = codestream(&[]);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert!(!codestream.header().part2_markers_present());
+    assert!(codestream.header().multi_component_transform().is_none());
+}
+
+#[test]
+fn test_part2_marker_malformed() {
+    // MCT, Lmct = 1
+    let data = codestream(&[0xFF, 0x74, 0x00, 0x01]);
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("malformed marker MCT (0xFF74)"));
+}
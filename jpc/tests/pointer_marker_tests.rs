@@ -1,8 +1,10 @@
 use std::{fs::File, io::BufReader, path::Path};
 
 use jpc::{
-    decode_jpc, CodingBlockStyle, CodingStyleDefault, CommentRegistrationValue,
-    MultipleComponentTransformation, ProgressionOrder, QuantizationStyle, TransformationFilter,
+    decode_jpc, CodingBlockStyle, CodingStyleDefault, CodingStyleMarkerSegment,
+    CommentMarkerSegment, CommentRegistrationValue, ImageAndTileSizeMarkerSegment,
+    MultipleComponentTransformation, ProgressionOrder, QuantizationDefaultMarkerSegment,
+    QuantizationStyle, TlmMismatch, TransformationFilter,
 };
 
 fn init_logger() {
@@ -41,13 +43,14 @@ fn test_tlm() {
     assert_eq!(siz.image_vertical_offset(), 0);
     assert_eq!(siz.reference_tile_width(), 2);
     assert_eq!(siz.reference_tile_height(), 1);
+    assert_eq!(siz.num_tiles(), 1);
     assert_eq!(siz.no_components(), 3);
     assert_eq!(siz.precision(0).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(0).unwrap(), false);
+    assert!(!siz.values_are_signed(0).unwrap());
     assert_eq!(siz.precision(1).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(1).unwrap(), false);
+    assert!(!siz.values_are_signed(1).unwrap());
     assert_eq!(siz.precision(2).unwrap(), 16);
-    assert_eq!(siz.values_are_signed(2).unwrap(), false);
+    assert!(!siz.values_are_signed(2).unwrap());
     assert_eq!(siz.horizontal_separation(0).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(1).unwrap(), 1);
     assert_eq!(siz.horizontal_separation(2).unwrap(), 1);
@@ -106,14 +109,8 @@ fn test_tlm() {
         TransformationFilter::Reversible
     );
 
-    assert_eq!(
-        cod.coding_style_parameters().has_defined_precinct_size(),
-        false
-    );
-    assert_eq!(
-        cod.coding_style_parameters().has_default_precinct_size(),
-        true
-    );
+    assert!(!cod.coding_style_parameters().has_defined_precinct_size());
+    assert!(cod.coding_style_parameters().has_default_precinct_size());
     assert!(cod.coding_style_parameters().precinct_sizes().is_some());
     let precincts = cod.coding_style_parameters().precinct_sizes().unwrap();
     assert_eq!(precincts[0].width_exponent(), 15);
@@ -171,3 +168,230 @@ fn test_tlm() {
         "Created by OpenJPEG version 2.5.0"
     );
 }
+
+#[test]
+fn test_marker_segments_from_bytes() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let data = std::fs::read(path).expect("file should exist");
+    let codestream = decode_jpc(&mut std::io::Cursor::new(&data)).unwrap();
+    // Bytes of the first segment with the marker, from the length field
+    let segment_bytes = |name: &str| {
+        let marker = codestream
+            .marker_map()
+            .iter()
+            .find(|marker| marker.name() == name)
+            .unwrap();
+        let start = marker.offset() as usize + 2;
+        &data[start..start + marker.length().unwrap() as usize]
+    };
+    let header = codestream.header();
+
+    let siz = ImageAndTileSizeMarkerSegment::from_bytes(segment_bytes("SIZ")).unwrap();
+    let expected = header.image_and_tile_size_marker_segment();
+    assert_eq!(siz.offset(), 2);
+    assert_eq!(siz.length(), expected.length());
+    assert_eq!(siz.reference_grid_width(), expected.reference_grid_width());
+    assert_eq!(
+        siz.reference_tile_height(),
+        expected.reference_tile_height()
+    );
+    assert_eq!(siz.no_components(), 3);
+    assert_eq!(siz.precision(2).unwrap(), expected.precision(2).unwrap());
+
+    let cod = CodingStyleMarkerSegment::from_bytes(segment_bytes("COD")).unwrap();
+    let expected = header.coding_style_marker_segment();
+    assert_eq!(cod.length(), expected.length());
+    assert_eq!(cod.progression_order(), expected.progression_order());
+    assert_eq!(cod.no_layers(), expected.no_layers());
+    assert_eq!(
+        cod.coding_style_parameters().code_block_width(),
+        expected.coding_style_parameters().code_block_width()
+    );
+
+    let qcd = QuantizationDefaultMarkerSegment::from_bytes(segment_bytes("QCD")).unwrap();
+    let expected = header.quantization_default_marker_segment();
+    assert_eq!(qcd.length(), expected.length());
+    assert_eq!(
+        qcd.quantization_style_u8(),
+        expected.quantization_style_u8()
+    );
+    assert_eq!(qcd.quantization_values(), expected.quantization_values());
+
+    let com = CommentMarkerSegment::from_bytes(segment_bytes("COM")).unwrap();
+    assert_eq!(com.registration_value(), CommentRegistrationValue::Latin);
+    assert_eq!(
+        com.comment_utf8().unwrap(),
+        "Created by OpenJPEG version 2.5.0"
+    );
+
+    // The bytes shall be exactly the length of the segment
+    let siz = segment_bytes("SIZ");
+    let error = ImageAndTileSizeMarkerSegment::from_bytes(&siz[..siz.len() - 1]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "malformed marker SIZ (0xFF51) at byte offset 0"
+    );
+    let mut com = segment_bytes("COM").to_vec();
+    com.push(0);
+    assert!(CommentMarkerSegment::from_bytes(&com).is_err());
+    // Lqcd covering less than the parameters read
+    assert!(QuantizationDefaultMarkerSegment::from_bytes(&[0, 3, 0x40, 0]).is_err());
+    assert!(CodingStyleMarkerSegment::from_bytes(&[]).is_err());
+}
+
+// Decode tlm.j2k with its TLM marker segment (Ltlm = 9) replaced by `tlm`.
+fn decode_with_tlm(tlm: &[u8]) -> Result<jpc::ContiguousCodestream, Box<dyn std::error::Error>> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let mut data = std::fs::read(path).expect("file should exist");
+    let tlm_offset = 0x47;
+    assert_eq!(data[tlm_offset..tlm_offset + 4], [0xFF, 0x55, 0x00, 0x09]);
+    data.splice(tlm_offset..tlm_offset + 11, tlm.iter().cloned());
+    decode_jpc(&mut std::io::Cursor::new(data))
+}
+
+fn tile_part_lengths(codestream: &jpc::ContiguousCodestream) -> Vec<(Option<u16>, u32)> {
+    let header = codestream.header();
+    assert_eq!(header.tile_part_lengths_segments().len(), 1);
+    header.tile_part_lengths_segments()[0]
+        .tile_part_lengths()
+        .iter()
+        .map(|tile_part_length| {
+            (
+                *tile_part_length.tile_index(),
+                tile_part_length.tile_length(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_tlm_without_tile_indices() {
+    // ST = 0, SP = 0
+    let codestream = decode_with_tlm(&[0xFF, 0x55, 0x00, 0x06, 0x00, 0x00, 0x00, 0x41]).unwrap();
+    assert_eq!(tile_part_lengths(&codestream), vec![(None, 65)]);
+
+    // ST = 0, SP = 1
+    let codestream =
+        decode_with_tlm(&[0xFF, 0x55, 0x00, 0x08, 0x00, 0x40, 0x00, 0x00, 0x00, 0x41]).unwrap();
+    assert_eq!(tile_part_lengths(&codestream), vec![(None, 65)]);
+}
+
+#[test]
+fn test_tlm_8_bit_tile_indices() {
+    // ST = 1, SP = 0
+    let codestream =
+        decode_with_tlm(&[0xFF, 0x55, 0x00, 0x07, 0x00, 0x10, 0x00, 0x00, 0x41]).unwrap();
+    assert_eq!(tile_part_lengths(&codestream), vec![(Some(0), 65)]);
+}
+
+#[test]
+fn test_tlm_16_bit_tile_indices() {
+    // ST = 2, SP = 0
+    let codestream =
+        decode_with_tlm(&[0xFF, 0x55, 0x00, 0x08, 0x00, 0x20, 0x00, 0x00, 0x00, 0x41]).unwrap();
+    assert_eq!(tile_part_lengths(&codestream), vec![(Some(0), 65)]);
+}
+
+#[test]
+fn test_tlm_32_bit_lengths() {
+    // ST = 2, SP = 1, with two entries of which the second is not in the codestream
+    let codestream = decode_with_tlm(&[
+        0xFF, 0x55, 0x00, 0x10, 0x00, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x01, 0x02, 0x00,
+        0x01, 0x00, 0x00,
+    ])
+    .unwrap();
+    assert_eq!(
+        tile_part_lengths(&codestream),
+        vec![(Some(0), 65), (Some(0x0102), 0x0001_0000)]
+    );
+}
+
+#[test]
+fn test_tlm_reserved_tile_index_size() {
+    // ST = 3
+    let result = decode_with_tlm(&[0xFF, 0x55, 0x00, 0x06, 0x00, 0x30, 0x00, 0x41]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "malformed marker TLM (0xFF55) at byte offset 71"
+    );
+}
+
+#[test]
+fn test_tlm_partial_entry() {
+    // ST = 1, SP = 1, with Ltlm one byte short of a complete entry
+    let result = decode_with_tlm(&[0xFF, 0x55, 0x00, 0x08, 0x00, 0x50, 0x00, 0x00, 0x00, 0x41]);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "malformed marker TLM (0xFF55) at byte offset 71"
+    );
+}
+
+#[test]
+fn test_validate_tlm() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("tlm.j2k");
+    let file = File::open(path).expect("file should exist");
+    let codestream = decode_jpc(&mut BufReader::new(file)).unwrap();
+    assert_eq!(codestream.validate_tlm(), Ok(()));
+
+    // Without tile indices
+    let codestream = decode_with_tlm(&[0xFF, 0x55, 0x00, 0x06, 0x00, 0x00, 0x00, 0x41]).unwrap();
+    assert_eq!(codestream.validate_tlm(), Ok(()));
+}
+
+#[test]
+fn test_validate_tlm_length_mismatch() {
+    let codestream =
+        decode_with_tlm(&[0xFF, 0x55, 0x00, 0x07, 0x00, 0x10, 0x00, 0x00, 0x40]).unwrap();
+    let mismatch = codestream.validate_tlm().unwrap_err();
+    assert_eq!(
+        mismatch,
+        TlmMismatch::Length {
+            tile_part: 0,
+            tile_index: 0,
+            tlm_length: 64,
+            psot: 65
+        }
+    );
+    assert_eq!(
+        mismatch.to_string(),
+        "tile-part 0 of tile 0 has TLM length 64 but Psot 65"
+    );
+}
+
+#[test]
+fn test_validate_tlm_tile_index_mismatch() {
+    let codestream =
+        decode_with_tlm(&[0xFF, 0x55, 0x00, 0x07, 0x00, 0x10, 0x01, 0x00, 0x41]).unwrap();
+    assert_eq!(
+        codestream.validate_tlm(),
+        Err(TlmMismatch::TileIndex {
+            tile_part: 0,
+            tlm_tile_index: 1,
+            sot_tile_index: 0
+        })
+    );
+}
+
+#[test]
+fn test_validate_tlm_count_mismatch() {
+    let codestream = decode_with_tlm(&[
+        0xFF, 0x55, 0x00, 0x10, 0x00, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x01, 0x02, 0x00,
+        0x01, 0x00, 0x00,
+    ])
+    .unwrap();
+    let mismatch = codestream.validate_tlm().unwrap_err();
+    assert_eq!(
+        mismatch,
+        TlmMismatch::Count {
+            tlm_entries: 2,
+            tile_parts: 1
+        }
+    );
+    assert_eq!(mismatch.to_string(), "TLM has 2 entries for 1 tile-parts");
+}
@@ -0,0 +1,653 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use jpc::{codestream_length, decode_jpc, CodestreamHeaderBuilder, DecodeWarning};
+
+fn sample(filename: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(filename);
+    std::fs::read(path).expect("file should exist")
+}
+
+// Two tile-parts of tile 0, the second with a PLT marker segment for three
+// packets of 3, 200 and 1 bytes. The TLM marker segment is optional.
+fn codestream_with_packets(with_tlm: bool) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    if with_tlm {
+        // TLM, Ltlm = 14, Ztlm = 0, ST = 1, SP = 1
+        buffer.extend_from_slice(&[0xFF, 0x55, 0x00, 0x0E, 0x00, 0x50]);
+        buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x10]);
+        buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0xE3]);
+    }
+
+    // SOT, Lsot = 10, Isot = 0, Psot = 16, TPsot = 0, TNsot = 2
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x10, 0x00, 0x02]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xAA, 0xBB]);
+
+    // SOT, Lsot = 10, Isot = 0, Psot = 227, TPsot = 1, TNsot = 2
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0xE3, 0x01, 0x02]);
+    // PLT, Lplt = 7, Zplt = 0, Iplt = 3, 200, 1
+    buffer.extend_from_slice(&[0xFF, 0x58, 0x00, 0x07, 0x00, 0x03, 0x81, 0x48, 0x01]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93]);
+    buffer.extend_from_slice(&[0x01; 3]);
+    buffer.extend_from_slice(&[0x02; 200]);
+    buffer.extend_from_slice(&[0x03; 1]);
+
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_tile_part_bytes_with_tlm() {
+    let data = sample("tlm.j2k");
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let sot_offset = codestream.tile_parts()[0].start_of_tile_segment().offset() as usize;
+
+    let bytes = codestream.tile_part_bytes(&mut reader, 0, 0).unwrap();
+    assert_eq!(bytes.len(), 65);
+    assert_eq!(bytes, &data[sot_offset..sot_offset + 65]);
+}
+
+#[test]
+fn test_tile_part_bytes_without_tlm() {
+    let data = sample("blue.j2k");
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    assert!(codestream.header().tile_part_lengths_segments().is_empty());
+    let sot = codestream.tile_parts()[0].start_of_tile_segment();
+    let range = sot.offset() as usize..(sot.offset() + sot.tile_length() as u64) as usize;
+
+    let bytes = codestream.tile_part_bytes(&mut reader, 0, 0).unwrap();
+    assert_eq!(bytes, &data[range]);
+
+    let result = codestream.tile_part_bytes(&mut reader, 1, 0);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "invalid value for tile_part"
+    );
+}
+
+#[test]
+fn test_tile_part_bytes_multiple_tile_parts() {
+    for with_tlm in [true, false] {
+        let data = codestream_with_packets(with_tlm);
+        let mut reader = Cursor::new(&data);
+        let codestream = decode_jpc(&mut reader).unwrap();
+        assert_eq!(codestream.tile_parts().len(), 2);
+        assert_eq!(codestream.validate_tlm(), Ok(()));
+
+        let bytes = codestream.tile_part_bytes(&mut reader, 0, 0).unwrap();
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(bytes[14..], [0xAA, 0xBB]);
+
+        let bytes = codestream.tile_part_bytes(&mut reader, 0, 1).unwrap();
+        assert_eq!(bytes.len(), 227);
+        assert_eq!(bytes[..2], [0xFF, 0x90]);
+        assert_eq!(bytes[226], 0x03);
+    }
+}
+
+#[test]
+fn test_packet_bytes() {
+    let data = codestream_with_packets(false);
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+
+    let tile_part = &codestream.tile_parts()[1];
+    assert_eq!(tile_part.packet_length_segments().len(), 1);
+    assert_eq!(
+        tile_part.packet_length_segments()[0].packet_lengths(),
+        [3, 200, 1]
+    );
+
+    assert_eq!(
+        codestream.packet_bytes(&mut reader, 0, 1, 0).unwrap(),
+        vec![0x01; 3]
+    );
+    assert_eq!(
+        codestream.packet_bytes(&mut reader, 0, 1, 1).unwrap(),
+        vec![0x02; 200]
+    );
+    assert_eq!(
+        codestream.packet_bytes(&mut reader, 0, 1, 2).unwrap(),
+        vec![0x03; 1]
+    );
+
+    let result = codestream.packet_bytes(&mut reader, 0, 1, 3);
+    assert_eq!(result.unwrap_err().to_string(), "invalid value for packet");
+
+    // The first tile-part has no PLT marker segment
+    let result = codestream.packet_bytes(&mut reader, 0, 0, 0);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "missing marker PLT (0xFF58)"
+    );
+}
+
+#[test]
+fn test_packet_lengths_unterminated() {
+    // The last Iplt byte continues the length, even with no bits set in it
+    let mut data = codestream_with_packets(false);
+    let plt_offset = data
+        .windows(2)
+        .position(|window| window == [0xFF, 0x58])
+        .unwrap();
+    data[plt_offset + 8] = 0x80;
+    let result = decode_jpc(&mut Cursor::new(&data));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "malformed marker PLT (0xFF58) at byte offset {}",
+            plt_offset
+        )
+    );
+}
+
+// Replace Psot of the tile-part whose SOT marker is at the given index
+fn set_psot(data: &mut [u8], tile_part: usize, psot: u32) {
+    let sot_offset = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == [0xFF, 0x90, 0x00, 0x0A])
+        .nth(tile_part)
+        .unwrap()
+        .0;
+    data[sot_offset + 6..sot_offset + 10].copy_from_slice(&psot.to_be_bytes());
+}
+
+#[test]
+fn test_tile_part_length_matches() {
+    let data = codestream_with_packets(false);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert!(codestream.report().warnings().is_empty());
+}
+
+#[test]
+fn test_tile_part_length_too_large() {
+    let mut data = codestream_with_packets(false);
+    set_psot(&mut data, 1, 300);
+
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(codestream.tile_parts().len(), 2);
+    assert_eq!(
+        codestream.report().warnings(),
+        &[DecodeWarning::TilePartLengthMismatch {
+            tile_part: 1,
+            tile_index: 0,
+            expected: 300,
+            actual: 227
+        }]
+    );
+    assert_eq!(
+        codestream.report().warnings()[0].to_string(),
+        "tile-part 1 of tile 0 has Psot 300 but 227 bytes"
+    );
+}
+
+#[test]
+fn test_tile_part_length_too_small() {
+    let mut data = codestream_with_packets(false);
+    set_psot(&mut data, 0, 15);
+
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(codestream.tile_parts().len(), 2);
+    assert_eq!(
+        codestream.report().warnings(),
+        &[DecodeWarning::TilePartLengthMismatch {
+            tile_part: 0,
+            tile_index: 0,
+            expected: 15,
+            actual: 16
+        }]
+    );
+    assert_eq!(
+        codestream.tile_parts()[1]
+            .start_of_tile_segment()
+            .tile_part_index(),
+        1
+    );
+}
+
+// Two tile-parts of tile 0 with their packet headers in two PPM marker
+// segments, the second continuing the Ippm parameters of the second tile-part.
+// The marker segments are out of Zppm order.
+fn codestream_with_ppm(second_nppm: u8) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    // PPM, Lppm = 5, Zppm = 1, Ippm continued
+    buffer.extend_from_slice(&[0xFF, 0x60, 0x00, 0x05, 0x01, 0x23, 0x24]);
+    // PPM, Lppm = 16, Zppm = 0, Nppm = 3, Ippm, Nppm, Ippm
+    buffer.extend_from_slice(&[0xFF, 0x60, 0x00, 0x10, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x03, 0x11, 0x12, 0x13]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, second_nppm, 0x21, 0x22]);
+
+    for tile_part in 0..2 {
+        // SOT, Lsot = 10, Isot = 0, Psot = 16, TNsot = 2
+        buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+        buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x10, tile_part, 0x02]);
+        // SOD
+        buffer.extend_from_slice(&[0xFF, 0x93, 0xAA, 0xBB]);
+    }
+
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_packed_packet_headers_ppm() {
+    let data = codestream_with_ppm(4);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(
+        codestream.header().packed_packet_headers_segments().len(),
+        2
+    );
+
+    assert_eq!(
+        codestream.packed_packet_headers(0, 0).unwrap(),
+        Some(vec![0x11, 0x12, 0x13])
+    );
+    assert_eq!(
+        codestream.packed_packet_headers(0, 1).unwrap(),
+        Some(vec![0x21, 0x22, 0x23, 0x24])
+    );
+    assert!(codestream.packed_packet_headers(1, 0).is_err());
+}
+
+#[test]
+fn test_packed_packet_headers_ppm_truncated() {
+    // Nppm of the second tile-part is longer than the Ippm parameters
+    let data = codestream_with_ppm(5);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+
+    assert!(codestream.packed_packet_headers(0, 0).unwrap().is_some());
+    assert_eq!(
+        codestream
+            .packed_packet_headers(0, 1)
+            .unwrap_err()
+            .to_string(),
+        "malformed marker PPM (0xFF60) at byte offset 87"
+    );
+}
+
+#[test]
+fn test_packed_packet_headers_ppt() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    // SOT, Lsot = 10, Isot = 0, Psot = 29, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x1D, 0x00, 0x01]);
+    // PPT, Lppt = 4, Zppt = 1, Ippt
+    buffer.extend_from_slice(&[0xFF, 0x61, 0x00, 0x04, 0x01, 0x33]);
+    // PPT, Lppt = 5, Zppt = 0, Ippt
+    buffer.extend_from_slice(&[0xFF, 0x61, 0x00, 0x05, 0x00, 0x31, 0x32]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xAA, 0xBB]);
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    assert_eq!(
+        codestream.tile_parts()[0]
+            .packed_packet_headers_segments()
+            .len(),
+        2
+    );
+    assert_eq!(
+        codestream.packed_packet_headers(0, 0).unwrap(),
+        Some(vec![0x31, 0x32, 0x33])
+    );
+}
+
+#[test]
+fn test_packed_packet_headers_in_packets() {
+    let data = codestream_with_packets(false);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(codestream.packed_packet_headers(0, 1).unwrap(), None);
+}
+
+// Two tiles, with the tile-parts of tile 0 interleaved with the single
+// tile-part of tile 1, which does not give the number of tile-parts.
+fn codestream_with_interleaved_tile_parts() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .tile_size(4, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    // Isot, TPsot, TNsot and the two bytes of tile-part data
+    for (tile, part, parts, data) in [
+        (0, 0, 2, [0x01, 0x02]),
+        (1, 0, 0, [0x11, 0x12]),
+        (0, 1, 2, [0x03, 0x04]),
+    ]
+    .iter()
+    {
+        // SOT, Lsot = 10, Psot = 16
+        buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, *tile]);
+        buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x10, *part, *parts]);
+        // SOD
+        buffer.extend_from_slice(&[0xFF, 0x93]);
+        buffer.extend_from_slice(data);
+    }
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_tiles() {
+    let data = codestream_with_interleaved_tile_parts();
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let tiles = codestream.tiles();
+    assert_eq!(tiles.len(), 2);
+
+    assert_eq!(tiles[0].index(), 0);
+    assert_eq!(tiles[0].no_tile_parts(), 2);
+    assert!(tiles[0].is_complete());
+    let parts: Vec<u8> = tiles[0]
+        .tile_parts()
+        .iter()
+        .map(|tile_part| tile_part.start_of_tile_segment().tile_part_index())
+        .collect();
+    assert_eq!(parts, vec![0, 1]);
+
+    // TNsot of 0, so the number of tile-parts is found by scanning
+    assert_eq!(tiles[1].index(), 1);
+    assert_eq!(tiles[1].no_tile_parts(), 1);
+    assert!(tiles[1].is_complete());
+    assert!(codestream.tile(2).is_none());
+}
+
+#[test]
+fn test_tile_data() {
+    let data = codestream_with_interleaved_tile_parts();
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    assert_eq!(
+        codestream.tile_data(&mut reader, 0).unwrap(),
+        vec![0x01, 0x02, 0x03, 0x04]
+    );
+    assert_eq!(
+        codestream.tile_data(&mut reader, 1).unwrap(),
+        vec![0x11, 0x12]
+    );
+    assert_eq!(
+        codestream
+            .tile_data(&mut reader, 2)
+            .unwrap_err()
+            .to_string(),
+        "invalid value for tile"
+    );
+}
+
+#[test]
+fn test_tile_incomplete() {
+    // The second tile-part of tile 0 is missing
+    let mut data = codestream_with_packets(false);
+    let second_sot = data.len() - 2 - 227;
+    data.truncate(second_sot);
+    data.extend_from_slice(&[0xFF, 0xD9]);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let tile = codestream.tile(0).unwrap();
+    assert_eq!(tile.tile_parts().len(), 1);
+    assert_eq!(tile.no_tile_parts(), 2);
+    assert!(!tile.is_complete());
+}
+
+#[test]
+fn test_marker_map() {
+    let data = sample("tlm.j2k");
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let markers: Vec<(u16, &str, u64, Option<u16>)> = codestream
+        .marker_map()
+        .iter()
+        .map(|marker| {
+            (
+                marker.marker(),
+                marker.name(),
+                marker.offset(),
+                marker.length(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        markers,
+        vec![
+            (0xFF4F, "SOC", 0, None),
+            (0xFF51, "SIZ", 2, Some(47)),
+            (0xFF52, "COD", 51, Some(12)),
+            (0xFF5C, "QCD", 65, Some(4)),
+            (0xFF55, "TLM", 71, Some(9)),
+            (0xFF64, "COM", 82, Some(37)),
+            (0xFF90, "SOT", 121, Some(10)),
+            (0xFF93, "SOD", 133, None),
+            (0xFFD9, "EOC", 186, None),
+        ]
+    );
+}
+
+#[test]
+fn test_marker_map_unknown() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    let reserved_offset = buffer.len() as u64;
+    // Reserved marker, without a marker segment
+    buffer.extend_from_slice(&[0xFF, 0x30]);
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93]);
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let markers = codestream.marker_map();
+    let reserved = &markers[markers.len() - 4];
+    assert_eq!(reserved.marker(), 0xFF30);
+    assert_eq!(reserved.name(), "unknown");
+    assert_eq!(reserved.offset(), reserved_offset);
+    assert_eq!(reserved.length(), None);
+    assert_eq!(markers.last().unwrap().name(), "EOC");
+}
+
+// One tile of two 16x16 components with two decomposition levels in the main
+// header. The tile-part header has a COD with three layers, and a COC for
+// component 1 with one decomposition level and 2x2 then 4x4 precincts. The
+// PLT marker segment lists a one byte packet for each of the 105 packets.
+fn codestream_with_tile_coding_style() -> Vec<u8> {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(16, 16, 2, 8)
+        .no_decomposition_levels(2)
+        .write_to(&mut buffer)
+        .unwrap();
+
+    let mut tile_part = Vec::new();
+    // COD, Lcod = 12, Scod = 0, LRLCPP, 3 layers, no MCT, NL = 2, 64x64
+    // code-blocks, reversible
+    tile_part.extend_from_slice(&[0xFF, 0x52, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x03]);
+    tile_part.extend_from_slice(&[0x00, 0x02, 0x04, 0x04, 0x00, 0x01]);
+    // COC, Lcoc = 11, Ccoc = 1, Scoc = 1, NL = 1, PPx = PPy = 1 then 2
+    tile_part.extend_from_slice(&[0xFF, 0x53, 0x00, 0x0B, 0x01, 0x01, 0x01]);
+    tile_part.extend_from_slice(&[0x04, 0x04, 0x00, 0x01, 0x11, 0x22]);
+    // PLT, Lplt = 108, Zplt = 0
+    tile_part.extend_from_slice(&[0xFF, 0x58, 0x00, 0x6C, 0x00]);
+    tile_part.extend_from_slice(&[0x01; 105]);
+    // SOD
+    tile_part.extend_from_slice(&[0xFF, 0x93]);
+    tile_part.extend_from_slice(&[0x00; 105]);
+
+    // SOT, Lsot = 10, Isot = 0, TPsot = 0, TNsot = 1
+    let psot = (tile_part.len() + 12) as u32;
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&psot.to_be_bytes());
+    buffer.extend_from_slice(&[0x00, 0x01]);
+    buffer.extend(tile_part);
+
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+    buffer
+}
+
+#[test]
+fn test_expected_packet_count() {
+    let data = codestream_with_tile_coding_style();
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let tiles = codestream.tiles();
+    assert_eq!(tiles.len(), 1);
+    let tile = &tiles[0];
+
+    // The tile-part COD overrides the main header for component 0
+    let parameters = tile.coding_style_parameters(0);
+    assert_eq!(parameters.no_decomposition_levels(), 2);
+    assert!(parameters.has_default_precinct_size());
+    // The tile-part COC overrides the tile-part COD for component 1
+    let parameters = tile.coding_style_parameters(1);
+    assert_eq!(parameters.no_decomposition_levels(), 1);
+    assert!(parameters.has_defined_precinct_size());
+    assert_eq!(tile.coding_style_marker_segment().no_layers(), 3);
+
+    // Component 0 has one precinct in each of 3 resolution levels, and
+    // component 1 has 4x4 precincts in each of 2 resolution levels
+    let packets: usize = tile.tile_parts()[0]
+        .packet_length_segments()
+        .iter()
+        .map(|segment| segment.packet_lengths().len())
+        .sum();
+    assert_eq!(packets, 105);
+    assert_eq!(tile.expected_packet_count().unwrap(), 105);
+}
+
+#[test]
+fn test_expected_packet_count_main_header() {
+    let data = sample("blue.j2k");
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    let tiles = codestream.tiles();
+    let tile = &tiles[0];
+    assert!(std::ptr::eq(
+        tile.coding_style_parameters(0),
+        codestream
+            .header()
+            .coding_style_marker_segment()
+            .coding_style_parameters()
+    ));
+    // Three components of six resolution levels, each a single precinct, and
+    // one layer
+    assert_eq!(tile.expected_packet_count().unwrap(), 18);
+}
+
+#[test]
+fn test_codestream_length() {
+    let data = sample("tlm.j2k");
+    assert_eq!(
+        codestream_length(&mut Cursor::new(&data)).unwrap(),
+        data.len() as u64
+    );
+
+    // Padding after EOC is not counted, including bytes that look like EOC
+    let mut padded = data.clone();
+    padded.extend_from_slice(&[0x00, 0xFF, 0xD9, 0x00]);
+    assert_eq!(
+        codestream_length(&mut Cursor::new(&padded)).unwrap(),
+        data.len() as u64
+    );
+
+    // Counted from the position of the reader
+    let mut embedded = vec![0xAA; 5];
+    embedded.extend_from_slice(&padded);
+    let mut reader = Cursor::new(&embedded);
+    reader.set_position(5);
+    assert_eq!(codestream_length(&mut reader).unwrap(), data.len() as u64);
+
+    // Without EOC, the rest of the input
+    let truncated = &data[..data.len() - 2];
+    assert_eq!(
+        codestream_length(&mut Cursor::new(truncated)).unwrap(),
+        truncated.len() as u64
+    );
+}
+
+#[test]
+fn test_codestream_length_by_search() {
+    // A last tile-part with Psot of 0, and one with a wrong Psot, run to the
+    // EOC marker
+    let mut data = codestream_with_packets(false);
+    let length = data.len() as u64;
+    data.extend_from_slice(&[0x00; 8]);
+    set_psot(&mut data, 1, 0);
+    assert_eq!(codestream_length(&mut Cursor::new(&data)).unwrap(), length);
+    set_psot(&mut data, 1, 1000);
+    assert_eq!(codestream_length(&mut Cursor::new(&data)).unwrap(), length);
+}
+
+#[test]
+fn test_tile_part_data_extent() {
+    // One tile-part, with its SOD marker at 133 and EOC at 186
+    let data = sample("tlm.j2k");
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let tile_part = &codestream.tile_parts()[0];
+    assert_eq!(tile_part.start_of_tile_segment().offset(), 121);
+    assert_eq!(tile_part.sod_offset(), 133);
+    assert_eq!(tile_part.data_offset(), 135);
+    assert_eq!(tile_part.data_length(), 51);
+
+    // Tile-parts with 2 bytes of data, then 204 bytes after a PLT marker
+    // segment, ending at the next SOT and at EOC
+    let data = codestream_with_packets(false);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    let lengths: Vec<u64> = codestream
+        .tile_parts()
+        .iter()
+        .map(|tile_part| tile_part.data_length())
+        .collect();
+    assert_eq!(lengths, vec![2, 204]);
+    let second = &codestream.tile_parts()[1];
+    assert_eq!(
+        second.sod_offset(),
+        second.start_of_tile_segment().offset() + 12 + 9
+    );
+
+    // Psot beyond the EOC marker, or 0, gives the data up to EOC
+    let mut data = codestream_with_packets(false);
+    let mut expected = data[data.len() - 206..data.len() - 2].to_vec();
+    set_psot(&mut data, 1, 300);
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(codestream.tile_parts()[1].data_length(), 204);
+    set_psot(&mut data, 1, 0);
+    let mut reader = Cursor::new(&data);
+    let codestream = decode_jpc(&mut reader).unwrap();
+    assert_eq!(codestream.tile_parts()[1].data_length(), 204);
+    let mut first = vec![0xAA, 0xBB];
+    first.append(&mut expected);
+    assert_eq!(codestream.tile_data(&mut reader, 0).unwrap(), first);
+}
+
+#[test]
+fn test_is_high_throughput() {
+    let data = sample("tlm.j2k");
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert!(!codestream.is_high_throughput());
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../samples/ds0_ht_01_b11.j2k");
+    let data = std::fs::read(path).unwrap();
+    let codestream = decode_jpc(&mut Cursor::new(&data)).unwrap();
+    assert!(codestream.is_high_throughput());
+}
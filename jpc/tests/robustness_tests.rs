@@ -0,0 +1,147 @@
+use std::io::Cursor;
+use std::panic;
+use std::path::Path;
+
+use jpc::decode_jpc;
+
+// Small deterministic generator, so failures are reproducible.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn sample(filename: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(filename);
+    std::fs::read(path).expect("file should exist")
+}
+
+fn assert_no_panic(data: &[u8]) {
+    let result = panic::catch_unwind(|| {
+        let _ = decode_jpc(&mut Cursor::new(data));
+    });
+    assert!(result.is_ok(), "decode_jpc panicked on input {:02X?}", data);
+}
+
+fn assert_decode_image_no_panic(data: &[u8]) {
+    let result = panic::catch_unwind(|| {
+        let mut reader = Cursor::new(data);
+        if let Ok(codestream) = decode_jpc(&mut reader) {
+            let _ = codestream.decode_image(&mut reader);
+        }
+    });
+    assert!(
+        result.is_ok(),
+        "decode_image panicked on input {:02X?}",
+        data
+    );
+}
+
+#[test]
+fn test_truncated_codestreams() {
+    for filename in ["blue.j2k", "eph.j2k", "sop.j2k", "tlm.j2k"] {
+        let data = sample(filename);
+        for length in 0..data.len().min(512) {
+            assert_no_panic(&data[..length]);
+        }
+    }
+}
+
+#[test]
+fn test_mutated_codestreams() {
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    for filename in ["blue.j2k", "eph.j2k", "sop.j2k", "tlm.j2k"] {
+        let original = sample(filename);
+        for _ in 0..2000 {
+            let mut data = original.clone();
+            // Mutations are concentrated in the headers
+            let span = data.len().min(256);
+            for _ in 0..(1 + rng.next() % 4) {
+                let index = (rng.next() as usize) % span;
+                data[index] = rng.next() as u8;
+            }
+            assert_no_panic(&data);
+        }
+    }
+}
+
+#[test]
+fn test_random_codestreams() {
+    let mut rng = XorShift(0x0123_4567_89AB_CDEF);
+    for _ in 0..2000 {
+        let length = (rng.next() % 128) as usize;
+        // Start with SOC and SIZ so the parser goes beyond the first marker
+        let mut data = vec![0xFF, 0x4F, 0xFF, 0x51];
+        data.extend((0..length).map(|_| rng.next() as u8));
+        assert_no_panic(&data);
+    }
+}
+
+#[test]
+fn test_mutated_images() {
+    // Decoding goes on to the packets and code-blocks, so the mutations
+    // cover the whole codestream
+    let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+    for filename in ["blue.j2k", "eph.j2k", "sop.j2k", "tlm.j2k"] {
+        let original = sample(filename);
+        for _ in 0..100 {
+            let mut data = original.clone();
+            for _ in 0..(1 + rng.next() % 4) {
+                let index = (rng.next() as usize) % data.len();
+                data[index] = rng.next() as u8;
+            }
+            assert_decode_image_no_panic(&data);
+        }
+    }
+}
+
+#[test]
+fn test_decode_image_out_of_range() {
+    let decode_image = |data: &[u8]| {
+        assert_decode_image_no_panic(data);
+        let mut reader = Cursor::new(data);
+        let codestream = decode_jpc(&mut reader).unwrap();
+        codestream
+            .decode_image(&mut reader)
+            .unwrap_err()
+            .to_string()
+    };
+    let original = sample("tlm.j2k");
+
+    // Code-block width exponent xcb of 17, beyond the 10 of Table A.18, in
+    // SPcod at byte 61
+    let mut data = original.clone();
+    data[61] = 0x0F;
+    assert_eq!(
+        decode_image(&data),
+        "malformed marker COD (0xFF52) at byte offset 53"
+    );
+
+    // M_b of 7 guard bits and an exponent of 31, more than an i32 holds, in
+    // Sqcd and SPqcd at bytes 69 and 70
+    let mut data = original.clone();
+    data[69] = 0xE0;
+    data[70] = 0xF8;
+    assert_eq!(
+        decode_image(&data),
+        "unsupported feature for marker QCD (0xFF5C) at byte offset 0"
+    );
+
+    // Packet data of 1 bits, which increment Lblock without end
+    let mut data = original;
+    let end = data.len() - 2;
+    for (i, byte) in data[135..end].iter_mut().enumerate() {
+        *byte = if i % 2 == 0 { 0xFF } else { 0x7F };
+    }
+    assert_eq!(
+        decode_image(&data),
+        "codeword segment length of more than 32 bits at byte offset 7"
+    );
+}
@@ -0,0 +1,418 @@
+use std::io::Cursor;
+
+use jpc::{
+    decode_jpc, encode_image, verify_lossless, CodestreamHeaderBuilder, CommentDecodeError,
+    CommentRegistrationValue, ComponentInfo, Header, HeaderError, MultipleComponentTransformation,
+    ProgressionOrder, QuantizationStyle, TransformationFilter,
+};
+
+// Append a single empty tile-part and the EOC marker, so the header can be
+// parsed by decode_jpc.
+fn finish_codestream(buffer: &mut Vec<u8>) {
+    // SOT, Lsot = 10, Isot = 0, Psot = 14, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x0E, 0x00, 0x01]);
+    // SOD
+    buffer.extend_from_slice(&[0xFF, 0x93]);
+    // EOC
+    buffer.extend_from_slice(&[0xFF, 0xD9]);
+}
+
+#[test]
+fn test_header_builder_defaults_round_trip() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(128, 64, 3, 8)
+        .multiple_component_transformation(true)
+        .write_to(&mut buffer)
+        .unwrap();
+
+    // SOC + SIZ (2 + 47) + COD (2 + 12) + QCD (2 + 19)
+    assert_eq!(buffer.len(), 86);
+    finish_codestream(&mut buffer);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let header = codestream.header();
+
+    let siz = header.image_and_tile_size_marker_segment();
+    assert_eq!(siz.length(), 47);
+    assert_eq!(siz.decoder_capabilities(), 0);
+    assert_eq!(siz.reference_grid_width(), 128);
+    assert_eq!(siz.reference_grid_height(), 64);
+    assert_eq!(siz.image_horizontal_offset(), 0);
+    assert_eq!(siz.image_vertical_offset(), 0);
+    assert_eq!(siz.reference_tile_width(), 128);
+    assert_eq!(siz.reference_tile_height(), 64);
+    assert_eq!(siz.tile_horizontal_offset(), 0);
+    assert_eq!(siz.tile_vertical_offset(), 0);
+    assert_eq!(siz.no_components(), 3);
+    for i in 0..3 {
+        assert_eq!(siz.precision(i).unwrap(), 8);
+        assert!(!siz.values_are_signed(i).unwrap());
+        assert_eq!(siz.horizontal_separation(i).unwrap(), 1);
+        assert_eq!(siz.vertical_separation(i).unwrap(), 1);
+    }
+
+    let cod = header.coding_style_marker_segment();
+    assert_eq!(cod.length(), 12);
+    assert_eq!(cod.coding_style(), 0);
+    assert_eq!(cod.progression_order(), ProgressionOrder::LRLCPP);
+    assert_eq!(cod.no_layers(), 1);
+    assert_eq!(
+        cod.multiple_component_transformation(),
+        MultipleComponentTransformation::Multiple
+    );
+    let parameters = cod.coding_style_parameters();
+    assert_eq!(parameters.no_decomposition_levels(), 5);
+    assert_eq!(parameters.code_block_width(), 64);
+    assert_eq!(parameters.code_block_height(), 64);
+    assert_eq!(parameters.code_block_style(), 0);
+    assert_eq!(
+        parameters.transformation(),
+        TransformationFilter::Reversible
+    );
+    assert!(parameters.has_default_precinct_size());
+
+    let qcd = header.quantization_default_marker_segment();
+    assert_eq!(qcd.length(), 19);
+    assert_eq!(qcd.guard_bits(), 2);
+    assert_eq!(
+        qcd.quantization_info().style,
+        QuantizationStyle::NoQuantization
+    );
+    assert_eq!(
+        qcd.quantization_info().exponents(),
+        vec![8, 9, 9, 10, 9, 9, 10, 9, 9, 10, 9, 9, 10, 9, 9, 10]
+    );
+
+    assert!(header.comment_marker_segments().is_empty());
+}
+
+#[test]
+fn test_header_builder_options_round_trip() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(640, 480, 1, 12)
+        .values_are_signed(true)
+        .tile_size(256, 256)
+        .progression_order(ProgressionOrder::RLPCLP)
+        .no_layers(4)
+        .no_decomposition_levels(2)
+        .code_block_size(5, 4)
+        .transformation(TransformationFilter::Irreversible)
+        .guard_bits(1)
+        .comment("Created by jpc")
+        .write_to(&mut buffer)
+        .unwrap();
+    finish_codestream(&mut buffer);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let header = codestream.header();
+
+    let siz = header.image_and_tile_size_marker_segment();
+    assert_eq!(siz.reference_grid_width(), 640);
+    assert_eq!(siz.reference_grid_height(), 480);
+    assert_eq!(siz.reference_tile_width(), 256);
+    assert_eq!(siz.reference_tile_height(), 256);
+    assert_eq!(siz.no_components(), 1);
+    assert_eq!(siz.precision(0).unwrap(), 12);
+    assert!(siz.values_are_signed(0).unwrap());
+    let components: Vec<ComponentInfo> = siz.components().collect();
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].precision(), 12);
+    assert!(components[0].values_are_signed());
+
+    let cod = header.coding_style_marker_segment();
+    assert_eq!(cod.progression_order(), ProgressionOrder::RLPCLP);
+    assert_eq!(cod.no_layers(), 4);
+    assert_eq!(
+        cod.multiple_component_transformation(),
+        MultipleComponentTransformation::None
+    );
+    let parameters = cod.coding_style_parameters();
+    assert_eq!(parameters.no_decomposition_levels(), 2);
+    assert_eq!(parameters.code_block_width(), 32);
+    assert_eq!(parameters.code_block_height(), 16);
+    assert_eq!(
+        parameters.transformation(),
+        TransformationFilter::Irreversible
+    );
+
+    let qcd = header.quantization_default_marker_segment();
+    assert_eq!(qcd.guard_bits(), 1);
+    assert_eq!(
+        qcd.quantization_info().style,
+        QuantizationStyle::ScalarDerived
+    );
+    assert_eq!(qcd.quantization_info().exponents(), vec![12]);
+    assert_eq!(qcd.quantization_values(), vec![12 << 11]);
+
+    assert_eq!(header.comment_marker_segments().len(), 1);
+    let com = &header.comment_marker_segments()[0];
+    assert_eq!(com.registration_value(), CommentRegistrationValue::Latin);
+    assert_eq!(com.comment_utf8().unwrap(), "Created by jpc");
+}
+
+#[test]
+fn test_header_builder_invalid_code_block_size() {
+    let mut buffer = Vec::new();
+    let result = CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .code_block_size(6, 7)
+        .write_to(&mut buffer);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "invalid value for code_block_size"
+    );
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_header_builder_precinct_sizes() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .no_decomposition_levels(2)
+        .precinct_sizes(&[(0, 1), (5, 6), (15, 15)])
+        .write_to(&mut buffer)
+        .unwrap();
+    finish_codestream(&mut buffer);
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let parameters = codestream
+        .header()
+        .coding_style_marker_segment()
+        .coding_style_parameters();
+    assert!(parameters.has_defined_precinct_size());
+    let precincts: Vec<(u8, u8)> = parameters
+        .precinct_sizes()
+        .unwrap()
+        .iter()
+        .map(|precinct| (precinct.width_exponent(), precinct.height_exponent()))
+        .collect();
+    assert_eq!(precincts, vec![(0, 1), (5, 6), (15, 15)]);
+
+    // One for each resolution level, and only 0 for the lowest
+    for precinct_sizes in [&[(5, 5), (5, 5)][..], &[(5, 5), (0, 5), (5, 5)]] {
+        let result = CodestreamHeaderBuilder::new(8, 8, 1, 8)
+            .no_decomposition_levels(2)
+            .precinct_sizes(precinct_sizes)
+            .write_to(&mut Vec::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid value for precinct_sizes"
+        );
+    }
+}
+
+#[test]
+fn test_header_builder_latin_comment() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .comment("Café ½")
+        .write_to(&mut buffer)
+        .unwrap();
+    // COM, Lcom = 8, Rcom = 0 (binary)
+    buffer.extend_from_slice(&[0xFF, 0x64, 0x00, 0x08, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0xFF, 0x80, 0x7F]);
+    finish_codestream(&mut buffer);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let comments = codestream.header().comment_marker_segments();
+    assert_eq!(comments.len(), 2);
+
+    assert_eq!(
+        comments[0].registration_value(),
+        CommentRegistrationValue::Latin
+    );
+    assert_eq!(comments[0].comment_bytes(), b"Caf\xE9 \xBD");
+    assert_eq!(
+        comments[0].comment_utf8().unwrap_err(),
+        CommentDecodeError::InvalidUtf8 { valid_up_to: 3 }
+    );
+    assert_eq!(
+        comments[0].comment_utf8().unwrap_err().to_string(),
+        "comment has invalid UTF-8 at byte 3"
+    );
+    assert_eq!(comments[0].comment_latin1(), "Café ½");
+
+    assert_eq!(
+        comments[1].registration_value(),
+        CommentRegistrationValue::Binary
+    );
+    assert_eq!(comments[1].comment_bytes(), [0x00, 0xFF, 0x80, 0x7F]);
+    assert_eq!(
+        comments[1].comment_utf8().unwrap_err(),
+        CommentDecodeError::Binary
+    );
+}
+
+#[test]
+fn test_tile_part_comments() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .comment("Main")
+        .write_to(&mut buffer)
+        .unwrap();
+    let sot_offset = buffer.len() as u64;
+    // SOT, Lsot = 10, Isot = 0, Psot = 34, TPsot = 0, TNsot = 1
+    buffer.extend_from_slice(&[0xFF, 0x90, 0x00, 0x0A, 0x00, 0x00]);
+    buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x22, 0x00, 0x01]);
+    // COM, Lcom = 10, Rcom = 1 (Latin)
+    buffer.extend_from_slice(&[0xFF, 0x64, 0x00, 0x0A, 0x00, 0x01]);
+    buffer.extend_from_slice(b"Tile 0");
+    // COM, Lcom = 6, Rcom = 0 (binary)
+    buffer.extend_from_slice(&[0xFF, 0x64, 0x00, 0x06, 0x00, 0x00, 0xCA, 0xFE]);
+    // SOD, EOC
+    buffer.extend_from_slice(&[0xFF, 0x93, 0xFF, 0xD9]);
+
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    let comments = codestream.header().comment_marker_segments();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].comment_utf8().unwrap(), "Main");
+
+    let tile_parts = codestream.tile_parts();
+    assert_eq!(tile_parts.len(), 1);
+    let sot = tile_parts[0].start_of_tile_segment();
+    assert_eq!(sot.offset(), sot_offset);
+    assert_eq!(sot.length(), 10);
+    assert_eq!(sot.tile_index(), 0);
+    assert_eq!(sot.tile_length(), 34);
+    assert_eq!(sot.tile_part_index(), 0);
+    assert_eq!(sot.no_tile_parts(), 1);
+    assert_eq!(tile_parts[0].data_offset(), sot_offset + 34);
+
+    let comments = tile_parts[0].comment_marker_segments();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(
+        comments[0].registration_value(),
+        CommentRegistrationValue::Latin
+    );
+    assert_eq!(comments[0].comment_latin1(), "Tile 0");
+    assert_eq!(
+        comments[1].registration_value(),
+        CommentRegistrationValue::Binary
+    );
+    assert_eq!(comments[1].comment_bytes(), [0xCA, 0xFE]);
+}
+
+#[test]
+fn test_missing_required_markers() {
+    let mut buffer = Vec::new();
+    CodestreamHeaderBuilder::new(8, 8, 1, 8)
+        .write_to(&mut buffer)
+        .unwrap();
+    finish_codestream(&mut buffer);
+    let codestream = decode_jpc(&mut Cursor::new(&buffer)).unwrap();
+    assert!(codestream.header().missing_required_markers().is_empty());
+
+    // Without the QCD marker segment, which is last before the tile-part
+    let qcd = buffer
+        .windows(2)
+        .position(|window| window == [0xFF, 0x5C])
+        .unwrap();
+    let sot = buffer
+        .windows(2)
+        .position(|window| window == [0xFF, 0x90])
+        .unwrap();
+    buffer.drain(qcd..sot);
+    let error = decode_jpc(&mut Cursor::new(&buffer)).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<HeaderError>(),
+        Some(&HeaderError::MissingRequiredMarkers {
+            markers: vec!["QCD"]
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "main header is missing required markers QCD"
+    );
+
+    assert_eq!(
+        Header::default().missing_required_markers(),
+        vec!["SIZ", "COD", "QCD"]
+    );
+}
+
+// Encode the samples, and check that they are decoded back exactly
+fn assert_round_trip(samples: &[i32], parameters: &CodestreamHeaderBuilder) {
+    let codestream = encode_image(samples, parameters).unwrap();
+    verify_lossless(&mut Cursor::new(&codestream), &[samples.to_vec()]).unwrap();
+}
+
+// Samples of a gradient with noise, within the precision
+fn test_samples(width: u32, height: u32, precision: u8, signed: bool) -> Vec<i32> {
+    let mut state = 12345u32;
+    (0..width * height)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let gradient = (i % width + i / width) << precision.saturating_sub(6);
+            let value = (gradient + (state >> 16)) % (1 << precision);
+            if signed {
+                value as i32 - (1 << (precision - 1))
+            } else {
+                value as i32
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_encode_image_lossless() {
+    for (width, height, levels) in [(1, 1, 0), (2, 2, 1), (7, 5, 2), (64, 33, 5), (130, 70, 3)] {
+        let samples = test_samples(width, height, 8, false);
+        let parameters =
+            CodestreamHeaderBuilder::new(width, height, 1, 8).no_decomposition_levels(levels);
+        assert_round_trip(&samples, &parameters);
+    }
+
+    // Several code-blocks in each sub-band, and empty layers to follow
+    let samples = test_samples(45, 37, 8, false);
+    let parameters = CodestreamHeaderBuilder::new(45, 37, 1, 8)
+        .no_decomposition_levels(2)
+        .code_block_size(3, 2)
+        .no_layers(3)
+        .progression_order(ProgressionOrder::RLLCPP);
+    assert_round_trip(&samples, &parameters);
+
+    // Small precincts, several to a resolution level, in each progression
+    // order
+    let samples = test_samples(37, 29, 8, false);
+    for progression_order in [
+        ProgressionOrder::LRLCPP,
+        ProgressionOrder::RLLCPP,
+        ProgressionOrder::RLPCLP,
+        ProgressionOrder::PCRLLP,
+        ProgressionOrder::CPRLLP,
+    ] {
+        let parameters = CodestreamHeaderBuilder::new(37, 29, 1, 8)
+            .no_decomposition_levels(3)
+            .code_block_size(3, 3)
+            .precinct_sizes(&[(1, 2), (2, 2), (3, 4), (4, 3)])
+            .no_layers(2)
+            .progression_order(progression_order);
+        assert_round_trip(&samples, &parameters);
+    }
+
+    // Signed samples of a higher precision
+    let samples = test_samples(20, 12, 12, true);
+    let parameters = CodestreamHeaderBuilder::new(20, 12, 1, 12)
+        .values_are_signed(true)
+        .no_decomposition_levels(3)
+        .guard_bits(1);
+    assert_round_trip(&samples, &parameters);
+}
+
+#[test]
+fn test_encode_image_unsupported() {
+    let parameters = CodestreamHeaderBuilder::new(4, 4, 1, 8);
+    // Wrong number of samples, and a sample out of range
+    assert!(encode_image(&[0; 15], &parameters).is_err());
+    let mut samples = vec![0; 16];
+    samples[3] = 256;
+    assert!(encode_image(&samples, &parameters).is_err());
+
+    // More than one component, or tile, or the irreversible filter
+    let samples = vec![0; 16];
+    assert!(encode_image(&samples, &CodestreamHeaderBuilder::new(4, 4, 3, 8)).is_err());
+    let parameters = CodestreamHeaderBuilder::new(4, 4, 1, 8).tile_size(2, 2);
+    assert!(encode_image(&samples, &parameters).is_err());
+    let parameters =
+        CodestreamHeaderBuilder::new(4, 4, 1, 8).transformation(TransformationFilter::Irreversible);
+    assert!(encode_image(&samples, &parameters).is_err());
+}
@@ -363,7 +363,7 @@ fn encode_component_mapping_box<W: io::Write>(
         writeln!(
             writer,
             "        <xjp:mtype length=\"1\" type=\"integer\">{}</xjp:mtype>",
-            component_map.mapping_type()
+            component_map.mapping_type_u8()
         )?;
         writeln!(
             writer,